@@ -13,6 +13,7 @@ fn bench_memtable_insertion(c: &mut Criterion) {
     let mut memtable = MemTable::new(1024 * 1024, TableType::LocalStore);
     let mut rng = rand::thread_rng();
 
+    let mut timestamp = 0u64;
     c.bench_function("memtable_insert", |b| {
         b.iter_batched(
             || {
@@ -21,7 +22,8 @@ fn bench_memtable_insertion(c: &mut Criterion) {
                 (key, value)
             },
             |(key, value)| {
-                memtable.put(key, value, EntryType::Insert, 0);
+                timestamp += 1;
+                memtable.put(key, value, EntryType::Insert, 0, timestamp);
             },
             BatchSize::SmallInput,
         )
@@ -31,7 +33,7 @@ fn bench_memtable_insertion(c: &mut Criterion) {
 fn bench_wal_logging(c: &mut Criterion) {
     let dir = tempdir().unwrap();
     let wal_path = dir.path().join("test.wal");
-    let wal = WALManager::new(&wal_path).unwrap();
+    let wal = WALManager::new(&wal_path, TableType::History).unwrap();
     let mut rng = rand::thread_rng();
 
     c.bench_function("wal_log_sync", |b| {
@@ -53,6 +53,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("   Time: {:.2?}", write_duration);
     println!("   Throughput: {:.0} ops/sec", TOTAL_RECORDS as f64 / write_duration.as_secs_f64());
 
+    // Reads are measured against a settled tree, not a pile of level-0
+    // tables left behind by the write burst.
+    let start_checkpoint = Instant::now();
+    db.checkpoint()?;
+    println!("   Checkpoint (flush + compaction): {:.2?}", start_checkpoint.elapsed());
+
     // 2. Read Stress Test (Random Access)
     println!("\n📖 Phase 2: Random Read Stress Test");
     const READ_COUNT: u32 = 50_000;
@@ -1,5 +1,5 @@
 use std::fs::{File, OpenOptions};
-use std::io::{self, Seek, SeekFrom};
+use std::io::{self, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use crate::core::format::{BDBLogEntry, EntryType};
@@ -68,6 +68,14 @@ impl BlobLog {
         Ok(entry.value)
     }
 
+    /// Copies the whole log to `out`, holding off writers so the copy ends
+    /// on an entry boundary. Returns the number of bytes copied.
+    pub fn copy_to(&self, out: &mut impl Write) -> io::Result<u64> {
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(0))?;
+        io::copy(&mut *file, out)
+    }
+
     pub fn get_path(&self) -> PathBuf {
         self.path.clone()
     }
@@ -0,0 +1,104 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Wall-clock time in UNIX milliseconds.
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> u64;
+}
+
+/// `SystemTime::now()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+    }
+}
+
+/// A clock that only moves when told to, so tests of expiry, heat decay
+/// and retention can step time forward instead of sleeping.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(start_ms: u64) -> Self {
+        Self { now: AtomicU64::new(start_ms) }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now.store(now_ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+/// The clock a database reads time from, shared by every table opened with
+/// the same `BrowserDBConfig`. Defaults to [`SystemClock`].
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self(clock)
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        self.0.now_ms()
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self(Arc::new(SystemClock))
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedClock").field(&self.0.now_ms()).finish()
+    }
+}
+
+/// Write timestamps for one LSM tree. Newest-wins merging compares
+/// timestamps, so each one issued is strictly greater than the last even if
+/// the wall clock steps backward (NTP corrections, VM resume); the tree then
+/// runs slightly ahead until the clock catches up.
+pub struct MonotonicClock {
+    clock: SharedClock,
+    last: AtomicU64,
+}
+
+impl MonotonicClock {
+    pub fn new(clock: SharedClock) -> Self {
+        Self { clock, last: AtomicU64::new(0) }
+    }
+
+    /// `max(wall clock, last issued + 1)`.
+    pub fn next(&self) -> u64 {
+        let wall = self.clock.now_ms();
+        let previous = self
+            .last
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |last| Some(wall.max(last + 1)))
+            .unwrap_or_else(|last| last);
+        wall.max(previous + 1)
+    }
+
+    /// Makes later timestamps exceed `timestamp`, e.g. the newest one found
+    /// on disk, so writes after a restart behind a skewed clock still win.
+    pub fn observe(&self, timestamp: u64) {
+        self.last.fetch_max(timestamp, Ordering::AcqRel);
+    }
+}
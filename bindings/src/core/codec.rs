@@ -0,0 +1,101 @@
+//! Serializers that turn the typed tables' entries into the bytes the LSM
+//! tree stores, and back. Keys are always bincode, since their byte order
+//! is what the tree sorts by; only values go through a [`Codec`].
+//!
+//! Which codec a table uses is chosen per table in
+//! [`BrowserDBConfig::codecs`](crate::core::config::BrowserDBConfig::codecs)
+//! when the database is created and recorded in its `METADATA`. Values
+//! carry no tag of their own, so a database keeps those codecs for good.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Encodes and decodes table values.
+pub trait Codec {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+
+    /// Decodes `bytes`, borrowing from them where `T` allows it.
+    fn decode<'a, T: Deserialize<'a>>(&self, bytes: &'a [u8]) -> Result<T, CodecError>;
+}
+
+/// The compact binary encoding every table used before codecs were
+/// configurable. Fields are read back by position, so reordering or
+/// removing one breaks existing data.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        bincode::serialize(value).map_err(CodecError::Bincode)
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(&self, bytes: &'a [u8]) -> Result<T, CodecError> {
+        bincode::deserialize(bytes).map_err(CodecError::Bincode)
+    }
+}
+
+/// JSON objects keyed by field name: larger and slower than bincode, but
+/// readable in a hex dump and tolerant of reordered fields. Byte fields
+/// become arrays of numbers, so they cannot be borrowed when decoding.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(CodecError::Json)
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(&self, bytes: &'a [u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(bytes).map_err(CodecError::Json)
+    }
+}
+
+/// The codec of one table, as named in the config file and `METADATA`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueCodec {
+    #[default]
+    Bincode,
+    Json,
+}
+
+impl Codec for ValueCodec {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            ValueCodec::Bincode => BincodeCodec.encode(value),
+            ValueCodec::Json => JsonCodec.encode(value),
+        }
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(&self, bytes: &'a [u8]) -> Result<T, CodecError> {
+        match self {
+            ValueCodec::Bincode => BincodeCodec.decode(bytes),
+            ValueCodec::Json => JsonCodec.decode(bytes),
+        }
+    }
+}
+
+/// A value that could not be encoded or decoded, with the codec's error.
+#[derive(Debug)]
+pub enum CodecError {
+    Bincode(bincode::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Bincode(e) => write!(f, "bincode: {}", e),
+            CodecError::Json(e) => write!(f, "json: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Bincode(e) => Some(e),
+            CodecError::Json(e) => Some(e),
+        }
+    }
+}
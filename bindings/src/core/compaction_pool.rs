@@ -0,0 +1,65 @@
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+#[derive(Default)]
+struct PoolState {
+    active: usize,
+    peak: usize,
+}
+
+/// Slots for background compaction, shared by every table opened with the
+/// same `BrowserDBConfig`. Each table schedules its own jobs one at a time
+/// and takes a slot for the length of a job, so at most
+/// `LsmTreeConfig::compaction_workers` tables compact at once and a long
+/// history compaction does not hold up the other tables while slots are
+/// free. Cloning shares the slots.
+#[derive(Clone, Default)]
+pub struct CompactionPool {
+    state: Arc<(Mutex<PoolState>, Condvar)>,
+}
+
+/// A taken slot, given back when dropped.
+pub struct CompactionSlot {
+    pool: CompactionPool,
+}
+
+impl CompactionPool {
+    /// Waits up to `timeout` for one of `workers` slots to be free.
+    pub fn acquire_timeout(&self, workers: usize, timeout: Duration) -> Option<CompactionSlot> {
+        let (lock, cvar) = &*self.state;
+        let state = lock.lock().unwrap();
+        let (mut state, _) = cvar.wait_timeout_while(state, timeout, |s| s.active >= workers.max(1)).unwrap();
+        if state.active >= workers.max(1) {
+            return None;
+        }
+        state.active += 1;
+        state.peak = state.peak.max(state.active);
+        Some(CompactionSlot { pool: self.clone() })
+    }
+
+    /// Jobs running right now.
+    pub fn active(&self) -> usize {
+        self.state.0.lock().unwrap().active
+    }
+
+    /// Most jobs that have run at once.
+    pub fn peak(&self) -> usize {
+        self.state.0.lock().unwrap().peak
+    }
+}
+
+impl Drop for CompactionSlot {
+    fn drop(&mut self) {
+        let (lock, cvar) = &*self.pool.state;
+        lock.lock().unwrap().active -= 1;
+        cvar.notify_one();
+    }
+}
+
+impl fmt::Debug for CompactionPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.0.lock().unwrap();
+        f.debug_struct("CompactionPool").field("active", &state.active).field("peak", &state.peak).finish()
+    }
+}
@@ -0,0 +1,102 @@
+//! Value codecs of SSTables. Each codec other than `None` sits behind a
+//! cargo feature of the same name; a table written with a codec this build
+//! lacks fails to open with `ErrorKind::Unsupported` instead of being read
+//! as garbage.
+//!
+//! Only values are compressed, one entry at a time, so keys stay
+//! front-coded and blocks can still be decoded from any entry boundary.
+
+use crate::core::format::{CompressionType, TableType};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Codecs in order of preference when the configured one is missing.
+const PREFERENCE: [CompressionType; 4] = [
+    CompressionType::Zstd,
+    CompressionType::Zlib,
+    CompressionType::Lz4,
+    CompressionType::None,
+];
+
+/// Lower-case name of `codec`, as used in the config file.
+pub fn name(codec: CompressionType) -> &'static str {
+    match codec {
+        CompressionType::None => "none",
+        CompressionType::Zlib => "zlib",
+        CompressionType::Lz4 => "lz4",
+        CompressionType::Zstd => "zstd",
+    }
+}
+
+/// Whether this build can read and write `codec`.
+pub fn is_available(codec: CompressionType) -> bool {
+    match codec {
+        CompressionType::None => true,
+        CompressionType::Lz4 => cfg!(feature = "lz4"),
+        // No zlib or zstd implementation is linked in yet.
+        CompressionType::Zlib | CompressionType::Zstd => false,
+    }
+}
+
+/// The codec new tables are written with when `requested` is configured:
+/// `requested` itself if this build has it, otherwise the best one it has.
+pub fn resolve(requested: CompressionType) -> CompressionType {
+    if is_available(requested) {
+        return requested;
+    }
+    PREFERENCE.into_iter().find(|&codec| is_available(codec)).unwrap_or(CompressionType::None)
+}
+
+/// Data written with a codec this build does not include. Carried inside
+/// the `io::Error` of kind `Unsupported` that reading it fails with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingCodec {
+    pub codec: CompressionType,
+    /// The table and SSTable the data is in, when it came from one.
+    pub table: Option<TableType>,
+    pub file: Option<PathBuf>,
+}
+
+impl fmt::Display for MissingCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{} is ", file.display())?;
+        }
+        let codec = name(self.codec);
+        write!(f, "compressed with {}, which this build does not include; enable the `{}` feature", codec, codec)
+    }
+}
+
+impl std::error::Error for MissingCodec {}
+
+/// The error for data written with a codec this build does not include.
+pub fn unsupported(codec: CompressionType) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, MissingCodec { codec, table: None, file: None })
+}
+
+/// [`unsupported`], for the SSTable of `table` at `file`.
+pub fn unsupported_in(codec: CompressionType, table: TableType, file: PathBuf) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, MissingCodec { codec, table: Some(table), file: Some(file) })
+}
+
+/// Compress `value` with `codec`.
+pub fn compress(codec: CompressionType, value: &[u8]) -> io::Result<Vec<u8>> {
+    match codec {
+        CompressionType::None => Ok(value.to_vec()),
+        #[cfg(feature = "lz4")]
+        CompressionType::Lz4 => Ok(lz4_flex::compress_prepend_size(value)),
+        other => Err(unsupported(other)),
+    }
+}
+
+/// Reverse [`compress`].
+pub fn decompress(codec: CompressionType, value: &[u8]) -> io::Result<Vec<u8>> {
+    match codec {
+        CompressionType::None => Ok(value.to_vec()),
+        #[cfg(feature = "lz4")]
+        CompressionType::Lz4 => lz4_flex::decompress_size_prepended(value)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("lz4: {}", e))),
+        other => Err(unsupported(other)),
+    }
+}
@@ -1,26 +1,406 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
+use crate::core::format::{CompressionType, TableType};
+use crate::core::events::EventBus;
+use crate::core::clock::SharedClock;
+use crate::core::compaction_pool::CompactionPool;
+use crate::core::codec::ValueCodec;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LsmTreeConfig {
     pub max_level0_files: usize,
     pub max_memtable_size_mb: usize,
+    /// Writes after which the whole memtable is flushed even while under
+    /// `max_memtable_size_mb`, so many tiny entries do not sit in memory
+    /// and the WAL indefinitely. Counted across all shards, overwrites
+    /// included. `0` flushes on size alone.
+    #[serde(default = "default_memtable_max_ops")]
+    pub memtable_max_ops: usize,
     pub level_size_thresholds_mb: Vec<usize>,
+    /// Share of a core compaction may use, as a write rate. Jobs running at
+    /// the same time split it, so the total stays the same.
     #[serde(default = "default_compaction_cpu_limit")]
     pub compaction_cpu_limit: f64,
+    /// Tables that may compact at once, across all tables sharing the
+    /// config's `compaction_pool`. A table never runs two jobs at once.
+    #[serde(default = "default_compaction_workers")]
+    pub compaction_workers: usize,
+    /// Level-0 tables from which every write is delayed, 10 ms more for
+    /// each further table, so flushes do not outrun compaction.
+    #[serde(default = "default_l0_slowdown_files")]
+    pub l0_slowdown_files: usize,
+    /// Level-0 tables at which writes stop while a level-0 compaction is
+    /// queued or running, until it brings the count back down. What a
+    /// stopped write does is up to `write_stall`.
+    #[serde(default = "default_l0_stop_files")]
+    pub l0_stop_files: usize,
+    /// Memtable freezes the flush thread may fall behind by before writes
+    /// stop the same way, since each one it misses makes the next flush
+    /// bigger and slower.
+    #[serde(default = "default_max_pending_flushes")]
+    pub max_pending_flushes: usize,
+    #[serde(default)]
+    pub write_stall: WriteStall,
     #[serde(default = "default_compaction_idle_threshold_ms")]
     pub compaction_idle_threshold_ms: u64,
     #[serde(default = "default_compaction_deadline_sec")]
     pub compaction_deadline_sec: u64,
     #[serde(default)]
     pub verify_checksums: bool,
+    /// Front-code keys against the previous key in SSTable data and index
+    /// blocks. Disabling it only affects newly written SSTables.
+    #[serde(default = "default_prefix_compression")]
+    pub prefix_compression: bool,
+    /// Codec for values in newly written SSTables. A codec this build was
+    /// compiled without falls back to the best one it has; existing tables
+    /// keep the codec they were written with.
+    #[serde(default)]
+    pub compression: CompressionType,
+    #[serde(default)]
+    pub fsync_policy: FsyncPolicy,
+    /// Size at which each table's WAL moves on to a new segment file.
+    #[serde(default = "default_wal_segment_bytes")]
+    pub wal_segment_bytes: u64,
+    #[serde(default)]
+    pub bloom_fpr: BloomFprConfig,
+    #[serde(default)]
+    pub bloom_budget_bytes: BloomBudgetConfig,
+    #[serde(default)]
+    pub compaction_strategy: CompactionStrategyConfig,
+    /// Entries decoded from SSTables kept in an LRU cache per table, so hot
+    /// keys skip the block scan. `0` disables the cache.
+    #[serde(default = "default_entry_cache_entries")]
+    pub entry_cache_entries: usize,
+    /// Share of a level's entries that may be tombstones before the level
+    /// is compacted regardless of its size, so large deletes give their
+    /// space back. Leveled compaction only; `0` disables it.
+    #[serde(default = "default_tombstone_compaction_ratio")]
+    pub tombstone_compaction_ratio: f64,
+    /// Number of LSM levels of newly created trees. An existing tree keeps
+    /// the count recorded in its manifest.
+    #[serde(default = "default_max_levels")]
+    pub max_levels: usize,
+    #[serde(default)]
+    pub update_tags: UpdateTagConfig,
+}
+
+fn default_l0_slowdown_files() -> usize {
+    8
+}
+
+fn default_l0_stop_files() -> usize {
+    12
+}
+
+fn default_max_pending_flushes() -> usize {
+    8
+}
+
+fn default_wal_segment_bytes() -> u64 {
+    crate::core::wal::DEFAULT_SEGMENT_BYTES
+}
+
+fn default_tombstone_compaction_ratio() -> f64 {
+    0.3
+}
+
+fn default_max_levels() -> usize {
+    7
+}
+
+fn default_memtable_max_ops() -> usize {
+    1000
+}
+
+impl LsmTreeConfig {
+    /// `max_levels` limited to what a tree can use: at least two, so level
+    /// 0 has somewhere to compact into, and at most 256, the range of the
+    /// level byte in SSTable names and the manifest.
+    pub fn level_count(&self) -> usize {
+        self.max_levels.clamp(2, u8::MAX as usize + 1)
+    }
+}
+
+fn default_entry_cache_entries() -> usize {
+    1024
+}
+
+/// Default false-positive rate of SSTable bloom filters.
+pub const BLOOM_FPR: f64 = 0.01;
+
+fn default_bloom_fpr() -> f64 {
+    BLOOM_FPR
+}
+
+/// Bloom filter false-positive rate per table. Lower rates cost more bits
+/// per key: roughly 9.6 at 1%, 14.4 at 0.1%. Only newly written SSTables
+/// pick up a change.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BloomFprConfig {
+    #[serde(default = "default_bloom_fpr")]
+    pub history: f64,
+    #[serde(default = "default_bloom_fpr")]
+    pub bookmarks: f64,
+    #[serde(default = "default_bloom_fpr")]
+    pub cookies: f64,
+    #[serde(default = "default_bloom_fpr")]
+    pub cache: f64,
+    #[serde(default = "default_bloom_fpr")]
+    pub localstore: f64,
+    #[serde(default = "default_bloom_fpr")]
+    pub settings: f64,
+    #[serde(default = "default_bloom_fpr")]
+    pub binarystore: f64,
+}
+
+impl BloomFprConfig {
+    /// The rate for `table_type`, falling back to [`BLOOM_FPR`] when the
+    /// configured value is outside `(0, 1)`.
+    pub fn for_table(&self, table_type: TableType) -> f64 {
+        let fpr = match table_type {
+            TableType::History => self.history,
+            TableType::Bookmarks => self.bookmarks,
+            TableType::Cookies => self.cookies,
+            TableType::Cache => self.cache,
+            TableType::LocalStore => self.localstore,
+            TableType::Settings => self.settings,
+            TableType::BinaryStore => self.binarystore,
+        };
+        if fpr > 0.0 && fpr < 1.0 { fpr } else { BLOOM_FPR }
+    }
+}
+
+impl Default for BloomFprConfig {
+    fn default() -> Self {
+        Self {
+            history: BLOOM_FPR,
+            bookmarks: BLOOM_FPR,
+            cookies: BLOOM_FPR,
+            cache: BLOOM_FPR,
+            localstore: BLOOM_FPR,
+            settings: BLOOM_FPR,
+            binarystore: BLOOM_FPR,
+        }
+    }
+}
+
+/// Largest bloom filter, in bytes, of each SSTable per table. A filter
+/// that would need more for its `bloom_fpr` is built at this size instead,
+/// with a higher false-positive rate; `LSMTree::level_report` shows the
+/// rate actually reached. `0`, the default, leaves filters uncapped. Only
+/// newly written SSTables pick up a change.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BloomBudgetConfig {
+    #[serde(default)]
+    pub history: usize,
+    #[serde(default)]
+    pub bookmarks: usize,
+    #[serde(default)]
+    pub cookies: usize,
+    #[serde(default)]
+    pub cache: usize,
+    #[serde(default)]
+    pub localstore: usize,
+    #[serde(default)]
+    pub settings: usize,
+    #[serde(default)]
+    pub binarystore: usize,
+}
+
+impl BloomBudgetConfig {
+    /// The budget for `table_type`, `0` for none.
+    pub fn for_table(&self, table_type: TableType) -> usize {
+        match table_type {
+            TableType::History => self.history,
+            TableType::Bookmarks => self.bookmarks,
+            TableType::Cookies => self.cookies,
+            TableType::Cache => self.cache,
+            TableType::LocalStore => self.localstore,
+            TableType::Settings => self.settings,
+            TableType::BinaryStore => self.binarystore,
+        }
+    }
+}
+
+/// Whether each table looks up the key of every plain write to tag it
+/// `EntryType::Update` when it already has a live value, rather than
+/// `Insert`. The lookup stops at the memtable when the key is there, and
+/// otherwise probes each SSTable that could hold it; write-heavy tables
+/// can turn it off and have every write tagged `Insert`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateTagConfig {
+    #[serde(default = "default_update_tags")]
+    pub history: bool,
+    #[serde(default = "default_update_tags")]
+    pub bookmarks: bool,
+    #[serde(default = "default_update_tags")]
+    pub cookies: bool,
+    #[serde(default = "default_update_tags")]
+    pub cache: bool,
+    #[serde(default = "default_update_tags")]
+    pub localstore: bool,
+    #[serde(default = "default_update_tags")]
+    pub settings: bool,
+    #[serde(default = "default_update_tags")]
+    pub binarystore: bool,
+}
+
+fn default_update_tags() -> bool {
+    true
+}
+
+impl UpdateTagConfig {
+    pub fn for_table(&self, table_type: TableType) -> bool {
+        match table_type {
+            TableType::History => self.history,
+            TableType::Bookmarks => self.bookmarks,
+            TableType::Cookies => self.cookies,
+            TableType::Cache => self.cache,
+            TableType::LocalStore => self.localstore,
+            TableType::Settings => self.settings,
+            TableType::BinaryStore => self.binarystore,
+        }
+    }
+}
+
+impl Default for UpdateTagConfig {
+    fn default() -> Self {
+        Self {
+            history: true,
+            bookmarks: true,
+            cookies: true,
+            cache: true,
+            localstore: true,
+            settings: true,
+            binarystore: true,
+        }
+    }
+}
+
+/// How a table's SSTables are merged in the background.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionStrategy {
+    /// Level 0 is merged into level 1, and each level that outgrows its
+    /// `level_size_thresholds_mb` entry into the next. Keeps few tables per
+    /// lookup but rewrites data once per level.
+    #[default]
+    Leveled,
+    /// Every SSTable stays in level 0; once `max_level0_files` adjacent
+    /// tables are of similar size they are merged into one larger table.
+    /// Each entry is rewritten far less often, which suits append-mostly
+    /// tables such as history, at the cost of more tables per lookup.
+    SizeTiered,
+}
+
+/// Compaction strategy per table. A table switched to `SizeTiered` keeps
+/// whatever is already in its deeper levels; a vacuum folds it back in.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CompactionStrategyConfig {
+    #[serde(default)]
+    pub history: CompactionStrategy,
+    #[serde(default)]
+    pub bookmarks: CompactionStrategy,
+    #[serde(default)]
+    pub cookies: CompactionStrategy,
+    #[serde(default)]
+    pub cache: CompactionStrategy,
+    #[serde(default)]
+    pub localstore: CompactionStrategy,
+    #[serde(default)]
+    pub settings: CompactionStrategy,
+    #[serde(default)]
+    pub binarystore: CompactionStrategy,
+}
+
+impl CompactionStrategyConfig {
+    pub fn for_table(&self, table_type: TableType) -> CompactionStrategy {
+        match table_type {
+            TableType::History => self.history,
+            TableType::Bookmarks => self.bookmarks,
+            TableType::Cookies => self.cookies,
+            TableType::Cache => self.cache,
+            TableType::LocalStore => self.localstore,
+            TableType::Settings => self.settings,
+            TableType::BinaryStore => self.binarystore,
+        }
+    }
+}
+
+/// Codec of each typed table's values. Settings and BinaryStore store
+/// their values as given and have none. Only takes effect for a new
+/// database: an existing one keeps the codecs recorded in its `METADATA`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ValueCodecConfig {
+    #[serde(default)]
+    pub history: ValueCodec,
+    #[serde(default)]
+    pub bookmarks: ValueCodec,
+    #[serde(default)]
+    pub cookies: ValueCodec,
+    #[serde(default)]
+    pub cache: ValueCodec,
+    #[serde(default)]
+    pub localstore: ValueCodec,
+}
+
+impl ValueCodecConfig {
+    /// The codec for `table_type`; the default for Settings and BinaryStore.
+    pub fn for_table(&self, table_type: TableType) -> ValueCodec {
+        match table_type {
+            TableType::History => self.history,
+            TableType::Bookmarks => self.bookmarks,
+            TableType::Cookies => self.cookies,
+            TableType::Cache => self.cache,
+            TableType::LocalStore => self.localstore,
+            TableType::Settings | TableType::BinaryStore => ValueCodec::default(),
+        }
+    }
+}
+
+/// When data is forced to stable storage with `fsync`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// Every WAL append is written and synced before `put` returns. No
+    /// acknowledged write is lost on power failure, at the cost of one
+    /// `fsync` per write.
+    Always,
+    /// WAL appends are group-committed and synced every few milliseconds,
+    /// and SSTables are synced before they are published. A power failure
+    /// can lose the last few milliseconds of writes.
+    #[default]
+    OnFlush,
+    /// Never `fsync`; durability is left to the OS page cache. A crash of
+    /// the process loses nothing, but a power failure can lose or corrupt
+    /// anything written since the OS last flushed. Meant for ephemeral
+    /// profiles.
+    Never,
+}
+
+/// What a write does when level 0 has reached `l0_stop_files` or the flush
+/// thread is `max_pending_flushes` behind.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteStall {
+    /// Wait for flushes and compaction to catch up.
+    #[default]
+    Block,
+    /// Fail right away with `io::ErrorKind::WouldBlock`, for callers that
+    /// would rather drop or retry the write than wait.
+    Fail,
 }
 
 fn default_compaction_cpu_limit() -> f64 {
     0.05
 }
 
+/// Half the cores, between 1 and 4.
+fn default_compaction_workers() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| (n.get() / 2).clamp(1, 4))
+}
+
 fn default_compaction_idle_threshold_ms() -> u64 {
     5000
 }
@@ -29,21 +409,42 @@ fn default_compaction_deadline_sec() -> u64 {
     30
 }
 
+fn default_prefix_compression() -> bool {
+    true
+}
+
 impl Default for LsmTreeConfig {
     fn default() -> Self {
         Self {
             max_level0_files: 4,
             max_memtable_size_mb: 20,
+            memtable_max_ops: default_memtable_max_ops(),
             level_size_thresholds_mb: vec![10, 100, 1000, 10000, 100000, 1000000],
             compaction_cpu_limit: 0.05,
+            compaction_workers: default_compaction_workers(),
+            l0_slowdown_files: default_l0_slowdown_files(),
+            l0_stop_files: default_l0_stop_files(),
+            max_pending_flushes: default_max_pending_flushes(),
+            write_stall: WriteStall::Block,
             compaction_idle_threshold_ms: 5000,
             compaction_deadline_sec: 30,
             verify_checksums: false,
+            prefix_compression: true,
+            compression: CompressionType::None,
+            fsync_policy: FsyncPolicy::OnFlush,
+            wal_segment_bytes: default_wal_segment_bytes(),
+            bloom_fpr: BloomFprConfig::default(),
+            bloom_budget_bytes: BloomBudgetConfig::default(),
+            compaction_strategy: CompactionStrategyConfig::default(),
+            entry_cache_entries: default_entry_cache_entries(),
+            tombstone_compaction_ratio: default_tombstone_compaction_ratio(),
+            max_levels: default_max_levels(),
+            update_tags: UpdateTagConfig::default(),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct HeatmapConfig {
     pub max_entries: usize,
     pub hot_threshold: u32,
@@ -60,21 +461,125 @@ impl Default for HeatmapConfig {
     }
 }
 
-#[derive(Debug, Deserialize, Default, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetentionConfig {
+    /// History entries older than this many days are pruned by the
+    /// background sweeper. `0` disables the sweeper.
+    #[serde(default)]
+    pub history_max_age_days: u64,
+    #[serde(default = "default_sweep_interval_sec")]
+    pub sweep_interval_sec: u64,
+}
+
+fn default_sweep_interval_sec() -> u64 {
+    3600
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            history_max_age_days: 0,
+            sweep_interval_sec: 3600,
+        }
+    }
+}
+
+/// Weights of the "frecency" score that `HistoryTable::search_frecency`
+/// ranks by:
+///
+/// `visit_weight * ln(1 + visit_count)
+///  + recency_weight * 0.5^(age_days / recency_half_life_days)
+///  + heat_weight * ln(1 + heat)`
+///
+/// where `heat` is how often the entry was read lately. The recency term is
+/// 1 for a visit right now and halves every `recency_half_life_days`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FrecencyConfig {
+    #[serde(default = "default_frecency_weight")]
+    pub visit_weight: f64,
+    #[serde(default = "default_frecency_weight")]
+    pub recency_weight: f64,
+    #[serde(default = "default_frecency_weight")]
+    pub heat_weight: f64,
+    #[serde(default = "default_recency_half_life_days")]
+    pub recency_half_life_days: f64,
+}
+
+fn default_frecency_weight() -> f64 {
+    1.0
+}
+
+fn default_recency_half_life_days() -> f64 {
+    7.0
+}
+
+impl FrecencyConfig {
+    /// The score of an entry visited `visit_count` times, last `age_ms`
+    /// ago, with `heat` recent reads. Visits in the future count as now.
+    pub fn score(&self, visit_count: u32, age_ms: f64, heat: u32) -> f64 {
+        let age_days = age_ms.max(0.0) / 86_400_000.0;
+        let recency = if self.recency_half_life_days > 0.0 {
+            0.5f64.powf(age_days / self.recency_half_life_days)
+        } else {
+            0.0
+        };
+        self.visit_weight * (visit_count as f64).ln_1p()
+            + self.recency_weight * recency
+            + self.heat_weight * (heat as f64).ln_1p()
+    }
+}
+
+impl Default for FrecencyConfig {
+    fn default() -> Self {
+        Self {
+            visit_weight: 1.0,
+            recency_weight: 1.0,
+            heat_weight: 1.0,
+            recency_half_life_days: 7.0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct BrowserDBConfig {
+    /// Save the Ultra tables to `ultra.snapshot` when the database is closed
+    /// and load them back on the next open. A crash still loses Ultra data.
+    #[serde(default)]
+    pub ultra_snapshot_on_close: bool,
     #[serde(default)]
     pub lsm_tree: LsmTreeConfig,
     #[serde(default)]
     pub heatmap: HeatmapConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub frecency: FrecencyConfig,
+    #[serde(default)]
+    pub codecs: ValueCodecConfig,
+    /// Hooks for flush, compaction, mode switch and WAL replay events. Not
+    /// persisted; register them before `open_with_config` to also see the
+    /// WAL replay on open, or later through `BrowserDB::on_event`.
+    #[serde(skip)]
+    pub events: EventBus,
+    /// Wall clock behind write timestamps. Not persisted; only tests need
+    /// anything but the system clock.
+    #[serde(skip)]
+    pub clock: SharedClock,
+    /// Compaction slots shared by every table opened with this config.
+    #[serde(skip)]
+    pub compaction_pool: CompactionPool,
 }
 
 impl BrowserDBConfig {
     pub fn load_or_default(base_path: &Path) -> Self {
+        Self::load(base_path).unwrap_or_default()
+    }
+
+    /// Reads `browserdb.toml` from `base_path`. Returns `None` if the file
+    /// does not exist; a file that fails to parse yields the defaults.
+    pub fn load(base_path: &Path) -> Option<Self> {
         let config_path = base_path.join("browserdb.toml");
-        if let Ok(content) = fs::read_to_string(&config_path) {
-            toml::from_str(&content).unwrap_or_default()
-        } else {
-            Self::default()
-        }
+        let content = fs::read_to_string(&config_path).ok()?;
+        Some(toml::from_str(&content).unwrap_or_default())
     }
 }
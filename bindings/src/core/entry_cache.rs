@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use parking_lot::Mutex;
+
+use crate::core::lsm_tree::KVEntry;
+
+type CacheKey = (u64, Vec<u8>);
+
+/// Bounded LRU of entries decoded by `SSTable::get`, keyed by SSTable id and
+/// key bytes. SSTables are immutable, so an entry stays valid until its
+/// table is compacted away; callers drop those with [`EntryCache::invalidate`].
+pub struct EntryCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct LruState {
+    /// Entries and their last-use tick by SSTable id, then key, so lookups
+    /// can borrow the key instead of building a `CacheKey`.
+    tables: HashMap<u64, HashMap<Vec<u8>, (KVEntry, u64)>>,
+    len: usize,
+    /// Last-use tick to key; the first entry is the least recently used.
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl LruState {
+    fn touch(&mut self, sstable_id: u64, key: &[u8]) -> Option<KVEntry> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (entry, last_used) = self.tables.get_mut(&sstable_id)?.get_mut(key)?;
+        let cache_key = self.recency.remove(last_used).unwrap_or_else(|| (sstable_id, key.to_vec()));
+        *last_used = tick;
+        let entry = entry.clone();
+        self.recency.insert(tick, cache_key);
+        Some(entry)
+    }
+}
+
+impl EntryCache {
+    /// A cache of at most `capacity` entries. `0` disables it.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(LruState::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// The cached entry, counted as a hit.
+    pub fn get(&self, sstable_id: u64, key: &[u8]) -> Option<KVEntry> {
+        let found = self.state.lock().touch(sstable_id, key);
+        if found.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// Caches an entry that had to be decoded, counted as a miss. Lookups of
+    /// keys a table does not hold are neither.
+    pub fn insert(&self, sstable_id: u64, entry: KVEntry) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let mut state = self.state.lock();
+        state.tick += 1;
+        let tick = state.tick;
+        let LruState { tables, len, recency, .. } = &mut *state;
+        let key = (sstable_id, entry.key.clone());
+        match tables.entry(sstable_id).or_default().insert(entry.key.clone(), (entry, tick)) {
+            Some((_, last_used)) => {
+                recency.remove(&last_used);
+            }
+            None => *len += 1,
+        }
+        recency.insert(tick, key);
+        while *len > self.capacity {
+            let Some((_, (id, oldest))) = recency.pop_first() else { break };
+            if let Some(table) = tables.get_mut(&id) {
+                table.remove(&oldest);
+                if table.is_empty() {
+                    tables.remove(&id);
+                }
+            }
+            *len -= 1;
+        }
+    }
+
+    /// Drops every cached entry of `sstable_id`.
+    pub fn invalidate(&self, sstable_id: u64) {
+        let mut state = self.state.lock();
+        let LruState { tables, len, recency, .. } = &mut *state;
+        if let Some(table) = tables.remove(&sstable_id) {
+            *len -= table.len();
+            for (_, last_used) in table.values() {
+                recency.remove(last_used);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `(hits, misses)` since the cache was created.
+    pub fn hits_and_misses(&self) -> (u64, u64) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+use std::sync::Arc;
+use parking_lot::RwLock;
+
+use crate::core::format::TableType;
+use crate::core::modes::DatabaseMode;
+
+/// Something the storage engine did in the background, reported to hooks
+/// registered with `BrowserDB::on_event`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbEvent {
+    /// A memtable was written out as a level-0 SSTable of `bytes` bytes.
+    Flushed { table: TableType, entries: usize, bytes: u64 },
+    /// Tables at `level` were merged into the next level (or, for a vacuum,
+    /// into the last one), freeing `reclaimed` bytes on disk.
+    Compacted { table: TableType, level: usize, reclaimed: u64 },
+    /// The database moved between Persistent and Ultra mode.
+    ModeSwitched { from: DatabaseMode, to: DatabaseMode },
+    /// `entries` writes were recovered from the WAL on open.
+    WalReplayed { table: TableType, entries: usize },
+    /// The flush thread could not write `entries` entries out; they stay in
+    /// memory for the next attempt.
+    FlushFailed { table: TableType, entries: usize, error: String },
+}
+
+pub type EventHook = Box<dyn Fn(&DbEvent) + Send + Sync>;
+
+/// The registered hooks, shared by every table opened with the same
+/// `BrowserDBConfig`. Cloning shares the hooks.
+///
+/// Hooks run synchronously on the thread that caused the event, often the
+/// background flush or compaction thread, so they should be quick and must
+/// not register further hooks.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    hooks: Arc<RwLock<Vec<EventHook>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self, hook: EventHook) {
+        self.hooks.write().push(hook);
+    }
+
+    pub fn emit(&self, event: &DbEvent) {
+        for hook in self.hooks.read().iter() {
+            hook(event);
+        }
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus").field("hooks", &self.hooks.read().len()).finish()
+    }
+}
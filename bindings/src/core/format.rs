@@ -4,12 +4,27 @@ use crc32fast::Hasher;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const MAGIC_BYTES: &[u8; 9] = b"BROWSERDB";
-pub const BDB_VERSION: u8 = 2;
+/// On-disk format version written by this build. Version 2 added per-entry
+/// expiry; version 3 stores the `url_hash` keys of History and Cache
+/// big-endian (see [`upgrade_key`]).
+pub const BDB_VERSION: u8 = 3;
 pub const BDB_HEADER_SIZE: usize = 47;
 pub const BDB_FOOTER_SIZE: usize = 60;
 pub const BDB_BLOCK_SIZE: usize = 4096;
+/// Restart interval of SSTables written before [`BDB_FLAG_SPARSE_INDEX`].
 pub const BDB_RESTART_INTERVAL: usize = 16;
 
+/// Header flag: the SSTable index block stores each key front-coded against
+/// the previous index key (shared length + suffix) instead of in full.
+pub const BDB_FLAG_PREFIX_INDEX: u32 = 1;
+/// Header flag: the SSTable index holds one entry per data block (first key,
+/// offset, length) rather than one per row, and a serialized bloom filter is
+/// stored between the block checksums and the index.
+pub const BDB_FLAG_SPARSE_INDEX: u32 = 2;
+/// Header flag: the header's `reserved` field holds the number of
+/// tombstones in the SSTable.
+pub const BDB_FLAG_TOMBSTONE_COUNT: u32 = 4;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum TableType {
@@ -22,17 +37,43 @@ pub enum TableType {
     BinaryStore = 7,
 }
 
-impl From<u8> for TableType {
-    fn from(v: u8) -> Self {
+/// Rewrites `key`, read from a file of `table_type` in format `version`, in
+/// the current format's layout. Before version 3 the 16-byte `url_hash`
+/// keys of History and Cache were little-endian, which does not sort in
+/// numeric order, and so was the `url_hash` that ends a history title
+/// posting (`idx:history:title:<word>:<url_hash>`). Every other key is
+/// unchanged.
+pub fn upgrade_key(table_type: TableType, version: u8, key: &mut [u8]) {
+    if version >= 3 || !matches!(table_type, TableType::History | TableType::Cache) {
+        return;
+    }
+    if key.len() == 16 {
+        key.reverse();
+    } else if table_type == TableType::History && key.starts_with(b"idx:history:title:") && key.len() >= 16 + b"idx:history:title:".len() {
+        let suffix = key.len() - 16;
+        key[suffix..].reverse();
+    }
+}
+
+/// The error for a discriminant byte no variant of `what` has, from a
+/// damaged file or one written by a newer build.
+fn unknown_discriminant(what: &str, v: u8) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unknown {} {}", what, v))
+}
+
+impl TryFrom<u8> for TableType {
+    type Error = io::Error;
+
+    fn try_from(v: u8) -> io::Result<Self> {
         match v {
-            1 => TableType::History,
-            2 => TableType::Cookies,
-            3 => TableType::Cache,
-            4 => TableType::LocalStore,
-            5 => TableType::Settings,
-            6 => TableType::Bookmarks,
-            7 => TableType::BinaryStore,
-            _ => TableType::History,
+            1 => Ok(TableType::History),
+            2 => Ok(TableType::Cookies),
+            3 => Ok(TableType::Cache),
+            4 => Ok(TableType::LocalStore),
+            5 => Ok(TableType::Settings),
+            6 => Ok(TableType::Bookmarks),
+            7 => Ok(TableType::BinaryStore),
+            _ => Err(unknown_discriminant("table type", v)),
         }
     }
 }
@@ -49,30 +90,51 @@ pub enum EntryType {
     Increment = 7,
 }
 
-impl From<u8> for EntryType {
-    fn from(v: u8) -> Self {
+impl TryFrom<u8> for EntryType {
+    type Error = io::Error;
+
+    fn try_from(v: u8) -> io::Result<Self> {
         match v {
-            1 => EntryType::Insert,
-            2 => EntryType::Update,
-            3 => EntryType::Delete,
-            4 => EntryType::BatchStart,
-            5 => EntryType::BatchEnd,
-            6 => EntryType::BlobIndex,
-            7 => EntryType::Increment,
-            _ => EntryType::Insert,
+            1 => Ok(EntryType::Insert),
+            2 => Ok(EntryType::Update),
+            3 => Ok(EntryType::Delete),
+            4 => Ok(EntryType::BatchStart),
+            5 => Ok(EntryType::BatchEnd),
+            6 => Ok(EntryType::BlobIndex),
+            7 => Ok(EntryType::Increment),
+            _ => Err(unknown_discriminant("entry type", v)),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Codec of the values in an SSTable, recorded in its header. Which ones a
+/// build can read and write is up to its cargo features; see
+/// [`crate::core::compression`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum CompressionType {
+    #[default]
     None = 0,
     Zlib = 1,
     Lz4 = 2,
     Zstd = 3,
 }
 
+impl TryFrom<u8> for CompressionType {
+    type Error = io::Error;
+
+    fn try_from(v: u8) -> io::Result<Self> {
+        match v {
+            0 => Ok(CompressionType::None),
+            1 => Ok(CompressionType::Zlib),
+            2 => Ok(CompressionType::Lz4),
+            3 => Ok(CompressionType::Zstd),
+            _ => Err(unknown_discriminant("compression codec", v)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum EncryptionType {
@@ -81,6 +143,19 @@ pub enum EncryptionType {
     ChaCha20 = 2,
 }
 
+impl TryFrom<u8> for EncryptionType {
+    type Error = io::Error;
+
+    fn try_from(v: u8) -> io::Result<Self> {
+        match v {
+            0 => Ok(EncryptionType::None),
+            1 => Ok(EncryptionType::AES256),
+            2 => Ok(EncryptionType::ChaCha20),
+            _ => Err(unknown_discriminant("encryption type", v)),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BDBFileHeader {
     pub magic: [u8; 9],
@@ -164,20 +239,9 @@ impl BDBFileHeader {
         let modified_at = reader.read_u64::<LittleEndian>()?;
         let flags = reader.read_u32::<LittleEndian>()?;
         let reserved = reader.read_u32::<LittleEndian>()?;
-        let table_type = reader.read_u8()?.into();
-        let compression = match reader.read_u8()? {
-            0 => CompressionType::None,
-            1 => CompressionType::Zlib,
-            2 => CompressionType::Lz4,
-            3 => CompressionType::Zstd,
-            _ => CompressionType::None,
-        };
-        let encryption = match reader.read_u8()? {
-            0 => EncryptionType::None,
-            1 => EncryptionType::AES256,
-            2 => EncryptionType::ChaCha20,
-            _ => EncryptionType::None,
-        };
+        let table_type = TableType::try_from(reader.read_u8()?)?;
+        let compression = CompressionType::try_from(reader.read_u8()?)?;
+        let encryption = EncryptionType::try_from(reader.read_u8()?)?;
         
         let mut reserved_bytes = [0u8; 6];
         reader.read_exact(&mut reserved_bytes)?;
@@ -295,7 +359,7 @@ impl BDBLogEntry {
     }
 
     pub fn read<R: Read>(reader: &mut R, version: u8) -> io::Result<Self> {
-        let entry_type = reader.read_u8()?.into();
+        let entry_type = EntryType::try_from(reader.read_u8()?)?;
         let key_len = read_varint(reader)?;
         let value_len = read_varint(reader)?;
         
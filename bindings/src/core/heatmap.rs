@@ -1,8 +1,9 @@
 use parking_lot::RwLock;
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use crate::core::clock::SharedClock;
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct BDBKey {
     pub data: Vec<u8>,
@@ -29,11 +30,55 @@ pub struct HeatEntry {
     pub created_at: u64,
 }
 
+/// Heat levels used to bucket keys into hot / warm / cold.
+pub mod heat_thresholds {
+    /// Matches the default `HeatmapConfig::hot_threshold`.
+    pub const HOT: u32 = 10;
+    pub const WARM: u32 = 3;
+
+    pub fn categorize_heat(heat: u32) -> &'static str {
+        if heat >= HOT {
+            "hot"
+        } else if heat >= WARM {
+            "warm"
+        } else {
+            "cold"
+        }
+    }
+}
+
+/// Aggregate view of tracked keys, bucketed with [`heat_thresholds`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeatMapStats {
+    pub total_entries: u64,
+    pub hot_entries: u64,
+    pub warm_entries: u64,
+    pub cold_entries: u64,
+    pub average_heat: f64,
+}
+
+impl HeatMapStats {
+    /// Folds `other` into `self`, weighting the average by entry count.
+    pub fn merge(&mut self, other: &HeatMapStats) {
+        let total = self.total_entries + other.total_entries;
+        if total > 0 {
+            self.average_heat = (self.average_heat * self.total_entries as f64
+                + other.average_heat * other.total_entries as f64)
+                / total as f64;
+        }
+        self.total_entries = total;
+        self.hot_entries += other.hot_entries;
+        self.warm_entries += other.warm_entries;
+        self.cold_entries += other.cold_entries;
+    }
+}
+
 pub struct HeatTracker {
     max_entries: usize,
     decay_factor: f64,
     hot_threshold: u32,
     last_decay_time: AtomicU64,
+    clock: SharedClock,
     // Use an Array of RwLock<HashMap> to shard the lock and reduce contention
     heat_entries: Vec<RwLock<HashMap<Vec<u8>, HeatEntry>>>,
 }
@@ -46,7 +91,13 @@ impl Default for HeatTracker {
 
 impl HeatTracker {
     pub fn new(max_entries: usize) -> Self {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Self::with_clock(max_entries, SharedClock::default())
+    }
+
+    /// [`HeatTracker::new`] with heat decaying by `clock` rather than the
+    /// system clock.
+    pub fn with_clock(max_entries: usize, clock: SharedClock) -> Self {
+        let now = clock.now_ms() / 1000;
         let mut heat_entries = Vec::with_capacity(32);
         for _ in 0..32 {
             heat_entries.push(RwLock::new(HashMap::new()));
@@ -57,10 +108,15 @@ impl HeatTracker {
             decay_factor: 0.95,
             hot_threshold: 10,
             last_decay_time: AtomicU64::new(now),
+            clock,
             heat_entries,
         }
     }
 
+    fn now_secs(&self) -> u64 {
+        self.clock.now_ms() / 1000
+    }
+
     fn get_shard(&self, key: &[u8]) -> usize {
         let mut hash = 0usize;
         for &b in key {
@@ -72,7 +128,7 @@ impl HeatTracker {
     pub fn record_access(&self, key: &[u8], query_type: QueryType) {
         self.apply_decay();
         
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let now = self.now_secs();
         
         let increment = match query_type {
             QueryType::Read => 1,
@@ -83,12 +139,17 @@ impl HeatTracker {
 
         let shard_idx = self.get_shard(key);
         let mut entries = self.heat_entries[shard_idx].write();
-        let entry = entries.entry(key.to_vec()).or_insert(HeatEntry {
-            heat: 0,
-            access_count: 0,
-            last_access: now,
-            created_at: now,
-        });
+        // Only a key seen for the first time is copied; reads of tracked
+        // keys do not allocate.
+        if !entries.contains_key(key) {
+            entries.insert(key.to_vec(), HeatEntry {
+                heat: 0,
+                access_count: 0,
+                last_access: now,
+                created_at: now,
+            });
+        }
+        let entry = entries.get_mut(key).expect("inserted above");
         
         entry.heat = entry.heat.saturating_add(increment);
         entry.access_count += 1;
@@ -98,24 +159,58 @@ impl HeatTracker {
     pub fn get_heat(&self, key: &[u8]) -> u32 {
         let shard_idx = self.get_shard(key);
         if let Some(entry) = self.heat_entries[shard_idx].read().get(key) {
-             // Simple decay simulation for read
-            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
-            let age_seconds = now.saturating_sub(entry.last_access);
-            let decay_cycles = age_seconds / 60;
-            
-            if decay_cycles > 0 {
-                let factor = self.decay_factor.powf(decay_cycles as f64);
-                return (entry.heat as f64 * factor) as u32;
-            }
-            return entry.heat;
+            let now = self.now_secs();
+            return self.decayed_heat(entry, now);
         }
         0
     }
+
+    /// When `key` was last accessed, in seconds by the tracker's clock.
+    /// `None` if it is not tracked.
+    pub fn last_access(&self, key: &[u8]) -> Option<u64> {
+        let shard_idx = self.get_shard(key);
+        self.heat_entries[shard_idx].read().get(key).map(|entry| entry.last_access)
+    }
+
+    /// Simple decay simulation for read
+    fn decayed_heat(&self, entry: &HeatEntry, now: u64) -> u32 {
+        let age_seconds = now.saturating_sub(entry.last_access);
+        let decay_cycles = age_seconds / 60;
+
+        if decay_cycles > 0 {
+            let factor = self.decay_factor.powf(decay_cycles as f64);
+            return (entry.heat as f64 * factor) as u32;
+        }
+        entry.heat
+    }
+
+    /// Buckets every tracked key by its current (decayed) heat.
+    pub fn stats(&self) -> HeatMapStats {
+        let now = self.now_secs();
+        let mut stats = HeatMapStats::default();
+        let mut heat_sum = 0u64;
+        for shard in &self.heat_entries {
+            for entry in shard.read().values() {
+                let heat = self.decayed_heat(entry, now);
+                heat_sum += heat as u64;
+                stats.total_entries += 1;
+                match heat_thresholds::categorize_heat(heat) {
+                    "hot" => stats.hot_entries += 1,
+                    "warm" => stats.warm_entries += 1,
+                    _ => stats.cold_entries += 1,
+                }
+            }
+        }
+        if stats.total_entries > 0 {
+            stats.average_heat = heat_sum as f64 / stats.total_entries as f64;
+        }
+        stats
+    }
     
     fn apply_decay(&self) {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let now = self.now_secs();
         let last_time = self.last_decay_time.load(Ordering::Acquire);
-        if now - last_time < 60 {
+        if now.saturating_sub(last_time) < 60 {
             return;
         }
 
@@ -161,8 +256,19 @@ pub struct BloomFilter {
 
 impl BloomFilter {
     pub fn new(expected_elements: usize, false_positive_rate: f64) -> Self {
+        Self::with_budget(expected_elements, false_positive_rate, 0)
+    }
+
+    /// [`BloomFilter::new`] with the bit array capped at `max_bytes`. When
+    /// the requested rate needs more, the filter gets `max_bytes` and the
+    /// hash count that suits that size, and so a higher false-positive
+    /// rate. `0` means no cap.
+    pub fn with_budget(expected_elements: usize, false_positive_rate: f64, max_bytes: usize) -> Self {
         let expected_elements = expected_elements.max(1);
-        let optimal_bit_size = -((expected_elements as f64 * false_positive_rate.ln()) / (2.0f64.ln().powi(2))) as usize;
+        let mut optimal_bit_size = -((expected_elements as f64 * false_positive_rate.ln()) / (2.0f64.ln().powi(2))) as usize;
+        if max_bytes > 0 {
+            optimal_bit_size = optimal_bit_size.min(max_bytes * 8);
+        }
         let bit_array_size = optimal_bit_size.div_ceil(8).max(1);
         let k = ((optimal_bit_size as f64 / expected_elements as f64) * 2.0f64.ln()) as u32;
         
@@ -181,6 +287,45 @@ impl BloomFilter {
         }
     }
     
+    /// Size of the bit array in bytes.
+    pub fn size_bytes(&self) -> usize {
+        self.bit_array_size
+    }
+
+    /// False-positive rate implied by how full the filter is,
+    /// `(set bits / total bits) ^ num_hashes`.
+    pub fn estimated_fpr(&self) -> f64 {
+        let set: u32 = self.bit_array.iter().map(|b| b.count_ones()).sum();
+        (set as f64 / (self.bit_array_size * 8) as f64).powi(self.num_hashes as i32)
+    }
+
+    /// Serialize as `num_hashes (u32 LE) | bit array`, for persisting the
+    /// filter alongside an SSTable.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.bit_array.len());
+        bytes.extend_from_slice(&self.num_hashes.to_le_bytes());
+        bytes.extend_from_slice(&self.bit_array);
+        bytes
+    }
+
+    /// Inverse of [`BloomFilter::to_bytes`]. Returns `None` for a truncated
+    /// or empty filter.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() <= 4 {
+            return None;
+        }
+        let num_hashes = u32::from_le_bytes(bytes[..4].try_into().ok()?);
+        if num_hashes == 0 {
+            return None;
+        }
+        let bit_array = bytes[4..].to_vec();
+        Some(Self {
+            bit_array_size: bit_array.len(),
+            bit_array,
+            num_hashes,
+        })
+    }
+
     pub fn might_contain(&self, key: &[u8]) -> bool {
         for i in 0..self.num_hashes {
             let hash = self.hash(key, i);
@@ -261,4 +406,18 @@ mod tests {
         bf.add(key);
         assert!(bf.might_contain(key));
     }
+
+    #[test]
+    fn test_bloom_filter_roundtrip() {
+        let mut bf = BloomFilter::new(100, 0.01);
+        for i in 0..100u32 {
+            bf.add(&i.to_le_bytes());
+        }
+
+        let restored = BloomFilter::from_bytes(&bf.to_bytes()).unwrap();
+        for i in 0..100u32 {
+            assert!(restored.might_contain(&i.to_le_bytes()));
+        }
+        assert!(BloomFilter::from_bytes(&[1, 0]).is_none());
+    }
 }
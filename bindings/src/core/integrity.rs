@@ -0,0 +1,170 @@
+//! Read-only consistency check of a table directory: the manifest against
+//! the SSTables on disk, and every SSTable's checksums, index order and
+//! bloom filter against its entries. Nothing is modified; see `repair` for
+//! recovering from what this finds.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::core::format::{TableType, BDB_BLOCK_SIZE, BDB_HEADER_SIZE};
+use crate::core::lsm_tree::SSTable;
+use crate::core::manifest::Manifest;
+
+/// One thing an integrity check found wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityProblem {
+    pub table: TableType,
+    /// The SSTable or manifest the problem is in.
+    pub file: PathBuf,
+    /// Byte offset in `file` of the damaged block or entry, when the
+    /// problem is local to one.
+    pub offset: Option<u64>,
+    pub reason: String,
+}
+
+/// What an integrity check looked at and what it found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub sstables_checked: usize,
+    /// Entries (including tombstones) read and checked.
+    pub entries_checked: u64,
+    pub problems: Vec<IntegrityProblem>,
+}
+
+impl IntegrityReport {
+    /// Whether the check found nothing wrong.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Adds the counts and problems of `other` to this report.
+    pub fn merge(&mut self, other: IntegrityReport) {
+        self.sstables_checked += other.sstables_checked;
+        self.entries_checked += other.entries_checked;
+        self.problems.extend(other.problems);
+    }
+
+    fn problem(&mut self, table: TableType, file: &Path, offset: Option<u64>, reason: String) {
+        self.problems.push(IntegrityProblem { table, file: file.to_path_buf(), offset, reason });
+    }
+}
+
+/// Checks the table stored in `dir` under `prefix`: that its manifest can
+/// be read and names exactly the SSTables on disk, and every one of those.
+/// The caller keeps flushes and compactions from changing the files
+/// meanwhile.
+pub fn check_table(dir: &Path, table_type: TableType, prefix: &str, bloom_fpr: f64, report: &mut IntegrityReport) -> io::Result<()> {
+    let file_prefix = format!("{}_", prefix);
+    let mut on_disk = Vec::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        if name.starts_with(&file_prefix) && name.ends_with(".sst") && path.is_file() {
+            on_disk.push(path);
+        }
+    }
+    on_disk.sort();
+
+    let manifest = Manifest::new(dir, prefix);
+    let listed = match manifest.load() {
+        Ok(Some((_, tables))) => tables.into_iter().map(|(_, name)| dir.join(name)).collect(),
+        // Trees that never wrote a manifest load every SSTable on disk.
+        Ok(None) => on_disk.clone(),
+        Err(e) => {
+            report.problem(table_type, manifest.path(), None, format!("unreadable manifest: {}", e));
+            on_disk.clone()
+        }
+    };
+
+    let listed_set: HashSet<&PathBuf> = listed.iter().collect();
+    for path in on_disk.iter().filter(|p| !listed_set.contains(p)) {
+        report.problem(table_type, path, None, "SSTable is not listed in the manifest".to_string());
+    }
+    for path in &listed {
+        if path.is_file() {
+            check_sstable(path, table_type, bloom_fpr, report);
+        } else {
+            report.problem(table_type, path, None, "SSTable listed in the manifest is missing".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Checks one SSTable: header, footer and whole-file CRC, every block
+/// checksum and entry CRC, that the index and the entries are in key order
+/// and within the data region, and that the bloom filter holds every key.
+fn check_sstable(path: &Path, table_type: TableType, bloom_fpr: f64, report: &mut IntegrityReport) {
+    report.sstables_checked += 1;
+    let mut sst = match SSTable::open(path.to_path_buf(), 0, true, bloom_fpr) {
+        Ok(sst) => sst,
+        Err(e) => {
+            report.problem(table_type, path, None, e.to_string());
+            // The header and footer may still be fine, with the damage in
+            // the data region; if so, narrow it down below.
+            match SSTable::open_for_repair(path.to_path_buf(), 0, bloom_fpr) {
+                Ok(sst) => sst,
+                Err(_) => return,
+            }
+        }
+    };
+
+    for i in 0..sst.block_checksums.len() {
+        let start = BDB_HEADER_SIZE + i * BDB_BLOCK_SIZE;
+        if start >= sst.data_end {
+            break;
+        }
+        if let Err(e) = sst.verify_blocks(start, start + 1) {
+            report.problem(table_type, path, Some(start as u64), e.to_string());
+        }
+    }
+
+    // Blocks were checked above; entries are checked by their own CRCs so
+    // a damaged one is located exactly.
+    sst.verify_checksums = false;
+    let mut previous: Option<Vec<u8>> = None;
+    let mut entries = 0u64;
+    let mut damaged = false;
+    for (n, block) in sst.index.iter().enumerate() {
+        let position = block.position;
+        if position < BDB_HEADER_SIZE as u64 || position as usize + block.size > sst.data_end {
+            report.problem(table_type, path, Some(position), format!("index entry {} points outside the data region", n));
+            damaged = true;
+            continue;
+        }
+        if n > 0 && sst.index[n - 1].key >= block.key {
+            report.problem(table_type, path, Some(position), format!("index entry {} is out of key order", n));
+        }
+
+        let mut iter = sst.block_iter(block);
+        loop {
+            let offset = iter.position();
+            let entry = match iter.next() {
+                None => break,
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => {
+                    report.problem(table_type, path, Some(offset), format!("damaged entry: {}", e));
+                    damaged = true;
+                    break;
+                }
+            };
+            entries += 1;
+            if offset == position && entry.key != block.key {
+                report.problem(table_type, path, Some(offset), format!("index entry {} does not match the first key of its block", n));
+            }
+            if previous.as_ref().is_some_and(|p| *p >= entry.key) {
+                report.problem(table_type, path, Some(offset), "entry is out of key order".to_string());
+            }
+            if sst.bloom_filter.as_ref().is_some_and(|bloom| !bloom.might_contain(&entry.key)) {
+                report.problem(table_type, path, Some(offset), "bloom filter does not contain the entry's key".to_string());
+            }
+            previous = Some(entry.key);
+        }
+    }
+
+    if !damaged && entries != sst.entry_count {
+        report.problem(table_type, path, None, format!("footer counts {} entries, found {}", sst.entry_count, entries));
+    }
+    report.entries_checked += entries;
+}
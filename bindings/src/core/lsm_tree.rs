@@ -6,17 +6,27 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::collections::{BTreeMap, BinaryHeap, HashSet};
 use std::cmp::Ordering;
+use std::borrow::Cow;
 use parking_lot::{RwLock, RwLockReadGuard};
 use memmap2::Mmap;
 use self_cell::self_cell;
 use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
-use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
 use std::sync::{Mutex, Condvar};
 
-use crate::core::format::{BDBLogEntry, EntryType, TableType, BDBFileHeader, BDBFileFooter, BDB_HEADER_SIZE, BDB_FOOTER_SIZE, BDB_BLOCK_SIZE, BDB_RESTART_INTERVAL};
+use crate::core::format::{BDBLogEntry, CompressionType, EntryType, TableType, BDBFileHeader, BDBFileFooter, BDB_HEADER_SIZE, BDB_FOOTER_SIZE, BDB_BLOCK_SIZE, BDB_FLAG_PREFIX_INDEX, BDB_FLAG_SPARSE_INDEX, BDB_FLAG_TOMBSTONE_COUNT, BDB_VERSION, upgrade_key};
 use crate::core::heatmap::{BloomFilter, HeatTracker, QueryType};
 use crate::core::wal::WALManager;
 use crate::core::blob_log::{BlobLog, BlobPointer, BlobLogIterator};
+use crate::core::manifest::Manifest;
+use crate::core::integrity::{self, IntegrityReport};
+use crate::core::repair::QUARANTINE_DIR;
+use crate::core::config::{CompactionStrategy, FsyncPolicy, LsmTreeConfig, WriteStall};
+use crate::core::events::DbEvent;
+use crate::core::entry_cache::EntryCache;
+use crate::core::clock::{MonotonicClock, SharedClock};
+use crate::core::compression;
 
 #[derive(Debug, Clone)]
 pub struct KVEntry {
@@ -91,7 +101,7 @@ impl MemTable {
         }
     }
 
-    pub fn put(&mut self, key: Vec<u8>, mut value: Vec<u8>, mut entry_type: EntryType, expires_at: u64) {
+    pub fn put(&mut self, key: Vec<u8>, mut value: Vec<u8>, mut entry_type: EntryType, expires_at: u64, timestamp: u64) {
         if entry_type == EntryType::Increment {
             if let Some(existing) = self.entries.get(&key) {
                 if existing.entry_type == EntryType::Increment {
@@ -126,7 +136,7 @@ impl MemTable {
         let entry = KVEntry {
             key: key.clone(),
             value,
-            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            timestamp,
             expires_at,
             entry_type,
             deleted: entry_type == EntryType::Delete,
@@ -186,14 +196,56 @@ pub struct IndexEntry {
 pub struct SSTable {
     pub level: u8,
     pub file_path: PathBuf,
-    pub mmap: Mmap,
+    /// Unmapped in `drop` before an obsolete file is unlinked.
+    pub mmap: ManuallyDrop<Mmap>,
     pub index: Vec<IndexEntry>,
     pub bloom_filter: Option<BloomFilter>,
     pub block_checksums: Vec<u32>,
     pub data_end: usize,
     pub verify_checksums: bool,
+    /// On-disk format version from the file header. Tables older than
+    /// `BDB_VERSION` are rewritten when the tree is opened.
+    pub format_version: u8,
+    /// Codec of the entry values, from the file header.
+    pub compression: CompressionType,
+    /// Unique within the process; keys this table's entries in the
+    /// `EntryCache`.
+    pub id: u64,
+    /// Entries decoded by `get`, for measuring the entry cache.
+    pub decoded: AtomicU64,
+    /// Set by `mark_obsolete`: the file is unlinked once the last `Arc` to
+    /// the table is dropped.
+    obsolete: AtomicBool,
+    /// Entries in the table, tombstones included.
+    pub entry_count: u64,
+    /// Tombstones among them; 0 for tables written before the count was
+    /// recorded in the header.
+    pub tombstones: u64,
+    /// Largest key and newest timestamp, kept so lookups can rule the
+    /// table out without touching its bloom filter or blocks.
+    last_key: Vec<u8>,
+    newest_timestamp: u64,
+}
+
+impl Drop for SSTable {
+    fn drop(&mut self) {
+        // SAFETY: `mmap` is not used after this. Unmapping first lets the
+        // file be removed on Windows, which refuses to delete mapped files.
+        unsafe { ManuallyDrop::drop(&mut self.mmap) };
+        if self.obsolete.load(AtomicOrdering::Acquire) {
+            if let Err(e) = retry_on_permission_denied(|| fs::remove_file(&self.file_path)) {
+                eprintln!("Failed to remove SSTable file {}: {}", self.file_path.display(), e);
+            }
+        }
+    }
 }
 
+static NEXT_SSTABLE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Last component of SSTable filenames. Unique within the process, so two
+/// tables created in the same millisecond still get distinct names.
+static NEXT_SSTABLE_FILE: AtomicU64 = AtomicU64::new(0);
+
 pub struct SSTableIterator<'a> {
     sstable: &'a SSTable,
     offset: usize,
@@ -201,6 +253,13 @@ pub struct SSTableIterator<'a> {
     last_key: Vec<u8>,
 }
 
+impl SSTableIterator<'_> {
+    /// File offset of the entry the next call to `next` reads.
+    pub fn position(&self) -> u64 {
+        self.offset as u64
+    }
+}
+
 impl<'a> Iterator for SSTableIterator<'a> {
     type Item = io::Result<KVEntry>;
 
@@ -212,6 +271,7 @@ impl<'a> Iterator for SSTableIterator<'a> {
         // Verify block checksum before reading entry (only if configured)
         if self.sstable.verify_checksums {
             if let Err(e) = self.sstable.verify_blocks(self.offset, self.offset + 1) {
+                self.offset = self.limit;
                 return Some(Err(e));
             }
         }
@@ -219,26 +279,75 @@ impl<'a> Iterator for SSTableIterator<'a> {
         let data = &self.sstable.mmap[self.offset..self.limit];
         let mut cursor = io::Cursor::new(data);
 
-        match read_compressed_entry(&mut cursor, &self.last_key) {
+        match read_compressed_entry(&mut cursor, &self.last_key, self.sstable.format_version) {
             Ok(log_entry) => {
                 let size = cursor.position() as usize;
                 self.offset += size;
                 self.last_key = log_entry.key.clone();
+                let value = match self.sstable.decode_value(log_entry.value) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        self.offset = self.limit;
+                        return Some(Err(e));
+                    }
+                };
                 Some(Ok(KVEntry {
                     key: log_entry.key,
-                    value: log_entry.value,
+                    value,
                     timestamp: log_entry.timestamp,
                     expires_at: log_entry.expires_at,
                     entry_type: log_entry.entry_type,
                     deleted: log_entry.entry_type == EntryType::Delete,
                 }))
             }
-            Err(e) => Some(Err(e)),
+            Err(e) => {
+                // Nothing past an undecodable entry can be located.
+                self.offset = self.limit;
+                Some(Err(e))
+            }
         }
     }
 }
 
-fn write_compressed_entry<W: io::Write>(writer: &mut W, entry: &BDBLogEntry, shared: usize) -> io::Result<usize> {
+/// Collapse a per-row index into one entry per run of rows that starts with
+/// an unshared key, so every entry marks a point where decoding can begin.
+fn group_rows_into_blocks(data: &[u8], rows: Vec<IndexEntry>) -> Vec<IndexEntry> {
+    let mut blocks: Vec<IndexEntry> = Vec::new();
+    for row in rows {
+        // Entry layout: type (u8) followed by the shared-prefix varint.
+        let shared = data
+            .get(row.position as usize + 1..)
+            .and_then(|mut rest| crate::core::format::read_varint(&mut rest).ok());
+        let starts_block = shared.unwrap_or(0) == 0;
+        match blocks.last_mut() {
+            Some(block) if !starts_block => {
+                block.size += row.size;
+                block.timestamp = block.timestamp.max(row.timestamp);
+            }
+            _ => blocks.push(row),
+        }
+    }
+    blocks
+}
+
+fn shared_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+fn read_front_coded_key<R: io::Read>(reader: &mut R, prev_key: &[u8]) -> io::Result<Vec<u8>> {
+    let shared = crate::core::format::read_varint(reader)? as usize;
+    let suffix_len = crate::core::format::read_varint(reader)? as usize;
+    if shared > prev_key.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "front-coded key shares more than the previous key"));
+    }
+    let mut key = Vec::with_capacity(shared + suffix_len);
+    key.extend_from_slice(&prev_key[..shared]);
+    key.resize(shared + suffix_len, 0);
+    reader.read_exact(&mut key[shared..])?;
+    Ok(key)
+}
+
+fn write_compressed_entry<W: io::Write>(writer: &mut W, entry: &BDBLogEntry, shared: usize, version: u8) -> io::Result<usize> {
     let mut bytes_written = 0;
 
     writer.write_u8(entry.entry_type as u8)?;
@@ -258,7 +367,9 @@ fn write_compressed_entry<W: io::Write>(writer: &mut W, entry: &BDBLogEntry, sha
     writer.write_u64::<LittleEndian>(entry.timestamp)?;
     bytes_written += 8;
 
-    bytes_written += crate::core::format::write_varint(writer, entry.expires_at)?;
+    if version >= 2 {
+        bytes_written += crate::core::format::write_varint(writer, entry.expires_at)?;
+    }
 
     let mut hasher = crc32fast::Hasher::new();
     hasher.update(&[entry.entry_type as u8]);
@@ -282,14 +393,74 @@ fn write_compressed_entry<W: io::Write>(writer: &mut W, entry: &BDBLogEntry, sha
     Ok(bytes_written)
 }
 
-fn read_compressed_entry<R: io::Read>(reader: &mut R, full_key: &[u8]) -> io::Result<BDBLogEntry> {
+fn compressed_entry_crc(entry_type: EntryType, shared: usize, key_suffix: &[u8], value: &[u8], timestamp: u64) -> io::Result<u32> {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&[entry_type as u8]);
+
+    let mut shared_buf = [0u8; 10];
+    let n = {
+        let mut writer_ref = &mut shared_buf[..];
+        crate::core::format::write_varint(&mut writer_ref, shared as u64)?
+    };
+    hasher.update(&shared_buf[..n]);
+
+    hasher.update(key_suffix);
+    hasher.update(value);
+    hasher.update(&timestamp.to_le_bytes());
+    Ok(hasher.finalize())
+}
+
+/// An SSTable entry whose value still points into the table's mmap, unless
+/// the table is compressed and the value had to be decoded.
+pub struct EntryRef<'a> {
+    pub key: Vec<u8>,
+    pub value: Cow<'a, [u8]>,
+    pub timestamp: u64,
+    pub expires_at: u64,
+    pub entry_type: EntryType,
+}
+
+/// [`read_compressed_entry`] over a slice, borrowing the value instead of
+/// copying it. Returns the entry and its encoded length.
+fn read_entry_ref<'a>(data: &'a [u8], full_key: &[u8], version: u8) -> io::Result<(EntryRef<'a>, usize)> {
+    let mut cursor = io::Cursor::new(data);
+    let entry_type = EntryType::try_from(cursor.read_u8()?)?;
+    let shared = crate::core::format::read_varint(&mut cursor)? as usize;
+    let non_shared = crate::core::format::read_varint(&mut cursor)? as usize;
+    let value_len = crate::core::format::read_varint(&mut cursor)? as usize;
+
+    let suffix_start = cursor.position() as usize;
+    let value_start = suffix_start.saturating_add(non_shared);
+    let value_end = value_start.saturating_add(value_len);
+    if value_end > data.len() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "entry runs past the block"));
+    }
+    let key_suffix = &data[suffix_start..value_start];
+    let value = &data[value_start..value_end];
+
+    cursor.set_position(value_end as u64);
+    let timestamp = cursor.read_u64::<LittleEndian>()?;
+    let expires_at = if version >= 2 { crate::core::format::read_varint(&mut cursor).unwrap_or(0) } else { 0 };
+    let read_crc = cursor.read_u32::<LittleEndian>()?;
+    if read_crc != compressed_entry_crc(entry_type, shared, key_suffix, value, timestamp)? {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "CRC mismatch in compressed entry"));
+    }
+
+    let mut key = Vec::with_capacity(shared + non_shared);
+    key.extend_from_slice(&full_key[..full_key.len().min(shared)]);
+    key.extend_from_slice(key_suffix);
+    Ok((EntryRef { key, value: Cow::Borrowed(value), timestamp, expires_at, entry_type }, cursor.position() as usize))
+}
+
+/// Version 1 tables have no `expires_at` between the timestamp and the CRC.
+fn read_compressed_entry<R: io::Read>(reader: &mut R, full_key: &[u8], version: u8) -> io::Result<BDBLogEntry> {
     let entry_type_res = reader.read_u8();
     if let Err(ref e) = entry_type_res {
         if e.kind() == io::ErrorKind::UnexpectedEof {
             return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "EOF"));
         }
     }
-    let entry_type = entry_type_res?.into();
+    let entry_type = EntryType::try_from(entry_type_res?)?;
     let shared = crate::core::format::read_varint(reader)? as usize;
     let non_shared = crate::core::format::read_varint(reader)? as usize;
     let value_len = crate::core::format::read_varint(reader)? as usize;
@@ -312,25 +483,10 @@ fn read_compressed_entry<R: io::Read>(reader: &mut R, full_key: &[u8]) -> io::Re
     reader.read_exact(&mut value)?;
 
     let timestamp = reader.read_u64::<LittleEndian>()?;
-    let expires_at = crate::core::format::read_varint(reader).unwrap_or(0);
+    let expires_at = if version >= 2 { crate::core::format::read_varint(reader).unwrap_or(0) } else { 0 };
     let read_crc = reader.read_u32::<LittleEndian>()?;
 
-    let mut hasher = crc32fast::Hasher::new();
-    hasher.update(&[entry_type as u8]);
-
-    let mut shared_buf = [0u8; 10];
-    let n = {
-        let mut writer_ref = &mut shared_buf[..];
-        crate::core::format::write_varint(&mut writer_ref, shared as u64)?
-    };
-    hasher.update(&shared_buf[..n]);
-
-    hasher.update(&key_suffix);
-    hasher.update(&value);
-    hasher.update(&timestamp.to_le_bytes());
-    let calculated_crc = hasher.finalize();
-
-    if read_crc != calculated_crc {
+    if read_crc != compressed_entry_crc(entry_type, shared, &key_suffix, &value, timestamp)? {
         return Err(io::Error::new(io::ErrorKind::InvalidData, "CRC mismatch in compressed entry"));
     }
 
@@ -361,12 +517,12 @@ struct MemTableIteratorWrapper<'a> {
 }
 
 impl<'a> MemTableIteratorWrapper<'a> {
-    fn new(guard: RwLockReadGuard<'a, MemTable>, prefix: Vec<u8>) -> Self {
+    fn new(guard: RwLockReadGuard<'a, MemTable>, start: Vec<u8>, prefix: Vec<u8>) -> Self {
         let cell = MemTableIterCell::new(guard, |guard| {
-            if prefix.is_empty() {
+            if start.is_empty() {
                 guard.entries.range::<Vec<u8>, _>(..)
             } else {
-                guard.entries.range(prefix.clone()..)
+                guard.entries.range(start..)
             }
         });
 
@@ -465,12 +621,15 @@ struct HeapSource<'a> {
 pub struct MergeIterator<'a> {
     heap: BinaryHeap<HeapNode>,
     sources: Vec<HeapSource<'a>>,
-    last_yielded_key: Vec<u8>,
+    /// `None` until the first key; an empty key is a valid key.
+    last_yielded_key: Option<Vec<u8>>,
     prefix: Vec<u8>,
+    /// Entries that expired before this are skipped.
+    now: u64,
 }
 
 impl<'a> MergeIterator<'a> {
-    pub fn new(iters: Vec<SourceIterator<'a>>, prefix: Vec<u8>) -> Self {
+    pub fn new(iters: Vec<SourceIterator<'a>>, prefix: Vec<u8>, now: u64) -> Self {
         let mut heap = BinaryHeap::new();
         let mut sources = Vec::new();
         for (i, mut src) in iters.into_iter().enumerate() {
@@ -509,8 +668,9 @@ impl<'a> MergeIterator<'a> {
         Self {
             heap,
             sources,
-            last_yielded_key: Vec::new(),
+            last_yielded_key: None,
             prefix,
+            now,
         }
     }
 }
@@ -519,7 +679,7 @@ impl<'a> Iterator for MergeIterator<'a> {
     type Item = io::Result<KVEntry>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let now = self.now;
 
         while let Some(node) = self.heap.pop() {
             let src = &mut self.sources[node.iter_index];
@@ -545,10 +705,10 @@ impl<'a> Iterator for MergeIterator<'a> {
                 continue;
             }
 
-            if !self.last_yielded_key.is_empty() && self.last_yielded_key == key {
+            if self.last_yielded_key.as_ref() == Some(&key) {
                 continue;
             }
-            self.last_yielded_key = key.clone();
+            self.last_yielded_key = Some(key.clone());
 
             let mut delta_sum: i64 = 0;
             let mut has_increments = false;
@@ -660,6 +820,114 @@ where
     f()
 }
 
+/// Whether `path` is the empty file [`SSTable::create`] reserves a table's
+/// name with until the finished table is renamed over it.
+pub(crate) fn is_reserved_name(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.len() == 0)
+}
+
+/// The timestamp and counter in an SSTable's filename, compared as
+/// numbers; `(0, 0)` for names not in that form.
+fn sstable_file_order(path: &Path) -> (u128, u64) {
+    let stem = path.file_stem().and_then(|n| n.to_str()).unwrap_or_default();
+    let mut parts = stem.split('_').skip(2).map(|p| p.parse::<u128>().ok());
+    match (parts.next().flatten(), parts.next().flatten()) {
+        (Some(timestamp), Some(counter)) => (timestamp, counter as u64),
+        _ => (0, 0),
+    }
+}
+
+/// Logs an SSTable that failed to open. A damaged one (`InvalidData`) is
+/// moved to the quarantine directory rather than dropped with the orphans,
+/// so whatever is intact can still be salvaged; anything else, such as a
+/// missing file, is left where it is.
+fn set_aside_sstable(base_path: &Path, path: &Path, error: &io::Error) -> OpenWarning {
+    eprintln!("Skipping SSTable {}: {}", path.display(), error);
+    let mut quarantined = None;
+    if error.kind() == io::ErrorKind::InvalidData {
+        let target = base_path.join(QUARANTINE_DIR).join(path.file_name().unwrap_or_default());
+        match fs::create_dir_all(base_path.join(QUARANTINE_DIR)).and_then(|_| fs::rename(path, &target)) {
+            Ok(()) => quarantined = Some(target),
+            Err(e) => eprintln!("Failed to quarantine SSTable {}: {}", path.display(), e),
+        }
+    }
+    OpenWarning { file: path.to_path_buf(), reason: error.to_string(), quarantined }
+}
+
+/// Left next to a tree's files when its final flush fails, for the next
+/// open to report: the number of unflushed entries, then the error.
+fn flush_error_path(base_path: &Path, prefix: &str) -> PathBuf {
+    base_path.join(format!("{}.flush-error", prefix))
+}
+
+/// Reports and removes the record of a failed shutdown flush, if any.
+/// `replayed` is how many entries the WAL gave back.
+fn take_flush_error(base_path: &Path, prefix: &str, replayed: usize) -> Option<OpenWarning> {
+    let path = flush_error_path(base_path, prefix);
+    let record = fs::read_to_string(&path).ok()?;
+    let (entries, error) = record.split_once('\n').unwrap_or(("?", record.as_str()));
+    let reason = format!(
+        "previous shutdown failed to persist {} entries ({}); {} were replayed from the WAL",
+        entries.trim(),
+        error.trim(),
+        replayed
+    );
+    eprintln!("{}: {}", path.display(), reason);
+    if let Err(e) = fs::remove_file(&path) {
+        eprintln!("Failed to remove {}: {}", path.display(), e);
+    }
+    Some(OpenWarning { file: path, reason, quarantined: None })
+}
+
+/// The oldest run of at least `min_tables` adjacent tables whose sizes are
+/// all within half to one and a half times the run's average, for
+/// size-tiered compaction. Only adjacent tables can be merged without
+/// reordering versions of a key across the tables in between.
+fn size_tiered_run(tables: &[Arc<SSTable>], min_tables: usize) -> Option<std::ops::Range<usize>> {
+    let min_tables = min_tables.max(2);
+    let sizes: Vec<u64> = tables.iter().map(|t| t.mmap.len() as u64).collect();
+    for start in 0..sizes.len() {
+        let mut total = sizes[start];
+        let mut end = start + 1;
+        while end < sizes.len() {
+            let avg = (total + sizes[end]) / (end + 1 - start) as u64;
+            if !sizes[start..=end].iter().all(|s| s * 2 >= avg && s * 2 <= avg * 3) {
+                break;
+            }
+            total += sizes[end];
+            end += 1;
+        }
+        if end - start >= min_tables {
+            return Some(start..end);
+        }
+    }
+    None
+}
+
+fn corruption(detail: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("SSTable corrupt: {}", detail))
+}
+
+/// Checks the footer against the file it closes: the recorded size catches
+/// truncation and appended junk, `file_crc` a damaged data region. Files
+/// written before the CRC was recorded carry 0 there and skip that check.
+fn verify_footer(footer: &BDBFileFooter, mmap: &[u8], check_crc: bool) -> io::Result<()> {
+    if footer.file_size != mmap.len() as u64 {
+        return Err(corruption(format!("footer records {} bytes, file has {}", footer.file_size, mmap.len())));
+    }
+    let footer_start = (mmap.len() - BDB_FOOTER_SIZE) as u64;
+    if footer.data_offset < BDB_HEADER_SIZE as u64 || footer.data_offset > footer.block_crc_offset || footer.block_crc_offset > footer_start {
+        return Err(corruption("footer offsets out of bounds".to_string()));
+    }
+    if check_crc && footer.file_crc != 0 {
+        let data = &mmap[footer.data_offset as usize..footer.block_crc_offset as usize];
+        if crc32fast::hash(data) != footer.file_crc {
+            return Err(corruption("data region does not match footer CRC".to_string()));
+        }
+    }
+    Ok(())
+}
+
 pub fn extract_prefix(key: &[u8]) -> &[u8] {
     if let Some(pos) = key.iter().position(|&b| b == b':') {
         &key[..=pos] // include the delimiter ':'
@@ -670,6 +938,13 @@ pub fn extract_prefix(key: &[u8]) -> &[u8] {
 }
 
 impl SSTable {
+    /// Deletes the file once nothing reads from the table any more. A
+    /// compaction or vacuum replacing the table drops the tree's reference
+    /// right away, but snapshots and iterators keep theirs, and with them
+    /// a mapping of a file that still exists, until they are dropped.
+    pub fn mark_obsolete(&self) {
+        self.obsolete.store(true, AtomicOrdering::Release);
+    }
 
     pub fn verify_blocks(&self, start_pos: usize, end_pos: usize) -> io::Result<()> {
         let first_block = (start_pos - BDB_HEADER_SIZE) / BDB_BLOCK_SIZE;
@@ -694,13 +969,31 @@ impl SSTable {
         Ok(())
     }
 
-    pub fn create(level: u8, entries: &BTreeMap<Vec<u8>, KVEntry>, base_path: &Path, table_type: TableType, rate_limit_mb: Option<f64>, verify_checksums: bool) -> io::Result<Self> {
+    /// Write `entries` to a new SSTable file. With `config.prefix_compression`
+    /// set, keys in both the data block and the index block are front-coded
+    /// against the previous key. The file is synced before it is published
+    /// unless `config.fsync_policy` is `Never`.
+    pub fn create(level: u8, entries: &BTreeMap<Vec<u8>, KVEntry>, base_path: &Path, table_type: TableType, rate_limit_mb: Option<f64>, config: &LsmTreeConfig) -> io::Result<Self> {
+        Self::create_in_format(BDB_VERSION, level, entries, base_path, table_type, rate_limit_mb, config)
+    }
+
+    /// [`SSTable::create`] writing an older on-disk format, for tests and
+    /// tools that exercise upgrades. Version 1 tables drop `expires_at`.
+    pub fn create_in_format(format_version: u8, level: u8, entries: &BTreeMap<Vec<u8>, KVEntry>, base_path: &Path, table_type: TableType, rate_limit_mb: Option<f64>, config: &LsmTreeConfig) -> io::Result<Self> {
+        if format_version == 0 || format_version > BDB_VERSION {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, format!("cannot write on-disk format version {}", format_version)));
+        }
+        let verify_checksums = config.verify_checksums;
+        let prefix_compression = config.prefix_compression;
+        let compression = compression::resolve(config.compression);
+        let sync = config.fsync_policy != FsyncPolicy::Never;
+        let tombstones = entries.values().filter(|e| e.deleted).count() as u64;
         let mut attempts = 0;
         let mut rate_limiter = rate_limit_mb.map(TokenBucket::new);
         loop {
             let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?.as_millis();
-            let timestamp_nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
-            let filename = format!("{}_{}_{}_{}.sst", 
+            let counter = NEXT_SSTABLE_FILE.fetch_add(1, AtomicOrdering::Relaxed);
+            let filename = format!("{}_{}_{}_{:06}.sst", 
                 match table_type {
                     TableType::History => "history",
                     TableType::Cookies => "cookies",
@@ -710,19 +1003,35 @@ impl SSTable {
                     TableType::Bookmarks => "bookmarks",
                     TableType::BinaryStore => "binarystore",
                 }, 
-                level, timestamp, timestamp_nanos % 100000);
+                level, timestamp, counter);
             let file_path = base_path.join(filename);
+            // Written under a temporary name and renamed into place once
+            // complete, so a crash mid-write never leaves a partial `.sst`
+            // behind. The final name is reserved with an empty file before
+            // the rename, so a name clash fails the attempt, and the retry
+            // picks a fresh name, instead of replacing another table.
+            let tmp_path = file_path.with_extension("sst.tmp");
+            let mut created_tmp = false;
+            let mut reserved = false;
             
             let res = (|| {
                 let mut file = retry_on_permission_denied(|| {
                     OpenOptions::new()
                         .read(true)
                         .write(true)
-                        .create(true)
-                        .open(&file_path)
+                        .create_new(true)
+                        .open(&tmp_path)
                 })?;
+                created_tmp = true;
 
                 let mut header = BDBFileHeader::new(table_type);
+                header.version = format_version;
+                header.compression = compression;
+                header.flags |= BDB_FLAG_SPARSE_INDEX | BDB_FLAG_TOMBSTONE_COUNT;
+                header.reserved = tombstones.min(u32::MAX as u64) as u32;
+                if prefix_compression {
+                    header.flags |= BDB_FLAG_PREFIX_INDEX;
+                }
                 header.write(&mut file)?;
                 let header_size = BDB_HEADER_SIZE;
 
@@ -734,61 +1043,76 @@ impl SSTable {
                 let mut max_entry_size = 0;
                 
                 let mut last_key: Vec<u8> = Vec::new();
-                let mut count = 0;
+                let mut block_start = offset;
+                let mut bloom = BloomFilter::with_budget(
+                    entries.len() * 2,
+                    config.bloom_fpr.for_table(table_type),
+                    config.bloom_budget_bytes.for_table(table_type),
+                );
+                // Entries are written field by field; buffer them into whole blocks.
+                let mut writer = io::BufWriter::with_capacity(BDB_BLOCK_SIZE * 4, &mut file);
 
                 for entry in entries.values() {
                     let bdb_entry = BDBLogEntry {
                         entry_type: entry.entry_type,
                         key: entry.key.clone(),
-                        value: entry.value.clone(),
+                        value: compression::compress(compression, &entry.value)?,
                         timestamp: entry.timestamp,
                         expires_at: entry.expires_at,
                         entry_crc: 0,
                     };
                     
-                    let pos = offset;
+                    // Start a new block once the current one reaches BDB_BLOCK_SIZE.
+                    // The first entry of a block never shares a prefix, so a
+                    // reader can start decoding from any block boundary.
+                    let new_block = index.is_empty() || offset - block_start >= BDB_BLOCK_SIZE as u64;
+                    if new_block {
+                        block_start = offset;
+                        index.push(IndexEntry {
+                            key: entry.key.clone(),
+                            position: offset,
+                            size: 0,
+                            timestamp: entry.timestamp,
+                        });
+                    }
 
                     // Prefix Compression Logic
-                    let shared = if count % BDB_RESTART_INTERVAL == 0 {
+                    let shared = if !prefix_compression || new_block {
                         0
                     } else {
-                        let mut shared = 0;
-                        while shared < last_key.len() && shared < entry.key.len() && last_key[shared] == entry.key[shared] {
-                            shared += 1;
-                        }
-                        shared
+                        shared_prefix_len(&last_key, &entry.key)
                     };
 
-                    // For now, we still use BDBLogEntry which writes the full key.
-                    // To support true prefix compression in the file, we'd need to modify BDBLogEntry::write.
-                    // Let's implement a "Compressed" write path.
-
-                    let size = self::write_compressed_entry(&mut file, &bdb_entry, shared)?;
+                    let size = self::write_compressed_entry(&mut writer, &bdb_entry, shared, format_version)?;
                     
                     if let Some(limiter) = &mut rate_limiter {
                         limiter.consume(size);
                     }
 
-                    index.push(IndexEntry {
-                        key: entry.key.clone(),
-                        position: pos,
-                        size,
-                        timestamp: entry.timestamp,
-                    });
+                    if let Some(block) = index.last_mut() {
+                        block.size += size;
+                        block.timestamp = block.timestamp.max(entry.timestamp);
+                    }
+                    bloom.add(&entry.key);
+                    bloom.add(extract_prefix(&entry.key));
                     
                     offset += size as u64;
                     total_key_size += entry.key.len() as u64;
                     max_entry_size = max_entry_size.max(size as u32);
                     last_key = entry.key.clone();
-                    count += 1;
                 }
+                writer.flush()?;
+                drop(writer);
 
                 let data_end = offset;
 
                 // Calculate Block Checksums
-                file.sync_all()?;
+                if sync {
+                    file.sync_all()?;
+                }
                 
                 let mut block_checksums = Vec::new();
+                let mut file_hasher = crc32fast::Hasher::new();
                 let mut curr = BDB_HEADER_SIZE as u64;
                 let mut buffer = vec![0u8; BDB_BLOCK_SIZE];
                 
@@ -802,6 +1126,7 @@ impl SSTable {
                     let mut hasher = crc32fast::Hasher::new();
                     hasher.update(&buffer[..to_read]);
                     block_checksums.push(hasher.finalize());
+                    file_hasher.update(&buffer[..to_read]);
                     curr += to_read as u64;
                 }
 
@@ -815,18 +1140,36 @@ impl SSTable {
                     offset += 4;
                 }
 
+                // Bloom filter block, between the checksums and the index
+                let bloom_bytes = bloom.to_bytes();
+                file.write_all(&bloom_bytes)?;
+                offset += bloom_bytes.len() as u64;
+
                 // Serialize the index block to the end of the file
                 let index_offset = offset;
                 // We'll write the number of index entries as u64, and then write each IndexEntry.
                 file.write_u64::<LittleEndian>(index.len() as u64)?;
                 offset += 8;
+                let mut prev_key: &[u8] = &[];
                 for idx in &index {
                     file.write_u64::<LittleEndian>(idx.position)?;
                     file.write_u64::<LittleEndian>(idx.size as u64)?;
                     file.write_u64::<LittleEndian>(idx.timestamp)?;
-                    file.write_u64::<LittleEndian>(idx.key.len() as u64)?;
-                    file.write_all(&idx.key)?;
-                    offset += 8 + 8 + 8 + 8 + idx.key.len() as u64;
+                    offset += 8 + 8 + 8;
+                    if prefix_compression {
+                        // Front-coded: shared length with the previous key, then the suffix.
+                        let shared = shared_prefix_len(prev_key, &idx.key);
+                        let suffix = &idx.key[shared..];
+                        offset += crate::core::format::write_varint(&mut file, shared as u64)? as u64;
+                        offset += crate::core::format::write_varint(&mut file, suffix.len() as u64)? as u64;
+                        file.write_all(suffix)?;
+                        offset += suffix.len() as u64;
+                        prev_key = &idx.key;
+                    } else {
+                        file.write_u64::<LittleEndian>(idx.key.len() as u64)?;
+                        file.write_all(&idx.key)?;
+                        offset += 8 + idx.key.len() as u64;
+                    }
                 }
 
                 let footer = BDBFileFooter {
@@ -839,39 +1182,54 @@ impl SSTable {
                     index_offset,
                     compression_ratio: 100,
                     reserved: [0; 2],
-                    file_crc: 0,
+                    file_crc: file_hasher.finalize(),
                 };
                 footer.write(&mut file)?;
-                file.sync_all()?;
+                if sync {
+                    file.sync_all()?;
+                }
                 
                 // On Windows, mapping a file that is open for writing can be problematic.
                 // We close the write handle first and retry opening for read/map.
                 drop(file);
+                retry_on_permission_denied(|| OpenOptions::new().write(true).create_new(true).open(&file_path))?;
+                reserved = true;
+                retry_on_permission_denied(|| fs::rename(&tmp_path, &file_path))?;
+                created_tmp = false;
+                reserved = false;
                 
                 let mmap = retry_on_permission_denied(|| {
                     let mmap_file = File::open(&file_path)?;
                     unsafe { Mmap::map(&mmap_file) }
                 })?;
                 
-                // Build Bloom Filter (including prefixes)
-                let mut bloom = BloomFilter::new(index.len() * 2, 0.01);
-                for idx in &index {
-                    bloom.add(&idx.key);
-                    bloom.add(extract_prefix(&idx.key));
-                }
-
-                Ok(Self {
+                Ok(Self::with_bounds(Self {
                     level,
                     file_path: file_path.clone(),
-                    mmap,
+                    mmap: ManuallyDrop::new(mmap),
                     index,
                     bloom_filter: Some(bloom),
                     block_checksums,
                     data_end: data_end as usize,
                     verify_checksums,
-                })
+                    format_version,
+                    compression,
+                    id: NEXT_SSTABLE_ID.fetch_add(1, AtomicOrdering::Relaxed),
+                    decoded: AtomicU64::new(0),
+                    obsolete: AtomicBool::new(false),
+                    entry_count: entries.len() as u64,
+                    tombstones,
+                    last_key: Vec::new(),
+                    newest_timestamp: 0,
+                }))
             })();
 
+            if res.is_err() && created_tmp {
+                let _ = fs::remove_file(&tmp_path);
+            }
+            if res.is_err() && reserved {
+                let _ = fs::remove_file(&file_path);
+            }
             match res {
                 Ok(sstable) => return Ok(sstable),
                 Err(_e) if attempts < 5 => {
@@ -884,19 +1242,127 @@ impl SSTable {
     }
     
     pub fn get(&self, key: &[u8]) -> Option<KVEntry> {
+        self.get_measured(key).0
+    }
+
+    /// [`SSTable::get`], along with the size of the block it read: 0 when
+    /// the bloom filter or the index rule the key out.
+    pub fn get_measured(&self, key: &[u8]) -> (Option<KVEntry>, u64) {
+        let Some(block) = self.block_for(key) else {
+            return (None, 0);
+        };
+        let read = block.size as u64;
+        for entry in self.block_iter(block) {
+            self.decoded.fetch_add(1, AtomicOrdering::Relaxed);
+            match entry {
+                Ok(kv) if kv.key.as_slice() == key => return (Some(kv), read),
+                Ok(kv) if kv.key.as_slice() > key => break,
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+        }
+
+        (None, read)
+    }
+
+    /// [`SSTable::get`] without copying the value out of the mmap.
+    pub fn get_ref(&self, key: &[u8]) -> Option<EntryRef<'_>> {
+        self.get_ref_measured(key).0
+    }
+
+    /// [`SSTable::get_ref`], along with the size of the block it read, as
+    /// [`SSTable::get_measured`] reports it.
+    pub fn get_ref_measured(&self, key: &[u8]) -> (Option<EntryRef<'_>>, u64) {
+        match self.block_for(key) {
+            Some(block) => (self.block_get_ref(block, key), block.size as u64),
+            None => (None, 0),
+        }
+    }
+
+    /// The version of `key` in this table with its value as stored, still
+    /// compressed if the table is, for callers that only need its type,
+    /// timestamp and expiry.
+    pub fn probe(&self, key: &[u8]) -> Option<EntryRef<'_>> {
+        self.block_find(self.block_for(key)?, key)
+    }
+
+    /// The last block whose first key is <= `key`, unless the bloom filter
+    /// or the index rule the key out.
+    fn block_for(&self, key: &[u8]) -> Option<&IndexEntry> {
         if let Some(bf) = &self.bloom_filter {
             if !bf.might_contain(key) {
                 return None;
             }
         }
+        let idx = self.index.partition_point(|b| b.key.as_slice() <= key);
+        idx.checked_sub(1).map(|idx| &self.index[idx])
+    }
 
-        if let Ok(idx) = self.index.binary_search_by(|i| i.key.as_slice().cmp(key)) {
-            return self.get_at_index(&self.index[idx]);
+    fn block_get_ref(&self, block: &IndexEntry, key: &[u8]) -> Option<EntryRef<'_>> {
+        let entry = self.block_find(block, key)?;
+        if self.compression == CompressionType::None {
+            return Some(entry);
         }
+        let value = Cow::Owned(compression::decompress(self.compression, &entry.value).ok()?);
+        Some(EntryRef { value, ..entry })
+    }
 
+    fn block_find(&self, block: &IndexEntry, key: &[u8]) -> Option<EntryRef<'_>> {
+        let mut offset = block.position as usize;
+        let limit = (offset + block.size).min(self.data_end);
+        let mut last_key = Vec::new(); // Block starts always have shared=0
+        while offset < limit {
+            if self.verify_checksums && self.verify_blocks(offset, offset + 1).is_err() {
+                return None;
+            }
+            let (entry, size) = read_entry_ref(&self.mmap[offset..limit], &last_key, self.format_version).ok()?;
+            self.decoded.fetch_add(1, AtomicOrdering::Relaxed);
+            match entry.key.as_slice().cmp(key) {
+                Ordering::Equal => return Some(entry),
+                Ordering::Greater => return None,
+                Ordering::Less => last_key = entry.key,
+            }
+            offset += size;
+        }
         None
     }
 
+    /// A value as stored in this table, decoded with its codec.
+    fn decode_value(&self, value: Vec<u8>) -> io::Result<Vec<u8>> {
+        if self.compression == CompressionType::None {
+            return Ok(value);
+        }
+        compression::decompress(self.compression, &value)
+    }
+
+    /// Newest write timestamp in the table, from the block index.
+    pub fn max_timestamp(&self) -> u64 {
+        self.newest_timestamp
+    }
+
+    /// Smallest and largest key in the table, or `None` if it is empty.
+    pub fn key_range(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        let first = self.index.first()?.key.clone();
+        Some((first, self.last_key.clone()))
+    }
+
+    /// Whether `key` lies within the table's key range.
+    pub fn covers(&self, key: &[u8]) -> bool {
+        self.index.first().is_some_and(|first| first.key.as_slice() <= key && key <= self.last_key.as_slice())
+    }
+
+    /// Iterate the entries of a single data block.
+    pub fn block_iter(&self, block: &IndexEntry) -> SSTableIterator<'_> {
+        let start = block.position as usize;
+        SSTableIterator {
+            sstable: self,
+            offset: start,
+            limit: (start + block.size).min(self.data_end),
+            last_key: Vec::new(), // Block starts always have shared=0
+        }
+    }
+
+    /// Read the first entry of the block described by `index_entry`.
     pub fn get_at_index(&self, index_entry: &IndexEntry) -> Option<KVEntry> {
         let start = index_entry.position as usize;
         let end = start + index_entry.size;
@@ -912,10 +1378,10 @@ impl SSTable {
         let data = &self.mmap[start..end];
         let mut cursor = io::Cursor::new(data);
 
-        if let Ok(log_entry) = read_compressed_entry(&mut cursor, &index_entry.key) {
+        if let Ok(log_entry) = read_compressed_entry(&mut cursor, &index_entry.key, self.format_version) {
             return Some(KVEntry {
                 key: log_entry.key,
-                value: log_entry.value,
+                value: self.decode_value(log_entry.value).ok()?,
                 timestamp: log_entry.timestamp,
                 expires_at: log_entry.expires_at,
                 entry_type: log_entry.entry_type,
@@ -925,6 +1391,10 @@ impl SSTable {
         None
     }
 
+    /// Every entry of the table in key order, tombstones included (with
+    /// `deleted` set). Values come back decompressed; values the tree moved
+    /// to its blob log are `EntryType::BlobIndex` pointers into it. The
+    /// iterator ends after the first error.
     pub fn iter(&self) -> SSTableIterator<'_> {
         let limit = self.data_end;
         SSTableIterator {
@@ -938,13 +1408,7 @@ impl SSTable {
     pub fn seek_prefix(&self, prefix: &[u8]) -> SSTableIterator<'_> {
         let limit = self.data_end;
 
-        // Find the first entry that might match the prefix
-        let idx = match self.index.binary_search_by(|i| i.key.as_slice().cmp(prefix)) {
-            Ok(i) => i,
-            Err(i) => i,
-        };
-
-        if idx >= self.index.len() {
+        if self.index.is_empty() {
             return SSTableIterator {
                 sstable: self,
                 offset: limit,
@@ -953,19 +1417,32 @@ impl SSTable {
             };
         }
 
-        // To make seek_prefix efficient and correct, we should jump to the nearest RESTART point
-        // BEFORE or AT the target index.
-        let restart_idx = (idx / BDB_RESTART_INTERVAL) * BDB_RESTART_INTERVAL;
+        // The first key >= prefix lives in the last block starting at or
+        // before the prefix (or at the start of the block after it).
+        let idx = self.index.partition_point(|b| b.key.as_slice() <= prefix).saturating_sub(1);
 
         SSTableIterator {
             sstable: self,
-            offset: self.index[restart_idx].position as usize,
+            offset: self.index[idx].position as usize,
             limit,
-            last_key: Vec::new(), // Restart point always has shared=0
+            last_key: Vec::new(), // Block starts always have shared=0
         }
     }
 
-    pub fn open(file_path: PathBuf, level: u8, verify_checksums: bool) -> io::Result<Self> {
+    /// Open an existing SSTable. `bloom_fpr` is only used when the file
+    /// carries no bloom filter and one has to be rebuilt from its keys.
+    pub fn open(file_path: PathBuf, level: u8, verify_checksums: bool, bloom_fpr: f64) -> io::Result<Self> {
+        Self::open_checked(file_path, level, verify_checksums, bloom_fpr, true)
+    }
+
+    /// Opens a table whose data region may be damaged, skipping the
+    /// whole-file CRC so intact blocks can still be read. Per-block
+    /// checksums are verified on every read instead.
+    pub fn open_for_repair(file_path: PathBuf, level: u8, bloom_fpr: f64) -> io::Result<Self> {
+        Self::open_checked(file_path, level, true, bloom_fpr, false)
+    }
+
+    fn open_checked(file_path: PathBuf, level: u8, verify_checksums: bool, bloom_fpr: f64, check_file_crc: bool) -> io::Result<Self> {
         let mmap = retry_on_permission_denied(|| {
             let file = OpenOptions::new().read(true).open(&file_path)?;
             unsafe { Mmap::map(&file) }
@@ -976,10 +1453,27 @@ impl SSTable {
         }
 
         let mut header_cursor = io::Cursor::new(&mmap[0..BDB_HEADER_SIZE]);
-        let _header = BDBFileHeader::read(&mut header_cursor)?;
+        let header = BDBFileHeader::read(&mut header_cursor)?;
+        if header.version == 0 || header.version > BDB_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "{} is in on-disk format version {}, this build reads versions 1 to {}",
+                    file_path.display(),
+                    header.version,
+                    BDB_VERSION
+                ),
+            ));
+        }
+        if !compression::is_available(header.compression) {
+            return Err(compression::unsupported_in(header.compression, header.table_type, file_path));
+        }
+        let prefix_index = header.flags & BDB_FLAG_PREFIX_INDEX != 0;
+        let sparse_index = header.flags & BDB_FLAG_SPARSE_INDEX != 0;
 
         let mut footer_cursor = io::Cursor::new(&mmap[mmap.len()-BDB_FOOTER_SIZE..]);
         let footer = BDBFileFooter::read(&mut footer_cursor)?;
+        verify_footer(&footer, &mmap, check_file_crc)?;
 
         // Load block checksums
         let mut block_checksums = Vec::new();
@@ -996,21 +1490,36 @@ impl SSTable {
             }
         }
 
-        let mut index = Vec::new();
         let index_offset = footer.index_offset as usize;
         let footer_start = mmap.len() - BDB_FOOTER_SIZE;
+
+        let mut bloom_filter = None;
+        let bloom_offset = block_crc_offset + block_checksums.len() * 4;
+        if sparse_index && bloom_offset < index_offset && index_offset <= footer_start {
+            bloom_filter = BloomFilter::from_bytes(&mmap[bloom_offset..index_offset]);
+        }
+
+        let mut index = Vec::new();
         if index_offset > 0 && index_offset < footer_start {
             let mut index_cursor = io::Cursor::new(&mmap[index_offset..footer_start]);
             if let Ok(entry_count) = index_cursor.read_u64::<LittleEndian>() {
+                let mut prev_key: Vec<u8> = Vec::new();
                 for _ in 0..entry_count {
-                    if let (Ok(position), Ok(size), Ok(timestamp), Ok(key_len)) = (
-                        index_cursor.read_u64::<LittleEndian>(),
+                    if let (Ok(position), Ok(size), Ok(timestamp)) = (
                         index_cursor.read_u64::<LittleEndian>(),
                         index_cursor.read_u64::<LittleEndian>(),
                         index_cursor.read_u64::<LittleEndian>(),
                     ) {
-                        let mut key = vec![0u8; key_len as usize];
-                        if index_cursor.read_exact(&mut key).is_ok() {
+                        let key = if prefix_index {
+                            read_front_coded_key(&mut index_cursor, &prev_key)
+                        } else {
+                            index_cursor.read_u64::<LittleEndian>().and_then(|key_len| {
+                                let mut key = vec![0u8; key_len as usize];
+                                index_cursor.read_exact(&mut key).map(|_| key)
+                            })
+                        };
+                        if let Ok(key) = key {
+                            prev_key.clone_from(&key);
                             index.push(IndexEntry {
                                 key,
                                 position,
@@ -1028,13 +1537,14 @@ impl SSTable {
         }
 
         // Fallback to full file parsing if index loading failed or is empty
+        let rows_indexed = !sparse_index || index.is_empty();
         if index.is_empty() {
             let mut offset = BDB_HEADER_SIZE;
             let data_end = footer.block_crc_offset as usize;
             let mut last_key = Vec::new();
             while offset < data_end {
                 let mut cursor = io::Cursor::new(&mmap[offset..data_end]);
-                match read_compressed_entry(&mut cursor, &last_key) {
+                match read_compressed_entry(&mut cursor, &last_key, header.version) {
                     Ok(entry) => {
                         let size = cursor.position() as usize;
                         index.push(IndexEntry {
@@ -1051,23 +1561,59 @@ impl SSTable {
             }
         }
         
-        // Build Bloom Filter (including prefixes)
-        let mut bloom = BloomFilter::new(index.len() * 2, 0.01);
-        for idx in &index {
-            bloom.add(&idx.key);
-            bloom.add(extract_prefix(&idx.key));
+        // Files written before the sparse layout (or recovered by a full
+        // scan) have one index entry per row and no stored bloom filter.
+        if rows_indexed && index.len() as u64 != footer.entry_count {
+            return Err(corruption(format!(
+                "footer counts {} entries, found {}",
+                footer.entry_count,
+                index.len()
+            )));
+        }
+        if bloom_filter.is_none() && rows_indexed {
+            let mut bloom = BloomFilter::new(index.len() * 2, bloom_fpr);
+            for idx in &index {
+                bloom.add(&idx.key);
+                bloom.add(extract_prefix(&idx.key));
+            }
+            bloom_filter = Some(bloom);
+        }
+        if rows_indexed {
+            index = group_rows_into_blocks(&mmap, index);
         }
 
-        Ok(Self {
+        Ok(Self::with_bounds(Self {
             level,
             file_path,
-            mmap,
+            mmap: ManuallyDrop::new(mmap),
             index,
-            bloom_filter: Some(bloom),
+            bloom_filter,
             block_checksums,
             data_end: footer.block_crc_offset as usize,
             verify_checksums,
-        })
+            format_version: header.version,
+            compression: header.compression,
+            id: NEXT_SSTABLE_ID.fetch_add(1, AtomicOrdering::Relaxed),
+            decoded: AtomicU64::new(0),
+            obsolete: AtomicBool::new(false),
+            entry_count: footer.entry_count,
+            tombstones: if header.flags & BDB_FLAG_TOMBSTONE_COUNT != 0 { header.reserved as u64 } else { 0 },
+            last_key: Vec::new(),
+            newest_timestamp: 0,
+        }))
+    }
+
+    /// Fills in `last_key` and `newest_timestamp` of a freshly loaded table.
+    fn with_bounds(mut self) -> Self {
+        self.newest_timestamp = self.index.iter().map(|block| block.timestamp).max().unwrap_or(0);
+        if let Some(last) = self.index.last() {
+            // A damaged block keeps failing; stop at the first error.
+            self.last_key = self.block_iter(last)
+                .map_while(Result::ok)
+                .last()
+                .map_or_else(|| last.key.clone(), |kv| kv.key);
+        }
+        self
     }
 }
 
@@ -1100,12 +1646,92 @@ pub struct LSMTree {
     pub inner: Arc<LSMTreeInner>,
 }
 
+/// Output of a heat-aware compaction: the hot table staying in the level
+/// and the cold table moving down.
+type HotColdTables = (Option<Arc<SSTable>>, Option<Arc<SSTable>>);
+
 #[derive(Debug)]
 pub struct CompactionTask {
     pub level: usize,
     pub created_at: SystemTime,
 }
 
+/// Shape of one LSM level, see [`LSMTree::level_report`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelInfo {
+    pub level: usize,
+    pub sstables: usize,
+    pub bytes: u64,
+    /// Smallest and largest key across the level's tables. Level 0 tables
+    /// overlap, so every one of them may be probed for a key in this span.
+    pub key_range: Option<(Vec<u8>, Vec<u8>)>,
+    /// Memory taken by the level's bloom filters.
+    pub bloom_bytes: u64,
+    /// Mean fill-based false-positive rate of the level's bloom filters,
+    /// or `None` if it has none. Above the configured `bloom_fpr` when
+    /// `bloom_budget_bytes` made the filters smaller.
+    pub bloom_fpr: Option<f64>,
+    /// Tombstones across the level's tables.
+    pub tombstones: u64,
+    /// Share of the level's entries that are tombstones, 0 when empty.
+    /// Compared against `tombstone_compaction_ratio`.
+    pub tombstone_density: f64,
+}
+
+/// I/O a tree has done since it was opened, see [`LSMTree::amplification`].
+/// Reads cover `get` only: scans and `with_value` are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Amplification {
+    /// Keys and values handed to writes.
+    pub user_bytes_written: u64,
+    /// WAL appends, plus every SSTable written by flushes, bulk loads and
+    /// compactions.
+    pub disk_bytes_written: u64,
+    /// Keys and values `get` returned.
+    pub bytes_returned: u64,
+    /// SSTable blocks `get` read to find them. Entry cache hits read none.
+    pub disk_bytes_read: u64,
+}
+
+impl Amplification {
+    /// Bytes written to disk per byte of user data; 0 before any write.
+    pub fn write_amplification(&self) -> f64 {
+        ratio(self.disk_bytes_written, self.user_bytes_written)
+    }
+
+    /// SSTable bytes read per byte returned; 0 before any read.
+    pub fn read_amplification(&self) -> f64 {
+        ratio(self.disk_bytes_read, self.bytes_returned)
+    }
+}
+
+fn ratio(a: u64, b: u64) -> f64 {
+    if b == 0 { 0.0 } else { a as f64 / b as f64 }
+}
+
+/// Share of the entries in `tables` that are tombstones.
+fn tombstone_density(tables: &[Arc<SSTable>]) -> f64 {
+    let entries: u64 = tables.iter().map(|t| t.entry_count).sum();
+    ratio(tables.iter().map(|t| t.tombstones).sum(), entries)
+}
+
+/// Tombstones a level needs before their density alone makes it due for
+/// compaction, so a handful of deletes does not rewrite small levels.
+const TOMBSTONE_COMPACTION_MIN: u64 = 1000;
+
+/// Something wrong found when a tree was opened, see
+/// [`LSMTree::open_warnings`]: an SSTable that could not be loaded, whose
+/// entries are missing from the tree, or a record left by a previous
+/// shutdown whose final flush failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenWarning {
+    pub file: PathBuf,
+    pub reason: String,
+    /// Where a damaged file was moved for `BrowserDB::repair` to salvage;
+    /// `None` if it was left in place, e.g. because it is missing.
+    pub quarantined: Option<PathBuf>,
+}
+
 pub struct CompactionQueue {
     pub pending: Vec<CompactionTask>,
     pub active_levels: HashSet<usize>,
@@ -1114,12 +1740,16 @@ pub struct CompactionQueue {
 pub struct LSMTreeInner {
     pub memtable: [RwLock<MemTable>; 16],
     pub frozen: [Mutex<Option<MemTable>>; 16],
-    pub levels: Vec<RwLock<Vec<Arc<SSTable>>>>, // 10 levels
+    pub levels: Vec<RwLock<Vec<Arc<SSTable>>>>, // `LsmTreeConfig::max_levels`, fixed per tree by the manifest
     pub base_path: PathBuf,
     pub table_type: TableType,
     pub wal: RwLock<WALManager>,
     pub blob_log: Arc<BlobLog>,
     pub heat_tracker: HeatTracker,
+    pub entry_cache: EntryCache,
+    /// Source of write timestamps.
+    pub clock: MonotonicClock,
+    pub manifest: Mutex<Manifest>,
     pub config: crate::core::config::BrowserDBConfig,
     pub indices: Vec<IndexDefinitionInternal>,
     pub is_index: bool,
@@ -1127,12 +1757,40 @@ pub struct LSMTreeInner {
     pub compaction_state: Arc<(Mutex<CompactionQueue>, Condvar)>,
     pub flush_state: Arc<(Mutex<()>, Condvar)>,
     pub flush_pending: std::sync::atomic::AtomicBool,
+    /// Writes since the memtable was last frozen by op count, checked
+    /// against `memtable_max_ops`.
+    pub memtable_ops: std::sync::atomic::AtomicUsize,
+    /// Freezes since the flush thread last collected the frozen buffers,
+    /// checked against `max_pending_flushes`.
+    pub pending_flushes: std::sync::atomic::AtomicUsize,
+    /// Held while frozen buffers are turned into SSTables, so `flush` does
+    /// not return while the flush thread still has one in flight.
+    pub flush_lock: Mutex<()>,
     pub flush_seq: AtomicU64,
+    /// Flushes and compactions finished since open, for metrics.
+    pub flushes: AtomicU64,
+    pub compactions: AtomicU64,
+    /// Writes held back by backpressure, and how long they waited
+    /// in total, for metrics.
+    pub write_stalls: AtomicU64,
+    pub write_stall_ms: AtomicU64,
+    /// Counters behind [`LSMTree::amplification`]; WAL bytes are the
+    /// WAL's own.
+    pub user_bytes_written: AtomicU64,
+    pub sstable_bytes_written: AtomicU64,
+    pub bytes_returned: AtomicU64,
+    pub sstable_bytes_read: AtomicU64,
     pub last_truncated_seq: AtomicU64,
     pub power_save_mode: std::sync::atomic::AtomicBool,
     pub low_memory_mode: std::sync::atomic::AtomicBool,
     pub shutdown: Arc<std::sync::atomic::AtomicBool>,
     pub shutdown_flush: Arc<std::sync::atomic::AtomicBool>,
+    /// The compaction and flush threads, joined by `LSMTree::close`.
+    pub workers: Mutex<Vec<std::thread::JoinHandle<()>>>,
+    /// Set by `LSMTree::close`; a closed tree is not flushed again on drop.
+    pub closed: std::sync::atomic::AtomicBool,
+    /// SSTables that failed to load at open.
+    pub open_warnings: Vec<OpenWarning>,
 }
 
 pub struct IndexDefinitionInternal {
@@ -1142,6 +1800,172 @@ pub struct IndexDefinitionInternal {
     pub tree: LSMTree,
 }
 
+/// What [`LSMTree::capture`] pinned of one tree: enough to copy its files
+/// while it stays in use.
+pub struct TreeCapture {
+    pub base_path: PathBuf,
+    /// File prefix shared by the tree's WAL, manifest, blob log and SSTables.
+    pub prefix: String,
+    pub levels: usize,
+    /// Live SSTables and their levels, in manifest order. Compaction cannot
+    /// unlink their files until the capture is dropped.
+    pub sstables: Vec<(u8, Arc<SSTable>)>,
+    pub blob_log: Arc<BlobLog>,
+}
+
+/// A point-in-time read view of an [`LSMTree`], taken by
+/// [`LSMTree::snapshot`]. Holds copies of the memtables and `Arc`s to the
+/// SSTables that were live at that moment, so later writes, flushes and
+/// compactions do not change what it returns. A compacted SSTable's file
+/// is only unlinked once the last snapshot holding it is dropped.
+pub struct LsmSnapshot {
+    /// Active memtable entries, then frozen ones; newest layer first.
+    memtables: [BTreeMap<Vec<u8>, KVEntry>; 2],
+    levels: Vec<Vec<Arc<SSTable>>>,
+    blob_log: Arc<BlobLog>,
+    clock: SharedClock,
+}
+
+impl LsmSnapshot {
+    /// [`LSMTree::get`] as of the moment the snapshot was taken. TTLs keep
+    /// running: an entry that expires after the snapshot is hidden once it
+    /// does.
+    pub fn get(&self, key: &[u8]) -> Option<KVEntry> {
+        let mut fold = VersionFold::default();
+        let resolved = match self.memtables.iter().filter_map(|mem| mem.get(key).cloned()).find_map(|entry| fold.add(entry)) {
+            Some(resolved) => resolved,
+            None => {
+                let mut versions = TableVersions::default();
+                for sstable in self.levels.iter().flat_map(|level| level.iter().rev()) {
+                    if versions.may_hold(sstable, key) {
+                        if let Some(entry) = sstable.get(key) {
+                            versions.add(&fold, entry);
+                        }
+                    }
+                }
+                versions.resolve(fold)?
+            }
+        };
+        visible_value(resolved, &self.blob_log, self.clock.now_ms())
+    }
+}
+
+/// Folds the versions of one key, newest first, into the value a read
+/// sees: the first non-increment entry is the base, and any increments
+/// seen before it are summed on top of it.
+#[derive(Default)]
+struct VersionFold {
+    delta_sum: i64,
+    newest_increment: Option<KVEntry>,
+    /// Timestamps of the increments folded in so far.
+    seen: Vec<u64>,
+}
+
+impl VersionFold {
+    /// Whether the version written at `timestamp` was already folded in,
+    /// e.g. from a frozen buffer whose SSTable is being published.
+    fn has_seen(&self, timestamp: u64) -> bool {
+        self.seen.contains(&timestamp)
+    }
+
+    /// Returns the resolved entry once `entry` settles the key.
+    fn add(&mut self, entry: KVEntry) -> Option<KVEntry> {
+        if entry.entry_type == EntryType::Increment {
+            self.seen.push(entry.timestamp);
+            self.delta_sum = self.delta_sum.wrapping_add(le_i64(&entry.value).unwrap_or(0));
+            if self.newest_increment.is_none() {
+                self.newest_increment = Some(entry);
+            }
+            return None;
+        }
+        match self.newest_increment.take() {
+            Some(increment) => {
+                let base = le_i64(&entry.value).unwrap_or(0);
+                Some(Self::sum_into(increment, base.wrapping_add(self.delta_sum)))
+            }
+            None => Some(entry),
+        }
+    }
+
+    /// Only increments were found; their base is 0.
+    fn finish(self) -> Option<KVEntry> {
+        let delta_sum = self.delta_sum;
+        self.newest_increment.map(|increment| Self::sum_into(increment, delta_sum))
+    }
+
+    fn sum_into(mut entry: KVEntry, sum: i64) -> KVEntry {
+        entry.value = sum.to_le_bytes().to_vec();
+        entry.entry_type = EntryType::Insert;
+        entry.deleted = false;
+        entry
+    }
+}
+
+/// The SSTable versions of one key, for folding on top of the memtable's.
+/// Tables are visited shallowest level first and newest first within a
+/// level, which is newest version first as long as every table sits where
+/// flushes and compactions put it. A repair, or a copy read while its flush
+/// is being published, can break that, so a version found does not end the
+/// walk: later tables are still probed unless their key range or newest
+/// timestamp rules out anything newer. Versions are then folded by
+/// timestamp, and a version seen twice counts once.
+#[derive(Default)]
+struct TableVersions {
+    found: Vec<KVEntry>,
+    /// Timestamp of the newest non-increment version found, below which
+    /// nothing can change the result.
+    settled_at: Option<u64>,
+}
+
+impl TableVersions {
+    fn may_hold(&self, sstable: &SSTable, key: &[u8]) -> bool {
+        sstable.covers(key) && self.settled_at.is_none_or(|ts| sstable.max_timestamp() > ts)
+    }
+
+    fn add(&mut self, fold: &VersionFold, entry: KVEntry) {
+        if fold.has_seen(entry.timestamp) || self.found.iter().any(|e| e.timestamp == entry.timestamp) {
+            return;
+        }
+        if entry.entry_type != EntryType::Increment {
+            self.settled_at = self.settled_at.max(Some(entry.timestamp));
+        }
+        self.found.push(entry);
+    }
+
+    fn resolve(mut self, mut fold: VersionFold) -> Option<KVEntry> {
+        self.found.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+        for entry in self.found {
+            if let Some(resolved) = fold.add(entry) {
+                return Some(resolved);
+            }
+        }
+        fold.finish()
+    }
+}
+
+fn le_i64(bytes: &[u8]) -> Option<i64> {
+    <[u8; 8]>::try_from(bytes).ok().map(i64::from_le_bytes)
+}
+
+/// Hide deleted and entries expired by `now` and load blob-separated
+/// values.
+fn visible_value(mut entry: KVEntry, blob_log: &BlobLog, now: u64) -> Option<KVEntry> {
+    if entry.deleted {
+        return None;
+    }
+    if entry.expires_at > 0 && entry.expires_at < now {
+        return None;
+    }
+    if entry.entry_type == EntryType::BlobIndex {
+        if let Some(ptr) = BlobPointer::decode(&entry.value) {
+            if let Ok(val) = blob_log.get(&ptr) {
+                entry.value = val;
+            }
+        }
+    }
+    Some(entry)
+}
+
 impl LSMTree {
     pub fn new(base_path: &Path, table_type: TableType, max_memtable_size: usize, config: crate::core::config::BrowserDBConfig) -> io::Result<Self> {
         Self::new_with_indices(base_path, table_type, max_memtable_size, config, Vec::new())
@@ -1174,11 +1998,6 @@ impl LSMTree {
         index_defs: Vec<IndexDefinition>,
         is_index: bool,
     ) -> io::Result<Self> {
-        let mut levels = Vec::with_capacity(10);
-        for _ in 0..10 {
-            levels.push(RwLock::new(Vec::new()));
-        }
-
         let wal_path = base_path.join(format!("{}.wal", match table_type {
             TableType::History => "history",
             TableType::Cookies => "cookies",
@@ -1188,7 +2007,7 @@ impl LSMTree {
             TableType::Bookmarks => "bookmarks",
             TableType::BinaryStore => "binarystore",
         }));
-        let wal = WALManager::new(&wal_path)?;
+        let wal = WALManager::open(&wal_path, table_type, config.lsm_tree.fsync_policy, config.lsm_tree.wal_segment_bytes)?;
 
         let blob_path = base_path.join(format!("{}.blob", match table_type {
             TableType::History => "history",
@@ -1215,8 +2034,9 @@ impl LSMTree {
 
         // Recover from WAL
         let entries = wal.read_all()?;
+        let mut replayed = 0;
         let mut in_batch = false;
-        let mut batch_entries: Vec<(Vec<u8>, Vec<u8>, EntryType)> = Vec::new();
+        let mut batch_entries: Vec<(Vec<u8>, Vec<u8>, EntryType, u64)> = Vec::new();
 
         for entry in entries {
             match entry.entry_type {
@@ -1226,23 +2046,28 @@ impl LSMTree {
                 }
                 EntryType::BatchEnd => {
                     if in_batch {
-                        for (k, v, t) in batch_entries.drain(..) {
+                        for (k, v, t, timestamp) in batch_entries.drain(..) {
                             let shard = (k.first().cloned().unwrap_or(0) % 16) as usize;
-                            memtable[shard].write().put(k, v, t, 0);
+                            memtable[shard].write().put(k, v, t, 0, timestamp);
+                            replayed += 1;
                         }
                         in_batch = false;
                     }
                 }
                 _ => {
                     if in_batch {
-                        batch_entries.push((entry.key, entry.value, entry.entry_type));
+                        batch_entries.push((entry.key, entry.value, entry.entry_type, entry.timestamp));
                     } else {
                         let shard = (entry.key.first().cloned().unwrap_or(0) % 16) as usize;
-                        memtable[shard].write().put(entry.key, entry.value, entry.entry_type, entry.expires_at);
+                        memtable[shard].write().put(entry.key, entry.value, entry.entry_type, entry.expires_at, entry.timestamp);
+                        replayed += 1;
                     }
                 }
             }
         }
+        if replayed > 0 && !is_index {
+            config.events.emit(&DbEvent::WalReplayed { table: table_type, entries: replayed });
+        }
 
         // Initialize indices
         let mut indices = Vec::new();
@@ -1269,49 +2094,121 @@ impl LSMTree {
             });
         }
 
-        // Recover existing SSTables
-        if let Ok(entries) = fs::read_dir(base_path) {
-            let prefix = match table_type {
-                TableType::History => "history",
-                TableType::Cookies => "cookies",
-                TableType::Cache => "cache",
-                TableType::LocalStore => "localstore",
-                TableType::Settings => "settings",
-                TableType::Bookmarks => "bookmarks",
-                TableType::BinaryStore => "binarystore",
-            };
-            
-            let mut loaded_sstables: Vec<(u8, Arc<SSTable>)> = Vec::new();
-
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().map_or(false, |ext| ext == "sst") {
-                    if let Some(fname) = path.file_name().and_then(|n| n.to_str()) {
-                        if fname.starts_with(prefix) {
-                            // Parse level from filename: prefix_level_timestamp_count.sst
-                            let parts: Vec<&str> = fname.split('_').collect();
-                            if parts.len() >= 2 {
-                                if let Ok(level) = parts[1].parse::<u8>() {
-                                    if level < 10 {
-                                        if let Ok(sst) = SSTable::open(path, level, config.lsm_tree.verify_checksums) {
-                                            loaded_sstables.push((level, Arc::new(sst)));
-                                        }
-                                    }
-                                }
-                            }
+        // Recover existing SSTables. The manifest is authoritative; files it
+        // does not list are leftovers of a flush or compaction that never
+        // committed, and their data is still covered by the WAL or by the
+        // tables they were merged from.
+        let manifest = Manifest::new(base_path, table_prefix);
+        let recorded = manifest.load().unwrap_or_else(|e| {
+            eprintln!("Ignoring unreadable manifest {}: {}", manifest.path().display(), e);
+            None
+        });
+        // A tree keeps the level count it was created with; the config only
+        // applies to new trees.
+        let level_count = recorded.as_ref().map(|(count, _)| *count).unwrap_or_else(|| config.lsm_tree.level_count());
+        let levels: Vec<RwLock<Vec<Arc<SSTable>>>> = (0..level_count).map(|_| RwLock::new(Vec::new())).collect();
+        let file_prefix = format!("{}_", table_prefix);
+        let table_files: Vec<PathBuf> = fs::read_dir(base_path)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(&file_prefix)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // `.sst.tmp` files are SSTables whose write never finished, and an
+        // empty `.sst` is the name reserved for one.
+        let (sstable_files, partial_files): (Vec<PathBuf>, Vec<PathBuf>) = table_files
+            .into_iter()
+            .filter(|p| p.extension().is_some_and(|ext| ext == "sst" || ext == "tmp"))
+            .partition(|p| p.extension().is_some_and(|ext| ext == "sst") && !is_reserved_name(p));
+        for path in partial_files {
+            let _ = fs::remove_file(&path);
+        }
+
+        let bloom_fpr = config.lsm_tree.bloom_fpr.for_table(table_type);
+        let mut loaded_sstables: Vec<(u8, Arc<SSTable>)> = Vec::new();
+        let mut open_warnings: Vec<OpenWarning> = take_flush_error(base_path, table_prefix, replayed).into_iter().collect();
+        match recorded {
+            Some((_, tables)) => {
+                for (level, name) in &tables {
+                    if *level as usize >= level_count {
+                        continue;
+                    }
+                    match SSTable::open(base_path.join(name), *level, config.lsm_tree.verify_checksums, bloom_fpr) {
+                        Ok(sst) => loaded_sstables.push((*level, Arc::new(sst))),
+                        Err(e) if e.kind() == io::ErrorKind::Unsupported => return Err(e),
+                        Err(e) => open_warnings.push(set_aside_sstable(base_path, &base_path.join(name), &e)),
+                    }
+                }
+                for path in sstable_files {
+                    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                    if !tables.iter().any(|(_, t)| t == name) {
+                        if let Err(e) = retry_on_permission_denied(|| fs::remove_file(&path)) {
+                            eprintln!("Failed to remove orphan SSTable {}: {}", path.display(), e);
                         }
                     }
                 }
             }
-            
-            // Add to levels
-            for (level, sst) in loaded_sstables {
-                levels[level as usize].write().push(sst);
+            None => {
+                // No manifest yet: derive levels from filenames
+                // (prefix_level_timestamp_counter.sst).
+                for path in sstable_files {
+                    let level = path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .and_then(|n| n.split('_').nth(1))
+                        .and_then(|l| l.parse::<u8>().ok());
+                    if let Some(level) = level.filter(|l| (*l as usize) < level_count) {
+                        match SSTable::open(path.clone(), level, config.lsm_tree.verify_checksums, bloom_fpr) {
+                            Ok(sst) => loaded_sstables.push((level, Arc::new(sst))),
+                            Err(e) if e.kind() == io::ErrorKind::Unsupported => return Err(e),
+                            Err(e) => open_warnings.push(set_aside_sstable(base_path, &path, &e)),
+                        }
+                    }
+                }
+                // Directory listings come in no particular order. Put each
+                // level oldest first by the newest write a table holds: a
+                // merge result gets a later filename than tables flushed
+                // while it ran, but holds older data.
+                loaded_sstables.sort_by_cached_key(|(level, sst)| (*level, sst.max_timestamp(), sstable_file_order(&sst.file_path)));
             }
         }
+
+        // Later writes must outrank everything already stored, even if the
+        // clock is now behind the one that wrote it.
+        let clock = MonotonicClock::new(config.clock.clone());
+        // Tables in an older format are rewritten in the current one, keys
+        // included. The originals are only unlinked once the manifest names
+        // the copies.
+        let mut upgraded = Vec::new();
+        for (level, sst) in loaded_sstables {
+            clock.observe(sst.max_timestamp());
+            let sst = if sst.format_version < BDB_VERSION {
+                let entries = sst
+                    .iter()
+                    .map(|e| {
+                        e.map(|mut e| {
+                            upgrade_key(table_type, sst.format_version, &mut e.key);
+                            (e.key.clone(), e)
+                        })
+                    })
+                    .collect::<io::Result<BTreeMap<_, _>>>()?;
+                let copy = SSTable::create(level, &entries, base_path, table_type, None, &config.lsm_tree)?;
+                upgraded.push(sst);
+                Arc::new(copy)
+            } else {
+                sst
+            };
+            levels[level as usize].write().push(sst);
+        }
+        for shard in &memtable {
+            clock.observe(shard.read().entries.values().map(|e| e.timestamp).max().unwrap_or(0));
+        }
         
         let last_active_time = Arc::new(AtomicU64::new(
-            SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+            config.clock.now_ms()
         ));
 
         let compaction_state = Arc::new((
@@ -1335,7 +2232,10 @@ impl LSMTree {
             table_type,
             wal: RwLock::new(wal),
             blob_log,
-            heat_tracker: HeatTracker::new(config.heatmap.max_entries),
+            heat_tracker: HeatTracker::with_clock(config.heatmap.max_entries, config.clock.clone()),
+            entry_cache: EntryCache::new(if is_index { 0 } else { config.lsm_tree.entry_cache_entries }),
+            clock,
+            manifest: Mutex::new(manifest),
             config,
             indices,
             is_index,
@@ -1343,17 +2243,42 @@ impl LSMTree {
             compaction_state,
             flush_state,
             flush_pending: std::sync::atomic::AtomicBool::new(false),
+            memtable_ops: std::sync::atomic::AtomicUsize::new(0),
+            pending_flushes: std::sync::atomic::AtomicUsize::new(0),
+            flush_lock: Mutex::new(()),
             flush_seq: AtomicU64::new(0),
+            flushes: AtomicU64::new(0),
+            compactions: AtomicU64::new(0),
+            write_stalls: AtomicU64::new(0),
+            write_stall_ms: AtomicU64::new(0),
+            user_bytes_written: AtomicU64::new(0),
+            sstable_bytes_written: AtomicU64::new(0),
+            bytes_returned: AtomicU64::new(0),
+            sstable_bytes_read: AtomicU64::new(0),
             last_truncated_seq: AtomicU64::new(0),
             power_save_mode: std::sync::atomic::AtomicBool::new(false),
             low_memory_mode: std::sync::atomic::AtomicBool::new(false),
             shutdown: Arc::clone(&shutdown),
             shutdown_flush: Arc::clone(&shutdown_flush),
+            workers: Mutex::new(Vec::new()),
+            closed: std::sync::atomic::AtomicBool::new(false),
+            open_warnings,
         });
+        inner.persist_manifest()?;
+        for sst in upgraded {
+            sst.mark_obsolete();
+        }
+
+        Self::start_workers(&inner);
+
+        Ok(Self { inner })
+    }
 
+    /// Starts the background compaction and flush threads.
+    fn start_workers(inner: &Arc<LSMTreeInner>) {
         // Start background compaction worker thread
-        let inner_clone = Arc::clone(&inner);
-        std::thread::spawn(move || {
+        let inner_clone = Arc::clone(inner);
+        let compaction_worker = std::thread::spawn(move || {
             // Set thread priorities (lowest)
             #[cfg(unix)]
             unsafe {
@@ -1381,11 +2306,11 @@ impl LSMTree {
                     let mut queue = lock.lock().unwrap();
                     loop {
                         // Check if we can run any pending task
-                        let mut task_idx = None;
+                        let mut runnable = Vec::new();
                         for (idx, pending_task) in queue.pending.iter().enumerate() {
                             if !queue.active_levels.contains(&pending_task.level) {
                                 // Check if we should delay due to "Silent Window" + "Max Deadline"
-                                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+                                let now = inner_clone.now_ms();
                                 let last_active = inner_clone.last_active_time.load(AtomicOrdering::Relaxed);
                                 let idle_duration = now.saturating_sub(last_active);
                                 
@@ -1396,10 +2321,16 @@ impl LSMTree {
                                 let deadline = inner_clone.config.lsm_tree.compaction_deadline_sec;
                                 let idle_threshold = inner_clone.config.lsm_tree.compaction_idle_threshold_ms;
 
+                                // Writers are held back from `l0_slowdown_files` on
+                                // (see `throttle_write`); they never go idle, so
+                                // waiting for the silent window would stall them
+                                // until the deadline.
+                                let writers_stopped = pending_task.level == 0 && l0_count >= inner_clone.config.lsm_tree.l0_stop_files;
                                 let force_run = (l0_count >= 4 && time_pending >= deadline) || 
                                                 (inner_clone.config.lsm_tree.max_level0_files >= 4 && 
                                                  l0_count >= inner_clone.config.lsm_tree.max_level0_files && 
-                                                 time_pending >= deadline);
+                                                 time_pending >= deadline) ||
+                                                (pending_task.level == 0 && l0_count >= inner_clone.config.lsm_tree.l0_slowdown_files);
                                 
                                 // If power_save_mode is enabled, defer/delay compaction tasks in worker queue (unless low_memory_mode is active)
                                 let power_save = inner_clone.power_save_mode.load(AtomicOrdering::SeqCst);
@@ -1408,22 +2339,28 @@ impl LSMTree {
                                 let mut is_idle_or_forced = idle_duration >= idle_threshold || force_run;
                                 if power_save && !low_mem {
                                     // Defer/disable compaction tasks: only run if extremely forced (e.g., time_pending >= 5 * deadline)
-                                    is_idle_or_forced = force_run && (time_pending >= 5 * deadline);
+                                    is_idle_or_forced = (force_run && (time_pending >= 5 * deadline)) || writers_stopped;
                                 }
 
                                 if is_idle_or_forced {
-                                    task_idx = Some(idx);
-                                    break;
+                                    runnable.push(idx);
                                 }
                             }
                         }
 
+                        // Of the levels ready to go, merge the least read
+                        // first; rewriting them costs lookups the least.
+                        let task_idx = runnable.into_iter().min_by_key(|idx| inner_clone.level_heat(queue.pending[*idx].level));
                         if let Some(idx) = task_idx {
                             let t = queue.pending.remove(idx);
                             queue.active_levels.insert(t.level);
                             break Some(t);
                         }
 
+                        if inner_clone.shutdown.load(AtomicOrdering::Relaxed) {
+                            break None;
+                        }
+
                         // Wait for a notification or a timeout to check silent window / deadline again
                         let result = cvar.wait_timeout(queue, std::time::Duration::from_millis(100)).unwrap();
                         queue = result.0;
@@ -1431,6 +2368,30 @@ impl LSMTree {
                 };
 
                 if let Some(t) = task {
+                    // Held for the whole job; other tables wait for a slot
+                    // once `compaction_workers` jobs are running.
+                    let workers = inner_clone.config.lsm_tree.compaction_workers;
+                    let slot = loop {
+                        if inner_clone.shutdown.load(AtomicOrdering::Relaxed) {
+                            break None;
+                        }
+                        if let Some(slot) = inner_clone.config.compaction_pool.acquire_timeout(workers, std::time::Duration::from_millis(100)) {
+                            break Some(slot);
+                        }
+                    };
+                    if slot.is_none() {
+                        break;
+                    }
+
+                    if t.level == 0 && inner_clone.compaction_strategy() == CompactionStrategy::SizeTiered {
+                        inner_clone.run_size_tiered();
+                        let (lock, cvar) = &*inner_clone.compaction_state;
+                        let mut queue = lock.lock().unwrap();
+                        queue.active_levels.remove(&t.level);
+                        cvar.notify_all();
+                        continue;
+                    }
+
                     // Gather tables to compact
                     let mut tables_to_compact = {
                         let levels = inner_clone.levels[t.level].read();
@@ -1463,8 +2424,8 @@ impl LSMTree {
         });
 
         // Start background flush worker thread
-        let flush_inner = Arc::clone(&inner);
-        std::thread::spawn(move || {
+        let flush_inner = Arc::clone(inner);
+        let flush_worker = std::thread::spawn(move || {
             #[cfg(unix)]
             unsafe {
                 extern "C" {
@@ -1499,28 +2460,47 @@ impl LSMTree {
                     flush_inner.flush_pending.store(false, AtomicOrdering::SeqCst);
                 }
 
-                // Process each shard's frozen buffer
+                // All frozen shards go into one level-0 table, so freezing
+                // every shard at once does not stall writers on level 0.
+                // Copied rather than taken: reads keep finding the entries
+                // in the frozen buffers until the table is in level 0, and
+                // a failed write leaves them for a retry.
+                let flushing = flush_inner.flush_lock.lock().unwrap();
+                flush_inner.pending_flushes.store(0, AtomicOrdering::SeqCst);
+                let mut entries = BTreeMap::new();
                 for shard in 0..16 {
-                    let frozen = flush_inner.frozen[shard].lock().unwrap().take();
-                        if let Some(mem) = frozen {
-                        if mem.is_empty() { continue; }
-
-                        let entries: BTreeMap<Vec<u8>, KVEntry> = mem.entries.into_iter().collect();
+                    let mut frozen = flush_inner.frozen[shard].lock().unwrap();
+                    match frozen.as_ref() {
+                        Some(mem) if !mem.is_empty() => entries.extend(mem.entries.clone()),
+                        _ => *frozen = None,
+                    }
+                }
 
-                        if let Ok(sstable) = SSTable::create(
-                            0, &entries, &flush_inner.base_path,
-                            flush_inner.table_type, None,
-                            flush_inner.config.lsm_tree.verify_checksums,
-                        ) {
+                if !entries.is_empty() {
+                    match flush_inner.create_sstable(0, &entries, None) {
+                        Ok(sstable) => {
                             let sstable = Arc::new(sstable);
+                            let bytes = sstable.mmap.len() as u64;
                             {
                                 let mut l0 = flush_inner.levels[0].write();
                                 l0.push(sstable);
                             }
+                            for shard in 0..16 {
+                                flush_inner.release_frozen(shard, &entries);
+                            }
+                            if let Err(e) = flush_inner.persist_manifest() {
+                                eprintln!("Failed to update manifest after flush: {}", e);
+                            }
+                            flush_inner.emit(DbEvent::Flushed { table: flush_inner.table_type, entries: entries.len(), bytes });
                             flush_inner.clone().trigger_compaction(0);
                         }
+                        Err(e) => {
+                            eprintln!("Failed to flush {:?} table: {}", flush_inner.table_type, e);
+                            flush_inner.emit(DbEvent::FlushFailed { table: flush_inner.table_type, entries: entries.len(), error: e.to_string() });
+                        }
                     }
                 }
+                drop(flushing);
 
                 // WAL truncation: check if all shards are flushed
                 let last_seq = flush_inner.last_truncated_seq.load(AtomicOrdering::SeqCst);
@@ -1535,8 +2515,7 @@ impl LSMTree {
                 }
             }
         });
-
-        Ok(Self { inner })
+        inner.workers.lock().unwrap().extend([compaction_worker, flush_worker]);
     }
     
     pub fn set_power_save_mode(&self, enabled: bool) {
@@ -1547,22 +2526,7 @@ impl LSMTree {
         self.inner.low_memory_mode.store(enabled, AtomicOrdering::SeqCst);
         if enabled {
             // Flush all active memtables to frozen buffers
-            for shard in 0..16 {
-                let mut mem = self.inner.memtable[shard].write();
-                if !mem.is_empty() {
-                    let frozen_entries = std::mem::take(&mut mem.entries);
-                    mem.clear();
-
-                    let frozen_mem = MemTable {
-                        entries: frozen_entries,
-                        max_size: mem.max_size,
-                        current_size: 0,
-                        entry_count: 0,
-                        table_type: mem.table_type,
-                    };
-                    *self.inner.frozen[shard].lock().unwrap() = Some(frozen_mem);
-                }
-            }
+            self.inner.freeze_all();
             self.inner.flush_pending.store(true, AtomicOrdering::SeqCst);
             self.inner.flush_state.1.notify_one();
         }
@@ -1576,6 +2540,71 @@ impl LSMTree {
         cvar.notify_all();
         self.inner.flush_state.1.notify_one();
     }
+
+    /// Shuts the tree down for good: stops the background threads, waits
+    /// for a running flush or compaction to finish and flushes the
+    /// memtable. After this nothing touches the tree's files, so another
+    /// tree can be opened on the same directory, e.g. by a mode switch.
+    /// Handles that outlive the close can still read.
+    ///
+    /// If the final flush fails, the tree is left open with its background
+    /// threads running again, so later writes are still flushed; the error
+    /// is returned and `close` can be retried.
+    pub fn close(&self) -> io::Result<()> {
+        if self.inner.closed.swap(true, AtomicOrdering::SeqCst) {
+            return Ok(());
+        }
+        let mut result = self.inner.indices.iter().try_for_each(|index| index.tree.close());
+        if result.is_ok() {
+            self.shutdown();
+            let workers = std::mem::take(&mut *self.inner.workers.lock().unwrap());
+            for worker in workers {
+                let _ = worker.join();
+            }
+            result = self.final_flush();
+        }
+        if result.is_err() {
+            self.reopen();
+        }
+        result
+    }
+
+    /// Undoes a failed [`close`](Self::close), of this tree and of its
+    /// indexes: restarts the background threads if they were stopped.
+    pub(crate) fn reopen(&self) {
+        for index in &self.inner.indices {
+            index.tree.reopen();
+        }
+        if self.inner.workers.lock().unwrap().is_empty() {
+            self.inner.shutdown.store(false, AtomicOrdering::Relaxed);
+            self.inner.shutdown_flush.store(false, AtomicOrdering::Relaxed);
+            Self::start_workers(&self.inner);
+        }
+        // Not a shutdown after all, so there is nothing for the next open
+        // to report.
+        let _ = fs::remove_file(flush_error_path(&self.inner.base_path, &self.inner.file_prefix()));
+        self.inner.closed.store(false, AtomicOrdering::SeqCst);
+    }
+
+    /// Flushes for a shutdown. If that fails, the number of entries left
+    /// unflushed and the error are recorded next to the tree's files, so
+    /// the next open can report them in `open_warnings`.
+    fn final_flush(&self) -> io::Result<()> {
+        let result = self.flush();
+        if let Err(e) = &result {
+            let unflushed: usize = (0..16)
+                .map(|shard| {
+                    let frozen = self.inner.frozen[shard].lock().unwrap().as_ref().map_or(0, |m| m.entries.len());
+                    frozen + self.inner.memtable[shard].read().entries.len()
+                })
+                .sum();
+            let path = flush_error_path(&self.inner.base_path, &self.inner.file_prefix());
+            if let Err(record_err) = fs::write(&path, format!("{}\n{}\n", unflushed, e)) {
+                eprintln!("Failed to record the failed flush in {}: {}", path.display(), record_err);
+            }
+        }
+        result
+    }
 }
 
 impl LSMTree {
@@ -1592,19 +2621,11 @@ impl LSMTree {
         value: Vec<u8>,
         allowed_fields: Option<&[&str]>,
     ) -> io::Result<()> {
-        let now_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let now_time = self.inner.now_ms();
         self.inner.last_active_time.store(now_time, AtomicOrdering::Relaxed);
 
-        // Level 0 Write Stall (Backpressure) to protect reads and prevent OOM/disk exhaustion.
-        let l0_count = self.inner.levels[0].read().len();
-        if l0_count >= 12 {
-            // Hard limit: stall incoming writes significantly
-            std::thread::sleep(std::time::Duration::from_millis(100));
-        } else if l0_count >= 8 {
-            // Soft limit: scale delay dynamically (e.g. 10ms - 40ms)
-            let delay = (l0_count - 7) as u64 * 10;
-            std::thread::sleep(std::time::Duration::from_millis(delay));
-        }
+        self.inner.throttle_write()?;
+        self.inner.count_user_bytes(key.len() + value.len());
 
         // Write-Side Indexing
         if !self.inner.is_index {
@@ -1626,76 +2647,112 @@ impl LSMTree {
             let ptr = self.inner.blob_log.put(&key, &value)?;
             (EntryType::BlobIndex, ptr.encode())
         } else {
-            (EntryType::Insert, value)
+            (self.inner.write_type(&key), value)
         };
 
         let mut wal_entry = BDBLogEntry::new(entry_type, key.clone(), stored_value.clone());
+        wal_entry.timestamp = self.inner.clock.next();
         self.inner.wal.read().log(&mut wal_entry)?;
 
         let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
         let mut mem = self.inner.memtable[shard].write();
-        mem.put(key, stored_value, entry_type, 0);
+        mem.put(key, stored_value, entry_type, 0, wal_entry.timestamp);
 
         let power_save = self.inner.power_save_mode.load(AtomicOrdering::SeqCst);
         let low_memory = self.inner.low_memory_mode.load(AtomicOrdering::SeqCst);
-        if mem.should_flush_tuned(power_save, low_memory) {
-            let frozen_entries = std::mem::take(&mut mem.entries);
-            mem.clear();
-
-            let frozen_mem = MemTable {
-                entries: frozen_entries,
-                max_size: mem.max_size,
-                current_size: 0,
-                entry_count: 0,
-                table_type: mem.table_type,
-            };
-            *self.inner.frozen[shard].lock().unwrap() = Some(frozen_mem);
-            drop(mem);
-
-            self.inner.flush_pending.store(true, AtomicOrdering::SeqCst);
-            self.inner.flush_state.1.notify_one();
+        let froze = mem.should_flush_tuned(power_save, low_memory);
+        if froze {
+            self.inner.freeze_shard(shard, &mut mem);
         }
+        drop(mem);
+        self.inner.count_writes(1, froze);
         Ok(())
     }
 
     pub fn increment(&self, key: Vec<u8>, delta: i64) -> io::Result<()> {
-        let now_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let now_time = self.inner.now_ms();
         self.inner.last_active_time.store(now_time, AtomicOrdering::Relaxed);
 
+        self.inner.throttle_write()?;
         let value = delta.to_le_bytes().to_vec();
+        self.inner.count_user_bytes(key.len() + value.len());
         let mut wal_entry = BDBLogEntry::new(EntryType::Increment, key.clone(), value.clone());
+        wal_entry.timestamp = self.inner.clock.next();
         self.inner.wal.read().log(&mut wal_entry)?;
 
         let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
         let mut mem = self.inner.memtable[shard].write();
-        mem.put(key, value, EntryType::Increment, 0);
+        mem.put(key, value, EntryType::Increment, 0, wal_entry.timestamp);
 
         let power_save = self.inner.power_save_mode.load(AtomicOrdering::SeqCst);
         let low_memory = self.inner.low_memory_mode.load(AtomicOrdering::SeqCst);
-        if mem.should_flush_tuned(power_save, low_memory) {
-            let frozen_entries = std::mem::take(&mut mem.entries);
-            mem.clear();
-
-            let frozen_mem = MemTable {
-                entries: frozen_entries,
-                max_size: mem.max_size,
-                current_size: 0,
-                entry_count: 0,
-                table_type: mem.table_type,
-            };
-            *self.inner.frozen[shard].lock().unwrap() = Some(frozen_mem);
-            drop(mem);
-
-            self.inner.flush_pending.store(true, AtomicOrdering::SeqCst);
-            self.inner.flush_state.1.notify_one();
+        let froze = mem.should_flush_tuned(power_save, low_memory);
+        if froze {
+            self.inner.freeze_shard(shard, &mut mem);
         }
+        drop(mem);
+        self.inner.count_writes(1, froze);
         Ok(())
     }
 
+    /// Writes `new` under `key` if its current value is `expected`, `None`
+    /// meaning no live value, and returns whether it did. The check and the
+    /// write happen under the key's memtable shard lock, so of several
+    /// swaps racing from the same `expected` exactly one succeeds.
+    pub fn compare_and_swap(&self, key: Vec<u8>, expected: Option<Vec<u8>>, new: Vec<u8>) -> io::Result<bool> {
+        let now_time = self.inner.now_ms();
+        self.inner.last_active_time.store(now_time, AtomicOrdering::Relaxed);
+
+        self.inner.throttle_write()?;
+
+        let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
+        let mut mem = self.inner.memtable[shard].write();
+        let current = self.inner.get_raw_below(&key, mem.get(&key).cloned())
+            .and_then(|entry| visible_value(entry, &self.inner.blob_log, now_time));
+        if current.as_ref().map(|e| &e.value) != expected.as_ref() {
+            return Ok(false);
+        }
+        self.inner.count_user_bytes(key.len() + new.len());
+
+        if !self.inner.is_index {
+            for idx in &self.inner.indices {
+                if let Some(idx_key) = (idx.extractor)(&key, &new) {
+                    idx.tree.put(idx_key, key.clone())?;
+                }
+            }
+        }
+
+        let (entry_type, stored_value) = if new.len() > 64 * 1024 {
+            let ptr = self.inner.blob_log.put(&key, &new)?;
+            (EntryType::BlobIndex, ptr.encode())
+        } else if current.is_some() && !self.inner.is_index {
+            (EntryType::Update, new)
+        } else {
+            (EntryType::Insert, new)
+        };
+
+        let mut wal_entry = BDBLogEntry::new(entry_type, key.clone(), stored_value.clone());
+        wal_entry.timestamp = self.inner.clock.next();
+        self.inner.wal.read().log(&mut wal_entry)?;
+        mem.put(key, stored_value, entry_type, 0, wal_entry.timestamp);
+
+        let power_save = self.inner.power_save_mode.load(AtomicOrdering::SeqCst);
+        let low_memory = self.inner.low_memory_mode.load(AtomicOrdering::SeqCst);
+        let froze = mem.should_flush_tuned(power_save, low_memory);
+        if froze {
+            self.inner.freeze_shard(shard, &mut mem);
+        }
+        drop(mem);
+        self.inner.count_writes(1, froze);
+        Ok(true)
+    }
+
     pub fn put_with_ttl(&self, key: Vec<u8>, value: Vec<u8>, ttl_ms: u64) -> io::Result<()> {
-        let now_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let now_time = self.inner.now_ms();
         self.inner.last_active_time.store(now_time, AtomicOrdering::Relaxed);
 
+        self.inner.throttle_write()?;
+        self.inner.count_user_bytes(key.len() + value.len());
         let expires_at = now_time + ttl_ms;
 
         // Write-Side Indexing
@@ -1708,7 +2765,7 @@ impl LSMTree {
         }
 
         let value_size = value.len();
-        let mut entry_type = EntryType::Insert;
+        let mut entry_type = self.inner.write_type(&key);
         let mut stored_value = value.clone();
 
         if value_size > 65536 {
@@ -1719,37 +2776,35 @@ impl LSMTree {
         }
 
         let mut wal_entry = BDBLogEntry::with_ttl(entry_type, key.clone(), stored_value.clone(), expires_at);
+        wal_entry.timestamp = self.inner.clock.next();
         self.inner.wal.read().log(&mut wal_entry)?;
 
         let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
         let mut mem = self.inner.memtable[shard].write();
-        mem.put(key, stored_value, entry_type, expires_at);
+        mem.put(key, stored_value, entry_type, expires_at, wal_entry.timestamp);
 
         let power_save = self.inner.power_save_mode.load(AtomicOrdering::SeqCst);
         let low_memory = self.inner.low_memory_mode.load(AtomicOrdering::SeqCst);
-        if mem.should_flush_tuned(power_save, low_memory) {
-            let frozen_entries = std::mem::take(&mut mem.entries);
-            mem.clear();
-
-            let frozen_mem = MemTable {
-                entries: frozen_entries,
-                max_size: mem.max_size,
-                current_size: 0,
-                entry_count: 0,
-                table_type: mem.table_type,
-            };
-            *self.inner.frozen[shard].lock().unwrap() = Some(frozen_mem);
-            drop(mem);
-
-            self.inner.flush_pending.store(true, AtomicOrdering::SeqCst);
-            self.inner.flush_state.1.notify_one();
+        let froze = mem.should_flush_tuned(power_save, low_memory);
+        if froze {
+            self.inner.freeze_shard(shard, &mut mem);
         }
+        drop(mem);
+        self.inner.count_writes(1, froze);
         Ok(())
     }
 
-    pub fn apply_batch(&self, batch: Batch) -> io::Result<()> {
-        let now_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    pub fn apply_batch(&self, mut batch: Batch) -> io::Result<()> {
+        let now_time = self.inner.now_ms();
         self.inner.last_active_time.store(now_time, AtomicOrdering::Relaxed);
+        self.inner.throttle_write()?;
+        self.inner.count_user_bytes(batch.entries.iter().map(|(k, v, _)| k.len() + v.len()).sum());
+
+        for (k, _, t) in batch.entries.iter_mut() {
+            if *t == EntryType::Insert {
+                *t = self.inner.write_type(k);
+            }
+        }
 
         // Write-Side Indexing for Batch
         if !self.inner.is_index {
@@ -1770,42 +2825,149 @@ impl LSMTree {
             }
         }
 
+        // One timestamp for the whole batch; it applies atomically.
+        let timestamp = self.inner.clock.next();
         let wal = self.inner.wal.write();
         wal.log(&mut BDBLogEntry::new(EntryType::BatchStart, Vec::new(), Vec::new()))?;
         for (k, v, t) in &batch.entries {
-            wal.log(&mut BDBLogEntry::new(*t, k.clone(), v.clone()))?;
+            let mut entry = BDBLogEntry::new(*t, k.clone(), v.clone());
+            entry.timestamp = timestamp;
+            wal.log(&mut entry)?;
         }
         wal.log(&mut BDBLogEntry::new(EntryType::BatchEnd, Vec::new(), Vec::new()))?;
         drop(wal);
 
-        let power_save = self.inner.power_save_mode.load(AtomicOrdering::SeqCst);
-        let low_memory = self.inner.low_memory_mode.load(AtomicOrdering::SeqCst);
-        let mut flush_shards: Vec<usize> = Vec::new();
+        // Group by shard so each memtable lock is taken once per batch.
+        let mut by_shard: Vec<Batch> = (0..16).map(|_| Batch::new()).collect();
         for (k, v, t) in batch.entries {
             let shard = (k.first().cloned().unwrap_or(0) % 16) as usize;
+            by_shard[shard].entries.push((k, v, t));
+        }
+
+        let power_save = self.inner.power_save_mode.load(AtomicOrdering::SeqCst);
+        let low_memory = self.inner.low_memory_mode.load(AtomicOrdering::SeqCst);
+        let mut writes = 0;
+        let mut froze = false;
+        for (shard, shard_batch) in by_shard.into_iter().enumerate() {
+            if shard_batch.entries.is_empty() { continue; }
             let mut mem = self.inner.memtable[shard].write();
-            mem.put(k, v, t, 0);
+            writes += shard_batch.entries.len();
+            for (k, v, t) in shard_batch.entries {
+                mem.put(k, v, t, 0, timestamp);
+            }
             if mem.should_flush_tuned(power_save, low_memory) {
-                let frozen_entries = std::mem::take(&mut mem.entries);
-                mem.clear();
+                self.inner.freeze_shard(shard, &mut mem);
+                froze = true;
+            }
+        }
 
-                let frozen_mem = MemTable {
-                    entries: frozen_entries,
-                    max_size: mem.max_size,
-                    current_size: 0,
-                    entry_count: 0,
-                    table_type: mem.table_type,
+        self.inner.count_writes(writes, froze);
+        Ok(())
+    }
+
+    /// Writes many entries at once.
+    ///
+    /// Batches that fit in the memtable go through [`LSMTree::apply_batch`].
+    /// Larger ones are sorted and handed to [`LSMTree::ingest_sorted`].
+    pub fn put_bulk(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) -> io::Result<()> {
+        let capacity = self.inner.memtable[0].read().max_size * 16;
+        let batch_size: usize = entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+        if batch_size < capacity {
+            let mut batch = Batch::new();
+            for (k, v) in entries {
+                batch.put(k, v);
+            }
+            return self.apply_batch(batch);
+        }
+
+        let sorted: BTreeMap<Vec<u8>, Vec<u8>> = entries.into_iter().collect();
+        self.ingest_sorted(sorted.into_iter())
+    }
+
+    /// Bulk-loads a stream of entries in strictly increasing key order,
+    /// bypassing the WAL and memtable.
+    ///
+    /// Whatever is buffered is flushed first, then the stream is cut into
+    /// Level 0 SSTables of about one memtable each. The tables are only
+    /// published (and recorded in the manifest) once the whole stream has
+    /// been written, so they shadow every older version of their keys. Out
+    /// of order or duplicate keys fail with `InvalidInput` and nothing is
+    /// published.
+    pub fn ingest_sorted(&self, entries: impl Iterator<Item = (Vec<u8>, Vec<u8>)>) -> io::Result<()> {
+        self.flush()?;
+        let capacity = self.inner.memtable[0].read().max_size * 16;
+        // Loading into an empty table, every key is new.
+        let empty = self.inner.levels.iter().all(|level| level.read().is_empty());
+
+        let now = self.inner.clock.next();
+        self.inner.last_active_time.store(now, AtomicOrdering::Relaxed);
+
+        let mut tables: Vec<SSTable> = Vec::new();
+        let mut index_puts: Vec<(usize, Vec<u8>, Vec<u8>)> = Vec::new();
+        let res = (|| {
+            let mut chunk: BTreeMap<Vec<u8>, KVEntry> = BTreeMap::new();
+            let mut chunk_size = 0;
+            let mut last_key: Option<Vec<u8>> = None;
+            for (key, value) in entries {
+                if last_key.as_ref().is_some_and(|last| key <= *last) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "ingest_sorted: keys must be strictly increasing"));
+                }
+                last_key = Some(key.clone());
+                self.inner.count_user_bytes(key.len() + value.len());
+
+                if !self.inner.is_index {
+                    for (i, idx) in self.inner.indices.iter().enumerate() {
+                        if let Some(idx_key) = (idx.extractor)(&key, &value) {
+                            index_puts.push((i, idx_key, key.clone()));
+                        }
+                    }
+                }
+                let (entry_type, value) = if value.len() > 64 * 1024 {
+                    let ptr = self.inner.blob_log.put(&key, &value)?;
+                    (EntryType::BlobIndex, ptr.encode())
+                } else if empty {
+                    (EntryType::Insert, value)
+                } else {
+                    (self.inner.write_type(&key), value)
                 };
-                *self.inner.frozen[shard].lock().unwrap() = Some(frozen_mem);
-                drop(mem);
-                flush_shards.push(shard);
+                chunk_size += key.len() + value.len();
+                chunk.insert(key.clone(), KVEntry {
+                    key,
+                    value,
+                    timestamp: now,
+                    expires_at: 0,
+                    entry_type,
+                    deleted: false,
+                });
+
+                if chunk_size >= capacity {
+                    tables.push(self.inner.create_sstable(0, &chunk, None)?);
+                    chunk.clear();
+                    chunk_size = 0;
+                }
+            }
+            if !chunk.is_empty() {
+                tables.push(self.inner.create_sstable(0, &chunk, None)?);
+            }
+            Ok(())
+        })();
+
+        if let Err(e) = res {
+            for table in tables {
+                table.mark_obsolete();
             }
+            return Err(e);
+        }
+        if tables.is_empty() {
+            return Ok(());
         }
 
-        if !flush_shards.is_empty() {
-            self.inner.flush_pending.store(true, AtomicOrdering::SeqCst);
-            self.inner.flush_state.1.notify_one();
+        for (i, idx_key, key) in index_puts {
+            self.inner.indices[i].tree.put(idx_key, key)?;
         }
+        self.inner.levels[0].write().extend(tables.into_iter().map(Arc::new));
+        self.inner.persist_manifest()?;
+        self.inner.clone().trigger_compaction(0);
         Ok(())
     }
 
@@ -1814,87 +2976,136 @@ impl LSMTree {
             idx.tree.clear()?;
         }
 
-        for shard in &self.inner.memtable {
-            shard.write().clear();
+        // Keeps the flush thread from landing a frozen buffer afterwards.
+        let _flushing = self.inner.flush_lock.lock().unwrap();
+        for shard in 0..16 {
+            self.inner.memtable[shard].write().clear();
+            *self.inner.frozen[shard].lock().unwrap() = None;
         }
+        self.inner.memtable_ops.store(0, AtomicOrdering::SeqCst);
+        self.inner.pending_flushes.store(0, AtomicOrdering::SeqCst);
 
-        let mut levels = Vec::new();
+        let mut removed = Vec::new();
         for l in &self.inner.levels {
-            levels.push(l.write());
+            removed.extend(l.write().drain(..));
         }
+        self.inner.persist_manifest()?;
 
-        for mut level in levels {
-            for sstable in level.drain(..) {
-                #[cfg(target_os = "windows")]
-                {
-                    let path = sstable.file_path.clone();
-                    drop(sstable);
-                    let _ = fs::remove_file(&path);
-                }
-                #[cfg(not(target_os = "windows"))]
-                {
-                    let _ = fs::remove_file(&sstable.file_path);
-                }
-            }
+        for sstable in removed {
+            self.inner.entry_cache.invalidate(sstable.id);
+            sstable.mark_obsolete();
         }
 
         Ok(())
     }
-    
+
+    /// Empties every secondary index and re-derives it from the live
+    /// entries. Returns the number of index entries written.
+    pub fn rebuild_indices(&self) -> io::Result<usize> {
+        if self.inner.indices.is_empty() {
+            return Ok(0);
+        }
+        for idx in &self.inner.indices {
+            idx.tree.clear()?;
+        }
+        let mut written = 0;
+        for entry in self.all_entries() {
+            for idx in &self.inner.indices {
+                if let Some(idx_key) = (idx.extractor)(&entry.key, &entry.value) {
+                    idx.tree.put(idx_key, entry.key.clone())?;
+                    written += 1;
+                }
+            }
+        }
+        Ok(written)
+    }
+
     pub fn get(&self, key: &[u8]) -> Option<KVEntry> {
-        let now_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let now_time = self.inner.now_ms();
         self.inner.last_active_time.store(now_time, AtomicOrdering::Relaxed);
 
         self.inner.heat_tracker.record_access(key, QueryType::Read);
 
-        let mut entry = self.inner.get_raw(key)?;
-        if entry.deleted {
-            return None;
-        }
+        let entry = self.inner.get_raw(key)?;
+        let entry = visible_value(entry, &self.inner.blob_log, self.inner.now_ms())?;
+        self.inner.bytes_returned.fetch_add((entry.key.len() + entry.value.len()) as u64, AtomicOrdering::Relaxed);
+        Some(entry)
+    }
 
-        let now = now_time;
-        if entry.expires_at > 0 && entry.expires_at < now {
-            return None;
-        }
+    /// Calls `f` with the value of `key`. When the newest version is a
+    /// plain value in an SSTable, `f` borrows it straight from the mmap and
+    /// nothing is copied. Values still in a memtable, blob-separated values
+    /// and counters with pending increments have to be assembled first and
+    /// are passed from an owned copy, as [`LSMTree::get`] returns them.
+    pub fn with_value<R>(&self, key: &[u8], f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let now_time = self.inner.now_ms();
+        self.inner.last_active_time.store(now_time, AtomicOrdering::Relaxed);
+        self.inner.heat_tracker.record_access(key, QueryType::Read);
 
-        if entry.entry_type == EntryType::BlobIndex {
-            if let Some(ptr) = BlobPointer::decode(&entry.value) {
-                if let Ok(val) = self.inner.blob_log.get(&ptr) {
-                    entry.value = val;
+        let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
+        let in_memtable = self.inner.memtable[shard].read().get(key).is_some();
+        let in_frozen = self.inner.frozen[shard].lock().unwrap().as_ref().is_some_and(|m| m.get(key).is_some());
+        if !in_memtable && !in_frozen {
+            // Pinned, so compaction is not held up while `f` runs.
+            let tables: Vec<Arc<SSTable>> = self.inner.levels.iter()
+                .flat_map(|level| level.read().iter().rev().cloned().collect::<Vec<_>>())
+                .collect();
+            // The newest version by timestamp, as `get` resolves it: a
+            // table higher up may hold an older copy, e.g. after a repair.
+            let mut newest: Option<EntryRef<'_>> = None;
+            for table in &tables {
+                if !table.covers(key) || newest.as_ref().is_some_and(|e| table.max_timestamp() <= e.timestamp) {
+                    continue;
+                }
+                let (entry, read) = table.get_ref_measured(key);
+                self.inner.sstable_bytes_read.fetch_add(read, AtomicOrdering::Relaxed);
+                if let Some(entry) = entry {
+                    if newest.as_ref().is_none_or(|e| entry.timestamp > e.timestamp) {
+                        newest = Some(entry);
+                    }
+                }
+            }
+            let entry = newest?;
+            match entry.entry_type {
+                EntryType::Insert | EntryType::Update => {
+                    if entry.expires_at > 0 && entry.expires_at < now_time {
+                        return None;
+                    }
+                    self.inner.bytes_returned.fetch_add((key.len() + entry.value.len()) as u64, AtomicOrdering::Relaxed);
+                    return Some(f(&entry.value));
                 }
+                EntryType::Delete => return None,
+                // Blob pointers and increments are assembled by `get_raw`.
+                _ => {}
             }
         }
-        Some(entry)
+
+        let entry = self.inner.get_raw(key)?;
+        let entry = visible_value(entry, &self.inner.blob_log, self.inner.now_ms())?;
+        self.inner.bytes_returned.fetch_add((entry.key.len() + entry.value.len()) as u64, AtomicOrdering::Relaxed);
+        Some(f(&entry.value))
     }
 
     pub fn delete(&self, key: Vec<u8>) -> io::Result<()> {
-        let now_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let now_time = self.inner.now_ms();
         self.inner.last_active_time.store(now_time, AtomicOrdering::Relaxed);
+        self.inner.throttle_write()?;
+        self.inner.count_user_bytes(key.len());
 
         let mut wal_entry = BDBLogEntry::new(EntryType::Delete, key.clone(), Vec::new());
+        wal_entry.timestamp = self.inner.clock.next();
         self.inner.wal.read().log(&mut wal_entry)?;
 
         let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
         let mut mem = self.inner.memtable[shard].write();
-        mem.put(key, Vec::new(), EntryType::Delete, 0);
-
-        if mem.should_flush() {
-            let frozen_entries = std::mem::take(&mut mem.entries);
-            mem.clear();
-
-            let frozen_mem = MemTable {
-                entries: frozen_entries,
-                max_size: mem.max_size,
-                current_size: 0,
-                entry_count: 0,
-                table_type: mem.table_type,
-            };
-            *self.inner.frozen[shard].lock().unwrap() = Some(frozen_mem);
-            drop(mem);
+        mem.put(key, Vec::new(), EntryType::Delete, 0, wal_entry.timestamp);
 
-            self.inner.flush_pending.store(true, AtomicOrdering::SeqCst);
-            self.inner.flush_state.1.notify_one();
+        let froze = mem.should_flush();
+        if froze {
+            self.inner.freeze_shard(shard, &mut mem);
         }
+        drop(mem);
+        self.inner.count_writes(1, froze);
         Ok(())
     }
 
@@ -1913,17 +3124,31 @@ impl LSMTree {
         for (i, shard) in self.inner.memtable.iter().enumerate() {
             let guard = shard.read();
             iters.push(SourceIterator {
-                iter: Box::new(MemTableIteratorWrapper::new(guard, prefix.to_vec())),
+                iter: Box::new(MemTableIteratorWrapper::new(guard, prefix.to_vec(), prefix.to_vec())),
                 source_id: i,
             });
         }
+        iters.extend(self.frozen_sources(prefix, prefix));
 
         // 2. SSTable Iterators
-        let mut source_id = 16;
+        iters.extend(self.sstable_sources(prefix));
+
+        MergeIterator::new(iters, prefix.to_vec(), self.inner.now_ms())
+    }
+
+    /// One merge source per SSTable that may hold keys under `prefix`,
+    /// numbered from 32 in level order. Each pins its table and decodes one
+    /// entry at a time.
+    fn sstable_sources(&self, prefix: &[u8]) -> Vec<SourceIterator<'static>> {
+        // Blooms hold each key's `extract_prefix`. A shorter scan prefix,
+        // such as `page/` for `page/0001`, is in none of them.
+        let bloom_usable = prefix.contains(&b':') || prefix.len() >= 8;
+        let mut iters = Vec::new();
+        let mut source_id = 32;
         for level in &self.inner.levels {
             let sstables = level.read();
             for sstable in sstables.iter() {
-                if !prefix.is_empty() {
+                if bloom_usable {
                     if let Some(bf) = &sstable.bloom_filter {
                         if !bf.might_contain(prefix) && !bf.might_contain(extract_prefix(prefix)) {
                             continue;
@@ -1940,137 +3165,296 @@ impl LSMTree {
                 source_id += 1;
             }
         }
-
-        MergeIterator::new(iters, prefix.to_vec())
+        iters
     }
 
-    pub fn scan_with_predicate<F>(&self, prefix: &[u8], predicate: F) -> Vec<KVEntry>
-    where F: Fn(&KVEntry) -> bool {
-        let mut results: BTreeMap<Vec<u8>, Option<KVEntry>> = BTreeMap::new();
+    /// Like [`LSMTree::streaming_iter`] without a prefix, but starting at the
+    /// first key `>= start`. Each source seeks directly to `start`, so resuming
+    /// a scan does not re-read the keys before it.
+    pub fn streaming_iter_from<'a>(&'a self, start: &[u8]) -> MergeIterator<'a> {
+        let mut iters = Vec::new();
 
-        // 1. MemTable
-        for shard_lock in &self.inner.memtable {
-            let mem = shard_lock.read();
-            let range = if prefix.is_empty() {
-                mem.entries.range::<Vec<u8>, _>(..)
-            } else {
-                mem.entries.range(prefix.to_vec()..)
-            };
+        for (i, shard) in self.inner.memtable.iter().enumerate() {
+            let guard = shard.read();
+            iters.push(SourceIterator {
+                iter: Box::new(MemTableIteratorWrapper::new(guard, start.to_vec(), Vec::new())),
+                source_id: i,
+            });
+        }
+        iters.extend(self.frozen_sources(start, &[]));
 
-            for (key, entry) in range {
-                if !prefix.is_empty() && !key.starts_with(prefix) { break; }
-                let mut deref_entry = entry.clone();
-                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
-                if deref_entry.expires_at > 0 && deref_entry.expires_at < now {
-                    results.insert(key.clone(), None);
-                    continue;
-                }
-                if deref_entry.entry_type == EntryType::BlobIndex && !deref_entry.deleted {
-                    if let Some(ptr) = BlobPointer::decode(&deref_entry.value) {
-                        if let Ok(val) = self.inner.blob_log.get(&ptr) {
-                            deref_entry.value = val;
-                        }
-                    }
-                }
+        let mut source_id = 32;
+        for level in &self.inner.levels {
+            let sstables = level.read();
+            for sstable in sstables.iter() {
+                let sst_clone = Arc::clone(sstable);
+                let cell = SSTableIterCell::new(sst_clone, |sst| sst.seek_prefix(start));
+                // The seek lands on a block boundary, possibly before `start`.
+                let start = start.to_vec();
+                let iter = SSTableStreamWrapper { cell }
+                    .skip_while(move |kv| kv.as_ref().is_ok_and(|kv| kv.key < start));
 
-                if !deref_entry.deleted && predicate(&deref_entry) {
-                    results.insert(key.clone(), Some(deref_entry));
-                } else {
-                    results.insert(key.clone(), None);
-                }
+                iters.push(SourceIterator {
+                    iter: Box::new(iter),
+                    source_id,
+                });
+                source_id += 1;
             }
         }
 
-        // 2. Levels (newest SSTables first)
-        for level in &self.inner.levels {
-            let sstables = level.read();
-            for sstable in sstables.iter().rev() {
-                if !prefix.is_empty() {
-                    if let Some(bf) = &sstable.bloom_filter {
-                        if !bf.might_contain(prefix) && !bf.might_contain(extract_prefix(prefix)) {
-                            continue;
-                        }
-                    }
-                }
-                let start_idx = if prefix.is_empty() {
-                    0
-                } else {
-                    match sstable.index.binary_search_by(|idx| idx.key.as_slice().cmp(prefix)) {
-                        Ok(i) => i,
-                        Err(i) => i,
-                    }
-                };
+        MergeIterator::new(iters, Vec::new(), self.inner.now_ms())
+    }
 
-                for idx in &sstable.index[start_idx..] {
-                    if !prefix.is_empty() && !idx.key.starts_with(prefix) { break; }
-                    if !results.contains_key(&idx.key) {
-                        // Directly read from mmap
-                        if let Some(mut kv) = sstable.get_at_index(idx) {
-                            if kv.expires_at > 0 && kv.expires_at < SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64 {
-                                continue;
-                            }
-                            if kv.entry_type == EntryType::BlobIndex && !kv.deleted {
-                                if let Some(ptr) = BlobPointer::decode(&kv.value) {
-                                    if let Ok(val) = self.inner.blob_log.get(&ptr) {
-                                        kv.value = val;
-                                    }
-                                }
-                            }
+    /// The frozen buffers' entries from `start` on that match `prefix`, as
+    /// merge sources numbered after the memtable shards. They are copied, so
+    /// a long-lived iterator does not hold up `freeze_shard`.
+    fn frozen_sources(&self, start: &[u8], prefix: &[u8]) -> Vec<SourceIterator<'static>> {
+        (0..16)
+            .filter_map(|shard| {
+                let frozen = self.inner.frozen[shard].lock().unwrap();
+                let entries: Vec<KVEntry> = frozen
+                    .as_ref()?
+                    .entries
+                    .range(start.to_vec()..)
+                    .take_while(|(key, _)| key.starts_with(prefix))
+                    .map(|(_, entry)| entry.clone())
+                    .collect();
+                Some(SourceIterator { iter: Box::new(entries.into_iter().map(Ok)), source_id: 16 + shard })
+            })
+            .collect()
+    }
 
-                            if !kv.deleted && predicate(&kv) {
-                                results.insert(idx.key.clone(), Some(kv));
-                            } else {
-                                results.insert(idx.key.clone(), None);
-                            }
-                        }
-                    }
+    /// Replace a `BlobIndex` pointer with the value it references. Entries
+    /// yielded by [`LSMTree::streaming_iter`] still carry the raw pointer.
+    pub fn resolve_blob(&self, entry: &mut KVEntry) {
+        if entry.entry_type == EntryType::BlobIndex && !entry.deleted {
+            if let Some(ptr) = BlobPointer::decode(&entry.value) {
+                if let Ok(val) = self.inner.blob_log.get(&ptr) {
+                    entry.value = val;
                 }
             }
         }
+    }
 
-        results.into_values().flatten().collect()
+    /// Live entries under `prefix` that pass `predicate`, in key order.
+    /// SSTables are merged entry by entry, so besides the result only the
+    /// memtable's matching entries and one entry per table are held in
+    /// memory, however large the tree is.
+    pub fn scan_with_predicate<F>(&self, prefix: &[u8], predicate: F) -> Vec<KVEntry>
+    where F: Fn(&KVEntry) -> bool {
+        let mut iters = self.memtable_sources(prefix);
+        iters.extend(self.frozen_sources(prefix, prefix));
+        iters.extend(self.sstable_sources(prefix));
+
+        // A table that fails to decode ends its source; the others go on.
+        MergeIterator::new(iters, prefix.to_vec(), self.inner.now_ms())
+            .filter_map(Result::ok)
+            .filter_map(|mut kv| {
+                self.resolve_blob(&mut kv);
+                predicate(&kv).then_some(kv)
+            })
+            .collect()
+    }
+
+    /// The memtable shards' entries under `prefix`, copied like
+    /// [`LSMTree::frozen_sources`] so a scan does not block writers.
+    fn memtable_sources(&self, prefix: &[u8]) -> Vec<SourceIterator<'static>> {
+        (0..16)
+            .map(|shard| {
+                let mem = self.inner.memtable[shard].read();
+                let entries: Vec<KVEntry> = mem
+                    .entries
+                    .range(prefix.to_vec()..)
+                    .take_while(|(key, _)| key.starts_with(prefix))
+                    .map(|(_, entry)| entry.clone())
+                    .collect();
+                SourceIterator { iter: Box::new(entries.into_iter().map(Ok)), source_id: shard }
+            })
+            .collect()
     }
     
-    pub fn flush(&self) -> io::Result<()> {
-        let mut all_entries = BTreeMap::new();
+    /// SSTables that could not be loaded when the tree was opened. Every
+    /// other table loads regardless; these are left out, so their entries
+    /// are missing until `BrowserDB::repair` salvages what it can.
+    pub fn open_warnings(&self) -> &[OpenWarning] {
+        &self.inner.open_warnings
+    }
 
-        // Drain frozen buffers first
-        for shard in 0..16 {
-            let frozen = self.inner.frozen[shard].lock().unwrap().take();
-            if let Some(mem) = frozen {
-                for (k, v) in mem.entries {
-                    all_entries.insert(k, v);
-                }
+    /// SSTable count, size and key span of every level, for diagnosing read
+    /// amplification: a point lookup may probe each level-0 table plus one
+    /// table per deeper level.
+    pub fn level_report(&self) -> Vec<LevelInfo> {
+        self.inner.levels.iter().enumerate().map(|(level, tables)| {
+            let tables = tables.read();
+            let key_range = tables.iter().filter_map(|t| t.key_range()).reduce(|(lo, hi), (t_lo, t_hi)| {
+                (lo.min(t_lo), hi.max(t_hi))
+            });
+            let blooms: Vec<&BloomFilter> = tables.iter().filter_map(|t| t.bloom_filter.as_ref()).collect();
+            LevelInfo {
+                level,
+                sstables: tables.len(),
+                bytes: tables.iter().map(|t| t.mmap.len() as u64).sum(),
+                key_range,
+                bloom_bytes: blooms.iter().map(|b| b.size_bytes() as u64).sum(),
+                bloom_fpr: (!blooms.is_empty())
+                    .then(|| blooms.iter().map(|b| b.estimated_fpr()).sum::<f64>() / blooms.len() as f64),
+                tombstones: tables.iter().map(|t| t.tombstones).sum(),
+                tombstone_density: tombstone_density(&tables),
             }
+        }).collect()
+    }
+
+    /// Write and read amplification since the tree was opened. Disk
+    /// writes outgrow user writes as compaction rewrites data; reads grow
+    /// with the tables and blocks a `get` has to look at.
+    pub fn amplification(&self) -> Amplification {
+        let load = |counter: &AtomicU64| counter.load(AtomicOrdering::Relaxed);
+        Amplification {
+            user_bytes_written: load(&self.inner.user_bytes_written),
+            disk_bytes_written: self.inner.wal.read().bytes_logged() + load(&self.inner.sstable_bytes_written),
+            bytes_returned: load(&self.inner.bytes_returned),
+            disk_bytes_read: load(&self.inner.sstable_bytes_read),
         }
+    }
 
-        // Drain active memtables
-        for shard in &self.inner.memtable {
-            let mut mem = shard.write();
-            let entries = std::mem::take(&mut mem.entries);
-            for (k, v) in entries {
-                all_entries.insert(k, v);
+    /// Take a consistent read view of the tree. Waits for an in-flight
+    /// flush so its entries are not caught between the frozen buffer and
+    /// level 0, then copies the memtables and pins the current SSTables.
+    /// The copy is proportional to the memtable size, so snapshots are
+    /// meant for short-lived multi-key reads.
+    pub fn snapshot(&self) -> LsmSnapshot {
+        let _flushing = self.inner.flush_lock.lock().unwrap();
+        let mut memtables = [BTreeMap::new(), BTreeMap::new()];
+        {
+            let shards: Vec<_> = self.inner.memtable.iter().map(|m| m.read()).collect();
+            for (shard, mem) in shards.iter().enumerate() {
+                memtables[0].extend(mem.entries.iter().map(|(k, e)| (k.clone(), e.clone())));
+                if let Some(frozen) = self.inner.frozen[shard].lock().unwrap().as_ref() {
+                    memtables[1].extend(frozen.entries.iter().map(|(k, e)| (k.clone(), e.clone())));
+                }
             }
-            mem.clear();
         }
+        let levels = self.inner.levels.iter().map(|level| level.read().clone()).collect();
+        LsmSnapshot {
+            memtables,
+            levels,
+            blob_log: Arc::clone(&self.inner.blob_log),
+            clock: self.inner.config.clock.clone(),
+        }
+    }
+
+    /// Flushes the tree and its index trees and pins the SSTables that then
+    /// hold everything written before the call, one [`TreeCapture`] per
+    /// tree. Writes racing with it may or may not be included.
+    pub fn capture(&self) -> io::Result<Vec<TreeCapture>> {
+        self.flush()?;
+        let prefix = self.inner.file_prefix();
+        let sstables = {
+            let _flushing = self.inner.flush_lock.lock().unwrap();
+            // All levels at once, so a compaction moving tables down is
+            // seen either before or after.
+            let levels: Vec<_> = self.inner.levels.iter().map(|level| level.read()).collect();
+            let mut sstables = Vec::new();
+            for (level, tables) in levels.iter().enumerate() {
+                sstables.extend(tables.iter().map(|sst| (level as u8, Arc::clone(sst))));
+            }
+            sstables
+        };
+        let mut captures = vec![TreeCapture {
+            base_path: self.inner.base_path.clone(),
+            prefix,
+            levels: self.inner.levels.len(),
+            sstables,
+            blob_log: Arc::clone(&self.inner.blob_log),
+        }];
+        for index in &self.inner.indices {
+            captures.extend(index.tree.capture()?);
+        }
+        Ok(captures)
+    }
+
+    /// How full the active memtable is, `current_size / max_size` summed
+    /// over all shards. Frozen buffers waiting for the flush thread are not
+    /// counted.
+    pub fn memtable_fill_ratio(&self) -> f32 {
+        let (used, capacity) = self.inner.memtable.iter().fold((0, 0), |(used, capacity), shard| {
+            let mem = shard.read();
+            (used + mem.current_size, capacity + mem.max_size)
+        });
+        if capacity == 0 {
+            return 0.0;
+        }
+        used as f32 / capacity as f32
+    }
+
+    /// Bytes buffered in the active memtable across all shards.
+    pub fn memtable_bytes(&self) -> u64 {
+        self.inner.memtable.iter().map(|shard| shard.read().current_size as u64).sum()
+    }
 
+    /// Mean of the fill-based false-positive estimate over every SSTable
+    /// with a bloom filter, or `None` if there are none.
+    pub fn bloom_fpr_estimate(&self) -> Option<f64> {
+        let mut estimates = Vec::new();
+        for level in &self.inner.levels {
+            estimates.extend(level.read().iter().filter_map(|t| t.bloom_filter.as_ref()).map(|b| b.estimated_fpr()));
+        }
+        if estimates.is_empty() {
+            return None;
+        }
+        Some(estimates.iter().sum::<f64>() / estimates.len() as f64)
+    }
+
+    /// Writes the memtable and any frozen buffers out as one level-0
+    /// SSTable. The entries are frozen rather than dropped while it is
+    /// written, so reads keep finding them, and if the write fails (the disk
+    /// is full, the directory was made read-only) they stay frozen for the
+    /// next flush to retry. The WAL is only truncated once nothing is left
+    /// frozen.
+    pub fn flush(&self) -> io::Result<()> {
+        let _flushing = self.inner.flush_lock.lock().unwrap();
+        for (shard, mem) in self.inner.memtable.iter().enumerate() {
+            let mut mem = mem.write();
+            if !mem.is_empty() {
+                self.inner.freeze_shard(shard, &mut mem);
+            }
+        }
+        self.inner.memtable_ops.store(0, AtomicOrdering::SeqCst);
+        self.inner.pending_flushes.store(0, AtomicOrdering::SeqCst);
+
+        let mut all_entries = BTreeMap::new();
+        for frozen in &self.inner.frozen {
+            if let Some(mem) = frozen.lock().unwrap().as_ref() {
+                all_entries.extend(mem.entries.clone());
+            }
+        }
         if all_entries.is_empty() { return Ok(()); }
-        
+
         // Create SSTable (Level 0)
-        let sstable = Arc::new(SSTable::create(0, &all_entries, &self.inner.base_path, self.inner.table_type, None, self.inner.config.lsm_tree.verify_checksums)?);
-        
+        let sstable = Arc::new(self.inner.create_sstable(0, &all_entries, None)?);
+
+        let bytes = sstable.mmap.len() as u64;
+
         // Add to Level 0
         {
             let mut l0 = self.inner.levels[0].write();
             l0.push(sstable);
         }
+        for shard in 0..16 {
+            self.inner.release_frozen(shard, &all_entries);
+        }
+        self.inner.persist_manifest()?;
+        self.inner.emit(DbEvent::Flushed { table: self.inner.table_type, entries: all_entries.len(), bytes });
 
         // Trigger cascading compaction starting from Level 0
         self.inner.clone().trigger_compaction(0);
 
-        // Truncate WAL after successful flush
-        self.inner.wal.write().truncate()?;
-        
+        // Truncate WAL after successful flush, unless writes frozen
+        // meanwhile still depend on it.
+        if self.inner.frozen.iter().all(|f| f.lock().unwrap().is_none()) {
+            self.inner.wal.write().truncate()?;
+        }
+
         Ok(())
     }
 
@@ -2078,29 +3462,259 @@ impl LSMTree {
         self.inner.merge_sstables(level, tables)
     }
 
+    /// Flush the memtable and run a full compaction across all levels,
+    /// discarding tombstones and overwritten values. Secondary index trees
+    /// are vacuumed as well. Returns the number of SSTable bytes reclaimed.
+    pub fn vacuum(&self) -> io::Result<u64> {
+        self.flush()?;
+        let mut reclaimed = self.inner.vacuum_levels()?;
+        for index in &self.inner.indices {
+            reclaimed += index.tree.vacuum()?;
+        }
+        Ok(reclaimed)
+    }
+
+    /// Checks the tree's manifest and SSTables, and those of its index
+    /// trees, without changing anything; see [`integrity::check_table`].
+    /// Flushes and compactions wait until it is done, so it sees one
+    /// consistent set of files. The memtable and WAL are not checked.
+    pub fn check_integrity(&self) -> io::Result<IntegrityReport> {
+        let (lock, cvar) = &*self.inner.compaction_state;
+        {
+            let mut queue = lock.lock().unwrap();
+            while !queue.active_levels.is_empty() {
+                queue = cvar.wait(queue).unwrap();
+            }
+            queue.active_levels.extend(0..self.inner.levels.len());
+        }
+
+        let mut report = IntegrityReport::default();
+        let result = {
+            let _flushing = self.inner.flush_lock.lock().unwrap();
+            let bloom_fpr = self.inner.config.lsm_tree.bloom_fpr.for_table(self.inner.table_type);
+            integrity::check_table(&self.inner.base_path, self.inner.table_type, &self.inner.file_prefix(), bloom_fpr, &mut report)
+        };
+
+        let mut queue = lock.lock().unwrap();
+        queue.active_levels.clear();
+        cvar.notify_all();
+        drop(queue);
+
+        result?;
+        for idx in &self.inner.indices {
+            report.merge(idx.tree.check_integrity()?);
+        }
+        Ok(report)
+    }
+
+    /// Collapses every on-disk version of `key` into one, without waiting
+    /// for compaction to reach them: the table holding the newest version
+    /// keeps the resolved entry and the others are rewritten without the
+    /// key. A key whose newest version is a delete disappears from disk
+    /// altogether. For frequently rewritten keys such as counters. Flushes
+    /// first; returns the number of versions dropped.
+    pub fn compact_key(&self, key: &[u8]) -> io::Result<usize> {
+        self.flush()?;
+        let (lock, cvar) = &*self.inner.compaction_state;
+        {
+            let mut queue = lock.lock().unwrap();
+            while !queue.active_levels.is_empty() {
+                queue = cvar.wait(queue).unwrap();
+            }
+            queue.active_levels.extend(0..self.inner.levels.len());
+        }
+
+        let result = self.inner.compact_key_reserved(key);
+
+        let mut queue = lock.lock().unwrap();
+        queue.active_levels.clear();
+        cvar.notify_all();
+        drop(queue);
+
+        result
+    }
+
+    /// Flushes the memtable and runs the compactions the levels are due
+    /// for, waiting for any already running, so the tree is left with an
+    /// empty memtable and no compaction pending. Index trees are
+    /// checkpointed as well. Unlike [`vacuum`](Self::vacuum) levels within
+    /// their limits are left alone.
+    pub fn checkpoint(&self) -> io::Result<()> {
+        self.flush()?;
+        self.inner.settle_levels();
+        for index in &self.inner.indices {
+            index.tree.checkpoint()?;
+        }
+        Ok(())
+    }
+
     pub fn run_blob_gc(&self) -> io::Result<()> {
         self.inner.run_blob_gc()
     }
 }
 
 impl LSMTreeInner {
-    pub fn trigger_compaction(self: Arc<Self>, level: usize) {
-        if level >= 9 { return; }
+    /// Wall-clock time from the config's clock, for expiry and idleness.
+    fn now_ms(&self) -> u64 {
+        self.config.clock.now_ms()
+    }
+
+    /// File prefix shared by the tree's WAL, manifest, blob log and
+    /// SSTables, e.g. `history`.
+    fn file_prefix(&self) -> String {
+        let manifest = self.manifest.lock().unwrap();
+        manifest.path().file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string()
+    }
+
+    /// Size above which level `level` (1 or deeper) is compacted.
+    fn level_threshold_bytes(&self, level: usize) -> u64 {
+        self.config.lsm_tree.level_size_thresholds_mb.get(level - 1)
+            .cloned()
+            .unwrap_or(10 * 10usize.pow(level as u32 - 1)) as u64 * 1024 * 1024
+    }
+
+    /// Average heat of the keys starting each block of `level`'s tables,
+    /// a cheap sample of how often the level is read.
+    fn level_heat(&self, level: usize) -> u64 {
+        let tables = self.levels[level].read();
+        let (total, blocks) = tables.iter().flat_map(|t| &t.index).fold((0u64, 0u64), |(total, blocks), idx| {
+            (total + self.heat_tracker.get_heat(&idx.key) as u64, blocks + 1)
+        });
+        total / blocks.max(1)
+    }
 
-        let should_compact = {
-            let levels = self.levels[level].read();
-            if level == 0 {
-                levels.len() >= self.config.lsm_tree.max_level0_files
+    /// Merges `tables` of `level` for compaction into `level + 1`. Keys at
+    /// or above the heatmap's hot threshold are written to a table of their
+    /// own that stays in `level`, where lookups reach them sooner; the rest
+    /// go to the returned table for `level + 1`. Either may be `None` when
+    /// it would be empty.
+    fn merge_by_heat(&self, level: usize, tables: &[Arc<SSTable>]) -> io::Result<HotColdTables> {
+        let mut cold = self.merge_entries(tables, false)?;
+        let hot_threshold = self.config.heatmap.hot_threshold.max(1);
+        let mut hot: BTreeMap<Vec<u8>, KVEntry> = BTreeMap::new();
+        // Tombstones always go down, towards where they can be dropped.
+        cold.retain(|key, entry| {
+            if !entry.deleted && self.heat_tracker.get_heat(key) >= hot_threshold {
+                hot.insert(key.clone(), entry.clone());
+                false
             } else {
-                let total_size: u64 = levels.iter().map(|s| s.mmap.len() as u64).sum();
-                let threshold = self.config.lsm_tree.level_size_thresholds_mb.get(level - 1)
-                    .cloned()
-                    .unwrap_or(10 * 10usize.pow(level as u32 - 1)) as u64 * 1024 * 1024;
-                total_size > threshold
+                true
+            }
+        });
+
+        // Keeping the hot keys must not leave the level due for compaction
+        // again straight away, or it would be rewritten over and over.
+        let hot_bytes: u64 = hot.iter().map(|(k, e)| (k.len() + e.value.len()) as u64).sum();
+        let keep_hot = if level == 0 {
+            self.config.lsm_tree.max_level0_files > 1
+        } else {
+            hot_bytes <= self.level_threshold_bytes(level) / 2
+        };
+        if !keep_hot {
+            cold.append(&mut hot);
+        }
+        // Tombstones can go once nothing older could hold the key: in the
+        // last level, or when every level below this one is empty. The
+        // whole of `level` is being merged, so none of it is left behind.
+        if level + 2 == self.levels.len() || self.levels[level + 1..].iter().all(|l| l.read().is_empty()) {
+            cold.retain(|_, entry| !entry.deleted);
+        }
+
+        let rate_limit = self.compaction_rate_limit();
+        let create = |level: usize, entries: &BTreeMap<Vec<u8>, KVEntry>| -> io::Result<Option<Arc<SSTable>>> {
+            if entries.is_empty() {
+                return Ok(None);
             }
+            let sst = self.create_sstable(level as u8, entries, rate_limit)?;
+            Ok(Some(Arc::new(sst)))
+        };
+        Ok((create(level, &hot)?, create(level + 1, &cold)?))
+    }
+
+    /// Write rate limit for compaction output in MB/s, derived from
+    /// `compaction_cpu_limit` (the default 0.05 gives 10 MB/s) and split
+    /// between the background jobs running across all tables.
+    fn compaction_rate_limit(&self) -> Option<f64> {
+        let total = if self.config.lsm_tree.compaction_cpu_limit > 0.0 {
+            self.config.lsm_tree.compaction_cpu_limit * 200.0
+        } else {
+            10.0
         };
+        Some(total / self.config.compaction_pool.active().max(1) as f64)
+    }
+
+    fn compaction_strategy(&self) -> CompactionStrategy {
+        self.config.lsm_tree.compaction_strategy.for_table(self.table_type)
+    }
+
+    /// Whether `level` has outgrown its limit: `max_level0_files` tables
+    /// (or a size-tiered run) for level 0, its byte threshold deeper down.
+    /// Under leveled compaction a level is also due once tombstones make up
+    /// more than `tombstone_compaction_ratio` of its entries, and there are
+    /// at least `TOMBSTONE_COMPACTION_MIN` of them.
+    fn level_due(&self, level: usize) -> bool {
+        let levels = self.levels[level].read();
+        if self.compaction_strategy() == CompactionStrategy::SizeTiered {
+            level == 0 && size_tiered_run(&levels, self.config.lsm_tree.max_level0_files).is_some()
+        } else if self.tombstone_heavy(&levels) {
+            true
+        } else if level == 0 {
+            levels.len() >= self.config.lsm_tree.max_level0_files
+        } else {
+            let total_size: u64 = levels.iter().map(|s| s.mmap.len() as u64).sum();
+            total_size > self.level_threshold_bytes(level)
+        }
+    }
+
+    fn tombstone_heavy(&self, tables: &[Arc<SSTable>]) -> bool {
+        let ratio = self.config.lsm_tree.tombstone_compaction_ratio;
+        ratio > 0.0
+            && tables.iter().map(|t| t.tombstones).sum::<u64>() >= TOMBSTONE_COMPACTION_MIN
+            && tombstone_density(tables) > ratio
+    }
+
+    /// Runs every compaction the levels are due for on the calling thread,
+    /// shallowest level first, so that none is due when it returns. All
+    /// levels are reserved meanwhile, as in `vacuum_levels`, and queued
+    /// tasks for levels that no longer need one are dropped.
+    fn settle_levels(self: &Arc<Self>) {
+        let (lock, cvar) = &*self.compaction_state;
+        {
+            let mut queue = lock.lock().unwrap();
+            while !queue.active_levels.is_empty() {
+                queue = cvar.wait(queue).unwrap();
+            }
+            queue.active_levels.extend(0..self.levels.len());
+        }
+
+        for level in 0..self.levels.len() - 1 {
+            while self.level_due(level) {
+                let shape = |lvl: &Vec<Arc<SSTable>>| (lvl.len(), lvl.iter().map(|t| t.mmap.len()).sum::<usize>());
+                let before = shape(&self.levels[level].read());
+                if level == 0 && self.compaction_strategy() == CompactionStrategy::SizeTiered {
+                    self.run_size_tiered();
+                } else {
+                    let tables = self.levels[level].read().clone();
+                    self.clone().run_compaction_cascade(level, tables);
+                }
+                // A failed merge leaves the level as it was.
+                if shape(&self.levels[level].read()) == before {
+                    break;
+                }
+            }
+        }
+
+        let mut queue = lock.lock().unwrap();
+        queue.pending.retain(|t| self.level_due(t.level));
+        queue.active_levels.clear();
+        cvar.notify_all();
+    }
+
+    pub fn trigger_compaction(self: Arc<Self>, level: usize) {
+        // The last level has nowhere to compact into.
+        if level + 1 >= self.levels.len() { return; }
 
-        if should_compact {
+        if self.level_due(level) {
             let &(ref lock, ref cvar) = &*self.compaction_state;
             let mut queue = lock.lock().unwrap();
             
@@ -2116,55 +3730,259 @@ impl LSMTreeInner {
         }
     }
 
-    fn run_compaction_cascade(self: Arc<Self>, level: usize, tables_to_compact: Vec<Arc<SSTable>>) {
-        // Record compaction access in heat tracker
-        for table in &tables_to_compact {
-            let ht = &self.heat_tracker;
-            for idx in &table.index {
-                ht.record_access(&idx.key, QueryType::Compact);
+    /// Reports `event` to the hooks in the config. Index trees stay quiet;
+    /// their table's own events cover them.
+    fn emit(&self, event: DbEvent) {
+        match event {
+            DbEvent::Flushed { .. } => self.flushes.fetch_add(1, AtomicOrdering::Relaxed),
+            DbEvent::Compacted { .. } => self.compactions.fetch_add(1, AtomicOrdering::Relaxed),
+            _ => 0,
+        };
+        if !self.is_index {
+            self.config.events.emit(&event);
+        }
+    }
+
+    /// Records the current level layout in the manifest. Called after every
+    /// change to `levels` and before any replaced file is deleted.
+    fn persist_manifest(&self) -> io::Result<()> {
+        let manifest = self.manifest.lock().unwrap();
+        let mut tables = Vec::new();
+        for (level, lvl) in self.levels.iter().enumerate() {
+            for sst in lvl.read().iter() {
+                if let Some(name) = sst.file_path.file_name().and_then(|n| n.to_str()) {
+                    tables.push((level as u8, name.to_string()));
+                }
             }
         }
+        manifest.store(self.levels.len(), &tables, self.config.lsm_tree.fsync_policy != FsyncPolicy::Never)
+    }
 
-        if let Ok(new_sst) = self.merge_sstables((level + 1) as u8, tables_to_compact.clone()) {
+    fn run_compaction_cascade(self: Arc<Self>, level: usize, tables_to_compact: Vec<Arc<SSTable>>) {
+        // Compaction reads are not recorded as heat: hot keys stay behind,
+        // so counting them would keep whatever compaction touches hot.
+        let old_size: u64 = tables_to_compact.iter().map(|t| t.mmap.len() as u64).sum();
+        if let Ok((hot_sst, cold_sst)) = self.merge_by_heat(level, &tables_to_compact) {
             let next_level = level + 1;
+            let new_size = hot_sst.iter().chain(&cold_sst).map(|t| t.mmap.len() as u64).sum::<u64>();
             {
                 let mut current_lvl = self.levels[level].write();
                 let mut next_lvl = self.levels[next_level].write();
 
                 current_lvl.retain(|t| !tables_to_compact.iter().any(|tc| tc.file_path == t.file_path));
-                next_lvl.push(new_sst);
+                // Tables still in the level were added while merging, so
+                // they are newer and the hot keys go in front of them.
+                if let Some(sst) = hot_sst {
+                    current_lvl.insert(0, sst);
+                }
+                if let Some(sst) = cold_sst {
+                    next_lvl.push(sst);
+                }
             }
 
-            // Drop local references before removing files
-            let paths_to_remove: Vec<_> = tables_to_compact.iter().map(|t| t.file_path.clone()).collect();
+            // Until the manifest drops the inputs they are still what
+            // recovery loads, so keep their files around.
+            if let Err(e) = self.persist_manifest() {
+                eprintln!("Failed to update manifest after compaction: {}", e);
+                return;
+            }
+
+            // Removed once readers still holding them are done.
+            for table in &tables_to_compact {
+                self.entry_cache.invalidate(table.id);
+                table.mark_obsolete();
+            }
             drop(tables_to_compact);
-            for path in paths_to_remove {
-                if let Err(e) = retry_on_permission_denied(|| std::fs::remove_file(&path)) {
-                    eprintln!("Failed to remove SSTable file {}: {}", path.display(), e);
+            self.emit(DbEvent::Compacted { table: self.table_type, level, reclaimed: old_size.saturating_sub(new_size) });
+
+            // Cascade to the next level if it is now due
+            self.clone().trigger_compaction(next_level);
+        }
+    }
+
+    /// Size-tiered compaction: merges runs of similar-sized adjacent level-0
+    /// tables until none is left. The result takes the run's place, so
+    /// level 0 stays ordered oldest first.
+    fn run_size_tiered(&self) {
+        loop {
+            let run: Vec<Arc<SSTable>> = {
+                let l0 = self.levels[0].read();
+                match size_tiered_run(&l0, self.config.lsm_tree.max_level0_files) {
+                    Some(range) => l0[range].to_vec(),
+                    None => return,
+                }
+            };
+            // Tombstones can only go when nothing older could hold the key.
+            let drop_tombstones = Arc::ptr_eq(&self.levels[0].read()[0], &run[0])
+                && self.levels[1..].iter().all(|l| l.read().is_empty());
+            let old_size: u64 = run.iter().map(|t| t.mmap.len() as u64).sum();
+            let merged = match self.merge_into(0, &run, drop_tombstones) {
+                Ok(merged) => merged,
+                Err(e) => {
+                    eprintln!("Size-tiered compaction failed: {}", e);
+                    return;
                 }
+            };
+            let new_size = merged.mmap.len() as u64;
+            {
+                // Flushes only append, so the run is still contiguous.
+                let mut l0 = self.levels[0].write();
+                let Some(start) = l0.iter().position(|t| Arc::ptr_eq(t, &run[0])) else { return };
+                l0.splice(start..start + run.len(), std::iter::once(merged));
+            }
+            if let Err(e) = self.persist_manifest() {
+                eprintln!("Failed to update manifest after compaction: {}", e);
+                return;
             }
 
-            // Cascade to next level if threshold exceeded
-            if next_level < 9 {
-                let (should_compact_next, _tables_next) = {
-                    let levels = self.levels[next_level].read();
-                    let total_size: u64 = levels.iter().map(|s| s.mmap.len() as u64).sum();
-                    let threshold = self.config.lsm_tree.level_size_thresholds_mb.get(next_level - 1)
-                        .cloned()
-                        .unwrap_or(10 * 10usize.pow(next_level as u32 - 1)) as u64 * 1024 * 1024;
+            for table in &run {
+                self.entry_cache.invalidate(table.id);
+                table.mark_obsolete();
+            }
+            drop(run);
+            self.emit(DbEvent::Compacted { table: self.table_type, level: 0, reclaimed: old_size.saturating_sub(new_size) });
+        }
+    }
 
-                    if total_size > threshold {
-                        (true, levels.clone())
-                    } else {
-                        (false, vec![])
-                    }
-                };
+    /// Merge every SSTable into one table, physically dropping tombstones,
+    /// expired entries and superseded versions. Background compaction is
+    /// held off for the duration. Returns the number of bytes reclaimed.
+    fn vacuum_levels(&self) -> io::Result<u64> {
+        let (lock, cvar) = &*self.compaction_state;
+        {
+            let mut queue = lock.lock().unwrap();
+            while !queue.active_levels.is_empty() {
+                queue = cvar.wait(queue).unwrap();
+            }
+            queue.active_levels.extend(0..self.levels.len());
+        }
+
+        let result = self.vacuum_reserved_levels();
+
+        let mut queue = lock.lock().unwrap();
+        queue.active_levels.clear();
+        cvar.notify_all();
+        drop(queue);
+
+        result
+    }
 
-                if should_compact_next {
-                    self.clone().trigger_compaction(next_level);
+    fn compact_key_reserved(&self, key: &[u8]) -> io::Result<usize> {
+        // Newest first, as `get_raw` visits them. Only on-disk versions are
+        // folded: increments still in memory are added on top when read.
+        let mut holders: Vec<(usize, Arc<SSTable>, KVEntry)> = Vec::new();
+        for (level, lvl) in self.levels.iter().enumerate() {
+            for sstable in lvl.read().iter().rev() {
+                if let Some(entry) = self.sstable_get(sstable, key) {
+                    holders.push((level, Arc::clone(sstable), entry));
                 }
             }
         }
+        if holders.len() < 2 {
+            return Ok(0);
+        }
+        let mut fold = VersionFold::default();
+        let mut resolved = None;
+        for (_, _, entry) in &holders {
+            if let Some(entry) = fold.add(entry.clone()) {
+                resolved = Some(entry);
+                break;
+            }
+        }
+        let resolved = resolved.or_else(|| fold.finish()).filter(|e| !e.deleted);
+
+        let mut replacements = Vec::with_capacity(holders.len());
+        for (i, (level, sstable, _)) in holders.iter().enumerate() {
+            let mut entries = self.merge_entries(std::slice::from_ref(sstable), false)?;
+            entries.remove(key);
+            if i == 0 {
+                if let Some(entry) = &resolved {
+                    entries.insert(key.to_vec(), entry.clone());
+                }
+            }
+            let rewritten = if entries.is_empty() {
+                None
+            } else {
+                Some(Arc::new(self.create_sstable(*level as u8, &entries, None)?))
+            };
+            replacements.push(rewritten);
+        }
+
+        {
+            // Lock levels in ascending order, same as `run_compaction_cascade`.
+            // Each rewrite takes its original's place, keeping the order
+            // tables flushed meanwhile rely on.
+            let mut guards: Vec<_> = self.levels.iter().map(|l| l.write()).collect();
+            for ((level, sstable, _), rewritten) in holders.iter().zip(replacements) {
+                let lvl = &mut guards[*level];
+                let Some(pos) = lvl.iter().position(|t| t.file_path == sstable.file_path) else { continue };
+                match rewritten {
+                    Some(table) => lvl[pos] = table,
+                    None => {
+                        lvl.remove(pos);
+                    }
+                }
+            }
+        }
+        self.persist_manifest()?;
+
+        for (_, sstable, _) in &holders {
+            self.entry_cache.invalidate(sstable.id);
+            sstable.mark_obsolete();
+        }
+        Ok(holders.len() - usize::from(resolved.is_some()))
+    }
+
+    fn vacuum_reserved_levels(&self) -> io::Result<u64> {
+        // Newest first: lower levels shadow higher ones, and within a level
+        // later tables shadow earlier ones.
+        let mut tables: Vec<Arc<SSTable>> = Vec::new();
+        let mut target_level = 0;
+        for (level, lvl) in self.levels.iter().enumerate() {
+            let lvl = lvl.read();
+            if !lvl.is_empty() {
+                target_level = level;
+            }
+            tables.extend(lvl.iter().rev().cloned());
+        }
+        if tables.is_empty() {
+            return Ok(0);
+        }
+
+        let old_size: u64 = tables.iter().map(|t| t.mmap.len() as u64).sum();
+        // Every table is part of the merge, so no older copy of a key can
+        // survive elsewhere and tombstones are safe to drop.
+        let merged_entries = self.merge_entries(&tables, true)?;
+        let new_sstable = if merged_entries.is_empty() {
+            None
+        } else {
+            Some(Arc::new(self.create_sstable(target_level as u8, &merged_entries, None)?))
+        };
+        let new_size = new_sstable.as_ref().map(|t| t.mmap.len() as u64).unwrap_or(0);
+
+        {
+            // Lock levels in ascending order, same as `run_compaction_cascade`.
+            let mut guards: Vec<_> = self.levels.iter().map(|l| l.write()).collect();
+            for lvl in guards.iter_mut() {
+                lvl.retain(|t| !tables.iter().any(|tc| tc.file_path == t.file_path));
+            }
+            if let Some(sst) = new_sstable {
+                // Tables flushed while merging are newer, so the result goes
+                // in front of them.
+                guards[target_level].insert(0, sst);
+            }
+        }
+        self.persist_manifest()?;
+
+        for table in &tables {
+            self.entry_cache.invalidate(table.id);
+            table.mark_obsolete();
+        }
+        drop(tables);
+
+        let reclaimed = old_size.saturating_sub(new_size);
+        self.emit(DbEvent::Compacted { table: self.table_type, level: target_level, reclaimed });
+        Ok(reclaimed)
     }
 
     pub fn run_blob_gc(&self) -> io::Result<()> {
@@ -2183,22 +4001,26 @@ impl LSMTreeInner {
         let mut current_new_offset = 0u64;
 
         for entry_res in iter {
-            let (old_offset, _old_size, key, value) = entry_res?;
+            let (old_offset, _old_size, mut key, value) = entry_res?;
 
             // Check if this blob is still alive in LSM-tree
-            let is_alive = if let Some(kv) = self.get_raw(&key) {
-                if kv.entry_type == EntryType::BlobIndex {
-                    if let Some(ptr) = BlobPointer::decode(&kv.value) {
-                        ptr.offset == old_offset
-                    } else {
-                        false
-                    }
-                } else {
-                    false
+            let points_here = |key: &[u8]| match self.get_raw(key) {
+                Some(kv) if kv.entry_type == EntryType::BlobIndex => {
+                    BlobPointer::decode(&kv.value).is_some_and(|ptr| ptr.offset == old_offset)
                 }
-            } else {
-                false
+                _ => false,
             };
+            let mut is_alive = points_here(&key);
+            if !is_alive {
+                // The blob log is never rewritten on upgrade, so records
+                // from before format version 3 carry the old key.
+                let mut upgraded = key.clone();
+                upgrade_key(self.table_type, 1, &mut upgraded);
+                if upgraded != key && points_here(&upgraded) {
+                    key = upgraded;
+                    is_alive = true;
+                }
+            }
 
             if is_alive {
                 // Write to new log
@@ -2232,86 +4054,261 @@ impl LSMTreeInner {
         Ok(())
     }
 
-    fn get_raw(&self, key: &[u8]) -> Option<KVEntry> {
-        let mut delta_sum: i64 = 0;
-        let mut has_increments = false;
-        let mut newest_entry: Option<KVEntry> = None;
-
-        // 1. MemTable
-        let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
-        if let Some(entry_ref) = self.memtable[shard].read().get(key) {
-            let entry = entry_ref.clone();
-            if entry.entry_type == EntryType::Increment {
-                has_increments = true;
-                if entry.value.len() == 8 {
-                    let mut arr = [0u8; 8];
-                    arr.copy_from_slice(&entry.value);
-                    delta_sum = delta_sum.wrapping_add(i64::from_le_bytes(arr));
+    /// Drops the entries of `shard`'s frozen buffer that were written out as
+    /// `flushed`, which may span several shards. Writes merged into the buffer by a later `freeze_shard`
+    /// carry a newer timestamp and stay for the next flush.
+    fn release_frozen(&self, shard: usize, flushed: &BTreeMap<Vec<u8>, KVEntry>) {
+        let mut frozen = self.frozen[shard].lock().unwrap();
+        if let Some(mem) = frozen.as_mut() {
+            mem.entries.retain(|key, entry| flushed.get(key).is_none_or(|f| f.timestamp != entry.timestamp));
+            // A counter frozen again while its table was written had the new
+            // deltas added to the flushed ones; only the new ones remain due.
+            for (key, entry) in mem.entries.iter_mut() {
+                let Some(f) = flushed.get(key) else { continue };
+                if entry.entry_type == EntryType::Increment && f.entry_type == EntryType::Increment {
+                    if let (Some(total), Some(done)) = (le_i64(&entry.value), le_i64(&f.value)) {
+                        entry.value = total.wrapping_sub(done).to_le_bytes().to_vec();
+                    }
                 }
-                if newest_entry.is_none() {
-                    newest_entry = Some(entry.clone());
+            }
+            if mem.entries.is_empty() {
+                *frozen = None;
+            }
+        }
+    }
+
+    /// Backpressure, applied before every write. From `l0_slowdown_files`
+    /// level-0 tables on the write is delayed, 10 ms per table over, up to
+    /// 100 ms. It stops while the flush thread is `max_pending_flushes`
+    /// freezes behind, or from `l0_stop_files` tables on while a queued or
+    /// running level-0 compaction brings the count back down; stopped, it
+    /// waits or fails with `WouldBlock` under `WriteStall::Fail`. With no
+    /// such compaction scheduled nothing would bring the count down, so
+    /// the write only gets the longest delay.
+    fn throttle_write(&self) -> io::Result<()> {
+        let config = &self.config.lsm_tree;
+        let flush_behind = || config.max_pending_flushes > 0
+            && self.pending_flushes.load(AtomicOrdering::SeqCst) >= config.max_pending_flushes;
+        let l0_full = || self.levels[0].read().len() >= config.l0_stop_files && self.level0_compaction_scheduled();
+
+        let l0_count = self.levels[0].read().len();
+        let stopped = flush_behind() || l0_full();
+        if !stopped && l0_count < config.l0_slowdown_files {
+            return Ok(());
+        }
+        let started = std::time::Instant::now();
+        if stopped {
+            if config.write_stall == WriteStall::Fail {
+                self.write_stalls.fetch_add(1, AtomicOrdering::Relaxed);
+                let reason = if flush_behind() {
+                    format!("{} memtables are waiting to be flushed", self.pending_flushes.load(AtomicOrdering::SeqCst))
+                } else {
+                    format!("level 0 holds {} tables", l0_count)
+                };
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("{}; writes resume once flushes and compaction catch up", reason),
+                ));
+            }
+            while (flush_behind() || l0_full()) && !self.shutdown.load(AtomicOrdering::SeqCst) {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        } else {
+            let over = l0_count.saturating_sub(config.l0_slowdown_files) as u64 + 1;
+            std::thread::sleep(std::time::Duration::from_millis((over * 10).min(100)));
+        }
+        self.write_stalls.fetch_add(1, AtomicOrdering::Relaxed);
+        self.write_stall_ms.fetch_add(started.elapsed().as_millis() as u64, AtomicOrdering::Relaxed);
+        Ok(())
+    }
+
+    fn level0_compaction_scheduled(&self) -> bool {
+        let queue = self.compaction_state.0.lock().unwrap();
+        queue.active_levels.contains(&0) || queue.pending.iter().any(|task| task.level == 0)
+    }
+
+    /// Moves every non-empty memtable shard into its frozen slot.
+    fn freeze_all(&self) {
+        for shard in 0..16 {
+            let mut mem = self.memtable[shard].write();
+            if !mem.is_empty() {
+                self.freeze_shard(shard, &mut mem);
+            }
+        }
+    }
+
+    /// Counts `writes` toward `memtable_max_ops`, scaled like the byte
+    /// limit in power-save and low-memory mode. The call that reaches the
+    /// limit freezes every shard, however small. Wakes the flush thread if
+    /// that happened or the caller already `froze` a shard.
+    fn count_user_bytes(&self, bytes: usize) {
+        self.user_bytes_written.fetch_add(bytes as u64, AtomicOrdering::Relaxed);
+    }
+
+    fn count_writes(&self, writes: usize, froze: bool) {
+        let mut max_ops = self.config.lsm_tree.memtable_max_ops;
+        if self.low_memory_mode.load(AtomicOrdering::SeqCst) {
+            max_ops = max_ops.div_ceil(2);
+        } else if self.power_save_mode.load(AtomicOrdering::SeqCst) {
+            max_ops *= 2;
+        }
+        let counted = self.memtable_ops.fetch_add(writes, AtomicOrdering::SeqCst) + writes;
+        // Only the caller whose swap still sees the limit reached freezes.
+        let limit_hit = max_ops > 0
+            && counted >= max_ops
+            && self.memtable_ops.swap(0, AtomicOrdering::SeqCst) >= max_ops;
+        if limit_hit {
+            self.freeze_all();
+        }
+        if froze || limit_hit {
+            self.pending_flushes.fetch_add(1, AtomicOrdering::SeqCst);
+            self.flush_pending.store(true, AtomicOrdering::SeqCst);
+            self.flush_state.1.notify_one();
+        }
+    }
+
+    /// Moves a memtable shard's entries into its frozen slot for the flush
+    /// thread. If the previous frozen buffer has not been picked up yet, the
+    /// new entries are layered on top of it rather than replacing it.
+    fn freeze_shard(&self, shard: usize, mem: &mut MemTable) {
+        let entries = std::mem::take(&mut mem.entries);
+        mem.clear();
+
+        let mut frozen = self.frozen[shard].lock().unwrap();
+        match frozen.as_mut() {
+            Some(older) => {
+                for (key, entry) in entries {
+                    older.put(key, entry.value, entry.entry_type, entry.expires_at, entry.timestamp);
                 }
-            } else {
-                return Some(entry);
             }
+            None => {
+                *frozen = Some(MemTable {
+                    current_size: entries.values().map(KVEntry::size).sum(),
+                    entry_count: entries.len(),
+                    entries,
+                    max_size: mem.max_size,
+                    table_type: mem.table_type,
+                });
+            }
+        }
+    }
+
+    /// `Update` if `key` currently has a live value, `Insert` otherwise.
+    /// Only the newest version decides that, increments included, so no
+    /// value is read: the memtable and frozen buffer are checked first, then
+    /// every SSTable that could hold a newer version gets a key-only probe,
+    /// fronted by its bloom filter and bypassing the entry cache. The probes
+    /// are not counted as reads in [`LSMTree::amplification`].
+    ///
+    /// The tag is best-effort: it is decided before the write takes its
+    /// shard lock, so two first writes of a key racing each other can both
+    /// be tagged `Insert`. Index trees, and tables whose `update_tags` is
+    /// off, always get `Insert`.
+    fn write_type(&self, key: &[u8]) -> EntryType {
+        if self.is_index || !self.config.lsm_tree.update_tags.for_table(self.table_type) {
+            return EntryType::Insert;
         }
+        let now = self.now_ms();
+        let is_live = |deleted: bool, expires_at: u64| !deleted && (expires_at == 0 || expires_at >= now);
+        let tag = |live: bool| if live { EntryType::Update } else { EntryType::Insert };
 
-        // 2. Levels (0 to 9)
+        let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
+        if let Some(e) = self.memtable[shard].read().get(key) {
+            return tag(is_live(e.deleted, e.expires_at));
+        }
+        if let Some(e) = self.frozen[shard].lock().unwrap().as_ref().and_then(|m| m.get(key)) {
+            return tag(is_live(e.deleted, e.expires_at));
+        }
+
+        // (timestamp, live) of the newest version on disk
+        let mut newest: Option<(u64, bool)> = None;
         for level in &self.levels {
             let sstables = level.read();
             for sstable in sstables.iter().rev() {
-                if let Some(entry) = sstable.get(key) {
-                    if entry.entry_type == EntryType::Increment {
-                        has_increments = true;
-                        if entry.value.len() == 8 {
-                            let mut arr = [0u8; 8];
-                            arr.copy_from_slice(&entry.value);
-                            delta_sum = delta_sum.wrapping_add(i64::from_le_bytes(arr));
-                        }
-                        if newest_entry.is_none() {
-                            newest_entry = Some(entry.clone());
-                        }
-                    } else {
-                        // Found a base entry.
-                        if has_increments {
-                            let mut final_entry = newest_entry.unwrap();
-                            let base_val = if entry.value.len() == 8 {
-                                let mut arr = [0u8; 8];
-                                arr.copy_from_slice(&entry.value);
-                                i64::from_le_bytes(arr)
-                            } else {
-                                0
-                            };
-                            let sum = base_val.wrapping_add(delta_sum);
-                            final_entry.value = sum.to_le_bytes().to_vec();
-                            final_entry.entry_type = EntryType::Insert;
-                            final_entry.deleted = false;
-                            return Some(final_entry);
-                        } else {
-                            return Some(entry);
-                        }
+                if !sstable.covers(key) || newest.is_some_and(|(ts, _)| sstable.max_timestamp() <= ts) {
+                    continue;
+                }
+                if let Some(e) = sstable.probe(key) {
+                    if newest.is_none_or(|(ts, _)| e.timestamp > ts) {
+                        newest = Some((e.timestamp, is_live(e.entry_type == EntryType::Delete, e.expires_at)));
                     }
                 }
             }
         }
+        tag(newest.is_some_and(|(_, live)| live))
+    }
+
+    /// [`SSTable::create`] in this tree's directory, counting the bytes
+    /// written towards [`LSMTree::amplification`].
+    fn create_sstable(&self, level: u8, entries: &BTreeMap<Vec<u8>, KVEntry>, rate_limit_mb: Option<f64>) -> io::Result<SSTable> {
+        let sstable = SSTable::create(level, entries, &self.base_path, self.table_type, rate_limit_mb, &self.config.lsm_tree)?;
+        self.sstable_bytes_written.fetch_add(sstable.mmap.len() as u64, AtomicOrdering::Relaxed);
+        Ok(sstable)
+    }
 
-        // If we only found increments but no base, treat base as 0
-        if has_increments {
-            let mut final_entry = newest_entry.unwrap();
-            final_entry.value = delta_sum.to_le_bytes().to_vec();
-            final_entry.entry_type = EntryType::Insert;
-            final_entry.deleted = false;
-            return Some(final_entry);
+    /// `SSTable::get` through the entry cache.
+    fn sstable_get(&self, sstable: &SSTable, key: &[u8]) -> Option<KVEntry> {
+        if self.entry_cache.is_enabled() {
+            if let Some(entry) = self.entry_cache.get(sstable.id, key) {
+                return Some(entry);
+            }
         }
+        let (entry, read) = sstable.get_measured(key);
+        self.sstable_bytes_read.fetch_add(read, AtomicOrdering::Relaxed);
+        let entry = entry?;
+        if self.entry_cache.is_enabled() {
+            self.entry_cache.insert(sstable.id, entry.clone());
+        }
+        Some(entry)
+    }
 
-        None
+    fn get_raw(&self, key: &[u8]) -> Option<KVEntry> {
+        let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
+        let newest = self.memtable[shard].read().get(key).cloned();
+        self.get_raw_below(key, newest)
+    }
+
+    /// [`get_raw`](Self::get_raw) given the memtable's entry for `key`, for
+    /// callers that already hold its shard lock.
+    fn get_raw_below(&self, key: &[u8], newest: Option<KVEntry>) -> Option<KVEntry> {
+        let mut fold = VersionFold::default();
+
+        // 1. MemTable, then the frozen buffer awaiting flush
+        let shard = (key.first().cloned().unwrap_or(0) % 16) as usize;
+        if let Some(entry) = newest {
+            if let Some(resolved) = fold.add(entry) {
+                return Some(resolved);
+            }
+        }
+        if let Some(entry) = self.frozen[shard].lock().unwrap().as_ref().and_then(|m| m.get(key).cloned()) {
+            if let Some(resolved) = fold.add(entry) {
+                return Some(resolved);
+            }
+        }
+
+        // 2. Levels, shallowest first
+        let mut versions = TableVersions::default();
+        for level in &self.levels {
+            let sstables = level.read();
+            for sstable in sstables.iter().rev() {
+                if versions.may_hold(sstable, key) {
+                    if let Some(entry) = self.sstable_get(sstable, key) {
+                        versions.add(&fold, entry);
+                    }
+                }
+            }
+        }
+        versions.resolve(fold)
     }
 
     fn apply_batch_direct(&self, batch: Batch, entry_type: EntryType) -> io::Result<()> {
+        let timestamp = self.clock.next();
         let wal = self.wal.write();
         wal.log(&mut BDBLogEntry::new(EntryType::BatchStart, Vec::new(), Vec::new()))?;
         for (k, v, _t) in &batch.entries {
-            wal.log(&mut BDBLogEntry::new(entry_type, k.clone(), v.clone()))?;
+            let mut entry = BDBLogEntry::new(entry_type, k.clone(), v.clone());
+            entry.timestamp = timestamp;
+            wal.log(&mut entry)?;
         }
         wal.log(&mut BDBLogEntry::new(EntryType::BatchEnd, Vec::new(), Vec::new()))?;
         drop(wal);
@@ -2319,14 +4316,31 @@ impl LSMTreeInner {
         for (k, v, _t) in batch.entries {
             let shard = (k.first().cloned().unwrap_or(0) % 16) as usize;
             let mut mem = self.memtable[shard].write();
-            mem.put(k, v, entry_type, 0);
+            mem.put(k, v, entry_type, 0, timestamp);
         }
         Ok(())
     }
 
     pub fn merge_sstables(&self, level: u8, tables: Vec<Arc<SSTable>>) -> io::Result<Arc<SSTable>> {
-        let is_final_level = level == 9;
+        self.merge_into(level, &tables, level as usize + 1 == self.levels.len())
+    }
 
+    fn merge_into(&self, level: u8, tables: &[Arc<SSTable>], drop_tombstones: bool) -> io::Result<Arc<SSTable>> {
+        let merged_entries = self.merge_entries(tables, drop_tombstones)?;
+
+        let new_sstable = Arc::new(self.create_sstable(level, &merged_entries, self.compaction_rate_limit())?);
+
+        // Note: SSTable file removal is now handled in `run_compaction_cascade`
+        // after removing the table entries from `self.levels` to prevent locking
+        // and race conditions, especially on Windows.
+
+        Ok(new_sstable)
+    }
+
+    /// Merge `tables` into a single sorted map, keeping the newest version of
+    /// each key. Tombstones are kept unless `drop_tombstones` is set, which is
+    /// only safe when no older table outside `tables` can hold the same key.
+    fn merge_entries(&self, tables: &[Arc<SSTable>], drop_tombstones: bool) -> io::Result<BTreeMap<Vec<u8>, KVEntry>> {
         // Multi-way merge sort
         // We handle Iterator items that are Results now. If an iterator yields an error, we bubble it up.
         // `peekable` allows looking ahead, but since we have `Result`, peek() returns `&Result`.
@@ -2394,7 +4408,7 @@ impl LSMTreeInner {
                 }
 
                 if let Some(mut entry) = best_entry {
-                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+                    let now = self.now_ms();
                     if entry.expires_at > 0 && entry.expires_at < now {
                         // Skip expired
                     } else {
@@ -2411,7 +4425,7 @@ impl LSMTreeInner {
                             entry.entry_type = EntryType::Insert;
                         }
 
-                        if !is_final_level || !entry.deleted {
+                        if !drop_tombstones || !entry.deleted {
                             merged_entries.insert(key, entry);
                         }
                     }
@@ -2421,30 +4435,21 @@ impl LSMTreeInner {
             }
         }
 
-        // Derive write rate limit in MB/s from compaction_cpu_limit (e.g. compaction_cpu_limit * 200.0 MB/s, default 0.05 -> 10.0 MB/s)
-        let rate_limit = if self.config.lsm_tree.compaction_cpu_limit > 0.0 {
-            Some(self.config.lsm_tree.compaction_cpu_limit * 200.0)
-        } else {
-            Some(10.0)
-        };
-        let new_sstable = Arc::new(SSTable::create(level, &merged_entries, &self.base_path, self.table_type, rate_limit, self.config.lsm_tree.verify_checksums)?);
-
-        // Note: SSTable file removal is now handled in `run_compaction_cascade`
-        // after removing the table entries from `self.levels` to prevent locking
-        // and race conditions, especially on Windows.
-
-        Ok(new_sstable)
+        Ok(merged_entries)
     }
 }
 
 impl Drop for LSMTree {
     fn drop(&mut self) {
+        if self.inner.closed.load(AtomicOrdering::SeqCst) {
+            return;
+        }
         // Signal flush thread to stop
         self.inner.shutdown_flush.store(true, AtomicOrdering::Relaxed);
         self.inner.flush_state.1.notify_one();
 
         // Flush remaining data synchronously
-        if let Err(e) = self.flush() {
+        if let Err(e) = self.final_flush() {
             eprintln!("Failed to flush LSMTree on drop: {}", e);
         }
     }
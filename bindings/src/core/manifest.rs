@@ -0,0 +1,106 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const MANIFEST_MAGIC: &str = "BDBMANIFEST";
+
+/// Level count of trees whose manifest predates the count being recorded.
+const LEGACY_LEVELS: usize = 10;
+
+/// A tree's level count and its `(level, filename)` pairs.
+pub type ManifestContents = (usize, Vec<(u8, String)>);
+
+/// The set of live SSTables of one table, in level order and, within a
+/// level, oldest first.
+///
+/// Stored as `<table>.manifest` next to the table's WAL: a
+/// `BDBMANIFEST 2 <levels>` header, then one `<level> <filename>` line per
+/// SSTable. Every update writes a temporary
+/// file and renames it over the old one, so a reader sees either the
+/// previous or the new set, never a mix.
+pub struct Manifest {
+    path: PathBuf,
+}
+
+impl Manifest {
+    pub fn new(base_path: &Path, table_prefix: &str) -> Self {
+        Self {
+            path: base_path.join(format!("{}.manifest", table_prefix)),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the tree's level count and the recorded `(level, filename)`
+    /// pairs, or `None` if no manifest has been written yet.
+    pub fn load(&self) -> io::Result<Option<ManifestContents>> {
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut lines = BufReader::new(file).lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let level_count = match header.split(' ').collect::<Vec<_>>()[..] {
+            [MANIFEST_MAGIC, "1"] => LEGACY_LEVELS,
+            [MANIFEST_MAGIC, "2", levels] => levels
+                .parse::<usize>()
+                .ok()
+                .filter(|l| (2..=u8::MAX as usize + 1).contains(l))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid manifest level count"))?,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid manifest header")),
+        };
+
+        let mut tables = Vec::new();
+        for line in lines {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let (level, name) = line
+                .split_once(' ')
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed manifest line"))?;
+            let level = level
+                .parse::<u8>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            tables.push((level, name.to_string()));
+        }
+        Ok(Some((level_count, tables)))
+    }
+
+    /// Atomically replaces the manifest with `tables` of a tree with
+    /// `levels` levels. With `sync` unset the rename is still atomic, but
+    /// may not survive a power failure.
+    pub fn store(&self, levels: usize, tables: &[(u8, String)], sync: bool) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("manifest.tmp");
+        {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&tmp_path)?;
+            let mut buf = String::with_capacity(32 + tables.len() * 48);
+            buf.push_str(&format!("{} 2 {}\n", MANIFEST_MAGIC, levels));
+            for (level, name) in tables {
+                buf.push_str(&format!("{} {}\n", level, name));
+            }
+            file.write_all(buf.as_bytes())?;
+            if sync {
+                file.sync_all()?;
+            }
+        }
+        fs::rename(&tmp_path, &self.path)?;
+
+        // Persist the rename itself.
+        #[cfg(unix)]
+        if sync {
+            if let Some(dir) = self.path.parent().and_then(|d| File::open(d).ok()) {
+                let _ = dir.sync_all();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::core::config::BrowserDBConfig;
+use crate::core::modes::DatabaseMode;
+
+/// What `BrowserDB::open` needs to reopen a database the way it was last
+/// used: the mode of the default container and the configuration in effect.
+/// Its `codecs` are the ones the tables were created with and outrank any
+/// config the database is opened with.
+///
+/// Stored as TOML in `METADATA` at the database root and replaced
+/// atomically (temp file + rename) whenever either changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseMetadata {
+    pub mode: DatabaseMode,
+    #[serde(default)]
+    pub config: BrowserDBConfig,
+}
+
+impl DatabaseMetadata {
+    pub fn path(base_path: &Path) -> PathBuf {
+        base_path.join("METADATA")
+    }
+
+    /// Returns `None` if there is no `METADATA` file or it cannot be parsed.
+    pub fn load(base_path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(Self::path(base_path)).ok()?;
+        toml::from_str(&content).ok()
+    }
+
+    pub fn store(&self, base_path: &Path) -> io::Result<()> {
+        let content = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let path = Self::path(base_path);
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&tmp_path)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)
+    }
+}
@@ -1,7 +1,18 @@
 pub mod format;
+pub mod compression;
+pub mod codec;
 pub mod heatmap;
 pub mod blob_log;
+pub mod manifest;
+pub mod metadata;
 pub mod lsm_tree;
 pub mod modes;
 pub mod wal;
 pub mod config;
+pub mod events;
+pub mod entry_cache;
+pub mod clock;
+pub mod compaction_pool;
+pub mod repair;
+pub mod integrity;
+pub mod snapshot;
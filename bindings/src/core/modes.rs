@@ -2,14 +2,18 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 
-use crate::core::lsm_tree::LSMTree;
-use crate::core::format::TableType;
+use crate::core::lsm_tree::{LSMTree, LsmSnapshot};
+use crate::core::format::{upgrade_key, TableType, BDB_VERSION, MAGIC_BYTES};
 
 use std::fmt;
+use crate::core::clock::SharedClock;
 use crate::core::config::BrowserDBConfig;
+use crate::core::events::DbEvent;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DatabaseMode {
     Persistent,
     Ultra,
@@ -19,6 +23,9 @@ pub enum DatabaseMode {
 pub enum ModeSwitchError {
     DataLoss,
     IoError(std::io::Error),
+    /// Persistent mode was requested of a switcher made by
+    /// [`ModeSwitcher::in_memory`].
+    NoDirectory,
 }
 
 impl fmt::Display for ModeSwitchError {
@@ -26,6 +33,7 @@ impl fmt::Display for ModeSwitchError {
         match self {
             ModeSwitchError::DataLoss => write!(f, "Potential data loss detected"),
             ModeSwitchError::IoError(e) => write!(f, "IO error: {}", e),
+            ModeSwitchError::NoDirectory => write!(f, "An in-memory database has no directory for Persistent mode"),
         }
     }
 }
@@ -42,26 +50,42 @@ pub struct ModeConfig {
 
 pub type UltraEntry = (Vec<u8>, u64);
 
+/// Name of the file Ultra tables are saved to when
+/// `ultra_snapshot_on_close` is set.
+pub const ULTRA_SNAPSHOT_FILE: &str = "ultra.snapshot";
+
+/// On-disk form of an `UltraMode` snapshot: every table's raw map, expiry
+/// included, in `UltraMode::TABLES` order. The file holds `MAGIC_BYTES`,
+/// the format version its keys are in, then this struct in bincode;
+/// snapshots from before format version 3 are the bare struct.
+#[derive(Serialize, Deserialize)]
+struct UltraSnapshot {
+    tables: Vec<Vec<(Vec<u8>, UltraEntry)>>,
+}
+
+/// Lock stripes of an [`UltraTable`].
+const ULTRA_SHARDS: usize = 16;
+
+/// One Ultra-mode table: a hash map split into [`ULTRA_SHARDS`] lock
+/// stripes by a hash of the key, so writers to different keys rarely wait
+/// on each other. Operations on the whole table lock every stripe.
 pub struct UltraTable {
-    pub data: RwLock<HashMap<Vec<u8>, UltraEntry>>,
+    shards: Vec<RwLock<HashMap<Vec<u8>, UltraEntry>>>,
     pub entry_count: std::sync::atomic::AtomicUsize,
+    /// Decides which entries have expired.
+    pub clock: SharedClock,
 }
 
 impl Default for UltraTable {
     fn default() -> Self {
-        Self {
-            data: RwLock::new(HashMap::new()),
-            entry_count: std::sync::atomic::AtomicUsize::new(0),
-        }
+        Self::with_clock(SharedClock::default())
     }
 }
 
-#[inline]
-fn now_ms() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
+fn ultra_shard_index(key: &[u8]) -> usize {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    std::hash::Hash::hash(key, &mut hasher);
+    std::hash::Hasher::finish(&hasher) as usize % ULTRA_SHARDS
 }
 
 impl UltraTable {
@@ -69,8 +93,23 @@ impl UltraTable {
         Self::default()
     }
 
+    pub fn with_clock(clock: SharedClock) -> Self {
+        Self {
+            shards: (0..ULTRA_SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+            entry_count: std::sync::atomic::AtomicUsize::new(0),
+            clock,
+        }
+    }
+
+    fn shard(&self, key: &[u8]) -> &RwLock<HashMap<Vec<u8>, UltraEntry>> {
+        &self.shards[ultra_shard_index(key)]
+    }
+
     pub fn clear(&self) {
-        self.data.write().clear();
+        let mut shards: Vec<_> = self.shards.iter().map(|s| s.write()).collect();
+        for shard in &mut shards {
+            shard.clear();
+        }
         self.entry_count.store(0, std::sync::atomic::Ordering::SeqCst);
     }
 
@@ -79,28 +118,76 @@ impl UltraTable {
     /// Enforced lazily on read; use [`UltraTable::purge_expired`] to reclaim
     /// memory from expired entries.
     pub fn put(&self, key: Vec<u8>, value: Vec<u8>, expires_at: u64) {
-        if self.data.write().insert(key, (value, expires_at)).is_none() {
+        if self.shard(&key).write().insert(key, (value, expires_at)).is_none() {
             self.entry_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         }
     }
 
+    /// [`UltraTable::put`] for many entries, taking each stripe's write
+    /// lock once.
+    pub fn put_many(&self, entries: Vec<(Vec<u8>, Vec<u8>)>) {
+        let mut by_shard: Vec<Vec<(Vec<u8>, Vec<u8>)>> = (0..ULTRA_SHARDS).map(|_| Vec::new()).collect();
+        for (key, value) in entries {
+            by_shard[ultra_shard_index(&key)].push((key, value));
+        }
+        let mut added = 0;
+        for (shard, entries) in self.shards.iter().zip(by_shard) {
+            if entries.is_empty() {
+                continue;
+            }
+            let mut data = shard.write();
+            for (key, value) in entries {
+                if data.insert(key, (value, 0)).is_none() {
+                    added += 1;
+                }
+            }
+        }
+        self.entry_count.fetch_add(added, std::sync::atomic::Ordering::SeqCst);
+    }
+
     pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
-        let data = self.data.read();
+        let data = self.shard(key).read();
         let (value, expires_at) = data.get(key)?.clone();
-        if expires_at != 0 && expires_at < now_ms() {
+        if expires_at != 0 && expires_at < self.clock.now_ms() {
             return None;
         }
         Some(value)
     }
 
+    /// Calls `f` with the value of `key`, borrowed under the read lock.
+    pub fn with_value<R>(&self, key: &[u8], f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let data = self.shard(key).read();
+        let (value, expires_at) = data.get(key)?;
+        if *expires_at != 0 && *expires_at < self.clock.now_ms() {
+            return None;
+        }
+        Some(f(value))
+    }
+
     pub fn delete(&self, key: &[u8]) {
-        if self.data.write().remove(key).is_some() {
+        if self.shard(key).write().remove(key).is_some() {
             self.entry_count.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
         }
     }
 
+    /// Replaces the value of `key` with `new` if it is `expected`, `None`
+    /// meaning absent or expired, under the stripe's write lock. Returns
+    /// whether it did. The new value never expires.
+    pub fn compare_and_swap(&self, key: Vec<u8>, expected: Option<Vec<u8>>, new: Vec<u8>) -> bool {
+        let now = self.clock.now_ms();
+        let mut data = self.shard(&key).write();
+        let current = data.get(&key).filter(|(_, expires_at)| *expires_at == 0 || *expires_at >= now);
+        if current.map(|(value, _)| value) != expected.as_ref() {
+            return false;
+        }
+        if data.insert(key, (new, 0)).is_none() {
+            self.entry_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+        true
+    }
+
     pub fn increment(&self, key: &[u8], delta: i64) {
-        let mut data = self.data.write();
+        let mut data = self.shard(key).write();
         let entry = data.entry(key.to_vec());
         match entry {
             std::collections::hash_map::Entry::Occupied(mut occupied) => {
@@ -128,26 +215,70 @@ impl UltraTable {
     /// out but not removed from the table; call [`UltraTable::purge_expired`]
     /// to actually reclaim them.
     pub fn all_entries(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
-        let now = now_ms();
-        self.data
-            .read()
-            .iter()
+        let now = self.clock.now_ms();
+        self.raw_entries()
+            .into_iter()
             .filter(|(_, (_, expires_at))| *expires_at == 0 || *expires_at >= now)
-            .map(|(k, (v, _)): (&Vec<u8>, &UltraEntry)| (k.clone(), v.clone()))
+            .map(|(k, (v, _))| (k, v))
             .collect()
     }
 
+    /// Point-in-time copy of every entry, expired ones and their expiry
+    /// included. All stripes are read-locked together for the copy.
+    pub fn raw_entries(&self) -> HashMap<Vec<u8>, UltraEntry> {
+        let shards: Vec<_> = self.shards.iter().map(|s| s.read()).collect();
+        let mut entries = HashMap::with_capacity(shards.iter().map(|s| s.len()).sum());
+        for shard in &shards {
+            entries.extend(shard.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        entries
+    }
+
+    /// Replaces the whole table with `entries`.
+    pub fn replace_all(&self, entries: impl IntoIterator<Item = (Vec<u8>, UltraEntry)>) {
+        let mut shards: Vec<_> = self.shards.iter().map(|s| s.write()).collect();
+        for shard in &mut shards {
+            shard.clear();
+        }
+        for (key, entry) in entries {
+            shards[ultra_shard_index(&key)].insert(key, entry);
+        }
+        let len = shards.iter().map(|s| s.len()).sum();
+        self.entry_count.store(len, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Number of keys, expired or not, for which `f` returns `true`.
+    pub fn count_keys(&self, f: impl Fn(&[u8]) -> bool) -> usize {
+        self.shards.iter().map(|s| s.read().keys().filter(|k| f(k)).count()).sum()
+    }
+
+    /// Number of unexpired keys for which `f` returns `true`.
+    pub fn live_count(&self, f: impl Fn(&[u8]) -> bool) -> usize {
+        let now = self.clock.now_ms();
+        self.shards
+            .iter()
+            .map(|s| s.read().iter().filter(|(k, (_, expires_at))| (*expires_at == 0 || *expires_at >= now) && f(k)).count())
+            .sum()
+    }
+
+    /// Bytes held in keys and values, expired entries included.
+    pub fn size_bytes(&self) -> u64 {
+        self.shards.iter().map(|s| s.read().iter().map(|(k, (v, _))| (k.len() + v.len()) as u64).sum::<u64>()).sum()
+    }
+
     /// Remove all expired entries and return the number of entries purged.
     /// Use this to free memory in long-lived Ultra mode sessions; no
     /// background thread is spawned (by design — Ultra mode avoids
     /// background work).
     pub fn purge_expired(&self) -> usize {
-        let now = now_ms();
-        let mut data = self.data.write();
-        let before = data.len();
-        data.retain(|_, (_, expires_at)| *expires_at == 0 || *expires_at >= now);
-        let purged = before - data.len();
-        drop(data);
+        let now = self.clock.now_ms();
+        let mut purged = 0;
+        for shard in &self.shards {
+            let mut data = shard.write();
+            let before = data.len();
+            data.retain(|_, (_, expires_at)| *expires_at == 0 || *expires_at >= now);
+            purged += before - data.len();
+        }
         if purged > 0 {
             self.entry_count.fetch_sub(purged, std::sync::atomic::Ordering::SeqCst);
         }
@@ -157,13 +288,13 @@ impl UltraTable {
 
 pub struct PersistentMode {
     pub path: PathBuf,
-    pub history: LSMTree,
-    pub bookmarks: LSMTree,
-    pub cookies: LSMTree,
-    pub cache: LSMTree,
-    pub localstore: LSMTree,
-    pub settings: LSMTree,
-    pub binarystore: LSMTree,
+    pub history: Arc<LSMTree>,
+    pub bookmarks: Arc<LSMTree>,
+    pub cookies: Arc<LSMTree>,
+    pub cache: Arc<LSMTree>,
+    pub localstore: Arc<LSMTree>,
+    pub settings: Arc<LSMTree>,
+    pub binarystore: Arc<LSMTree>,
 }
 
 impl PersistentMode {
@@ -181,6 +312,33 @@ impl PersistentMode {
         Self::new_with_indices(path, config, HashMap::new())
     }
 
+    /// `LSMTree::close` for every table. If one fails, the tables closed
+    /// before it are reopened, so the mode stays usable.
+    pub fn close(&self) -> std::io::Result<()> {
+        let tables = [&self.history, &self.bookmarks, &self.cookies, &self.cache, &self.localstore, &self.settings, &self.binarystore];
+        for (i, table) in tables.iter().enumerate() {
+            if let Err(e) = table.close() {
+                for closed in &tables[..i] {
+                    closed.reopen();
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn table(&self, table_type: TableType) -> &Arc<LSMTree> {
+        match table_type {
+            TableType::History => &self.history,
+            TableType::Bookmarks => &self.bookmarks,
+            TableType::Cookies => &self.cookies,
+            TableType::Cache => &self.cache,
+            TableType::LocalStore => &self.localstore,
+            TableType::Settings => &self.settings,
+            TableType::BinaryStore => &self.binarystore,
+        }
+    }
+
     pub fn new_with_indices(
         path: &Path,
         config: &ModeConfig,
@@ -190,46 +348,122 @@ impl PersistentMode {
         let max_mem = config.ext_config.lsm_tree.max_memtable_size_mb * 1024 * 1024;
         Ok(Self {
             path: path.to_path_buf(),
-            history: LSMTree::new_with_indices(path, TableType::History, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::History).unwrap_or_default())?,
-            bookmarks: LSMTree::new_with_indices(path, TableType::Bookmarks, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::Bookmarks).unwrap_or_default())?,
-            cookies: LSMTree::new_with_indices(path, TableType::Cookies, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::Cookies).unwrap_or_default())?,
-            cache: LSMTree::new_with_indices(path, TableType::Cache, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::Cache).unwrap_or_default())?,
-            localstore: LSMTree::new_with_indices(path, TableType::LocalStore, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::LocalStore).unwrap_or_default())?,
-            settings: LSMTree::new_with_indices(path, TableType::Settings, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::Settings).unwrap_or_default())?,
-            binarystore: LSMTree::new_with_indices(path, TableType::BinaryStore, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::BinaryStore).unwrap_or_default())?,
+            history: Arc::new(LSMTree::new_with_indices(path, TableType::History, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::History).unwrap_or_default())?),
+            bookmarks: Arc::new(LSMTree::new_with_indices(path, TableType::Bookmarks, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::Bookmarks).unwrap_or_default())?),
+            cookies: Arc::new(LSMTree::new_with_indices(path, TableType::Cookies, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::Cookies).unwrap_or_default())?),
+            cache: Arc::new(LSMTree::new_with_indices(path, TableType::Cache, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::Cache).unwrap_or_default())?),
+            localstore: Arc::new(LSMTree::new_with_indices(path, TableType::LocalStore, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::LocalStore).unwrap_or_default())?),
+            settings: Arc::new(LSMTree::new_with_indices(path, TableType::Settings, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::Settings).unwrap_or_default())?),
+            binarystore: Arc::new(LSMTree::new_with_indices(path, TableType::BinaryStore, max_mem, config.ext_config.clone(), index_defs.remove(&TableType::BinaryStore).unwrap_or_default())?),
         })
     }
 }
 
 pub struct UltraMode {
-    pub history: UltraTable,
-    pub bookmarks: UltraTable,
-    pub cookies: UltraTable,
-    pub cache: UltraTable,
-    pub localstore: UltraTable,
-    pub settings: UltraTable,
-    pub binarystore: UltraTable,
+    pub history: Arc<UltraTable>,
+    pub bookmarks: Arc<UltraTable>,
+    pub cookies: Arc<UltraTable>,
+    pub cache: Arc<UltraTable>,
+    pub localstore: Arc<UltraTable>,
+    pub settings: Arc<UltraTable>,
+    pub binarystore: Arc<UltraTable>,
 }
 
 impl Default for UltraMode {
     fn default() -> Self {
-        Self {
-            history: UltraTable::new(),
-            bookmarks: UltraTable::new(),
-            cookies: UltraTable::new(),
-            cache: UltraTable::new(),
-            localstore: UltraTable::new(),
-            settings: UltraTable::new(),
-            binarystore: UltraTable::new(),
-        }
+        Self::with_clock(SharedClock::default())
     }
 }
 
 impl UltraMode {
+    const TABLES: [TableType; 7] = [
+        TableType::History,
+        TableType::Bookmarks,
+        TableType::Cookies,
+        TableType::Cache,
+        TableType::LocalStore,
+        TableType::Settings,
+        TableType::BinaryStore,
+    ];
+
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Tables whose entries expire by `clock`.
+    pub fn with_clock(clock: SharedClock) -> Self {
+        let table = || Arc::new(UltraTable::with_clock(clock.clone()));
+        Self {
+            history: table(),
+            bookmarks: table(),
+            cookies: table(),
+            cache: table(),
+            localstore: table(),
+            settings: table(),
+            binarystore: table(),
+        }
+    }
+
+    /// Writes every table to `path` (temp file + rename). Expired entries
+    /// are left out.
+    pub fn save_snapshot(&self, path: &Path) -> std::io::Result<()> {
+        self.purge_expired_all();
+        let snapshot = UltraSnapshot {
+            tables: Self::TABLES.iter()
+                .map(|t| self.table(*t).raw_entries().into_iter().collect())
+                .collect(),
+        };
+        let mut bytes = MAGIC_BYTES.to_vec();
+        bytes.push(BDB_VERSION);
+        bincode::serialize_into(&mut bytes, &snapshot)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            std::io::Write::write_all(&mut file, &bytes)?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, path)
+    }
+
+    /// Replaces the contents of every table with the snapshot at `path`.
+    pub fn load_snapshot(&self, path: &Path) -> std::io::Result<()> {
+        let bytes = std::fs::read(path)?;
+        let (version, body) = match bytes.strip_prefix(&MAGIC_BYTES[..]) {
+            Some([version, body @ ..]) if *version <= BDB_VERSION => (*version, body),
+            Some(_) => {
+                return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Ultra snapshot is from a newer on-disk format"));
+            }
+            None => (2, &bytes[..]),
+        };
+        let snapshot: UltraSnapshot = bincode::deserialize(body)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if snapshot.tables.len() != Self::TABLES.len() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Ultra snapshot has the wrong number of tables"));
+        }
+
+        for (table_type, entries) in Self::TABLES.iter().zip(snapshot.tables) {
+            self.table(*table_type).replace_all(entries.into_iter().map(|(mut key, entry)| {
+                upgrade_key(*table_type, version, &mut key);
+                (key, entry)
+            }));
+        }
+        Ok(())
+    }
+
+    pub fn table(&self, table_type: TableType) -> &Arc<UltraTable> {
+        match table_type {
+            TableType::History => &self.history,
+            TableType::Bookmarks => &self.bookmarks,
+            TableType::Cookies => &self.cookies,
+            TableType::Cache => &self.cache,
+            TableType::LocalStore => &self.localstore,
+            TableType::Settings => &self.settings,
+            TableType::BinaryStore => &self.binarystore,
+        }
+    }
+
     pub fn clear(&self) {
         self.history.clear();
         self.bookmarks.clear();
@@ -258,6 +492,63 @@ pub enum CurrentMode {
     Ultra(Box<UltraMode>),
 }
 
+impl CurrentMode {
+    pub fn mode(&self) -> DatabaseMode {
+        match self {
+            CurrentMode::Persistent(_) => DatabaseMode::Persistent,
+            CurrentMode::Ultra(_) => DatabaseMode::Ultra,
+        }
+    }
+
+    pub fn table(&self, table_type: TableType) -> TableHandle {
+        match self {
+            CurrentMode::Persistent(pm) => TableHandle::Persistent(Arc::clone(pm.table(table_type))),
+            CurrentMode::Ultra(um) => TableHandle::Ultra(Arc::clone(um.table(table_type))),
+        }
+    }
+
+    /// Point-in-time copy of one table. In Persistent mode the memtables are
+    /// copied and the SSTables pinned; in Ultra mode the whole map is copied.
+    pub fn snapshot(&self, table_type: TableType) -> TableSnapshot {
+        match self {
+            CurrentMode::Persistent(pm) => TableSnapshot::Persistent(pm.table(table_type).snapshot()),
+            CurrentMode::Ultra(um) => {
+                let table = um.table(table_type);
+                TableSnapshot::Ultra(table.raw_entries(), table.clock.clone())
+            }
+        }
+    }
+}
+
+/// A single table of the active mode, detached from the mode lock.
+#[derive(Clone)]
+pub enum TableHandle {
+    Persistent(Arc<LSMTree>),
+    Ultra(Arc<UltraTable>),
+}
+
+/// A frozen copy of one table, see [`CurrentMode::snapshot`].
+pub enum TableSnapshot {
+    Persistent(LsmSnapshot),
+    /// The table's entries and the clock that expires them.
+    Ultra(HashMap<Vec<u8>, UltraEntry>, SharedClock),
+}
+
+impl TableSnapshot {
+    pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            TableSnapshot::Persistent(s) => s.get(key).map(|e| e.value),
+            TableSnapshot::Ultra(data, clock) => {
+                let (value, expires_at) = data.get(key)?;
+                if *expires_at != 0 && *expires_at < clock.now_ms() {
+                    return None;
+                }
+                Some(value.clone())
+            }
+        }
+    }
+}
+
 pub struct ModeSwitcher {
     pub current_mode: Arc<RwLock<CurrentMode>>,
     pub config: ModeConfig,
@@ -277,7 +568,7 @@ impl ModeSwitcher {
     ) -> std::io::Result<Self> {
         let current = match mode {
             DatabaseMode::Persistent => CurrentMode::Persistent(PersistentMode::new_with_indices(path, &config, index_defs)?),
-            DatabaseMode::Ultra => CurrentMode::Ultra(Box::new(UltraMode::new())),
+            DatabaseMode::Ultra => CurrentMode::Ultra(Box::new(UltraMode::with_clock(config.ext_config.clock.clone()))),
         };
         
         Ok(Self {
@@ -287,16 +578,58 @@ impl ModeSwitcher {
         })
     }
     
+    /// A switcher in Ultra mode with no directory behind it, so nothing it
+    /// holds ever touches the disk. It cannot switch to Persistent mode.
+    pub fn in_memory(config: ModeConfig) -> Self {
+        Self {
+            current_mode: Arc::new(RwLock::new(CurrentMode::Ultra(Box::new(UltraMode::with_clock(config.ext_config.clock.clone()))))),
+            config,
+            base_path: PathBuf::new(),
+        }
+    }
+
+    /// Whether this switcher was made by [`ModeSwitcher::in_memory`].
+    pub fn is_in_memory(&self) -> bool {
+        self.base_path.as_os_str().is_empty()
+    }
+
+    /// Clone a handle to one table of the active mode. The mode lock is only
+    /// held for the clone, so readers of different tables never contend on
+    /// it and a mode switch does not wait for in-flight reads. A read that
+    /// races a switch sees the table as it was before the switch.
+    ///
+    /// Writes keep holding `current_mode.read()` for their duration so that
+    /// `switch_mode` cannot migrate a table while a write is landing in it.
+    pub fn table(&self, table_type: TableType) -> TableHandle {
+        self.current_mode.read().table(table_type)
+    }
+
+    /// Moves every table into `new_mode`. Taking the write lock waits for
+    /// in-flight writes. Old Persistent trees are then closed, joining their
+    /// flush and compaction threads, before their entries are copied, so the
+    /// copy sees every write and no background job spans the switch. Reads
+    /// holding a [`TableHandle`] finish against the old tables.
     pub fn switch_mode(&self, new_mode: DatabaseMode, path: &Path) -> Result<(), ModeSwitchError> {
         let mut current = self.current_mode.write();
-        
+        let old_mode = current.mode();
+        if old_mode == new_mode {
+            return Ok(());
+        }
+        if new_mode == DatabaseMode::Persistent && self.is_in_memory() {
+            return Err(ModeSwitchError::NoDirectory);
+        }
+
         let new_instance = match new_mode {
             DatabaseMode::Persistent => CurrentMode::Persistent(
                 PersistentMode::new(path, &self.config).map_err(ModeSwitchError::IoError)?
             ),
-            DatabaseMode::Ultra => CurrentMode::Ultra(Box::new(UltraMode::new())),
+            DatabaseMode::Ultra => CurrentMode::Ultra(Box::new(UltraMode::with_clock(self.config.ext_config.clock.clone()))),
         };
 
+        if let CurrentMode::Persistent(old_pm) = &*current {
+            old_pm.close().map_err(ModeSwitchError::IoError)?;
+        }
+
         // Data Migration
         match (&*current, &new_instance) {
             (CurrentMode::Persistent(old_pm), CurrentMode::Ultra(new_um)) => {
@@ -321,6 +654,8 @@ impl ModeSwitcher {
         }
 
         *current = new_instance;
+        drop(current);
+        self.config.ext_config.events.emit(&DbEvent::ModeSwitched { from: old_mode, to: new_mode });
         Ok(())
     }
 }
@@ -0,0 +1,154 @@
+//! Offline recovery of a table directory whose manifest or SSTables were
+//! damaged. Every readable entry is rewritten into fresh level-0 SSTables
+//! (with new block indexes and bloom filters) and a new manifest is written;
+//! files that could not be fully read are moved aside instead of deleted.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::{BrowserDBConfig, FsyncPolicy};
+use crate::core::format::{upgrade_key, TableType};
+use crate::core::lsm_tree::{is_reserved_name, SSTable};
+use crate::core::manifest::Manifest;
+
+/// Directory, next to the table files, that damaged SSTables are moved to.
+pub const QUARANTINE_DIR: &str = "corrupt";
+
+/// Every table kept in a container directory, with its file prefix.
+pub const TABLES: [(TableType, &str); 7] = [
+    (TableType::History, "history"),
+    (TableType::Bookmarks, "bookmarks"),
+    (TableType::Cookies, "cookies"),
+    (TableType::Cache, "cache"),
+    (TableType::LocalStore, "localstore"),
+    (TableType::Settings, "settings"),
+    (TableType::BinaryStore, "binarystore"),
+];
+
+/// What a repair salvaged and what it gave up on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Level-0 SSTables written from salvaged entries.
+    pub sstables_rebuilt: usize,
+    /// Entries (including tombstones) carried over into the new SSTables.
+    pub entries_recovered: usize,
+    /// Data blocks dropped, from the first entry that failed to decode or
+    /// its CRC check up to the end of the block.
+    pub blocks_discarded: usize,
+    /// Files moved into `corrupt/`: unreadable, or only partly recovered.
+    pub quarantined: Vec<PathBuf>,
+}
+
+/// Repairs every table in `dir`. WALs and blob logs are left alone; they are
+/// replayed and resolved as usual on the next open.
+pub fn repair_dir(dir: &Path, config: &BrowserDBConfig, report: &mut RepairReport) -> io::Result<()> {
+    for (table_type, prefix) in TABLES {
+        repair_table(dir, table_type, prefix, config, report)?;
+    }
+    Ok(())
+}
+
+/// `(level, timestamp, counter)` from `prefix_level_timestamp_counter.sst`.
+fn sstable_age(path: &Path) -> Option<(u8, u64, u64)> {
+    let stem = path.file_stem()?.to_str()?;
+    let mut parts = stem.rsplitn(4, '_');
+    let counter = parts.next()?.parse().ok()?;
+    let timestamp = parts.next()?.parse().ok()?;
+    let level = parts.next()?.parse().ok()?;
+    Some((level, timestamp, counter))
+}
+
+fn repair_table(
+    dir: &Path,
+    table_type: TableType,
+    prefix: &str,
+    config: &BrowserDBConfig,
+    report: &mut RepairReport,
+) -> io::Result<()> {
+    let file_prefix = format!("{}_", prefix);
+    let mut sstable_files = Vec::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        if !name.starts_with(&file_prefix) || !path.is_file() {
+            continue;
+        }
+        if name.ends_with(".sst.tmp") || (name.ends_with(".sst") && is_reserved_name(&path)) {
+            fs::remove_file(&path)?;
+        } else if name.ends_with(".sst") {
+            sstable_files.push(path);
+        }
+    }
+    // Oldest first: deeper levels hold older data, and within a level the
+    // name's timestamp orders the files. Unparseable names go last.
+    sstable_files.sort_by_key(|p| {
+        let (level, timestamp, counter) = sstable_age(p).unwrap_or((0, u64::MAX, u64::MAX));
+        (Reverse(level), timestamp, counter, p.clone())
+    });
+
+    let bloom_fpr = config.lsm_tree.bloom_fpr.for_table(table_type);
+    let mut rebuilt = Vec::new();
+    let mut salvaged = Vec::new();
+    for path in sstable_files {
+        let sst = match SSTable::open_for_repair(path.clone(), 0, bloom_fpr) {
+            Ok(sst) => sst,
+            // Written by a newer build: not damaged, and not ours to rewrite.
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => return Err(e),
+            Err(_) => {
+                quarantine(dir, &path, report)?;
+                continue;
+            }
+        };
+
+        let mut entries = BTreeMap::new();
+        let mut damaged_blocks = 0;
+        for block in &sst.index {
+            for entry in sst.block_iter(block) {
+                match entry {
+                    Ok(mut kv) => {
+                        upgrade_key(table_type, sst.format_version, &mut kv.key);
+                        entries.insert(kv.key.clone(), kv);
+                    }
+                    Err(_) => {
+                        damaged_blocks += 1;
+                        break;
+                    }
+                }
+            }
+        }
+        drop(sst);
+
+        if !entries.is_empty() {
+            let table = SSTable::create(0, &entries, dir, table_type, None, &config.lsm_tree)?;
+            let name = table.file_path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            rebuilt.push((0u8, name));
+            report.sstables_rebuilt += 1;
+            report.entries_recovered += entries.len();
+        }
+        report.blocks_discarded += damaged_blocks;
+        if damaged_blocks > 0 {
+            quarantine(dir, &path, report)?;
+        } else {
+            salvaged.push(path);
+        }
+    }
+
+    Manifest::new(dir, prefix).store(config.lsm_tree.level_count(), &rebuilt, config.lsm_tree.fsync_policy != FsyncPolicy::Never)?;
+    // Only now that the manifest points at the rewritten copies.
+    for path in salvaged {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn quarantine(dir: &Path, path: &Path, report: &mut RepairReport) -> io::Result<()> {
+    let quarantine_dir = dir.join(QUARANTINE_DIR);
+    fs::create_dir_all(&quarantine_dir)?;
+    let target = quarantine_dir.join(path.file_name().unwrap_or_default());
+    fs::rename(path, &target)?;
+    report.quarantined.push(target);
+    Ok(())
+}
@@ -0,0 +1,300 @@
+//! Copies of a database directory for backups. A full snapshot holds every
+//! file; an incremental one holds only the files that changed since the
+//! snapshot it builds on and refers back to the chain for the rest.
+//! SSTables never change once written, so one already in the base is
+//! recognised by its name alone; other files are compared by CRC.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::blob_log::BlobLog;
+use crate::core::lsm_tree::{SSTable, TreeCapture};
+use crate::core::manifest::Manifest;
+
+/// Layout version written to `SNAPSHOT`. Snapshots with a newer version
+/// are refused rather than half understood.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// File in a snapshot directory that holds its [`SnapshotMeta`].
+pub const SNAPSHOT_META_FILE: &str = "SNAPSHOT";
+
+/// One file of the database as of a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// Path relative to the database directory, `/`-separated.
+    pub path: String,
+    pub len: u64,
+    pub crc32: u32,
+    /// Id of the snapshot in the chain whose directory holds the bytes.
+    pub stored_in: String,
+}
+
+/// Describes a snapshot and the chain it belongs to. Stored as TOML in
+/// `SNAPSHOT` in the snapshot directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotMeta {
+    pub format_version: u32,
+    pub id: String,
+    /// Ids of the snapshots this one builds on, full snapshot first. Empty
+    /// for a full snapshot.
+    pub chain: Vec<String>,
+    /// Milliseconds since the Unix epoch, from the database's clock.
+    pub created_at: u64,
+    /// Every file of the database, whichever snapshot stores it.
+    pub files: Vec<SnapshotFile>,
+}
+
+impl SnapshotMeta {
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(dir.join(SNAPSHOT_META_FILE))?;
+        let meta: Self = toml::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if meta.format_version > SNAPSHOT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("snapshot {} has format version {}, newer than {}", meta.id, meta.format_version, SNAPSHOT_VERSION),
+            ));
+        }
+        Ok(meta)
+    }
+
+    fn store(&self, dir: &Path) -> io::Result<()> {
+        let content = toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let path = dir.join(SNAPSHOT_META_FILE);
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&tmp_path)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)
+    }
+
+    /// Whether this is a full snapshot rather than an increment.
+    pub fn is_full(&self) -> bool {
+        self.chain.is_empty()
+    }
+
+    /// Bytes stored in this snapshot's own directory.
+    pub fn stored_bytes(&self) -> u64 {
+        self.files.iter().filter(|f| f.stored_in == self.id).map(|f| f.len).sum()
+    }
+}
+
+/// Where the bytes of a file in a new snapshot come from.
+enum Source {
+    File(PathBuf),
+    SSTable(Arc<SSTable>),
+    Manifest { levels: usize, tables: Vec<(u8, String)> },
+    BlobLog(Arc<BlobLog>),
+    /// A captured tree's WAL: its entries are all in the pinned SSTables.
+    EmptyWal,
+}
+
+/// Writer that counts and checksums what passes through it.
+struct CrcWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    len: u64,
+}
+
+impl<W: Write> Write for CrcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Creates `dest` and its directory, fills it with `copy` and syncs it.
+/// Returns the length and CRC of what was written.
+fn copy_with_crc(dest: &Path, copy: impl FnOnce(&mut CrcWriter<File>) -> io::Result<()>) -> io::Result<(u64, u32)> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut out = CrcWriter { inner: File::create(dest)?, hasher: crc32fast::Hasher::new(), len: 0 };
+    copy(&mut out)?;
+    out.inner.sync_all()?;
+    Ok((out.len, out.hasher.finalize()))
+}
+
+/// `path` relative to `root` with `/` separators.
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let parts: Vec<_> = path.strip_prefix(root).ok()?.iter().map(|p| p.to_str()).collect::<Option<_>>()?;
+    Some(parts.join("/"))
+}
+
+/// Resolves a snapshot path under `dir`, refusing anything that would
+/// leave it.
+fn resolve(dir: &Path, path: &str) -> io::Result<PathBuf> {
+    let relative = Path::new(path);
+    if path.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid snapshot path {:?}", path)));
+    }
+    Ok(dir.join(relative))
+}
+
+/// Files under `dir`, skipping the lock file, temporary files and the
+/// directory `skip` (canonical).
+fn walk(dir: &Path, skip: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if path.canonicalize()? != skip {
+                walk(&path, skip, files)?;
+            }
+        } else {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            if name != "browserdb.lock" && !name.ends_with(".tmp") {
+                files.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a snapshot of the database at `root` to `out`: a full one, or
+/// with `base` one holding only what changed since it. Files of the
+/// `captures` trees are taken from them; everything else under `root` is
+/// copied as it is.
+pub fn write(root: &Path, captures: &[TreeCapture], base: Option<&SnapshotMeta>, out: &Path, now: u64) -> io::Result<SnapshotMeta> {
+    if out.join(SNAPSHOT_META_FILE).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already holds a snapshot", out.display()),
+        ));
+    }
+    fs::create_dir_all(out)?;
+    let out = out.canonicalize()?;
+
+    let mut sources = BTreeMap::new();
+    let mut captured = Vec::new();
+    for capture in captures {
+        let dir = relative(root, &capture.base_path)
+            .ok_or_else(|| io::Error::other(format!("{} is outside the database", capture.base_path.display())))?;
+        let path = |name: String| if dir.is_empty() { name } else { format!("{}/{}", dir, name) };
+        let mut tables = Vec::new();
+        for (level, sst) in &capture.sstables {
+            let name = sst.file_path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            tables.push((*level, name.clone()));
+            sources.insert(path(name), Source::SSTable(Arc::clone(sst)));
+        }
+        sources.insert(path(format!("{}.manifest", capture.prefix)), Source::Manifest { levels: capture.levels, tables });
+        sources.insert(path(format!("{}.blob", capture.prefix)), Source::BlobLog(Arc::clone(&capture.blob_log)));
+        sources.insert(path(format!("{}.wal", capture.prefix)), Source::EmptyWal);
+        captured.push((capture.base_path.clone(), capture.prefix.as_str()));
+    }
+    // The rest: metadata, config and containers that are not open.
+    let mut files = Vec::new();
+    walk(root, &out, &mut files)?;
+    for file in files {
+        let (Some(parent), Some(name)) = (file.parent(), file.file_name().and_then(|n| n.to_str())) else {
+            continue;
+        };
+        let owned = captured.iter().any(|(dir, prefix)| {
+            dir == parent
+                && (name.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('.'))
+                    || (name.starts_with(&format!("{}_", prefix)) && name.ends_with(".sst")))
+        });
+        if !owned {
+            if let Some(path) = relative(root, &file) {
+                sources.insert(path, Source::File(file));
+            }
+        }
+    }
+
+    let id = format!("{:016x}", rand::random::<u64>());
+    let known: HashMap<&str, &SnapshotFile> = base.map(|b| b.files.iter().map(|f| (f.path.as_str(), f)).collect()).unwrap_or_default();
+    let mut meta = SnapshotMeta {
+        format_version: SNAPSHOT_VERSION,
+        id: id.clone(),
+        chain: base.map(|b| b.chain.iter().cloned().chain([b.id.clone()]).collect()).unwrap_or_default(),
+        created_at: now,
+        files: Vec::with_capacity(sources.len()),
+    };
+    for (path, source) in sources {
+        if let (Source::SSTable(_), Some(&known)) = (&source, known.get(path.as_str())) {
+            meta.files.push(known.clone());
+            continue;
+        }
+        let dest = resolve(&out, &path)?;
+        let (len, crc32) = match source {
+            Source::File(src) => copy_with_crc(&dest, |w| io::copy(&mut File::open(&src)?, w).map(drop))?,
+            Source::SSTable(sst) => copy_with_crc(&dest, |w| w.write_all(&sst.mmap))?,
+            Source::Manifest { levels, tables } => {
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let prefix = path.rsplit('/').next().and_then(|n| n.strip_suffix(".manifest")).unwrap_or_default();
+                Manifest::new(dest.parent().unwrap_or(&out), prefix).store(levels, &tables, true)?;
+                let bytes = fs::read(&dest)?;
+                (bytes.len() as u64, crc32fast::hash(&bytes))
+            }
+            Source::BlobLog(blob_log) => copy_with_crc(&dest, |w| blob_log.copy_to(w).map(drop))?,
+            Source::EmptyWal => copy_with_crc(&dest, |_| Ok(()))?,
+        };
+        match known.get(path.as_str()) {
+            Some(&known) if known.len == len && known.crc32 == crc32 => {
+                fs::remove_file(&dest)?;
+                meta.files.push(known.clone());
+            }
+            _ => meta.files.push(SnapshotFile { path, len, crc32, stored_in: id.clone() }),
+        }
+    }
+    meta.store(&out)?;
+    Ok(meta)
+}
+
+/// Rebuilds a database directory at `dest` from `chain`: a full snapshot's
+/// directory followed by the directories of its increments, in order.
+/// Every file is checked against its recorded length and CRC.
+pub fn restore(chain: &[PathBuf], dest: &Path) -> io::Result<()> {
+    let metas = chain.iter().map(|dir| SnapshotMeta::load(dir)).collect::<io::Result<Vec<_>>>()?;
+    let target = metas
+        .last()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no snapshot to restore"))?;
+    let expected: Vec<&String> = target.chain.iter().chain([&target.id]).collect();
+    let given: Vec<&String> = metas.iter().map(|m| &m.id).collect();
+    if given != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("snapshot {} needs the chain {:?}, got {:?}", target.id, expected, given),
+        ));
+    }
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is not empty", dest.display()),
+        ));
+    }
+
+    let dirs: HashMap<&str, &PathBuf> = metas.iter().map(|m| m.id.as_str()).zip(chain).collect();
+    fs::create_dir_all(dest)?;
+    for file in &target.files {
+        let dir = dirs.get(file.stored_in.as_str()).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{} is stored in unknown snapshot {}", file.path, file.stored_in))
+        })?;
+        let src = resolve(dir, &file.path)?;
+        let (len, crc32) = copy_with_crc(&resolve(dest, &file.path)?, |w| io::copy(&mut File::open(&src)?, w).map(drop))?;
+        if len != file.len || crc32 != file.crc32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} in snapshot {} failed its checksum", file.path, file.stored_in),
+            ));
+        }
+    }
+    Ok(())
+}
@@ -1,35 +1,239 @@
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufReader, BufWriter, Write, Seek};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Cursor, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use crossbeam_channel::{bounded, Sender};
-use crate::core::format::BDBLogEntry;
+use crate::core::format::{upgrade_key, BDBFileHeader, BDBLogEntry, TableType, BDB_HEADER_SIZE, BDB_VERSION};
+use crate::core::config::FsyncPolicy;
 
 const WAL_CHANNEL_CAPACITY: usize = 4096;
 
+/// Size at which the active segment is closed and the next one started,
+/// unless `LsmTreeConfig::wal_segment_bytes` says otherwise.
+pub const DEFAULT_SEGMENT_BYTES: u64 = 4 * 1024 * 1024;
+
+/// How long a group commit waits for more writers to join before syncing,
+/// unless `GROUP_COMMIT_BYTES` are already pending.
+const GROUP_COMMIT_WINDOW: Duration = Duration::from_micros(200);
+const GROUP_COMMIT_BYTES: usize = 32 * 1024;
+
+/// Progress of `FsyncPolicy::Always` group commits, shared by the writers
+/// waiting in `log` and the flush thread that syncs for them.
+#[derive(Default)]
+struct CommitState {
+    /// Entries written to the buffer so far.
+    appended: u64,
+    /// Entries known to be on disk.
+    synced: u64,
+    pending_bytes: usize,
+    /// A failed sync is sticky: the entries it covered may be lost, so
+    /// every later commit fails too.
+    error: Option<(io::ErrorKind, String)>,
+}
+
+/// Path of segment `id` of the log at `base`: `history.wal-000001`, ...
+fn segment_path(base: &Path, id: u64) -> PathBuf {
+    let mut name = base.file_name().unwrap_or_default().to_os_string();
+    name.push(format!("-{:06}", id));
+    base.with_file_name(name)
+}
+
+/// Ids and paths of the segments of the log at `base`, oldest first.
+fn list_segments(base: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let dir = match base.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}-", base.file_name().unwrap_or_default().to_string_lossy());
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if let Some(id) = name.strip_prefix(&prefix).and_then(|n| n.parse::<u64>().ok()) {
+            segments.push((id, path));
+        }
+    }
+    segments.sort_unstable();
+    Ok(segments)
+}
+
+/// Decodes records from `bytes` until one is cut short or fails its CRC,
+/// returning them with the length of the valid prefix.
+fn read_records(bytes: &[u8], version: u8) -> (Vec<BDBLogEntry>, usize) {
+    let mut cursor = Cursor::new(bytes);
+    let mut entries = Vec::new();
+    let mut valid = 0;
+    while let Ok(entry) = BDBLogEntry::read(&mut cursor, version) {
+        if entry.entry_crc != entry.calculate_crc() {
+            break;
+        }
+        entries.push(entry);
+        valid = cursor.position() as usize;
+    }
+    (entries, valid)
+}
+
+/// A segment's records after its header, with their keys in the current
+/// format, and whether every byte of the file decoded. A missing or foreign
+/// header yields nothing.
+fn read_segment(path: &Path, table_type: TableType) -> io::Result<(Vec<BDBLogEntry>, usize, bool)> {
+    let bytes = fs::read(path)?;
+    let mut cursor = Cursor::new(&bytes[..]);
+    match BDBFileHeader::read(&mut cursor) {
+        Ok(header) if header.table_type == table_type => {
+            let (mut entries, valid) = read_records(&bytes[BDB_HEADER_SIZE..], header.version);
+            for entry in &mut entries {
+                upgrade_key(table_type, header.version, &mut entry.key);
+            }
+            let valid = BDB_HEADER_SIZE + valid;
+            Ok((entries, valid, valid == bytes.len()))
+        }
+        _ => Ok((Vec::new(), 0, false)),
+    }
+}
+
+/// The segment appends go to. Once the next record would take it past
+/// `max_len` it is flushed, synced unless the policy is `Never`, and
+/// replaced by a new segment with the next id.
+struct ActiveSegment {
+    base: PathBuf,
+    table_type: TableType,
+    id: u64,
+    file: BufWriter<File>,
+    len: u64,
+    max_len: u64,
+    sync: bool,
+}
+
+impl ActiveSegment {
+    fn create(base: &Path, table_type: TableType, id: u64, max_len: u64, sync: bool) -> io::Result<Self> {
+        let mut file = BufWriter::with_capacity(32 * 1024, File::create(segment_path(base, id))?);
+        BDBFileHeader::new(table_type).write(&mut file)?;
+        Ok(Self {
+            base: base.to_path_buf(),
+            table_type,
+            id,
+            file,
+            len: BDB_HEADER_SIZE as u64,
+            max_len,
+            sync,
+        })
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let len = bytes.len() as u64;
+        if self.len > BDB_HEADER_SIZE as u64 && self.len + len > self.max_len {
+            self.file.flush()?;
+            if self.sync {
+                self.file.get_ref().sync_all()?;
+            }
+            self.start_next()?;
+        }
+        self.file.write_all(bytes)?;
+        self.len += len;
+        Ok(())
+    }
+
+    fn start_next(&mut self) -> io::Result<()> {
+        *self = Self::create(&self.base, self.table_type, self.id + 1, self.max_len, self.sync)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_all()
+    }
+}
+
+/// Write-ahead log of one LSM tree, kept as numbered segment files next to
+/// `path` (`history.wal-000001`, ...). Each segment starts with a
+/// `BDBFileHeader` and holds `BDBLogEntry` records, each with its CRC.
+/// Segments are only dropped by `truncate`, once everything logged is in
+/// SSTables.
 pub struct WALManager {
     sender: Sender<Vec<u8>>,
-    writer: Arc<Mutex<BufWriter<File>>>,
+    writer: Arc<Mutex<ActiveSegment>>,
     path: PathBuf,
+    table_type: TableType,
     stop_signal: Arc<AtomicBool>,
     writer_thread: Option<thread::JoinHandle<()>>,
     flush_thread: Option<thread::JoinHandle<()>>,
+    policy: FsyncPolicy,
+    syncs: Arc<AtomicU64>,
+    logged_bytes: AtomicU64,
+    commit: Arc<(Mutex<CommitState>, Condvar)>,
 }
 
 impl WALManager {
-    pub fn new(path: &Path) -> io::Result<Self> {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .read(true)
-            .open(path)?;
-            
-        file.seek(std::io::SeekFrom::End(0))?;
-
-        let writer = Arc::new(Mutex::new(BufWriter::with_capacity(32 * 1024, file)));
+    pub fn new(path: &Path, table_type: TableType) -> io::Result<Self> {
+        Self::with_policy(path, table_type, FsyncPolicy::OnFlush)
+    }
+
+    pub fn with_policy(path: &Path, table_type: TableType, policy: FsyncPolicy) -> io::Result<Self> {
+        Self::open(path, table_type, policy, DEFAULT_SEGMENT_BYTES)
+    }
+
+    /// Opens the WAL with the given fsync policy and segment size. With
+    /// `Always`, `log` blocks until its entry is synced, and concurrent
+    /// writers share one sync per group commit; `OnFlush` syncs every 5ms
+    /// in the background; `Never` only hands data to the OS.
+    ///
+    /// Recovery happens here. Segments are read in order up to the first
+    /// record that is cut short or fails its CRC, which after a crash is the
+    /// torn tail of the last segment. That segment is cut back to its valid
+    /// prefix and any later ones are removed, so `read_all` and new appends
+    /// carry on from the last good record. A WAL from before segments, a
+    /// single headerless file at `path`, is copied into the first segment
+    /// and removed.
+    pub fn open(path: &Path, table_type: TableType, policy: FsyncPolicy, segment_bytes: u64) -> io::Result<Self> {
+        let sync = policy != FsyncPolicy::Never;
+        let segments = list_segments(path)?;
+        let mut next_id = 1;
+        let mut torn = false;
+        for (id, segment) in segments {
+            if torn {
+                fs::remove_file(&segment)?;
+                continue;
+            }
+            next_id = id + 1;
+            let (_, valid, complete) = read_segment(&segment, table_type)?;
+            if !complete {
+                torn = true;
+                if valid == 0 {
+                    fs::remove_file(&segment)?;
+                    next_id = id;
+                } else {
+                    let file = OpenOptions::new().write(true).open(&segment)?;
+                    file.set_len(valid as u64)?;
+                    if sync {
+                        file.sync_all()?;
+                    }
+                }
+            }
+        }
+
+        let mut active = ActiveSegment::create(path, table_type, next_id, segment_bytes, sync)?;
+        if path.is_file() {
+            let (mut entries, _) = read_records(&fs::read(path)?, BDB_VERSION);
+            for entry in &mut entries {
+                // The headerless log predates format versions.
+                upgrade_key(table_type, 1, &mut entry.key);
+                let mut buf = Vec::with_capacity(256);
+                entry.write(&mut buf)?;
+                active.append(&buf)?;
+            }
+            active.sync()?;
+            fs::remove_file(path)?;
+        }
+
+        let writer = Arc::new(Mutex::new(active));
         let stop_signal = Arc::new(AtomicBool::new(false));
 
         let (sender, receiver): (Sender<Vec<u8>>, crossbeam_channel::Receiver<Vec<u8>>) = bounded(WAL_CHANNEL_CAPACITY);
@@ -42,7 +246,7 @@ impl WALManager {
                 match receiver.recv_timeout(Duration::from_millis(1)) {
                     Ok(bytes) => {
                         let mut w = writer_clone.lock().unwrap();
-                        let _ = w.write_all(&bytes);
+                        let _ = w.append(&bytes);
                     }
                     Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
                     Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
@@ -50,67 +254,177 @@ impl WALManager {
             }
             while let Ok(bytes) = receiver.try_recv() {
                 let mut w = writer_clone.lock().unwrap();
-                let _ = w.write_all(&bytes);
+                let _ = w.append(&bytes);
             }
         });
 
+        let syncs = Arc::new(AtomicU64::new(0));
+        let commit_state = Arc::new((Mutex::new(CommitState::default()), Condvar::new()));
         let flush_writer = Arc::clone(&writer);
         let flush_stop = Arc::clone(&stop_signal);
+        let flush_syncs = Arc::clone(&syncs);
+        let flush_commit = Arc::clone(&commit_state);
         let flush_thread = thread::spawn(move || {
+            if policy == FsyncPolicy::Always {
+                Self::run_group_commits(&flush_writer, &flush_commit, &flush_stop, &flush_syncs);
+                return;
+            }
+            let commit = |w: &mut ActiveSegment| {
+                if policy != FsyncPolicy::Never {
+                    let _ = w.sync();
+                    flush_syncs.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    let _ = w.flush();
+                }
+            };
             while !flush_stop.load(Ordering::Relaxed) {
                 thread::sleep(Duration::from_millis(5));
-                let mut w = flush_writer.lock().unwrap();
-                let _ = w.flush();
-                let _ = w.get_ref().sync_all();
+                commit(&mut flush_writer.lock().unwrap());
             }
-            let mut w = flush_writer.lock().unwrap();
-            let _ = w.flush();
-            let _ = w.get_ref().sync_all();
+            commit(&mut flush_writer.lock().unwrap());
         });
 
         Ok(Self {
             sender,
             writer,
             path: path.to_path_buf(),
+            table_type,
             stop_signal,
             writer_thread: Some(writer_thread),
             flush_thread: Some(flush_thread),
+            policy,
+            syncs,
+            logged_bytes: AtomicU64::new(0),
+            commit: commit_state,
         })
     }
 
+    /// Flush thread loop for `FsyncPolicy::Always`: waits for appended
+    /// entries, gives other writers `GROUP_COMMIT_WINDOW` to join, then
+    /// syncs once for all of them and wakes them up.
+    fn run_group_commits(
+        writer: &Mutex<ActiveSegment>,
+        commit: &(Mutex<CommitState>, Condvar),
+        stop: &AtomicBool,
+        syncs: &AtomicU64,
+    ) {
+        let (lock, cvar) = commit;
+        loop {
+            let mut state = lock.lock().unwrap();
+            while state.appended == state.synced {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                state = cvar.wait_timeout(state, Duration::from_millis(5)).unwrap().0;
+            }
+            let deadline = Instant::now() + GROUP_COMMIT_WINDOW;
+            while state.pending_bytes < GROUP_COMMIT_BYTES {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                state = cvar.wait_timeout(state, deadline - now).unwrap().0;
+            }
+            drop(state);
+
+            // Writers append under the writer lock, so holding it pins down
+            // exactly which entries this sync covers.
+            let mut w = writer.lock().unwrap();
+            let target = {
+                let mut state = lock.lock().unwrap();
+                state.pending_bytes = 0;
+                state.appended
+            };
+            let res = w.sync();
+            drop(w);
+            syncs.fetch_add(1, Ordering::Relaxed);
+
+            let mut state = lock.lock().unwrap();
+            match res {
+                Ok(()) => state.synced = target,
+                Err(e) => state.error = Some((e.kind(), e.to_string())),
+            }
+            cvar.notify_all();
+        }
+    }
+
     pub fn log(&self, entry: &mut BDBLogEntry) -> io::Result<()> {
         let mut buf = Vec::with_capacity(256);
         entry.write(&mut buf)?;
+        self.logged_bytes.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        if self.policy == FsyncPolicy::Always {
+            // Bypass the writer thread and wait for the group commit, so the
+            // entry is durable on return.
+            let (lock, cvar) = &*self.commit;
+            let seq = {
+                let mut w = self.writer.lock().unwrap();
+                w.append(&buf)?;
+                let mut state = lock.lock().unwrap();
+                state.appended += 1;
+                state.pending_bytes += buf.len();
+                state.appended
+            };
+            cvar.notify_all();
+
+            let mut state = lock.lock().unwrap();
+            while state.synced < seq && state.error.is_none() {
+                state = cvar.wait(state).unwrap();
+            }
+            if let Some((kind, msg)) = &state.error {
+                return Err(io::Error::new(*kind, msg.clone()));
+            }
+            return Ok(());
+        }
         self.sender.send(buf).map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))
     }
 
+    /// Number of times the log has been synced to disk.
+    pub fn sync_count(&self) -> u64 {
+        self.syncs.load(Ordering::Relaxed)
+    }
+
+    /// Bytes appended to the log since it was opened, truncations aside.
+    pub fn bytes_logged(&self) -> u64 {
+        self.logged_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Every record in the log, oldest first. Stops at the first record
+    /// that is cut short or fails its CRC.
     pub fn read_all(&self) -> io::Result<Vec<BDBLogEntry>> {
-        {
-            let mut w = self.writer.lock().unwrap();
-            w.flush()?;
-            w.get_ref().sync_all()?;
-        }
+        self.writer.lock().unwrap().flush()?;
 
-        let file = File::open(&self.path)?;
-        let mut reader = BufReader::new(file);
         let mut entries = Vec::new();
-
-        while let Ok(entry) = BDBLogEntry::read(&mut reader, crate::core::format::BDB_VERSION) {
-            entries.push(entry);
+        for (_, segment) in list_segments(&self.path)? {
+            let (records, _, complete) = read_segment(&segment, self.table_type)?;
+            entries.extend(records);
+            if !complete {
+                break;
+            }
         }
-
         Ok(entries)
     }
 
+    /// Paths of the log's segments, oldest first; the last one is active.
+    pub fn segments(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(list_segments(&self.path)?.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// Drops every segment once all they hold is in SSTables, and starts a
+    /// new active segment with the next id.
     pub fn truncate(&mut self) -> io::Result<()> {
         let mut w = self.writer.lock().unwrap();
         w.flush()?;
-        let file = w.get_mut();
-        
-        retry_on_permission_denied(|| {
-            file.set_len(0)?;
-            file.sync_all()
-        })
+        w.start_next()?;
+        let active = w.id;
+        if self.policy != FsyncPolicy::Never {
+            w.sync()?;
+        }
+        for (id, segment) in list_segments(&self.path)? {
+            if id < active {
+                retry_on_permission_denied(|| fs::remove_file(&segment))?;
+            }
+        }
+        Ok(())
     }
 
     pub fn stop_flush_thread(&mut self) {
@@ -180,21 +494,52 @@ mod tests {
         let dir = tempdir().unwrap();
         let wal_path = dir.path().join("test.wal");
 
-        {
-            let wal = WALManager::new(&wal_path).unwrap();
+        let tail = {
+            let wal = WALManager::new(&wal_path, TableType::History).unwrap();
             let mut entry = BDBLogEntry::new(EntryType::Insert, b"key1".to_vec(), b"value1".to_vec());
             wal.log(&mut entry).unwrap();
-        }
+            wal.segments().unwrap().pop().unwrap()
+        };
 
         {
-            let mut file = OpenOptions::new().append(true).open(&wal_path).unwrap();
+            let mut file = OpenOptions::new().append(true).open(&tail).unwrap();
             file.write_all(b"partial data").unwrap();
         }
 
-        let wal = WALManager::new(&wal_path).unwrap();
+        let wal = WALManager::new(&wal_path, TableType::History).unwrap();
         let entries = wal.read_all().unwrap();
 
         assert_eq!(entries.len(), 1);
         assert_eq!(entries[0].key, b"key1");
     }
+
+    fn log_n(wal: &WALManager, n: usize) {
+        for i in 0..n {
+            let mut entry = BDBLogEntry::new(EntryType::Insert, vec![i as u8], b"v".to_vec());
+            wal.log(&mut entry).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_wal_fsync_policy_honored() {
+        let dir = tempdir().unwrap();
+
+        let always = WALManager::with_policy(&dir.path().join("always.wal"), TableType::History, FsyncPolicy::Always).unwrap();
+        log_n(&always, 10);
+        // Synced inline, before `log` returns.
+        assert!(always.sync_count() >= 10);
+        assert_eq!(always.read_all().unwrap().len(), 10);
+
+        let on_flush = WALManager::with_policy(&dir.path().join("on_flush.wal"), TableType::History, FsyncPolicy::OnFlush).unwrap();
+        log_n(&on_flush, 10);
+        thread::sleep(Duration::from_millis(50));
+        assert!(on_flush.sync_count() > 0);
+
+        let mut never = WALManager::with_policy(&dir.path().join("never.wal"), TableType::History, FsyncPolicy::Never).unwrap();
+        log_n(&never, 10);
+        thread::sleep(Duration::from_millis(50));
+        never.truncate().unwrap();
+        never.stop_flush_thread();
+        assert_eq!(never.sync_count(), 0);
+    }
 }
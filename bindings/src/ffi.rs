@@ -1,6 +1,8 @@
 use crate::BrowserDB;
 use crate::HistoryEntry;
 use crate::LocalStoreEntry;
+use crate::hash::hash_str;
+use crate::keys::localstore_key;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int};
 use std::ptr;
@@ -41,7 +43,7 @@ pub extern "C" fn browserdb_history_insert(
     let url_str = unsafe { CStr::from_ptr(url) }.to_string_lossy().into_owned();
     let title_str = unsafe { CStr::from_ptr(title) }.to_string_lossy().into_owned();
 
-    let url_hash = calculate_hash(&url_str);
+    let url_hash = hash_str(&url_str);
     let entry = HistoryEntry {
         timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis(),
         url: url_str,
@@ -70,7 +72,7 @@ pub extern "C" fn browserdb_history_insert_with_ttl(
     let url_str = unsafe { CStr::from_ptr(url) }.to_string_lossy().into_owned();
     let title_str = unsafe { CStr::from_ptr(title) }.to_string_lossy().into_owned();
 
-    let url_hash = calculate_hash(&url_str);
+    let url_hash = hash_str(&url_str);
     let entry = HistoryEntry {
         timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis(),
         url: url_str,
@@ -142,7 +144,7 @@ pub extern "C" fn browserdb_localstore_insert(
     let key_str = unsafe { CStr::from_ptr(key) }.to_string_lossy().into_owned();
     let value_str = unsafe { CStr::from_ptr(value) }.to_string_lossy().into_owned();
 
-    let origin_hash = calculate_hash(&origin_str);
+    let origin_hash = hash_str(&origin_str);
     let entry = LocalStoreEntry {
         origin_hash,
         key: key_str,
@@ -166,7 +168,7 @@ pub extern "C" fn browserdb_localstore_get(
     let origin_str = unsafe { CStr::from_ptr(origin) }.to_string_lossy().into_owned();
     let key_str = unsafe { CStr::from_ptr(key) }.to_string_lossy().into_owned();
 
-    let origin_hash = calculate_hash(&origin_str);
+    let origin_hash = hash_str(&origin_str);
     match db.localstore().get_by_origin(origin_hash) {
         Ok(entries) => {
             for entry in entries {
@@ -190,13 +192,10 @@ pub extern "C" fn browserdb_localstore_remove(
     let db = unsafe { &*db };
     let origin_str = unsafe { CStr::from_ptr(origin) }.to_string_lossy().into_owned();
     let key_str = unsafe { CStr::from_ptr(key) }.to_string_lossy().into_owned();
-    let origin_hash = calculate_hash(&origin_str);
+    let origin_hash = hash_str(&origin_str);
 
     let current_mode = db.localstore().container.switcher.current_mode.read();
-    let key_bytes = match bincode::serialize(&(origin_hash, &key_str)) {
-        Ok(k) => k,
-        Err(_) => return -1,
-    };
+    let key_bytes = localstore_key(origin_hash, &key_str);
 
     match &*current_mode {
         crate::core::modes::CurrentMode::Persistent(pm) => {
@@ -217,13 +216,13 @@ pub extern "C" fn browserdb_localstore_clear(
     if db.is_null() || origin.is_null() { return -1; }
     let db = unsafe { &*db };
     let origin_str = unsafe { CStr::from_ptr(origin) }.to_string_lossy().into_owned();
-    let origin_hash = calculate_hash(&origin_str);
+    let origin_hash = hash_str(&origin_str);
 
     let current_mode = db.localstore().container.switcher.current_mode.read();
     match db.localstore().get_by_origin(origin_hash) {
         Ok(entries) => {
             for entry in entries {
-                let key_bytes = bincode::serialize(&(origin_hash, &entry.key)).unwrap();
+                let key_bytes = localstore_key(origin_hash, &entry.key);
                 match &*current_mode {
                     crate::core::modes::CurrentMode::Persistent(pm) => {
                         let _ = pm.localstore.delete(key_bytes);
@@ -249,7 +248,7 @@ pub extern "C" fn browserdb_localstore_get_all(
     if db.is_null() || origin.is_null() { return -1; }
     let db = unsafe { &*db };
     let origin_str = unsafe { CStr::from_ptr(origin) }.to_string_lossy().into_owned();
-    let origin_hash = calculate_hash(&origin_str);
+    let origin_hash = hash_str(&origin_str);
 
     match db.localstore().get_by_origin(origin_hash) {
         Ok(entries) => {
@@ -316,12 +315,3 @@ pub extern "C" fn browserdb_settings_remove(
     }
     0
 }
-
-pub(crate) fn calculate_hash(s: &str) -> u128 {
-    let mut hash: u128 = 0x6c62272e07bb014262b821756295c58d;
-    for byte in s.bytes() {
-        hash = hash ^ (byte as u128);
-        hash = hash.wrapping_mul(0x1000000000000000000013B);
-    }
-    hash
-}
@@ -0,0 +1,20 @@
+//! Hashing of URLs, origins and other strings into the `u128` keys used by
+//! the tables (`url_hash`, `domain_hash`, `origin_hash`).
+
+/// 128-bit FNV-1a of `s`. Stable across versions and platforms, since
+/// stored keys depend on it.
+pub fn hash_str(s: &str) -> u128 {
+    let mut hash: u128 = 0x6c62272e07bb014262b821756295c58d;
+    for byte in s.bytes() {
+        hash ^= byte as u128;
+        hash = hash.wrapping_mul(0x1000000000000000000013B);
+    }
+    hash
+}
+
+/// `domain_hash` for a cookie domain: [`hash_str`] of the domain lowercased
+/// and without the leading dot, so `.Example.com` and `example.com` land on
+/// the same key prefix.
+pub fn domain_hash(domain: &str) -> u128 {
+    hash_str(&domain.trim_start_matches('.').to_ascii_lowercase())
+}
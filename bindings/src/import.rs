@@ -0,0 +1,108 @@
+//! Imports browsing history from other browsers' SQLite profiles. Enabled by
+//! the `browser-import` feature.
+
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::hash::hash_str;
+use crate::{HistoryEntry, HistoryTable};
+pub use crate::ImportResult;
+
+/// Milliseconds between 1601-01-01 (the WebKit epoch) and 1970-01-01.
+const WEBKIT_EPOCH_OFFSET_MS: i64 = 11_644_473_600_000;
+
+/// Rows handed to `insert_bulk` at a time.
+const IMPORT_CHUNK: usize = 10_000;
+
+/// Chrome's `last_visit_time`: microseconds since 1601-01-01 UTC.
+fn webkit_to_unix_ms(micros: i64) -> u128 {
+    (micros / 1000 - WEBKIT_EPOCH_OFFSET_MS).max(0) as u128
+}
+
+/// Firefox's `last_visit_date`: microseconds since 1970-01-01 UTC.
+fn unix_micros_to_ms(micros: i64) -> u128 {
+    (micros / 1000).max(0) as u128
+}
+
+/// A URL needs a scheme (`http:`, `file:`, `about:` ...) and no whitespace
+/// or control characters.
+fn is_valid_url(url: &str) -> bool {
+    let Some((scheme, rest)) = url.split_once(':') else {
+        return false;
+    };
+    let mut scheme_chars = scheme.chars();
+    scheme_chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme_chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        && !rest.is_empty()
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+impl<'a> HistoryTable<'a> {
+    /// Imports the `urls` table of a Chrome (or Chromium-based) `History`
+    /// file. The file is opened read-only; Chrome keeps it locked while
+    /// running, so import from a copy or with the browser closed.
+    pub fn import_chrome_history(&self, path: &Path) -> Result<ImportResult, Box<dyn std::error::Error>> {
+        self.import_sqlite_history(
+            path,
+            "SELECT url, title, visit_count, last_visit_time FROM urls",
+            webkit_to_unix_ms,
+        )
+    }
+
+    /// Imports the `moz_places` table of a Firefox `places.sqlite` file,
+    /// opened read-only.
+    pub fn import_firefox_history(&self, path: &Path) -> Result<ImportResult, Box<dyn std::error::Error>> {
+        self.import_sqlite_history(
+            path,
+            "SELECT url, title, visit_count, last_visit_date FROM moz_places",
+            unix_micros_to_ms,
+        )
+    }
+
+    /// Runs `query`, which must yield `(url, title, visit_count,
+    /// visit_time)`, and bulk-inserts the rows. NULL titles and times become
+    /// empty and 0.
+    fn import_sqlite_history(
+        &self,
+        path: &Path,
+        query: &str,
+        to_unix_ms: fn(i64) -> u128,
+    ) -> Result<ImportResult, Box<dyn std::error::Error>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        let mut stmt = conn.prepare(query)?;
+        let mut rows = stmt.query([])?;
+
+        let mut result = ImportResult::default();
+        let mut chunk = Vec::with_capacity(IMPORT_CHUNK);
+        while let Some(row) = rows.next()? {
+            let url = match row.get::<_, String>(0) {
+                Ok(url) if is_valid_url(&url) => url,
+                _ => {
+                    result.skipped += 1;
+                    continue;
+                }
+            };
+            let title: Option<String> = row.get(1)?;
+            let visit_count: Option<i64> = row.get(2)?;
+            let visit_time: Option<i64> = row.get(3)?;
+            chunk.push(HistoryEntry {
+                timestamp: to_unix_ms(visit_time.unwrap_or(0)),
+                url_hash: hash_str(&url),
+                url,
+                title: title.unwrap_or_default(),
+                visit_count: visit_count.unwrap_or(0).clamp(0, u32::MAX as i64) as u32,
+            });
+            if chunk.len() == IMPORT_CHUNK {
+                self.insert_bulk(&chunk)?;
+                result.imported += chunk.len();
+                chunk.clear();
+            }
+        }
+        if !chunk.is_empty() {
+            self.insert_bulk(&chunk)?;
+            result.imported += chunk.len();
+        }
+        Ok(result)
+    }
+}
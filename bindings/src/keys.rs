@@ -0,0 +1,123 @@
+//! Primary keys of the tables. Every insert, get, delete and scan builds its
+//! key here, so they cannot drift apart.
+//!
+//! History and cache entries are keyed by their `url_hash` as 16 big-endian
+//! bytes ([`ordered_u128`]), so tables, which sort keys as byte strings,
+//! keep them in numeric order and range scans and `scan_page` walk them
+//! that way. Databases from before on-disk format version 3, which stored
+//! these keys little-endian, are rewritten when they are opened.
+//!
+//! The other tables keep bincode's layout: a `u128` hash as 16
+//! little-endian bytes, then, for composite keys, the string's length as a
+//! little-endian `u64` and its bytes. The hash alone is therefore a byte
+//! prefix of every composite key built from it, which is what the
+//! per-domain and per-origin scans rely on. Their order means nothing, and
+//! stored data depends on these bytes; they must not change.
+
+fn hash_key(hash: u128) -> Vec<u8> {
+    hash.to_le_bytes().to_vec()
+}
+
+fn composite_key(hash: u128, name: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(16 + 8 + name.len());
+    key.extend_from_slice(&hash.to_le_bytes());
+    key.extend_from_slice(&(name.len() as u64).to_le_bytes());
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+fn decode_composite_key(key: &[u8]) -> Option<(u128, String)> {
+    let hash = u128::from_le_bytes(key.get(..16)?.try_into().ok()?);
+    let len = u64::from_le_bytes(key.get(16..24)?.try_into().ok()?);
+    let name = key.get(24..)?;
+    if name.len() as u64 != len {
+        return None;
+    }
+    Some((hash, String::from_utf8(name.to_vec()).ok()?))
+}
+
+/// `n` as 16 big-endian bytes, which sort as byte slices in numeric order.
+pub fn ordered_u128(n: u128) -> [u8; 16] {
+    n.to_be_bytes()
+}
+
+/// The number an [`ordered_u128`] encodes.
+pub fn decode_ordered_u128(bytes: &[u8]) -> Option<u128> {
+    Some(u128::from_be_bytes(bytes.try_into().ok()?))
+}
+
+/// `n` as 8 big-endian bytes, which sort as byte slices in numeric order.
+pub fn ordered_u64(n: u64) -> [u8; 8] {
+    n.to_be_bytes()
+}
+
+/// The number an [`ordered_u64`] encodes.
+pub fn decode_ordered_u64(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.try_into().ok()?))
+}
+
+/// Key of a history entry: `url_hash` as an [`ordered_u128`].
+pub fn history_key(url_hash: u128) -> Vec<u8> {
+    ordered_u128(url_hash).to_vec()
+}
+
+/// [`history_key`] as an array, for lookups that only borrow the key and
+/// so need not allocate it.
+pub fn history_key_bytes(url_hash: u128) -> [u8; 16] {
+    ordered_u128(url_hash)
+}
+
+/// `url_hash` of a [`history_key`].
+pub fn decode_history_key(key: &[u8]) -> Option<u128> {
+    decode_ordered_u128(key)
+}
+
+/// Key of a cache entry. Same layout as [`history_key`].
+pub fn cache_key(url_hash: u128) -> Vec<u8> {
+    ordered_u128(url_hash).to_vec()
+}
+
+/// [`cache_key`] as an array, like [`history_key_bytes`].
+pub fn cache_key_bytes(url_hash: u128) -> [u8; 16] {
+    ordered_u128(url_hash)
+}
+
+/// `url_hash` of a [`cache_key`].
+pub fn decode_cache_key(key: &[u8]) -> Option<u128> {
+    decode_ordered_u128(key)
+}
+
+/// Key of a bookmark: `url_hash` as 16 little-endian bytes.
+pub fn bookmark_key(url_hash: u128) -> Vec<u8> {
+    hash_key(url_hash)
+}
+
+/// Key of a cookie.
+pub fn cookie_key(domain_hash: u128, name: &str) -> Vec<u8> {
+    composite_key(domain_hash, name)
+}
+
+/// Prefix shared by every [`cookie_key`] of `domain_hash`.
+pub fn cookie_prefix(domain_hash: u128) -> Vec<u8> {
+    hash_key(domain_hash)
+}
+
+/// `(domain_hash, name)` of a [`cookie_key`].
+pub fn decode_cookie_key(key: &[u8]) -> Option<(u128, String)> {
+    decode_composite_key(key)
+}
+
+/// Key of a localStorage entry.
+pub fn localstore_key(origin_hash: u128, key: &str) -> Vec<u8> {
+    composite_key(origin_hash, key)
+}
+
+/// Prefix shared by every [`localstore_key`] of `origin_hash`.
+pub fn localstore_prefix(origin_hash: u128) -> Vec<u8> {
+    hash_key(origin_hash)
+}
+
+/// `(origin_hash, key)` of a [`localstore_key`].
+pub fn decode_localstore_key(key: &[u8]) -> Option<(u128, String)> {
+    decode_composite_key(key)
+}
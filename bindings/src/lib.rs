@@ -1,17 +1,36 @@
 pub mod core;
 pub mod ffi;
+pub mod hash;
+pub mod keys;
+#[cfg(feature = "browser-import")]
+pub mod import;
 
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::collections::HashMap;
-use std::{fs::{self, File}, io};
-use parking_lot::RwLock;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::borrow::Cow;
+use std::{fmt, fs::{self, File}, io};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use parking_lot::{Mutex, RwLock};
 use serde::{Serialize, Deserialize};
 use fs2::FileExt;
 
 pub use crate::core::modes::{DatabaseMode, ModeConfig};
-use crate::core::modes::{ModeSwitcher, CurrentMode};
-use crate::core::config::BrowserDBConfig;
+use crate::core::modes::{ModeSwitcher, ModeSwitchError, CurrentMode, TableHandle, TableSnapshot, ULTRA_SNAPSHOT_FILE};
+use crate::core::format::{CompressionType, TableType};
+use crate::core::compression::{self, MissingCodec};
+use crate::core::config::{BrowserDBConfig, ValueCodecConfig};
+pub use crate::core::codec::{BincodeCodec, Codec, CodecError, JsonCodec, ValueCodec};
+use crate::core::metadata::DatabaseMetadata;
+pub use crate::core::heatmap::{heat_thresholds, HeatMapStats};
+pub use crate::core::events::{DbEvent, EventBus, EventHook};
+pub use crate::core::lsm_tree::{Amplification, LevelInfo, OpenWarning};
+pub use crate::core::repair::RepairReport;
+pub use crate::core::integrity::{IntegrityProblem, IntegrityReport};
+pub use crate::core::snapshot::{SnapshotFile, SnapshotMeta};
+use crate::core::repair;
+use crate::core::snapshot;
+use crate::core::lsm_tree::LSMTree;
 
 pub mod types {
     pub use super::{
@@ -24,6 +43,103 @@ pub mod cookie_flags {
     pub const NONE: u8 = 0;
     pub const SECURE: u8 = 1;
     pub const HTTPONLY: u8 = 2;
+    pub const SAME_SITE: u8 = 4;
+    pub const PERSISTENT: u8 = 8;
+    /// CHIPS: the cookie is keyed by the top-level site as well.
+    pub const PARTITIONED: u8 = 16;
+}
+
+/// Table errors worth telling apart from the rest.
+#[derive(Debug)]
+pub enum BrowserDbError {
+    /// The value under `key` in `table` is not a valid encoding of the
+    /// table's entry type in its codec, e.g. one written by an incompatible
+    /// build.
+    Serialization {
+        table: TableType,
+        key: Vec<u8>,
+        source: CodecError,
+    },
+    /// `table`'s memtable could not be written to disk, e.g. because the
+    /// disk is full or the directory became read-only. Nothing was lost:
+    /// the entries stay in memory and in the WAL for a later flush.
+    Io {
+        table: TableType,
+        source: io::Error,
+    },
+    /// An SSTable of `table` is compressed with `codec`, which this build
+    /// was compiled without. The file is left as it is; a build with the
+    /// codec's cargo feature reads it.
+    UnsupportedCodec {
+        table: TableType,
+        codec: CompressionType,
+        file: PathBuf,
+    },
+}
+
+impl fmt::Display for BrowserDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrowserDbError::Serialization { table, key, source } => {
+                write!(f, "undecodable {:?} record {}: {}", table, key.escape_ascii(), source)
+            }
+            BrowserDbError::Io { table, source } => write!(f, "could not write {:?} to disk: {}", table, source),
+            BrowserDbError::UnsupportedCodec { table, codec, file } => write!(
+                f,
+                "{:?} table {} is compressed with {}, which this build does not include; enable the `{}` feature",
+                table,
+                file.display(),
+                compression::name(*codec),
+                compression::name(*codec)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BrowserDbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BrowserDbError::Serialization { source, .. } => Some(source),
+            BrowserDbError::Io { source, .. } => Some(source),
+            BrowserDbError::UnsupportedCodec { .. } => None,
+        }
+    }
+}
+
+/// Attaches the record's location to a failed decode.
+fn located<T>(table: TableType, key: &[u8], decoded: Result<T, CodecError>) -> Result<T, BrowserDbError> {
+    decoded.map_err(|source| BrowserDbError::Serialization { table, key: key.to_vec(), source })
+}
+
+/// Decodes the value stored under `key` in `table` with the table's codec.
+fn decode<'v, T: Deserialize<'v>>(codec: ValueCodec, table: TableType, key: &[u8], value: &'v [u8]) -> Result<T, BrowserDbError> {
+    located(table, key, codec.decode(value))
+}
+
+/// Reports a failed flush of `table`.
+fn flush_failed(table: TableType) -> impl FnOnce(io::Error) -> BrowserDbError {
+    move |source| BrowserDbError::Io { table, source }
+}
+
+/// Reports a table that could not be opened: one in a codec this build
+/// lacks as [`BrowserDbError::UnsupportedCodec`], anything else as is.
+fn open_failed(e: io::Error) -> Box<dyn std::error::Error> {
+    match e.get_ref().and_then(|inner| inner.downcast_ref::<MissingCodec>()) {
+        Some(MissingCodec { codec, table: Some(table), file: Some(file) }) => {
+            Box::new(BrowserDbError::UnsupportedCodec { table: *table, codec: *codec, file: file.clone() })
+        }
+        _ => Box::new(e),
+    }
+}
+
+/// For scans: a record that does not decode is logged and skipped so that
+/// it cannot hide the rest of the table.
+fn skip_undecodable<T>(decoded: Result<T, BrowserDbError>) -> Option<T> {
+    decoded.map_err(|e| eprintln!("BrowserDB: skipping {}", e)).ok()
+}
+
+fn decode_or_skip<'v, T: Deserialize<'v>>(codec: ValueCodec, table: TableType, key: &[u8], value: &'v [u8]) -> Option<T> {
+    skip_undecodable(decode(codec, table, key, value))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,7 +169,7 @@ pub struct HistoryEntryRef<'a> {
     pub visit_count: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CookieEntry {
     pub domain_hash: u128,
     pub name: String,
@@ -87,10 +203,16 @@ impl CookieEntry {
             flags: 0,
         }
     }
-    pub fn set_secure(&mut self) { self.flags |= 1; }
-    pub fn set_httponly(&mut self) { self.flags |= 2; }
-    pub fn is_secure(&self) -> bool { (self.flags & 1) != 0 }
-    pub fn is_httponly(&self) -> bool { (self.flags & 2) != 0 }
+    pub fn set_secure(&mut self) { self.flags |= cookie_flags::SECURE; }
+    pub fn set_httponly(&mut self) { self.flags |= cookie_flags::HTTPONLY; }
+    pub fn set_same_site(&mut self) { self.flags |= cookie_flags::SAME_SITE; }
+    pub fn set_persistent(&mut self) { self.flags |= cookie_flags::PERSISTENT; }
+    pub fn set_partitioned(&mut self) { self.flags |= cookie_flags::PARTITIONED; }
+    pub fn is_secure(&self) -> bool { (self.flags & cookie_flags::SECURE) != 0 }
+    pub fn is_httponly(&self) -> bool { (self.flags & cookie_flags::HTTPONLY) != 0 }
+    pub fn is_same_site(&self) -> bool { (self.flags & cookie_flags::SAME_SITE) != 0 }
+    pub fn is_persistent(&self) -> bool { (self.flags & cookie_flags::PERSISTENT) != 0 }
+    pub fn is_partitioned(&self) -> bool { (self.flags & cookie_flags::PARTITIONED) != 0 }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,6 +222,11 @@ pub struct CacheEntry {
     pub body: Vec<u8>,
     pub etag: String,
     pub last_modified: u128,
+    /// When the response goes stale, in milliseconds since the epoch, from
+    /// `max-age` or `Expires`. `0` never expires. Stale entries are dropped
+    /// by [`CacheTable::get`] and [`CacheTable::purge_stale`].
+    #[serde(default)]
+    pub expires_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,6 +236,68 @@ pub struct CacheEntryRef<'a> {
     pub body: &'a [u8],
     pub etag: &'a str,
     pub last_modified: u128,
+    #[serde(default)]
+    pub expires_at: u64,
+}
+
+/// [`CacheEntry`] as stored before it had `expires_at`. Bincode does not
+/// fill in missing fields, so these are decoded separately.
+#[derive(Deserialize)]
+struct LegacyCacheEntry {
+    url_hash: u128,
+    headers: String,
+    body: Vec<u8>,
+    etag: String,
+    last_modified: u128,
+}
+
+#[derive(Deserialize)]
+struct LegacyCacheEntryRef<'a> {
+    url_hash: u128,
+    headers: &'a str,
+    body: &'a [u8],
+    etag: &'a str,
+    last_modified: u128,
+}
+
+impl CacheEntry {
+    /// Decodes a stored entry, including ones written without
+    /// `expires_at`, which never expire.
+    pub fn decode(codec: ValueCodec, value: &[u8]) -> Result<Self, CodecError> {
+        codec.decode(value).or_else(|e| {
+            let Ok(old) = codec.decode::<LegacyCacheEntry>(value) else { return Err(e) };
+            Ok(Self {
+                url_hash: old.url_hash,
+                headers: old.headers,
+                body: old.body,
+                etag: old.etag,
+                last_modified: old.last_modified,
+                expires_at: 0,
+            })
+        })
+    }
+
+    /// Whether the entry is stale at `now`, in milliseconds since the epoch.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at != 0 && self.expires_at <= now
+    }
+}
+
+impl<'a> CacheEntryRef<'a> {
+    /// [`CacheEntry::decode`] without copying the strings and body.
+    pub fn decode(codec: ValueCodec, value: &'a [u8]) -> Result<Self, CodecError> {
+        codec.decode(value).or_else(|e| {
+            let Ok(old) = codec.decode::<LegacyCacheEntryRef>(value) else { return Err(e) };
+            Ok(Self {
+                url_hash: old.url_hash,
+                headers: old.headers,
+                body: old.body,
+                etag: old.etag,
+                last_modified: old.last_modified,
+                expires_at: 0,
+            })
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -146,10 +335,74 @@ pub struct BookmarkEntryRef<'a> {
     pub created_at: u64,
 }
 
+/// Entries removed by [`BrowserDB::privacy_wipe_domain`], per table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrivacyWipeResult {
+    pub history: usize,
+    pub cookies: usize,
+    pub localstore: usize,
+    pub total_wiped: usize,
+}
+
+/// What a wipe or purge would remove, as reported by its `count_*`
+/// companion (e.g. [`Container::count_wipe`], [`HistoryTable::count_domain`])
+/// without deleting anything. `entries` is what the real call would return;
+/// `bytes` is the size of the keys and values it would delete.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RemovalCount {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+impl RemovalCount {
+    fn add(&mut self, key: &[u8], value_len: usize) {
+        self.entries += 1;
+        self.bytes += (key.len() + value_len) as u64;
+    }
+}
+
+/// What deleting every key of `table` under `prefix` would remove.
+fn count_prefix(table: TableHandle, prefix: &[u8]) -> RemovalCount {
+    let mut count = RemovalCount::default();
+    match table {
+        TableHandle::Persistent(t) => t.scan_prefix(prefix).iter().for_each(|e| count.add(&e.key, e.value.len())),
+        TableHandle::Ultra(t) => t.all_entries().iter().filter(|(k, _)| k.starts_with(prefix)).for_each(|(k, v)| count.add(k, v.len())),
+    }
+    count
+}
+
+/// Outcome of an import: a browser history import with the
+/// `browser-import` feature, or [`CookiesTable::import_cookies`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportResult {
+    /// Entries written to the table.
+    pub imported: usize,
+    /// Rows not written: history rows with a malformed URL, and cookies
+    /// that lost to a longer-lived or identical one when deduplicating.
+    pub skipped: usize,
+    /// Imported entries that replaced a stored one. Only counted when
+    /// deduplicating cookies.
+    pub overwritten: usize,
+}
+
+/// Level layout of each table, from [`BrowserDB::lsm_report`].
+pub type LsmReport = Vec<(TableType, Vec<LevelInfo>)>;
+
+/// SSTable bytes of one table around a [`BrowserDB::major_compact`].
+/// `bytes_before` is measured after the memtable is flushed, so the two
+/// cover the same data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
 pub struct Container {
     pub name: String,
     pub switcher: Arc<ModeSwitcher>,
     pub pku: u16, // Hardware Protection Key for Hajr HAL
+    /// Serializes `HistoryTable::record_visit`'s read-modify-write.
+    visit_lock: Mutex<()>,
 }
 
 impl Container {
@@ -160,10 +413,19 @@ impl Container {
     pub fn localstore(&self) -> LocalStoreTable<'_> { LocalStoreTable { container: self } }
     pub fn settings(&self) -> SettingsTable<'_> { SettingsTable { container: self } }
     pub fn binarystore(&self) -> BinaryStoreTable<'_> { BinaryStoreTable { container: self } }
+    pub fn kv(&self) -> KvStore<'_> { KvStore { container: self } }
+
+    /// The codec `table`'s values are stored with.
+    pub fn codec(&self, table: TableType) -> ValueCodec {
+        self.switcher.config.ext_config.codecs.for_table(table)
+    }
 
     pub fn set_mode(&self, mode: DatabaseMode) -> Result<(), Box<dyn std::error::Error>> {
         let path = self.switcher.base_path.clone();
-        self.switcher.switch_mode(mode, &path)?;
+        self.switcher.switch_mode(mode, &path).map_err(|e| match e {
+            ModeSwitchError::IoError(e) => open_failed(e),
+            other => other.into(),
+        })?;
         Ok(())
     }
 
@@ -186,6 +448,195 @@ impl Container {
         Ok(())
     }
 
+    /// What [`Container::wipe`] would remove, without removing it. Entries
+    /// are counted as [`Container::stats`] counts them; `bytes` also covers
+    /// the history title index and the chunks of streamed cache bodies.
+    pub fn count_wipe(&self) -> Result<RemovalCount, Box<dyn std::error::Error>> {
+        let mut count = RemovalCount { entries: self.stats()?.total_entries as usize, bytes: 0 };
+        for table in [TableType::History, TableType::Bookmarks, TableType::Cookies, TableType::Cache, TableType::LocalStore, TableType::Settings, TableType::BinaryStore] {
+            count.bytes += count_prefix(self.switcher.table(table), &[]).bytes;
+        }
+        Ok(count)
+    }
+
+    /// Forces a full compaction of every table, dropping deleted and
+    /// overwritten values from disk. Returns the number of bytes reclaimed.
+    /// A no-op in `CurrentMode::Ultra`, which keeps nothing on disk.
+    pub fn vacuum(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        let current_mode = self.switcher.current_mode.read();
+        match &*current_mode {
+            CurrentMode::Persistent(pm) => {
+                let mut reclaimed = 0;
+                reclaimed += pm.history.vacuum()?;
+                reclaimed += pm.bookmarks.vacuum()?;
+                reclaimed += pm.cookies.vacuum()?;
+                reclaimed += pm.cache.vacuum()?;
+                reclaimed += pm.localstore.vacuum()?;
+                reclaimed += pm.settings.vacuum()?;
+                reclaimed += pm.binarystore.vacuum()?;
+                Ok(reclaimed)
+            },
+            CurrentMode::Ultra(_) => Ok(0),
+        }
+    }
+
+    /// Merges every SSTable of `table` into one, in the deepest level that
+    /// held data, dropping tombstones and overwritten values: a
+    /// [`Container::vacuum`] of a single table, e.g. after a bulk delete.
+    /// The manifest switches to the merged table in one write. A no-op in
+    /// `CurrentMode::Ultra`.
+    pub fn major_compact(&self, table: TableType) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+        let CurrentMode::Persistent(pm) = &*self.switcher.current_mode.read() else { return Ok(CompactionReport::default()) };
+        let tree = pm.table(table);
+        let sstable_bytes = || tree.level_report().iter().map(|l| l.bytes).sum::<u64>();
+        tree.flush().map_err(flush_failed(table))?;
+        let bytes_before = sstable_bytes();
+        tree.vacuum()?;
+        Ok(CompactionReport { bytes_before, bytes_after: sstable_bytes() })
+    }
+
+    /// Flushes every table and runs the compactions they are due for, so
+    /// the memtables are empty and no compaction is pending when it
+    /// returns. A no-op in `CurrentMode::Ultra`.
+    pub fn checkpoint(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let CurrentMode::Persistent(pm) = &*self.switcher.current_mode.read() {
+            for table in [&pm.history, &pm.bookmarks, &pm.cookies, &pm.cache, &pm.localstore, &pm.settings, &pm.binarystore] {
+                table.checkpoint().map_err(flush_failed(table.inner.table_type))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Aggregates the per-table heat trackers into hot / warm / cold counts
+    /// and an average heat. `CurrentMode::Ultra` tracks no heat and reports
+    /// empty stats.
+    pub fn heatmap_stats(&self) -> Result<HeatMapStats, Box<dyn std::error::Error>> {
+        let mut stats = HeatMapStats::default();
+        if let CurrentMode::Persistent(pm) = &*self.switcher.current_mode.read() {
+            for table in [&pm.history, &pm.bookmarks, &pm.cookies, &pm.cache, &pm.localstore, &pm.settings, &pm.binarystore] {
+                stats.merge(&table.inner.heat_tracker.stats());
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Writes `table`'s memtable out as an SSTable, releasing its memory.
+    /// Fails with [`BrowserDbError::Io`] if the SSTable cannot be written,
+    /// keeping the entries for a retry. A no-op in `CurrentMode::Ultra`.
+    pub fn flush_table(&self, table: TableType) -> Result<(), Box<dyn std::error::Error>> {
+        if let CurrentMode::Persistent(pm) = &*self.switcher.current_mode.read() {
+            pm.table(table).flush().map_err(flush_failed(table))?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the table whose memtable is fullest, by
+    /// `LSMTree::memtable_fill_ratio`, and returns it. `None` if every
+    /// memtable is empty or in `CurrentMode::Ultra`.
+    pub fn flush_largest(&self) -> Result<Option<TableType>, Box<dyn std::error::Error>> {
+        let current_mode = self.switcher.current_mode.read();
+        let CurrentMode::Persistent(pm) = &*current_mode else {
+            return Ok(None);
+        };
+        let fullest = [&pm.history, &pm.bookmarks, &pm.cookies, &pm.cache, &pm.localstore, &pm.settings, &pm.binarystore]
+            .into_iter()
+            .map(|table| (table, table.memtable_fill_ratio()))
+            .filter(|(_, ratio)| *ratio > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match fullest {
+            Some((table, _)) => {
+                table.flush().map_err(flush_failed(table.inner.table_type))?;
+                Ok(Some(table.inner.table_type))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// `LSMTree::level_report` for each table. Empty in
+    /// `CurrentMode::Ultra`, which has no levels.
+    pub fn lsm_report(&self) -> Result<LsmReport, Box<dyn std::error::Error>> {
+        let mut report = Vec::new();
+        if let CurrentMode::Persistent(pm) = &*self.switcher.current_mode.read() {
+            for table in [&pm.history, &pm.bookmarks, &pm.cookies, &pm.cache, &pm.localstore, &pm.settings, &pm.binarystore] {
+                report.push((table.inner.table_type, table.level_report()));
+            }
+        }
+        Ok(report)
+    }
+
+    /// [`LSMTree::check_integrity`] for each table. Empty in
+    /// `CurrentMode::Ultra`, which keeps nothing on disk to check.
+    pub fn check_integrity(&self) -> Result<IntegrityReport, Box<dyn std::error::Error>> {
+        let mut report = IntegrityReport::default();
+        if let CurrentMode::Persistent(pm) = &*self.switcher.current_mode.read() {
+            for (table, _) in repair::TABLES {
+                report.merge(pm.table(table).check_integrity()?);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Write and read amplification of each table since open, for tuning
+    /// compaction. Empty in `CurrentMode::Ultra`. See [`Amplification`].
+    pub fn amplification(&self) -> Vec<(TableType, Amplification)> {
+        let mut report = Vec::new();
+        if let CurrentMode::Persistent(pm) = &*self.switcher.current_mode.read() {
+            for table in [&pm.history, &pm.bookmarks, &pm.cookies, &pm.cache, &pm.localstore, &pm.settings, &pm.binarystore] {
+                report.push((table.inner.table_type, table.amplification()));
+            }
+        }
+        report
+    }
+
+    /// "Forget this site": removes `domain`'s history (every URL containing
+    /// it, see [`HistoryTable::wipe_domain`]), the cookies stored under
+    /// [`hash::domain_hash`]`(domain)` and the localStorage of the origin
+    /// with that same hash.
+    pub fn privacy_wipe_domain(&self, domain: &str) -> Result<PrivacyWipeResult, Box<dyn std::error::Error>> {
+        let domain_hash = hash::domain_hash(domain);
+        let history = self.history().wipe_domain(domain)?;
+        let cookies = self.cookies().clear_domain(domain_hash)?;
+        let localstore = self.localstore().clear_origin(domain_hash)?;
+        Ok(PrivacyWipeResult { history, cookies, localstore, total_wiped: history + cookies + localstore })
+    }
+
+    /// The counts [`Container::privacy_wipe_domain`] would return, without
+    /// removing anything, for a "clear data for this site" confirmation.
+    pub fn count_privacy_wipe_domain(&self, domain: &str) -> Result<PrivacyWipeResult, Box<dyn std::error::Error>> {
+        let domain_hash = hash::domain_hash(domain);
+        let history = self.history().count_domain(domain)?.entries;
+        let cookies = self.cookies().count_domain(domain_hash)?.entries;
+        let localstore = self.localstore().count_origin(domain_hash)?.entries;
+        Ok(PrivacyWipeResult { history, cookies, localstore, total_wiped: history + cookies + localstore })
+    }
+
+    /// SSTables that could not be loaded when the tables were opened, so an
+    /// application can tell the user some data is missing. Damaged files
+    /// are quarantined for [`BrowserDB::repair`]. Empty in Ultra mode.
+    pub fn open_warnings(&self) -> Vec<(TableType, OpenWarning)> {
+        let mut warnings = Vec::new();
+        if let CurrentMode::Persistent(pm) = &*self.switcher.current_mode.read() {
+            for table in [&pm.history, &pm.bookmarks, &pm.cookies, &pm.cache, &pm.localstore, &pm.settings, &pm.binarystore] {
+                warnings.extend(table.open_warnings().iter().map(|w| (table.inner.table_type, w.clone())));
+            }
+        }
+        warnings
+    }
+
+    /// Takes a consistent read view of the container, see [`ReadSnapshot`].
+    pub fn snapshot_read(&self) -> Result<ReadSnapshot, Box<dyn std::error::Error>> {
+        let current_mode = self.switcher.current_mode.read();
+        Ok(ReadSnapshot {
+            history: current_mode.snapshot(TableType::History),
+            cookies: current_mode.snapshot(TableType::Cookies),
+            cache: current_mode.snapshot(TableType::Cache),
+            localstore: current_mode.snapshot(TableType::LocalStore),
+            settings: current_mode.snapshot(TableType::Settings),
+            binarystore: current_mode.snapshot(TableType::BinaryStore),
+            codecs: self.switcher.config.ext_config.codecs.clone(),
+        })
+    }
+
     pub fn stats(&self) -> Result<DatabaseStats, Box<dyn std::error::Error>> {
         let history = self.history().count()? as u64;
         let bookmarks = self.bookmarks().count()? as u64;
@@ -195,6 +646,23 @@ impl Container {
         let settings = self.settings().count()? as u64;
         let binarystore = self.binarystore().count()? as u64;
 
+        let (mut hits, mut misses, mut memory_usage) = (0, 0, 0);
+        match &*self.switcher.current_mode.read() {
+            CurrentMode::Persistent(pm) => {
+                for table in [&pm.history, &pm.bookmarks, &pm.cookies, &pm.cache, &pm.localstore, &pm.settings, &pm.binarystore] {
+                    let (h, m) = table.inner.entry_cache.hits_and_misses();
+                    hits += h;
+                    misses += m;
+                    memory_usage += table.memtable_bytes();
+                }
+            }
+            CurrentMode::Ultra(um) => {
+                for table in [&um.history, &um.bookmarks, &um.cookies, &um.cache, &um.localstore, &um.settings, &um.binarystore] {
+                    memory_usage += table.size_bytes();
+                }
+            }
+        }
+
         let mut disk_usage = 0;
         if let Ok(entries) = fs::read_dir(&self.switcher.base_path) {
             for entry in entries.flatten() {
@@ -213,30 +681,336 @@ impl Container {
             localstore_entries: localstore,
             settings_entries: settings,
             binarystore_entries: binarystore,
-            memory_usage_mb: 0,
+            memory_usage_bytes: memory_usage,
+            memory_usage_mb: memory_usage / 1024 / 1024,
+            disk_usage_bytes: disk_usage,
             disk_usage_mb: disk_usage / 1024 / 1024,
+            cache_hit_rate: if hits + misses > 0 { hits as f64 / (hits + misses) as f64 } else { 0.0 },
         })
     }
+
+    /// Renders table sizes and LSM internals in the Prometheus text
+    /// exposition format, one `table` label per table. The LSM series are
+    /// left out in `CurrentMode::Ultra`, as is a table whose count fails.
+    pub fn metrics_prometheus(&self) -> String {
+        use std::fmt::Write as _;
+
+        let mut out = String::new();
+        let family = |out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+            }
+        };
+
+        let mut entries = Vec::new();
+        for (table, name) in repair::TABLES {
+            let count = match table {
+                TableType::History => self.history().count(),
+                TableType::Bookmarks => self.bookmarks().count(),
+                TableType::Cookies => self.cookies().count(),
+                TableType::Cache => self.cache().count(),
+                TableType::LocalStore => self.localstore().count(),
+                TableType::Settings => self.settings().count(),
+                TableType::BinaryStore => self.binarystore().count(),
+            };
+            if let Ok(count) = count {
+                entries.push((format!("table=\"{}\"", name), count.to_string()));
+            }
+        }
+        family(&mut out, "browserdb_entries", "gauge", "Live entries per table.", &entries);
+
+        let current_mode = self.switcher.current_mode.read();
+        let CurrentMode::Persistent(pm) = &*current_mode else {
+            return out;
+        };
+        let (mut sstables, mut memtable, mut hit_rate, mut flushes, mut compactions, mut fpr) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let (mut stalls, mut stall_seconds) = (Vec::new(), Vec::new());
+        let (mut write_amp, mut read_amp) = (Vec::new(), Vec::new());
+        for (table, name) in repair::TABLES {
+            let tree = pm.table(table);
+            let label = format!("table=\"{}\"", name);
+            for info in tree.level_report() {
+                sstables.push((format!("{},level=\"{}\"", label, info.level), info.sstables.to_string()));
+            }
+            memtable.push((label.clone(), tree.memtable_bytes().to_string()));
+            let (hits, misses) = tree.inner.entry_cache.hits_and_misses();
+            let rate = if hits + misses > 0 { hits as f64 / (hits + misses) as f64 } else { 0.0 };
+            hit_rate.push((label.clone(), rate.to_string()));
+            flushes.push((label.clone(), tree.inner.flushes.load(std::sync::atomic::Ordering::Relaxed).to_string()));
+            compactions.push((label.clone(), tree.inner.compactions.load(std::sync::atomic::Ordering::Relaxed).to_string()));
+            stalls.push((label.clone(), tree.inner.write_stalls.load(std::sync::atomic::Ordering::Relaxed).to_string()));
+            let stall_ms = tree.inner.write_stall_ms.load(std::sync::atomic::Ordering::Relaxed);
+            stall_seconds.push((label.clone(), (stall_ms as f64 / 1000.0).to_string()));
+            let amplification = tree.amplification();
+            write_amp.push((label.clone(), amplification.write_amplification().to_string()));
+            read_amp.push((label.clone(), amplification.read_amplification().to_string()));
+            if let Some(estimate) = tree.bloom_fpr_estimate() {
+                fpr.push((label, estimate.to_string()));
+            }
+        }
+        family(&mut out, "browserdb_sstables", "gauge", "SSTables per table and level.", &sstables);
+        family(&mut out, "browserdb_memtable_bytes", "gauge", "Bytes buffered in the active memtable.", &memtable);
+        family(&mut out, "browserdb_entry_cache_hit_ratio", "gauge", "Share of SSTable reads answered by the entry cache.", &hit_rate);
+        family(&mut out, "browserdb_flushes_total", "counter", "Memtable flushes since open.", &flushes);
+        family(&mut out, "browserdb_compactions_total", "counter", "Compactions since open.", &compactions);
+        family(&mut out, "browserdb_write_stalls_total", "counter", "Writes delayed or stopped by backpressure since open.", &stalls);
+        family(&mut out, "browserdb_write_stall_seconds_total", "counter", "Time writes spent held back by backpressure since open.", &stall_seconds);
+        family(&mut out, "browserdb_bloom_fpr_estimate", "gauge", "Mean bloom filter false-positive estimate over a table's SSTables.", &fpr);
+        family(&mut out, "browserdb_write_amplification", "gauge", "Bytes written to the WAL and SSTables per byte of user data since open.", &write_amp);
+        family(&mut out, "browserdb_read_amplification", "gauge", "SSTable bytes read per byte returned by point reads since open.", &read_amp);
+        out
+    }
+}
+
+/// Read-only view of a container as of [`BrowserDB::snapshot_read`].
+/// Later writes, flushes and compactions are not visible through it, so
+/// several keys can be read as one consistent state. In Persistent mode it
+/// copies the memtables and pins the SSTables it may read; in Ultra mode it
+/// copies the tables. Dropping it releases both.
+pub struct ReadSnapshot {
+    history: TableSnapshot,
+    cookies: TableSnapshot,
+    cache: TableSnapshot,
+    localstore: TableSnapshot,
+    settings: TableSnapshot,
+    binarystore: TableSnapshot,
+    codecs: ValueCodecConfig,
+}
+
+impl ReadSnapshot {
+    pub fn get_history(&self, url_hash: u128) -> Result<Option<HistoryEntry>, Box<dyn std::error::Error>> {
+        let key = keys::history_key_bytes(url_hash);
+        Ok(self.history.get(&key).map(|v| decode(self.codecs.history, TableType::History, &key, &v)).transpose()?)
+    }
+
+    pub fn get_cookie(&self, domain_hash: u128, name: &str) -> Result<Option<CookieEntry>, Box<dyn std::error::Error>> {
+        let key = keys::cookie_key(domain_hash, name);
+        Ok(self.cookies.get(&key).map(|v| decode(self.codecs.cookies, TableType::Cookies, &key, &v)).transpose()?)
+    }
+
+    pub fn get_cache(&self, url_hash: u128) -> Result<Option<CacheEntry>, Box<dyn std::error::Error>> {
+        let key = keys::cache_key_bytes(url_hash);
+        let mut entry: Option<CacheEntry> = self.cache.get(&key).map(|v| located(TableType::Cache, &key, CacheEntry::decode(self.codecs.cache, &v))).transpose()?;
+        if let Some(entry) = entry.as_mut() {
+            fill_chunked_body(self.codecs.cache, entry, |k| self.cache.get(k))?;
+        }
+        Ok(entry)
+    }
+
+    pub fn get_localstore(&self, origin_hash: u128, key: &str) -> Result<Option<LocalStoreEntry>, Box<dyn std::error::Error>> {
+        let primary_key = keys::localstore_key(origin_hash, key);
+        Ok(self.localstore.get(&primary_key).map(|v| decode(self.codecs.localstore, TableType::LocalStore, &primary_key, &v)).transpose()?)
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self.settings.get(key.as_bytes()).map(String::from_utf8).transpose()?)
+    }
+
+    pub fn get_binary(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        Ok(self.binarystore.get(key))
+    }
+}
+
+/// Background thread that periodically prunes history entries older than
+/// `retention.history_max_age_days`. Stopped and joined on drop.
+struct RetentionSweeper {
+    stop: Arc<std::sync::atomic::AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl RetentionSweeper {
+    fn spawn(container: Arc<Container>, max_age_days: u64, interval_sec: u64) -> Self {
+        let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let stop_clone = Arc::clone(&stop);
+        let handle = std::thread::spawn(move || {
+            let interval = std::time::Duration::from_secs(interval_sec.max(1));
+            let tick = std::time::Duration::from_millis(100);
+            let mut waited = std::time::Duration::ZERO;
+            while !stop_clone.load(std::sync::atomic::Ordering::Relaxed) {
+                std::thread::sleep(tick);
+                waited += tick;
+                if waited < interval {
+                    continue;
+                }
+                waited = std::time::Duration::ZERO;
+
+                let now = container.switcher.config.ext_config.clock.now_ms();
+                let cutoff = now.saturating_sub(max_age_days * 24 * 60 * 60 * 1000);
+                if let Err(e) = container.history().prune_older_than(cutoff) {
+                    eprintln!("BrowserDB retention sweep failed: {}", e);
+                }
+            }
+        });
+        Self { stop, handle: Some(handle) }
+    }
+}
+
+impl Drop for RetentionSweeper {
+    fn drop(&mut self) {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
 }
 
+/// A database rooted at one directory. Handles opened on different
+/// directories share no state, so a process can keep several open at once,
+/// e.g. one per browser profile; the only thing two handles can share is
+/// the compaction pool of a [`BrowserDBConfig`] cloned between them. A
+/// directory is locked by the handle that opened it, see
+/// [`BrowserDB::open_without_locking`].
 pub struct BrowserDB {
     base_path: PathBuf,
     config: ModeConfig,
     containers: RwLock<HashMap<String, Arc<Container>>>,
     default_container: Arc<Container>,
-    _lock_file: File,
+    _retention_sweeper: Option<RetentionSweeper>,
+    closed: bool,
+    /// `None` for [`BrowserDB::open_in_memory`].
+    _lock_file: Option<File>,
+}
+
+/// Secondary indexes every container keeps on its LocalStore table.
+fn localstore_index_defs(codec: ValueCodec) -> Vec<crate::core::lsm_tree::IndexDefinition> {
+    vec![
+        crate::core::lsm_tree::IndexDefinition {
+            name: "value".to_string(),
+            field_name: "value".to_string(),
+            extractor: Arc::new(move |k, v| LocalStoreTable::extract_value_index(codec, k, v)),
+        },
+        crate::core::lsm_tree::IndexDefinition {
+            name: "key".to_string(),
+            field_name: "key".to_string(),
+            extractor: Arc::new(move |k, v| LocalStoreTable::extract_key_index(codec, k, v)),
+        },
+        crate::core::lsm_tree::IndexDefinition {
+            name: "origin_hash".to_string(),
+            field_name: "origin_hash".to_string(),
+            extractor: Arc::new(move |k, v| LocalStoreTable::extract_origin_index(codec, k, v)),
+        },
+    ]
 }
 
 impl BrowserDB {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        Self::open_with_locking(path, true)
+        Self::open_with_locking(path, true, None)
     }
 
     pub fn open_without_locking<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        Self::open_with_locking(path, false)
+        Self::open_with_locking(path, false, None)
+    }
+
+    /// Opens the database with an explicit configuration instead of reading
+    /// `browserdb.toml` from the database directory.
+    pub fn open_with_config<P: AsRef<Path>>(path: P, config: BrowserDBConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open_with_locking(path, true, Some(config))
+    }
+
+    /// Opens a database that lives only in memory. Every container starts
+    /// in Ultra mode and nothing is read from or written to disk, so no
+    /// directory, lock file or `METADATA` is involved; everything is gone
+    /// once it is dropped. Switching to Persistent mode fails with
+    /// [`ModeSwitchError::NoDirectory`](crate::core::modes::ModeSwitchError::NoDirectory).
+    pub fn open_in_memory() -> Result<Self, Box<dyn std::error::Error>> {
+        let config = Self::mode_config(BrowserDBConfig::default());
+        let mut db = Self {
+            base_path: PathBuf::new(),
+            config: config.clone(),
+            containers: RwLock::new(HashMap::new()),
+            // Replaced below, like in `open_with_locking`.
+            default_container: Arc::new(Container {
+                name: "dummy".to_string(),
+                switcher: Arc::new(ModeSwitcher::in_memory(config)),
+                pku: 0,
+                visit_lock: Mutex::new(()),
+            }),
+            _retention_sweeper: None,
+            closed: false,
+            _lock_file: None,
+        };
+        db.default_container = db.container("default")?;
+        Ok(db)
     }
 
-    fn open_with_locking<P: AsRef<Path>>(path: P, use_locking: bool) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Whether this database was opened with [`BrowserDB::open_in_memory`].
+    pub fn is_in_memory(&self) -> bool {
+        self._lock_file.is_none()
+    }
+
+    fn mode_config(ext_config: BrowserDBConfig) -> ModeConfig {
+        ModeConfig {
+            max_memory: 1024 * 1024 * 100, // 100MB Default
+            enable_compression: false,
+            enable_heat_tracking: true,
+            ext_config,
+        }
+    }
+
+    /// Rebuilds a database directory whose manifests or SSTables were
+    /// damaged. The database must not be open. Every entry that still
+    /// passes its CRC check is rewritten into level 0 under a new manifest,
+    /// files that could not be read completely are moved to `corrupt/`, and
+    /// LocalStore's secondary indexes are derived again from its entries.
+    pub fn repair<P: AsRef<Path>>(path: P) -> Result<RepairReport, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let lock_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.join("browserdb.lock"))?;
+        lock_file
+            .try_lock_exclusive()
+            .map_err(|_| io::Error::other("Database is already in use by another process"))?;
+
+        let metadata = DatabaseMetadata::load(path);
+        let mut config = BrowserDBConfig::load(path)
+            .or_else(|| metadata.as_ref().map(|m| m.config.clone()))
+            .unwrap_or_default();
+        if let Some(metadata) = &metadata {
+            config.codecs = metadata.config.codecs.clone();
+        }
+
+        let mut report = RepairReport::default();
+        repair::repair_dir(path, &config, &mut report).map_err(open_failed)?;
+        for entry in fs::read_dir(path)?.flatten() {
+            let dir = entry.path();
+            if !dir.is_dir() || !entry.file_name().to_str().is_some_and(|n| n.starts_with("container_")) {
+                continue;
+            }
+            // Index trees only hold keys derived from their table, so they
+            // are dropped rather than salvaged.
+            for idx in fs::read_dir(&dir)?.flatten() {
+                if idx.path().is_dir() && idx.file_name().to_str().is_some_and(|n| n.contains("_idx_")) {
+                    fs::remove_dir_all(idx.path())?;
+                }
+            }
+            repair::repair_dir(&dir, &config, &mut report).map_err(open_failed)?;
+
+            let max_mem = config.lsm_tree.max_memtable_size_mb * 1024 * 1024;
+            let localstore = LSMTree::new_with_indices(&dir, TableType::LocalStore, max_mem, config.clone(), localstore_index_defs(config.codecs.localstore))
+                .map_err(open_failed)?;
+            localstore.rebuild_indices()?;
+        }
+        Ok(report)
+    }
+
+    /// Rebuilds a database directory at `dest` from snapshots: a full one
+    /// followed by its increments in the order they were taken, as
+    /// directories. Every file is checked against the CRC recorded when it
+    /// was copied; a mismatch fails with `io::ErrorKind::InvalidData` and
+    /// leaves `dest` partly written. `dest` must be empty or not exist.
+    pub fn restore<P: AsRef<Path>, Q: AsRef<Path>>(chain: &[P], dest: Q) -> Result<(), Box<dyn std::error::Error>> {
+        let chain: Vec<PathBuf> = chain.iter().map(|p| p.as_ref().to_path_buf()).collect();
+        snapshot::restore(&chain, dest.as_ref())?;
+        Ok(())
+    }
+
+    fn open_with_locking<P: AsRef<Path>>(path: P, use_locking: bool, config: Option<BrowserDBConfig>) -> Result<Self, Box<dyn std::error::Error>> {
         let path = path.as_ref();
         if !path.exists() {
             fs::create_dir_all(path)?;
@@ -255,16 +1029,22 @@ impl BrowserDB {
             })?;
         }
 
-        let ext_config = BrowserDBConfig::load_or_default(path);
+        // An explicit config wins, then `browserdb.toml`, then whatever the
+        // database was last opened with.
+        let metadata = DatabaseMetadata::load(path);
+        let mut ext_config = config
+            .or_else(|| BrowserDBConfig::load(path))
+            .or_else(|| metadata.as_ref().map(|m| m.config.clone()))
+            .unwrap_or_default();
+        // Stored values do not say how they were encoded, so the codecs the
+        // database was created with stay in force.
+        if let Some(metadata) = &metadata {
+            ext_config.codecs = metadata.config.codecs.clone();
+        }
 
-        let config = ModeConfig {
-            max_memory: 1024 * 1024 * 100, // 100MB Default
-            enable_compression: false,
-            enable_heat_tracking: true,
-            ext_config,
-        };
+        let config = Self::mode_config(ext_config);
 
-        let db = Self {
+        let mut db = Self {
             base_path: path.to_path_buf(),
             config,
             containers: RwLock::new(HashMap::new()),
@@ -276,17 +1056,107 @@ impl BrowserDB {
                     enable_compression: false,
                     enable_heat_tracking: false,
                     ext_config: BrowserDBConfig::default(),
-                })?),
+                }).map_err(open_failed)?),
                 pku: 0,
+                visit_lock: Mutex::new(()),
             }),
-            _lock_file: lock_file,
+            _retention_sweeper: None,
+            closed: false,
+            _lock_file: Some(lock_file),
         };
         
         let default = db.container("default")?;
-        Ok(Self {
-            default_container: default,
-            ..db
-        })
+        // Always built in Persistent mode so existing SSTables are loaded;
+        // switching then migrates them exactly like `set_mode` would.
+        let mode = metadata.map(|m| m.mode).unwrap_or(DatabaseMode::Persistent);
+        if mode != DatabaseMode::Persistent {
+            default.set_mode(mode)?;
+        }
+        let snapshot_path = path.join(ULTRA_SNAPSHOT_FILE);
+        if snapshot_path.exists() {
+            if db.config.ext_config.ultra_snapshot_on_close {
+                if let CurrentMode::Ultra(um) = &*default.switcher.current_mode.read() {
+                    if let Err(e) = um.load_snapshot(&snapshot_path) {
+                        eprintln!("BrowserDB: ignoring unreadable Ultra snapshot: {}", e);
+                    }
+                }
+            }
+            // Consumed either way, so a later crash cannot bring this state back.
+            fs::remove_file(&snapshot_path)?;
+        }
+        DatabaseMetadata { mode, config: db.config.ext_config.clone() }.store(path)?;
+
+        let retention = &db.config.ext_config.retention;
+        let retention_sweeper = if retention.history_max_age_days > 0 {
+            Some(RetentionSweeper::spawn(
+                Arc::clone(&default),
+                retention.history_max_age_days,
+                retention.sweep_interval_sec,
+            ))
+        } else {
+            None
+        };
+        db.default_container = default;
+        db._retention_sweeper = retention_sweeper;
+        Ok(db)
+    }
+
+    /// Closes the database. Dropping it does the same, but only `close`
+    /// reports a failure to write the Ultra snapshot.
+    pub fn close(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.closed = true;
+        self.save_ultra_snapshot()?;
+        Ok(())
+    }
+
+    /// Writes a full snapshot of the database to the directory `out`, for
+    /// backups. The database stays usable; each table is flushed first and
+    /// writes racing with the snapshot may or may not be included. Every
+    /// open container must be in Persistent mode.
+    pub fn snapshot<P: AsRef<Path>>(&self, out: P) -> Result<SnapshotMeta, Box<dyn std::error::Error>> {
+        self.write_snapshot(None, out.as_ref())
+    }
+
+    /// Like [`BrowserDB::snapshot`], but `out` only receives the files that
+    /// changed since `base`, a full or incremental snapshot of this
+    /// database. Restoring needs `base`'s chain and `out`.
+    pub fn snapshot_incremental<P: AsRef<Path>>(&self, base: &SnapshotMeta, out: P) -> Result<SnapshotMeta, Box<dyn std::error::Error>> {
+        self.write_snapshot(Some(base), out.as_ref())
+    }
+
+    fn write_snapshot(&self, base: Option<&SnapshotMeta>, out: &Path) -> Result<SnapshotMeta, Box<dyn std::error::Error>> {
+        if self.is_in_memory() {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "an in-memory database has nothing on disk to snapshot").into());
+        }
+        let containers: Vec<Arc<Container>> = self.containers.read().values().cloned().collect();
+        // Held throughout, so no container changes mode mid-copy.
+        let modes: Vec<_> = containers.iter().map(|c| c.switcher.current_mode.read()).collect();
+        let mut captures = Vec::new();
+        for (container, mode) in containers.iter().zip(&modes) {
+            let CurrentMode::Persistent(pm) = &**mode else {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("container {} is in Ultra mode, so its data is not on disk", container.name),
+                ).into());
+            };
+            for (table, _) in repair::TABLES {
+                captures.extend(pm.table(table).capture()?);
+            }
+        }
+        let now = self.config.ext_config.clock.now_ms();
+        Ok(snapshot::write(&self.base_path, &captures, base, out, now)?)
+    }
+
+    /// With `ultra_snapshot_on_close` set and the default container in
+    /// Ultra mode, saves its tables to `ultra.snapshot` for the next open.
+    fn save_ultra_snapshot(&self) -> io::Result<()> {
+        if !self.config.ext_config.ultra_snapshot_on_close || self.is_in_memory() {
+            return Ok(());
+        }
+        if let CurrentMode::Ultra(um) = &*self.default_container.switcher.current_mode.read() {
+            um.save_snapshot(&self.base_path.join(ULTRA_SNAPSHOT_FILE))?;
+        }
+        Ok(())
     }
 
     pub fn container(&self, name: &str) -> Result<Arc<Container>, Box<dyn std::error::Error>> {
@@ -307,40 +1177,28 @@ impl BrowserDB {
         }
 
         let mut containers = self.containers.write();
-        let container_path = self.base_path.join(format!("container_{}", sanitized_name));
-        if !container_path.exists() {
-            fs::create_dir_all(&container_path)?;
-        }
+        let switcher = if self.is_in_memory() {
+            ModeSwitcher::in_memory(self.config.clone())
+        } else {
+            let container_path = self.base_path.join(format!("container_{}", sanitized_name));
+            if !container_path.exists() {
+                fs::create_dir_all(&container_path)?;
+            }
 
-        let mut index_defs = HashMap::new();
-        let ls_indices = vec![
-            crate::core::lsm_tree::IndexDefinition {
-                name: "value".to_string(),
-                field_name: "value".to_string(),
-                extractor: Arc::new(LocalStoreTable::extract_value_index),
-            },
-            crate::core::lsm_tree::IndexDefinition {
-                name: "key".to_string(),
-                field_name: "key".to_string(),
-                extractor: Arc::new(LocalStoreTable::extract_key_index),
-            },
-            crate::core::lsm_tree::IndexDefinition {
-                name: "origin_hash".to_string(),
-                field_name: "origin_hash".to_string(),
-                extractor: Arc::new(LocalStoreTable::extract_origin_index),
-            },
-        ];
-        index_defs.insert(crate::core::format::TableType::LocalStore, ls_indices);
+            let mut index_defs = HashMap::new();
+            index_defs.insert(TableType::LocalStore, localstore_index_defs(self.config.ext_config.codecs.localstore));
 
-        let switcher = ModeSwitcher::new_with_indices(&container_path, DatabaseMode::Persistent, self.config.clone(), index_defs)?;
+            ModeSwitcher::new_with_indices(&container_path, DatabaseMode::Persistent, self.config.clone(), index_defs).map_err(open_failed)?
+        };
 
         // Assign a pseudo-PKU based on name hash for Hajr HAL isolation
-        let pku = (ffi::calculate_hash(&sanitized_name) % 16) as u16;
+        let pku = (hash::hash_str(&sanitized_name) % 16) as u16;
 
         let container = Arc::new(Container {
             name: sanitized_name.clone(),
             switcher: Arc::new(switcher),
             pku,
+            visit_lock: Mutex::new(()),
         });
         containers.insert(sanitized_name, Arc::clone(&container));
         Ok(container)
@@ -367,18 +1225,132 @@ impl BrowserDB {
     pub fn binarystore(&self) -> BinaryStoreTable<'_> {
         BinaryStoreTable { container: &self.default_container }
     }
+    pub fn kv(&self) -> KvStore<'_> {
+        KvStore { container: &self.default_container }
+    }
 
+    /// Switches the default container's mode. The mode is recorded in
+    /// `METADATA`, so the next `open` starts in it.
     pub fn set_mode(&self, mode: DatabaseMode) -> Result<(), Box<dyn std::error::Error>> {
-        self.default_container.set_mode(mode)
+        self.default_container.set_mode(mode)?;
+        if !self.is_in_memory() {
+            DatabaseMetadata { mode, config: self.config.ext_config.clone() }.store(&self.base_path)?;
+        }
+        Ok(())
+    }
+
+    /// Per-table, per-level SSTable counts, sizes and key ranges of the
+    /// default container.
+    pub fn lsm_report(&self) -> Result<LsmReport, Box<dyn std::error::Error>> {
+        self.default_container.lsm_report()
+    }
+
+    /// Verifies everything on disk without changing it: for every table of
+    /// every opened container, the default one included, that the
+    /// manifest matches the SSTables present, and in each SSTable the
+    /// header, footer, block and entry checksums, index order and bloom
+    /// filter. Problems are listed in the report, not returned as errors;
+    /// `Err` means the check itself could not run. See
+    /// [`BrowserDB::repair`] for recovering from them.
+    pub fn check_integrity(&self) -> Result<IntegrityReport, Box<dyn std::error::Error>> {
+        let mut report = IntegrityReport::default();
+        let containers: Vec<Arc<Container>> = self.containers.read().values().cloned().collect();
+        for container in containers {
+            report.merge(container.check_integrity()?);
+        }
+        Ok(report)
+    }
+
+    /// See [`Container::amplification`].
+    pub fn amplification(&self) -> Vec<(TableType, Amplification)> {
+        self.default_container.amplification()
+    }
+
+    /// See [`Container::privacy_wipe_domain`].
+    pub fn privacy_wipe_domain(&self, domain: &str) -> Result<PrivacyWipeResult, Box<dyn std::error::Error>> {
+        self.default_container.privacy_wipe_domain(domain)
+    }
+
+    /// See [`Container::count_privacy_wipe_domain`].
+    pub fn count_privacy_wipe_domain(&self, domain: &str) -> Result<PrivacyWipeResult, Box<dyn std::error::Error>> {
+        self.default_container.count_privacy_wipe_domain(domain)
+    }
+
+    /// See [`Container::open_warnings`].
+    pub fn open_warnings(&self) -> Vec<(TableType, OpenWarning)> {
+        self.default_container.open_warnings()
+    }
+
+    /// See [`Container::flush_table`].
+    pub fn flush_table(&self, table: TableType) -> Result<(), Box<dyn std::error::Error>> {
+        self.default_container.flush_table(table)
+    }
+
+    /// Flushes the fullest memtable, e.g. when the OS signals low memory.
+    /// See [`Container::flush_largest`].
+    pub fn flush_largest(&self) -> Result<Option<TableType>, Box<dyn std::error::Error>> {
+        self.default_container.flush_largest()
+    }
+
+    /// Registers a hook for flush, compaction, mode switch and WAL replay
+    /// events of every container. See [`EventBus`] for where hooks run.
+    pub fn on_event(&self, hook: EventHook) {
+        self.config.ext_config.events.subscribe(hook);
     }
 
     pub fn stats(&self) -> Result<DatabaseStats, Box<dyn std::error::Error>> {
         self.default_container.stats()
     }
+
+    /// See [`Container::metrics_prometheus`].
+    pub fn metrics_prometheus(&self) -> String {
+        self.default_container.metrics_prometheus()
+    }
+
+    pub fn heatmap_stats(&self) -> Result<HeatMapStats, Box<dyn std::error::Error>> {
+        self.default_container.heatmap_stats()
+    }
     
     pub fn wipe(&self) -> Result<(), Box<dyn std::error::Error>> {
         self.default_container.wipe()
     }
+
+    /// See [`Container::count_wipe`].
+    pub fn count_wipe(&self) -> Result<RemovalCount, Box<dyn std::error::Error>> {
+        self.default_container.count_wipe()
+    }
+
+    pub fn vacuum(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        self.default_container.vacuum()
+    }
+
+    /// See [`Container::major_compact`].
+    pub fn major_compact(&self, table: TableType) -> Result<CompactionReport, Box<dyn std::error::Error>> {
+        self.default_container.major_compact(table)
+    }
+
+    /// Brings the default container to a quiescent state, e.g. between the
+    /// write and read phases of a benchmark. See [`Container::checkpoint`].
+    pub fn checkpoint(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.default_container.checkpoint()
+    }
+
+    /// Opens a snapshot-isolated read view of the default container. Gets
+    /// through the returned handle see the database as it was at this call.
+    pub fn snapshot_read(&self) -> Result<ReadSnapshot, Box<dyn std::error::Error>> {
+        self.default_container.snapshot_read()
+    }
+}
+
+impl Drop for BrowserDB {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        if let Err(e) = self.save_ultra_snapshot() {
+            eprintln!("BrowserDB: failed to save Ultra snapshot: {}", e);
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -391,30 +1363,312 @@ pub struct DatabaseStats {
     pub localstore_entries: u64,
     pub settings_entries: u64,
     pub binarystore_entries: u64,
+    /// Bytes of table data held in memory: the memtables in Persistent
+    /// mode, every key and value in Ultra mode.
+    pub memory_usage_bytes: u64,
     pub memory_usage_mb: u64,
+    /// Size of the files in the container's directory, in either mode.
+    pub disk_usage_bytes: u64,
     pub disk_usage_mb: u64,
+    /// Share of SSTable reads served by the entry cache
+    /// (`lsm_tree.entry_cache_entries`). 0 before any SSTable read.
+    pub cache_hit_rate: f64,
+}
+
+/// Probes `keys` in sorted order, so lookups walk memtables and SSTable
+/// indexes monotonically, and returns the raw values in the caller's order.
+fn probe_sorted<F>(keys: &[Vec<u8>], mut probe: F) -> Vec<Option<Vec<u8>>>
+where F: FnMut(&[u8]) -> Option<Vec<u8>> {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    order.sort_by(|&a, &b| keys[a].cmp(&keys[b]));
+
+    let mut values = vec![None; keys.len()];
+    for idx in order {
+        values[idx] = probe(&keys[idx]);
+    }
+    values
+}
+
+fn deserialize_many<T: serde::de::DeserializeOwned>(codec: ValueCodec, table: TableType, keys: &[Vec<u8>], values: Vec<Option<Vec<u8>>>) -> Result<Vec<Option<T>>, BrowserDbError> {
+    let mut entries = Vec::with_capacity(values.len());
+    for (key, value) in keys.iter().zip(values) {
+        entries.push(match value {
+            Some(v) => Some(decode(codec, table, key, &v)?),
+            None => None,
+        });
+    }
+    Ok(entries)
+}
+
+/// Key namespace shared by the history indexes, which live in the history
+/// table itself. History primary keys are 16 bytes, so they can never carry
+/// this prefix.
+const HISTORY_INDEX_PREFIX: &[u8] = b"idx:history:";
+
+/// Title index postings are `idx:history:title:<word>:<url_hash>` with an
+/// empty value.
+const HISTORY_TITLE_INDEX_PREFIX: &[u8] = b"idx:history:title:";
+
+/// Recency index postings are `idx:history:recent:<u128::MAX - timestamp>
+/// <url_hash>`, both big-endian, with an empty value. Inverting the
+/// timestamp makes the forward merge scan walk history newest first.
+const HISTORY_RECENT_INDEX_PREFIX: &[u8] = b"idx:history:recent:";
+
+fn is_index_posting(key: &[u8]) -> bool {
+    key.starts_with(HISTORY_INDEX_PREFIX)
+}
+
+fn recent_posting_key(timestamp: u128, url_hash: u128) -> Vec<u8> {
+    let mut key = HISTORY_RECENT_INDEX_PREFIX.to_vec();
+    key.extend_from_slice(&keys::ordered_u128(u128::MAX - timestamp));
+    key.extend_from_slice(&keys::ordered_u128(url_hash));
+    key
+}
+
+/// `(timestamp, url_hash)` of a recency posting.
+fn parse_recent_posting(key: &[u8]) -> Option<RecentCursor> {
+    let rest = key.strip_prefix(HISTORY_RECENT_INDEX_PREFIX)?;
+    let inverted = keys::decode_ordered_u128(rest.get(..16)?)?;
+    let url_hash = keys::decode_ordered_u128(rest.get(16..32)?)?;
+    Some((u128::MAX - inverted, url_hash))
+}
+
+/// Splits `text` into lowercase alphanumeric words, sorted and deduplicated.
+fn tokenize_title(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+fn title_posting_prefix(word: &str) -> Vec<u8> {
+    let mut key = HISTORY_TITLE_INDEX_PREFIX.to_vec();
+    key.extend_from_slice(word.as_bytes());
+    key.push(b':');
+    key
+}
+
+fn title_posting_key(word: &str, primary_key: &[u8]) -> Vec<u8> {
+    let mut key = title_posting_prefix(word);
+    key.extend_from_slice(primary_key);
+    key
+}
+
+/// Header row of `HistoryTable::export_csv`.
+const HISTORY_CSV_HEADER: [&str; 5] = ["timestamp", "url_hash", "url", "title", "visit_count"];
+
+/// Quotes `field` if it contains a separator, quote or line break, doubling
+/// any quotes inside (RFC 4180).
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Reads one RFC 4180 record. Quoted fields may span lines. Returns `None`
+/// at end of input.
+fn read_csv_record<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<String>>> {
+    let mut record = String::new();
+    loop {
+        if reader.read_line(&mut record)? == 0 {
+            if record.is_empty() {
+                return Ok(None);
+            }
+            break;
+        }
+        // An odd number of quotes means a quoted field continues on the next line.
+        if record.matches('"').count().is_multiple_of(2) {
+            break;
+        }
+    }
+    let record = record.strip_suffix('\n').unwrap_or(&record);
+    let record = record.strip_suffix('\r').unwrap_or(record);
+
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    Ok(Some(fields))
+}
+
+/// `entry` after one more visit at `now`, titled `title` unless it is empty.
+fn bump_visit(mut entry: HistoryEntry, title: &str, now: u128) -> HistoryEntry {
+    entry.visit_count = entry.visit_count.saturating_add(1);
+    entry.timestamp = now;
+    if !title.is_empty() {
+        entry.title = title.to_string();
+    }
+    entry
 }
 
 pub struct HistoryTable<'a> { container: &'a Container }
 impl<'a> HistoryTable<'a> {
+    fn codec(&self) -> ValueCodec {
+        self.container.codec(TableType::History)
+    }
+
     pub fn count(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => Ok(pm.history.all_entries().len()),
-            CurrentMode::Ultra(um) => Ok(um.history.all_entries().len()),
+        match self.container.switcher.table(TableType::History) {
+            TableHandle::Persistent(t) => Ok(t.all_entries().iter().filter(|e| !is_index_posting(&e.key)).count()),
+            TableHandle::Ultra(t) => Ok(t.live_count(|k| !is_index_posting(k))),
         }
     }
 
     pub fn insert(&self, entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&entry.url_hash)?;
-        let value = bincode::serialize(entry)?;
+        let key = keys::history_key(entry.url_hash);
+        let value = self.codec().encode(entry)?;
         
-        match &*self.container.switcher.current_mode.read() {
+        let current_mode = self.container.switcher.current_mode.read();
+        self.reindex(&current_mode, &key, Some(entry))?;
+        match &*current_mode {
             CurrentMode::Persistent(pm) => pm.history.put(key, value)?,
             CurrentMode::Ultra(um) => um.history.put(key, value, 0),
         }
         Ok(())
     }
 
+    /// Inserts many entries in one write.
+    ///
+    /// In `CurrentMode::Persistent` this goes through `LSMTree::put_bulk`:
+    /// one WAL append and one lock per memtable shard, or, for batches
+    /// larger than the memtable, a single sorted SSTable written directly.
+    /// Index postings are written in the same batch. If a `url_hash`
+    /// appears more than once, the last entry wins.
+    pub fn insert_bulk(&self, entries: &[HistoryEntry]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut seen = HashSet::new();
+        let mut writes: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(entries.len() * 2);
+        let current_mode = self.container.switcher.current_mode.read();
+        for entry in entries.iter().rev() {
+            if !seen.insert(entry.url_hash) {
+                continue;
+            }
+            let key = keys::history_key(entry.url_hash);
+            for posting in self.stage_postings(&current_mode, &key, Some(entry))? {
+                writes.push((posting, Vec::new()));
+            }
+            writes.push((key, self.codec().encode(entry)?));
+        }
+
+        match &*current_mode {
+            CurrentMode::Persistent(pm) => pm.history.put_bulk(writes)?,
+            CurrentMode::Ultra(um) => um.history.put_many(writes),
+        }
+        Ok(())
+    }
+
+    /// Removes a history entry and its index postings.
+    pub fn delete(&self, url_hash: u128) -> Result<(), Box<dyn std::error::Error>> {
+        let key = keys::history_key(url_hash);
+        let current_mode = self.container.switcher.current_mode.read();
+        self.reindex(&current_mode, &key, None)?;
+        match &*current_mode {
+            CurrentMode::Persistent(pm) => pm.history.delete(key)?,
+            CurrentMode::Ultra(um) => um.history.delete(&key),
+        }
+        Ok(())
+    }
+
+    /// Brings the title and recency postings for `primary_key` in line with
+    /// `new_entry`, diffing against the entry currently stored. `None` drops
+    /// them all.
+    fn reindex(&self, mode: &CurrentMode, primary_key: &[u8], new_entry: Option<&HistoryEntry>) -> Result<(), Box<dyn std::error::Error>> {
+        for posting in self.stage_postings(mode, primary_key, new_entry)? {
+            match mode {
+                CurrentMode::Persistent(pm) => pm.history.put(posting, Vec::new())?,
+                CurrentMode::Ultra(um) => um.history.put(posting, Vec::new(), 0),
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the postings `new_entry` no longer has and returns the keys
+    /// of the ones it adds, for the caller to write.
+    fn stage_postings(&self, mode: &CurrentMode, primary_key: &[u8], new_entry: Option<&HistoryEntry>) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
+        let stored = match mode {
+            CurrentMode::Persistent(pm) => pm.history.get(primary_key).map(|e| e.value),
+            CurrentMode::Ultra(um) => um.history.get(primary_key),
+        };
+        let stored = stored.and_then(|v| self.codec().decode::<HistoryEntry>(&v).ok());
+        let old_words = stored.as_ref().map(|e| tokenize_title(&e.title)).unwrap_or_default();
+        let new_words = new_entry.map(|e| tokenize_title(&e.title)).unwrap_or_default();
+        let old_recent = stored.map(|e| recent_posting_key(e.timestamp, e.url_hash));
+        let new_recent = new_entry.map(|e| recent_posting_key(e.timestamp, e.url_hash));
+
+        let stale = old_words.iter()
+            .filter(|w| new_words.binary_search(w).is_err())
+            .map(|word| title_posting_key(word, primary_key))
+            .chain(old_recent.clone().filter(|k| new_recent.as_ref() != Some(k)));
+        for posting in stale {
+            match mode {
+                CurrentMode::Persistent(pm) => pm.history.delete(posting)?,
+                CurrentMode::Ultra(um) => um.history.delete(&posting),
+            }
+        }
+        Ok(new_words.iter()
+            .filter(|w| old_words.binary_search(w).is_err())
+            .map(|word| title_posting_key(word, primary_key))
+            .chain(new_recent.filter(|k| old_recent.as_ref() != Some(k)))
+            .collect())
+    }
+
+    /// Records a navigation to `url`: bumps `visit_count` and moves
+    /// `timestamp` to now, or creates the entry with a count of 1. The
+    /// stored title is kept when `title` is empty. Visits to the same
+    /// container are serialized, so concurrent ones are all counted.
+    /// Returns the entry as written.
+    pub fn record_visit(&self, url: &str, title: &str) -> Result<HistoryEntry, Box<dyn std::error::Error>> {
+        let _visit = self.container.visit_lock.lock();
+        let url_hash = hash::hash_str(url);
+        let now = self.container.switcher.config.ext_config.clock.now_ms() as u128;
+
+        let entry = match self.get(url_hash)? {
+            Some(entry) => bump_visit(entry, title, now),
+            None => HistoryEntry {
+                timestamp: now,
+                url: url.to_string(),
+                url_hash,
+                title: title.to_string(),
+                visit_count: 1,
+            },
+        };
+        self.insert(&entry)?;
+        Ok(entry)
+    }
+
+    /// `record_visit` for a page already in history, by its `url_hash`:
+    /// bumps `visit_count`, moves `timestamp` to now and takes `title` if
+    /// it is not empty, serialized with other visits so none is lost.
+    /// Returns the entry as written, or `None` if there is no entry for
+    /// `url_hash` to count the visit against.
+    pub fn increment_visit(&self, url_hash: u128, title: &str) -> Result<Option<HistoryEntry>, Box<dyn std::error::Error>> {
+        let _visit = self.container.visit_lock.lock();
+        let now = self.container.switcher.config.ext_config.clock.now_ms() as u128;
+        let Some(entry) = self.get(url_hash)? else {
+            return Ok(None);
+        };
+        let entry = bump_visit(entry, title, now);
+        self.insert(&entry)?;
+        Ok(Some(entry))
+    }
+
     /// Inserts a history entry with a Time-To-Live.
     ///
     /// In `CurrentMode::Persistent`, the entry's expiry is stored and
@@ -422,16 +1676,15 @@ impl<'a> HistoryTable<'a> {
     /// In `CurrentMode::Ultra`, expiry is enforced lazily on read; a
     /// purge pass is triggered after the write to reclaim memory.
     pub fn insert_with_ttl(&self, entry: &HistoryEntry, ttl_ms: u64) -> Result<(), Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&entry.url_hash)?;
-        let value = bincode::serialize(entry)?;
+        let key = keys::history_key(entry.url_hash);
+        let value = self.codec().encode(entry)?;
 
-        match &*self.container.switcher.current_mode.read() {
+        let current_mode = self.container.switcher.current_mode.read();
+        self.reindex(&current_mode, &key, Some(entry))?;
+        match &*current_mode {
             CurrentMode::Persistent(pm) => pm.history.put_with_ttl(key, value, ttl_ms)?,
             CurrentMode::Ultra(um) => {
-                let expires_at = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64 + ttl_ms;
+                let expires_at = um.history.clock.now_ms() + ttl_ms;
                 um.history.put(key, value, expires_at);
                 um.purge_expired_all();
             }
@@ -440,7 +1693,7 @@ impl<'a> HistoryTable<'a> {
     }
 
     pub fn increment(&self, url_hash: u128, delta: i64) -> Result<(), Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&url_hash)?;
+        let key = keys::history_key(url_hash);
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => pm.history.increment(key, delta)?,
             CurrentMode::Ultra(um) => um.history.increment(&key, delta),
@@ -449,20 +1702,43 @@ impl<'a> HistoryTable<'a> {
     }
     
     pub fn get(&self, url_hash: u128) -> Result<Option<HistoryEntry>, Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&url_hash)?;
-        let value_opt = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => pm.history.get(&key).map(|e| e.value),
-            CurrentMode::Ultra(um) => um.history.get(&key),
+        let key = keys::history_key_bytes(url_hash);
+        let value_opt = match self.container.switcher.table(TableType::History) {
+            TableHandle::Persistent(t) => t.get(&key).map(|e| e.value),
+            TableHandle::Ultra(t) => t.get(&key),
         };
         
         if let Some(value) = value_opt {
-            let entry = bincode::deserialize(&value)?;
+            let entry = decode(self.codec(), TableType::History, &key, &value)?;
             Ok(Some(entry))
         } else {
             Ok(None)
         }
     }
 
+    /// Looks up many entries through a single table handle. Results are
+    /// returned in the same order as `url_hashes`.
+    pub fn get_many(&self, url_hashes: &[u128]) -> Result<Vec<Option<HistoryEntry>>, Box<dyn std::error::Error>> {
+        let keys: Vec<Vec<u8>> = url_hashes.iter().map(|&h| keys::history_key(h)).collect();
+        let values = match self.container.switcher.table(TableType::History) {
+            TableHandle::Persistent(t) => probe_sorted(&keys, |k| t.get(k).map(|e| e.value)),
+            TableHandle::Ultra(t) => probe_sorted(&keys, |k| t.get(k)),
+        };
+        Ok(deserialize_many(self.codec(), TableType::History, &keys, values)?)
+    }
+
+    /// How often `url_hash` has been read lately: `"hot"`, `"warm"` or
+    /// `"cold"` (see [`heat_thresholds`]). Untracked keys, and every key in
+    /// `CurrentMode::Ultra`, are `"cold"`.
+    pub fn heat_category(&self, url_hash: u128) -> &'static str {
+        let key = keys::history_key_bytes(url_hash);
+        let heat = match self.container.switcher.table(TableType::History) {
+            TableHandle::Persistent(t) => t.inner.heat_tracker.get_heat(&key),
+            TableHandle::Ultra(_) => 0,
+        };
+        heat_thresholds::categorize_heat(heat)
+    }
+
     /// Search the history table for entries whose `url` or `title` contain
     /// `query` (case-insensitive substring), ranked by "hotness":
     ///
@@ -474,73 +1750,539 @@ impl<'a> HistoryTable<'a> {
     pub fn hot_search(&self, query: &str, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
         let needle = query.to_lowercase();
 
-        let entries: Vec<(Vec<u8>, Vec<u8>)> = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => pm.history
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = match self.container.switcher.table(TableType::History) {
+            TableHandle::Persistent(t) => t
                 .all_entries()
                 .into_iter()
                 .map(|e| (e.key, e.value))
                 .collect(),
-            CurrentMode::Ultra(um) => um.history.all_entries(),
+            TableHandle::Ultra(t) => t.all_entries(),
+        };
+
+        let mut matched: Vec<HistoryEntry> = Vec::new();
+        for (key, value) in entries.into_iter().filter(|(k, _)| !is_index_posting(k)) {
+            if let Some(entry) = decode_or_skip::<HistoryEntry>(self.codec(), TableType::History, &key, &value) {
+                if needle.is_empty()
+                    || entry.url.to_lowercase().contains(&needle)
+                    || entry.title.to_lowercase().contains(&needle)
+                {
+                    matched.push(entry);
+                }
+            }
+        }
+
+        matched.sort_by(|a, b| {
+            b.visit_count
+                .cmp(&a.visit_count)
+                .then(b.timestamp.cmp(&a.timestamp))
+        });
+
+        matched.truncate(limit);
+        Ok(matched)
+    }
+
+    /// Address-bar search: entries whose `url` or `title` contain `query`
+    /// (case-insensitive substring), ranked by "frecency", a blend of visit
+    /// count, recency and heat weighted by `BrowserDBConfig::frecency` (see
+    /// [`FrecencyConfig`](crate::core::config::FrecencyConfig)). Ties go to
+    /// the more recent visit.
+    ///
+    /// Returns at most `limit` entries. An empty `query` ranks every entry.
+    /// Heat is only tracked in Persistent mode; in Ultra mode it is 0.
+    pub fn search_frecency(&self, query: &str, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        let config = &self.container.switcher.config.ext_config;
+        let now = config.clock.now_ms() as f64;
+        let needle = query.to_lowercase();
+
+        let table = self.container.switcher.table(TableType::History);
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = match &table {
+            TableHandle::Persistent(t) => t
+                .all_entries()
+                .into_iter()
+                .map(|e| (e.key, e.value))
+                .collect(),
+            TableHandle::Ultra(t) => t.all_entries(),
+        };
+
+        let mut scored: Vec<(f64, HistoryEntry)> = Vec::new();
+        for (key, value) in entries.into_iter().filter(|(k, _)| !is_index_posting(k)) {
+            let Some(entry) = decode_or_skip::<HistoryEntry>(self.codec(), TableType::History, &key, &value) else { continue };
+            if !needle.is_empty()
+                && !entry.url.to_lowercase().contains(&needle)
+                && !entry.title.to_lowercase().contains(&needle)
+            {
+                continue;
+            }
+            let heat = match &table {
+                TableHandle::Persistent(t) => t.inner.heat_tracker.get_heat(&key),
+                TableHandle::Ultra(_) => 0,
+            };
+            let score = config.frecency.score(entry.visit_count, now - entry.timestamp as f64, heat);
+            scored.push((score, entry));
+        }
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.timestamp.cmp(&a.1.timestamp)));
+        Ok(scored.into_iter().take(limit).map(|(_, entry)| entry).collect())
+    }
+
+    /// Full-text search over page titles. The query is tokenized the same
+    /// way titles are, the posting lists of its words are intersected, and
+    /// the matching entries are ranked like `hot_search`: `visit_count`
+    /// first, then recency.
+    ///
+    /// Only pages whose title contains every query word are returned. An
+    /// empty query matches nothing.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        let words = tokenize_title(query);
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let table = self.container.switcher.table(TableType::History);
+        let mut postings: Vec<Vec<Vec<u8>>> = Vec::with_capacity(words.len());
+        for word in &words {
+            let prefix = title_posting_prefix(word);
+            let mut keys: Vec<Vec<u8>> = match &table {
+                TableHandle::Persistent(t) => t
+                    .scan_prefix(&prefix)
+                    .into_iter()
+                    .map(|e| e.key[prefix.len()..].to_vec())
+                    .collect(),
+                TableHandle::Ultra(t) => t
+                    .all_entries()
+                    .into_iter()
+                    .filter(|(k, _)| k.starts_with(&prefix))
+                    .map(|(k, _)| k[prefix.len()..].to_vec())
+                    .collect(),
+            };
+            if keys.is_empty() {
+                return Ok(Vec::new());
+            }
+            keys.sort();
+            postings.push(keys);
+        }
+
+        // Intersect starting from the shortest list.
+        postings.sort_by_key(|p| p.len());
+        let (shortest, rest) = postings.split_first().ok_or("no posting lists")?;
+        let candidates: Vec<Vec<u8>> = shortest
+            .iter()
+            .filter(|k| rest.iter().all(|p| p.binary_search(k).is_ok()))
+            .cloned()
+            .collect();
+
+        let values = match &table {
+            TableHandle::Persistent(t) => probe_sorted(&candidates, |k| t.get(k).map(|e| e.value)),
+            TableHandle::Ultra(t) => probe_sorted(&candidates, |k| t.get(k)),
+        };
+        // Postings of expired entries linger until the next rewrite, so
+        // re-check each title against the query.
+        let mut matched: Vec<HistoryEntry> = candidates.iter()
+            .zip(values)
+            .filter_map(|(k, v)| decode_or_skip(self.codec(), TableType::History, k, &v?))
+            .filter(|e: &HistoryEntry| {
+                let title_words = tokenize_title(&e.title);
+                words.iter().all(|w| title_words.binary_search(w).is_ok())
+            })
+            .collect();
+
+        matched.sort_by(|a, b| {
+            b.visit_count
+                .cmp(&a.visit_count)
+                .then(b.timestamp.cmp(&a.timestamp))
+        });
+        matched.truncate(limit);
+        Ok(matched)
+    }
+
+    /// Returns up to `limit` entries in `url_hash` order, starting strictly after
+    /// `after` (or from the beginning), and the cursor to pass as
+    /// `after` for the next page. The cursor is `None` once the table is
+    /// exhausted.
+    ///
+    /// Key order is the order of the encoded `url_hash` bytes, not numeric
+    /// order. In `CurrentMode::Persistent` every page seeks straight to the
+    /// cursor through the ordered merge scan instead of skipping an offset.
+    pub fn scan_page(&self, after: Option<u128>, limit: usize) -> Result<(Vec<HistoryEntry>, Option<u128>), Box<dyn std::error::Error>> {
+        let start = match after {
+            Some(url_hash) => keys::history_key(url_hash),
+            None => Vec::new(),
+        };
+        let is_after = |key: &[u8]| after.is_none() || key > start.as_slice();
+
+        // One entry past the page tells whether another page follows.
+        let mut page: Vec<(Vec<u8>, HistoryEntry)> = Vec::with_capacity(limit + 1);
+        match self.container.switcher.table(TableType::History) {
+            TableHandle::Persistent(t) => {
+                for kv in t.streaming_iter_from(&start) {
+                    let mut kv = kv?;
+                    if !is_after(&kv.key) || is_index_posting(&kv.key) {
+                        continue;
+                    }
+                    t.resolve_blob(&mut kv);
+                    if let Some(entry) = decode_or_skip::<HistoryEntry>(self.codec(), TableType::History, &kv.key, &kv.value) {
+                        page.push((kv.key, entry));
+                        if page.len() > limit { break; }
+                    }
+                }
+            }
+            TableHandle::Ultra(t) => {
+                let mut entries: Vec<(Vec<u8>, Vec<u8>)> = t.all_entries().into_iter()
+                    .filter(|(k, _)| is_after(k) && !is_index_posting(k))
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                for (key, value) in entries {
+                    if let Some(entry) = decode_or_skip::<HistoryEntry>(self.codec(), TableType::History, &key, &value) {
+                        page.push((key, entry));
+                        if page.len() > limit { break; }
+                    }
+                }
+            }
+        }
+
+        let has_more = page.len() > limit;
+        page.truncate(limit);
+        let cursor = match page.last() {
+            Some((key, _)) if has_more => Some(keys::decode_history_key(key).ok_or("malformed history key")?),
+            _ => None,
+        };
+        Ok((page.into_iter().map(|(_, entry)| entry).collect(), cursor))
+    }
+
+    /// Like [`HistoryTable::scan_page`], but newest first: entries in
+    /// descending `timestamp` order (ties in ascending `url_hash` order),
+    /// starting strictly after the `(timestamp, url_hash)` cursor.
+    ///
+    /// Primary keys are hashes and carry no time order, so this walks the
+    /// recency index instead. Entries written before that index existed are
+    /// not listed until they are written again.
+    pub fn scan_page_rev(&self, after: Option<RecentCursor>, limit: usize) -> Result<(Vec<HistoryEntry>, Option<RecentCursor>), Box<dyn std::error::Error>> {
+        let table = self.container.switcher.table(TableType::History);
+        let mut start = match after {
+            Some((timestamp, url_hash)) => recent_posting_key(timestamp, url_hash),
+            None => HISTORY_RECENT_INDEX_PREFIX.to_vec(),
+        };
+        let mut exclusive = after.is_some();
+
+        // One entry past the page tells whether another page follows.
+        let mut page: Vec<(RecentCursor, HistoryEntry)> = Vec::with_capacity(limit + 1);
+        while page.len() <= limit {
+            // Postings are read in batches and resolved once the merge
+            // iterator, which holds memtable read guards, is dropped.
+            let wanted = limit + 1 - page.len();
+            let in_range = |key: &[u8]| key.starts_with(HISTORY_RECENT_INDEX_PREFIX) && !(exclusive && key == start.as_slice());
+            let postings: Vec<Vec<u8>> = match &table {
+                TableHandle::Persistent(t) => {
+                    let mut keys = Vec::with_capacity(wanted);
+                    for kv in t.streaming_iter_from(&start) {
+                        let kv = kv?;
+                        if !kv.key.starts_with(HISTORY_RECENT_INDEX_PREFIX) {
+                            break;
+                        }
+                        if in_range(&kv.key) {
+                            keys.push(kv.key);
+                            if keys.len() == wanted { break; }
+                        }
+                    }
+                    keys
+                }
+                TableHandle::Ultra(t) => {
+                    let mut keys: Vec<Vec<u8>> = t.all_entries().into_iter()
+                        .map(|(k, _)| k)
+                        .filter(|k| k.as_slice() >= start.as_slice() && in_range(k))
+                        .collect();
+                    keys.sort();
+                    keys.truncate(wanted);
+                    keys
+                }
+            };
+            let Some(last) = postings.last().cloned() else { break };
+            let exhausted = postings.len() < wanted;
+
+            let cursors: Vec<RecentCursor> = postings.iter().filter_map(|k| parse_recent_posting(k)).collect();
+            let keys: Vec<Vec<u8>> = cursors.iter().map(|&(_, url_hash)| keys::history_key(url_hash)).collect();
+            let values = match &table {
+                TableHandle::Persistent(t) => probe_sorted(&keys, |k| t.get(k).map(|e| e.value)),
+                TableHandle::Ultra(t) => probe_sorted(&keys, |k| t.get(k)),
+            };
+            // Postings of expired entries linger until the next rewrite.
+            for ((cursor, key), value) in cursors.into_iter().zip(&keys).zip(values) {
+                let entry = value.and_then(|v| decode_or_skip::<HistoryEntry>(self.codec(), TableType::History, key, &v));
+                if let Some(entry) = entry.filter(|e| e.timestamp == cursor.0) {
+                    page.push((cursor, entry));
+                }
+            }
+            if exhausted {
+                break;
+            }
+            start = last;
+            exclusive = true;
+        }
+
+        let has_more = page.len() > limit;
+        page.truncate(limit);
+        let cursor = match page.last() {
+            Some((cursor, _)) if has_more => Some(*cursor),
+            _ => None,
         };
+        Ok((page.into_iter().map(|(_, entry)| entry).collect(), cursor))
+    }
+
+    /// Iterates the whole table newest first, fetching pages through
+    /// [`HistoryTable::scan_page_rev`].
+    pub fn iter_rev(&self) -> HistoryRevIter<'a> {
+        HistoryRevIter {
+            history: HistoryTable { container: self.container },
+            page: Vec::new().into_iter(),
+            cursor: None,
+            done: false,
+        }
+    }
+
+    /// Writes the table as CSV, one `timestamp,url_hash,url,title,visit_count`
+    /// row per entry after a header row, in the same key order as
+    /// [`HistoryTable::scan_page`]. Streams page by page, so the table is
+    /// never loaded at once. Returns the number of entries written.
+    pub fn export_csv(&self, writer: impl Write) -> Result<usize, Box<dyn std::error::Error>> {
+        const PAGE: usize = 1024;
+        let mut writer = BufWriter::new(writer);
+        writeln!(writer, "{}", HISTORY_CSV_HEADER.join(","))?;
+
+        let mut written = 0;
+        let mut cursor = None;
+        loop {
+            let (page, next) = self.scan_page(cursor, PAGE)?;
+            for entry in &page {
+                writeln!(
+                    writer,
+                    "{},{},{},{},{}",
+                    entry.timestamp,
+                    entry.url_hash,
+                    csv_field(&entry.url),
+                    csv_field(&entry.title),
+                    entry.visit_count,
+                )?;
+            }
+            written += page.len();
+            match next {
+                Some(_) => cursor = next,
+                None => break,
+            }
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Reads CSV written by [`HistoryTable::export_csv`] and inserts it
+    /// through [`HistoryTable::insert_bulk`], replacing entries with the same
+    /// `url_hash`. Fails on a missing header or malformed row before
+    /// anything from that chunk is written. Returns the number of rows read.
+    pub fn import_csv(&self, reader: impl Read) -> Result<usize, Box<dyn std::error::Error>> {
+        const CHUNK: usize = 10_000;
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut reader = BufReader::new(reader);
+
+        match read_csv_record(&mut reader)? {
+            Some(header) if header == HISTORY_CSV_HEADER => {}
+            _ => return Err(invalid(format!("expected CSV header {}", HISTORY_CSV_HEADER.join(","))).into()),
+        }
 
-        let mut matched: Vec<HistoryEntry> = Vec::new();
-        for (_key, value) in entries {
-            if let Ok(entry) = bincode::deserialize::<HistoryEntry>(&value) {
-                if needle.is_empty()
-                    || entry.url.to_lowercase().contains(&needle)
-                    || entry.title.to_lowercase().contains(&needle)
-                {
-                    matched.push(entry);
-                }
+        let mut imported = 0;
+        let mut chunk = Vec::with_capacity(CHUNK);
+        let mut row = 1;
+        while let Some(fields) = read_csv_record(&mut reader)? {
+            row += 1;
+            if fields.len() == 1 && fields[0].is_empty() {
+                continue;
+            }
+            let [timestamp, url_hash, url, title, visit_count]: [String; 5] = fields
+                .try_into()
+                .map_err(|f: Vec<String>| invalid(format!("row {}: expected 5 fields, found {}", row, f.len())))?;
+            let number = |name: &str, e: std::num::ParseIntError| invalid(format!("row {}: bad {}: {}", row, name, e));
+            chunk.push(HistoryEntry {
+                timestamp: timestamp.parse().map_err(|e| number("timestamp", e))?,
+                url_hash: url_hash.parse().map_err(|e| number("url_hash", e))?,
+                url,
+                title,
+                visit_count: visit_count.parse().map_err(|e| number("visit_count", e))?,
+            });
+            if chunk.len() == CHUNK {
+                self.insert_bulk(&chunk)?;
+                imported += chunk.len();
+                chunk.clear();
             }
         }
+        if !chunk.is_empty() {
+            self.insert_bulk(&chunk)?;
+            imported += chunk.len();
+        }
+        Ok(imported)
+    }
 
-        matched.sort_by(|a, b| {
-            b.visit_count
-                .cmp(&a.visit_count)
-                .then(b.timestamp.cmp(&a.timestamp))
-        });
+    /// Tombstones every history entry whose `timestamp` is older than
+    /// `cutoff_ms` (UNIX milliseconds) and returns the number removed.
+    ///
+    /// In `CurrentMode::Persistent` the candidates come from the ordered
+    /// merge scan, so entries already flushed to SSTables are covered too.
+    pub fn prune_older_than(&self, cutoff_ms: u64) -> Result<u64, Box<dyn std::error::Error>> {
+        let current_mode = self.container.switcher.current_mode.read();
+        let mut stale: Vec<(Vec<u8>, HistoryEntry)> = Vec::new();
+        match &*current_mode {
+            CurrentMode::Persistent(pm) => {
+                // Collect first: the merge iterator holds memtable read guards,
+                // so deletes must wait until it is dropped.
+                for kv in pm.history.streaming_iter(&[]) {
+                    let mut kv = kv?;
+                    if is_index_posting(&kv.key) {
+                        continue;
+                    }
+                    pm.history.resolve_blob(&mut kv);
+                    if let Some(entry) = decode_or_skip::<HistoryEntry>(self.codec(), TableType::History, &kv.key, &kv.value) {
+                        if entry.timestamp < cutoff_ms as u128 {
+                            stale.push((kv.key, entry));
+                        }
+                    }
+                }
+            }
+            CurrentMode::Ultra(um) => {
+                for (key, value) in um.history.all_entries().into_iter().filter(|(k, _)| !is_index_posting(k)) {
+                    if let Some(entry) = decode_or_skip::<HistoryEntry>(self.codec(), TableType::History, &key, &value) {
+                        if entry.timestamp < cutoff_ms as u128 {
+                            stale.push((key, entry));
+                        }
+                    }
+                }
+            }
+        }
 
-        matched.truncate(limit);
-        Ok(matched)
+        let count = stale.len() as u64;
+        for (key, entry) in stale {
+            Self::drop_postings(&current_mode, &key, &entry)?;
+            match &*current_mode {
+                CurrentMode::Persistent(pm) => pm.history.delete(key)?,
+                CurrentMode::Ultra(um) => um.history.delete(&key),
+            }
+        }
+        Ok(count)
     }
 
+    /// Removes every entry whose URL contains `domain`. Returns how many
+    /// were removed.
     pub fn wipe_domain(&self, domain: &str) -> Result<usize, Box<dyn std::error::Error>> {
         let current_mode = self.container.switcher.current_mode.read();
-        let all_entries: Vec<(Vec<u8>, Vec<u8>)> = match &*current_mode {
+        let matches = self.domain_entries(&current_mode, domain);
+        let count = matches.len();
+        for (key, _, entry) in matches {
+            Self::drop_postings(&current_mode, &key, &entry)?;
+            match &*current_mode {
+                CurrentMode::Persistent(pm) => pm.history.delete(key)?,
+                CurrentMode::Ultra(um) => um.history.delete(&key),
+            }
+        }
+        Ok(count)
+    }
+
+    /// What [`HistoryTable::wipe_domain`] would remove, without removing it.
+    /// `bytes` leaves out the entries' title index postings.
+    pub fn count_domain(&self, domain: &str) -> Result<RemovalCount, Box<dyn std::error::Error>> {
+        let mut count = RemovalCount::default();
+        for (key, value_len, _) in self.domain_entries(&self.container.switcher.current_mode.read(), domain) {
+            count.add(&key, value_len);
+        }
+        Ok(count)
+    }
+
+    /// Entries whose URL contains `domain`, with the size of their values.
+    fn domain_entries(&self, mode: &CurrentMode, domain: &str) -> Vec<(Vec<u8>, usize, HistoryEntry)> {
+        let all_entries: Vec<(Vec<u8>, Vec<u8>)> = match mode {
             CurrentMode::Persistent(pm) => {
                 pm.history.all_entries().into_iter().map(|e| (e.key, e.value)).collect()
             },
             CurrentMode::Ultra(um) => um.history.all_entries(),
         };
 
-        let mut count = 0;
+        let mut matches = Vec::new();
         for (key, value) in all_entries {
-            let entry: HistoryEntry = bincode::deserialize(&value)?;
+            if is_index_posting(&key) {
+                continue;
+            }
+            let Some(entry) = decode_or_skip::<HistoryEntry>(self.codec(), TableType::History, &key, &value) else { continue };
             if entry.url.contains(domain) {
-                match &*current_mode {
-                    CurrentMode::Persistent(pm) => pm.history.delete(key)?,
-                    CurrentMode::Ultra(um) => um.history.delete(&key),
-                }
-                count += 1;
+                matches.push((key, value.len(), entry));
+            }
+        }
+        matches
+    }
+
+    fn drop_postings(mode: &CurrentMode, primary_key: &[u8], entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let postings = tokenize_title(&entry.title)
+            .into_iter()
+            .map(|word| title_posting_key(&word, primary_key))
+            .chain(std::iter::once(recent_posting_key(entry.timestamp, entry.url_hash)));
+        for posting in postings {
+            match mode {
+                CurrentMode::Persistent(pm) => pm.history.delete(posting)?,
+                CurrentMode::Ultra(um) => um.history.delete(&posting),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `(timestamp, url_hash)` of the last entry of a
+/// [`HistoryTable::scan_page_rev`] page.
+pub type RecentCursor = (u128, u128);
+
+/// Newest-first iterator returned by [`HistoryTable::iter_rev`].
+pub struct HistoryRevIter<'a> {
+    history: HistoryTable<'a>,
+    page: std::vec::IntoIter<HistoryEntry>,
+    cursor: Option<RecentCursor>,
+    done: bool,
+}
+
+impl<'a> Iterator for HistoryRevIter<'a> {
+    type Item = Result<HistoryEntry, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        const PAGE: usize = 256;
+        if let Some(entry) = self.page.next() {
+            return Some(Ok(entry));
+        }
+        if self.done {
+            return None;
+        }
+        match self.history.scan_page_rev(self.cursor, PAGE) {
+            Ok((page, next)) => {
+                self.done = next.is_none();
+                self.cursor = next;
+                self.page = page.into_iter();
+                self.page.next().map(Ok)
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
             }
         }
-        Ok(count)
     }
 }
 
 pub struct BookmarksTable<'a> { container: &'a Container }
 impl<'a> BookmarksTable<'a> {
+    fn codec(&self) -> ValueCodec {
+        self.container.codec(TableType::Bookmarks)
+    }
+
     pub fn count(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => Ok(pm.bookmarks.all_entries().len()),
-            CurrentMode::Ultra(um) => Ok(um.bookmarks.entry_count.load(std::sync::atomic::Ordering::SeqCst)),
+        match self.container.switcher.table(TableType::Bookmarks) {
+            TableHandle::Persistent(t) => Ok(t.all_entries().len()),
+            TableHandle::Ultra(t) => Ok(t.live_count(|_| true)),
         }
     }
 
     pub fn insert(&self, entry: &BookmarkEntry) -> Result<(), Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&entry.url_hash)?;
-        let value = bincode::serialize(entry)?;
+        let key = keys::bookmark_key(entry.url_hash);
+        let value = self.codec().encode(entry)?;
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => pm.bookmarks.put(key, value)?,
             CurrentMode::Ultra(um) => um.bookmarks.put(key, value, 0),
@@ -549,7 +2291,7 @@ impl<'a> BookmarksTable<'a> {
     }
 
     pub fn delete(&self, url_hash: u128) -> Result<(), Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&url_hash)?;
+        let key = keys::bookmark_key(url_hash);
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => pm.bookmarks.delete(key)?,
             CurrentMode::Ultra(um) => um.bookmarks.delete(&key),
@@ -558,17 +2300,16 @@ impl<'a> BookmarksTable<'a> {
     }
 
     pub fn get_all(&self) -> Result<Vec<BookmarkEntry>, Box<dyn std::error::Error>> {
-        let current_mode = self.container.switcher.current_mode.read();
-        let all_entries: Vec<(Vec<u8>, Vec<u8>)> = match &*current_mode {
-            CurrentMode::Persistent(pm) => {
-                pm.bookmarks.all_entries().into_iter().map(|e| (e.key, e.value)).collect()
+        let all_entries: Vec<(Vec<u8>, Vec<u8>)> = match self.container.switcher.table(TableType::Bookmarks) {
+            TableHandle::Persistent(t) => {
+                t.all_entries().into_iter().map(|e| (e.key, e.value)).collect()
             }
-            CurrentMode::Ultra(um) => um.bookmarks.all_entries(),
+            TableHandle::Ultra(t) => t.all_entries(),
         };
 
         let mut bookmarks = Vec::with_capacity(all_entries.len());
-        for (_key, value) in all_entries {
-            if let Ok(entry) = bincode::deserialize::<BookmarkEntry>(&value) {
+        for (key, value) in all_entries {
+            if let Some(entry) = decode_or_skip::<BookmarkEntry>(self.codec(), TableType::Bookmarks, &key, &value) {
                 bookmarks.push(entry);
             }
         }
@@ -576,18 +2317,33 @@ impl<'a> BookmarksTable<'a> {
     }
 }
 
+/// RFC 6265 path-match: `request_path` equals `cookie_path` or continues it
+/// at a `/`. An empty cookie path counts as `/`.
+fn cookie_path_matches(cookie_path: &str, request_path: &str) -> bool {
+    let cookie_path = if cookie_path.is_empty() { "/" } else { cookie_path };
+    let request_path = if request_path.is_empty() { "/" } else { request_path };
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
 pub struct CookiesTable<'a> { container: &'a Container }
 impl<'a> CookiesTable<'a> {
+    fn codec(&self) -> ValueCodec {
+        self.container.codec(TableType::Cookies)
+    }
+
     pub fn count(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => Ok(pm.cookies.all_entries().len()),
-            CurrentMode::Ultra(um) => Ok(um.cookies.entry_count.load(std::sync::atomic::Ordering::SeqCst)),
+        match self.container.switcher.table(TableType::Cookies) {
+            TableHandle::Persistent(t) => Ok(t.all_entries().len()),
+            TableHandle::Ultra(t) => Ok(t.live_count(|_| true)),
         }
     }
 
     pub fn insert(&self, entry: &CookieEntry) -> Result<(), Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&(entry.domain_hash, &entry.name))?;
-        let value = bincode::serialize(entry)?;
+        let key = keys::cookie_key(entry.domain_hash, &entry.name);
+        let value = self.codec().encode(entry)?;
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => pm.cookies.put(key, value)?,
             CurrentMode::Ultra(um) => um.cookies.put(key, value, 0),
@@ -596,7 +2352,7 @@ impl<'a> CookiesTable<'a> {
     }
 
     pub fn delete(&self, domain_hash: u128, name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&(domain_hash, name))?;
+        let key = keys::cookie_key(domain_hash, name);
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => pm.cookies.delete(key)?,
             CurrentMode::Ultra(um) => um.cookies.delete(&key),
@@ -605,54 +2361,171 @@ impl<'a> CookiesTable<'a> {
     }
 
     pub fn get(&self, domain_hash: u128, name: &str) -> Result<Option<CookieEntry>, Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&(domain_hash, name))?;
-        let value_opt = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => pm.cookies.get(&key).map(|e| e.value),
-            CurrentMode::Ultra(um) => um.cookies.get(&key),
+        let key = keys::cookie_key(domain_hash, name);
+        let value_opt = match self.container.switcher.table(TableType::Cookies) {
+            TableHandle::Persistent(t) => t.get(&key).map(|e| e.value),
+            TableHandle::Ultra(t) => t.get(&key),
         };
         if let Some(value) = value_opt {
-            let entry = bincode::deserialize(&value)?;
+            let entry = decode(self.codec(), TableType::Cookies, &key, &value)?;
             Ok(Some(entry))
         } else {
             Ok(None)
         }
     }
 
-    pub fn get_by_domain(&self, domain_hash: u128) -> Result<Vec<CookieEntry>, Box<dyn std::error::Error>> {
-        let prefix = bincode::serialize(&domain_hash)?;
-        let current_mode = self.container.switcher.current_mode.read();
-        let values: Vec<Vec<u8>> = match &*current_mode {
+    /// Looks up many `(domain_hash, name)` pairs through a single table
+    /// handle. Results are returned in the same order as `keys`.
+    pub fn get_many(&self, keys: &[(u128, &str)]) -> Result<Vec<Option<CookieEntry>>, Box<dyn std::error::Error>> {
+        let keys: Vec<Vec<u8>> = keys.iter().map(|&(h, name)| keys::cookie_key(h, name)).collect();
+        let values = match self.container.switcher.table(TableType::Cookies) {
+            TableHandle::Persistent(t) => probe_sorted(&keys, |k| t.get(k).map(|e| e.value)),
+            TableHandle::Ultra(t) => probe_sorted(&keys, |k| t.get(k)),
+        };
+        Ok(deserialize_many(self.codec(), TableType::Cookies, &keys, values)?)
+    }
+
+    /// Removes every cookie stored under `domain_hash`. Cookies of
+    /// subdomains have their own `domain_hash` and are kept. Returns how
+    /// many were removed.
+    pub fn clear_domain(&self, domain_hash: u128) -> Result<usize, Box<dyn std::error::Error>> {
+        let prefix = keys::cookie_prefix(domain_hash);
+        match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => {
-                pm.cookies.scan_prefix(&prefix).into_iter().map(|e| e.value).collect()
+                let entries = pm.cookies.scan_prefix(&prefix);
+                let count = entries.len();
+                for entry in entries {
+                    pm.cookies.delete(entry.key)?;
+                }
+                Ok(count)
             },
             CurrentMode::Ultra(um) => {
-                um.cookies.all_entries().into_iter()
+                let keys: Vec<Vec<u8>> = um.cookies.all_entries().into_iter()
+                    .map(|(k, _)| k)
+                    .filter(|k| k.starts_with(&prefix))
+                    .collect();
+                for key in &keys {
+                    um.cookies.delete(key);
+                }
+                Ok(keys.len())
+            }
+        }
+    }
+
+    /// Writes `cookies`, for example another browser's cookie jar being
+    /// merged in. Without `dedupe` every cookie is written, replacing any
+    /// stored cookie with the same `(domain_hash, name)`. With `dedupe` the
+    /// cookie that expires last wins among those sharing a `(domain_hash,
+    /// name)`, stored or imported: a session cookie (`expiry` 0) loses to
+    /// any with an expiry, and on a tie the newer write, the import or the
+    /// later of two imported cookies, wins. Cookies identical to the stored
+    /// one are skipped rather than rewritten.
+    pub fn import_cookies(&self, cookies: &[CookieEntry], dedupe: bool) -> Result<ImportResult, Box<dyn std::error::Error>> {
+        let mut result = ImportResult::default();
+        if !dedupe {
+            for cookie in cookies {
+                self.insert(cookie)?;
+            }
+            result.imported = cookies.len();
+            return Ok(result);
+        }
+
+        let mut winners: HashMap<(u128, &str), &CookieEntry> = HashMap::new();
+        for cookie in cookies {
+            match winners.entry((cookie.domain_hash, cookie.name.as_str())) {
+                Entry::Occupied(mut e) => {
+                    result.skipped += 1;
+                    if cookie.expiry >= e.get().expiry {
+                        e.insert(cookie);
+                    }
+                }
+                Entry::Vacant(e) => {
+                    e.insert(cookie);
+                }
+            }
+        }
+        let (keys, winners): (Vec<(u128, &str)>, Vec<&CookieEntry>) = winners.into_iter().unzip();
+        for (cookie, stored) in winners.into_iter().zip(self.get_many(&keys)?) {
+            match stored {
+                Some(stored) if stored.expiry > cookie.expiry || stored == *cookie => {
+                    result.skipped += 1;
+                    continue;
+                }
+                Some(_) => result.overwritten += 1,
+                None => {}
+            }
+            self.insert(cookie)?;
+            result.imported += 1;
+        }
+        Ok(result)
+    }
+
+    /// What [`CookiesTable::clear_domain`] would remove, without removing it.
+    pub fn count_domain(&self, domain_hash: u128) -> Result<RemovalCount, Box<dyn std::error::Error>> {
+        Ok(count_prefix(self.container.switcher.table(TableType::Cookies), &keys::cookie_prefix(domain_hash)))
+    }
+
+    pub fn get_by_domain(&self, domain_hash: u128) -> Result<Vec<CookieEntry>, Box<dyn std::error::Error>> {
+        let prefix = keys::cookie_prefix(domain_hash);
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = match self.container.switcher.table(TableType::Cookies) {
+            TableHandle::Persistent(t) => {
+                t.scan_prefix(&prefix).into_iter().map(|e| (e.key, e.value)).collect()
+            },
+            TableHandle::Ultra(t) => {
+                t.all_entries().into_iter()
                     .filter(|(k, _)| k.starts_with(&prefix))
-                    .map(|(_, v)| v)
                     .collect()
             }
         };
-        let mut cookies = Vec::with_capacity(values.len());
-        for value in values {
-            if let Ok(entry) = bincode::deserialize::<CookieEntry>(&value) {
+        let mut cookies = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            if let Some(entry) = decode_or_skip::<CookieEntry>(self.codec(), TableType::Cookies, &key, &value) {
                 cookies.push(entry);
             }
         }
         Ok(cookies)
     }
 
+    /// Cookies to attach to a request for `host` and `path`, matched as in
+    /// RFC 6265: the host's own cookies plus domain cookies (a `domain`
+    /// starting with `.`) of each parent domain, with a matching path and
+    /// not yet expired. Expects `domain_hash` to be [`hash::domain_hash`] of
+    /// the cookie's domain.
+    pub fn match_for_host(&self, host: &str, path: &str) -> Result<Vec<CookieEntry>, Box<dyn std::error::Error>> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let now_secs = self.container.switcher.config.ext_config.clock.now_ms() / 1000;
+
+        // An IP address has no parent domains.
+        let mut candidates = vec![host.as_str()];
+        if host.parse::<std::net::IpAddr>().is_err() {
+            candidates.extend(host.match_indices('.').map(|(i, _)| &host[i + 1..]).filter(|d| !d.is_empty()));
+        }
+
+        let mut cookies = Vec::new();
+        for (depth, domain) in candidates.into_iter().enumerate() {
+            for cookie in self.get_by_domain(hash::domain_hash(domain))? {
+                let host_only = !cookie.domain.starts_with('.');
+                let expired = cookie.expiry != 0 && cookie.expiry <= now_secs;
+                if (depth > 0 && host_only) || expired || !cookie_path_matches(&cookie.path, path) {
+                    continue;
+                }
+                cookies.push(cookie);
+            }
+        }
+        Ok(cookies)
+    }
+
     pub fn get_all(&self) -> Result<Vec<CookieEntry>, Box<dyn std::error::Error>> {
-        let current_mode = self.container.switcher.current_mode.read();
-        let all_entries: Vec<(Vec<u8>, Vec<u8>)> = match &*current_mode {
-            CurrentMode::Persistent(pm) => {
-                pm.cookies.all_entries().into_iter().map(|e| (e.key, e.value)).collect()
+        let all_entries: Vec<(Vec<u8>, Vec<u8>)> = match self.container.switcher.table(TableType::Cookies) {
+            TableHandle::Persistent(t) => {
+                t.all_entries().into_iter().map(|e| (e.key, e.value)).collect()
             }
-            CurrentMode::Ultra(um) => um.cookies.all_entries(),
+            TableHandle::Ultra(t) => t.all_entries(),
         };
 
         let mut cookies = Vec::with_capacity(all_entries.len());
-        for (_key, value) in all_entries {
-            if let Ok(entry) = bincode::deserialize::<CookieEntry>(&value) {
+        for (key, value) in all_entries {
+            if let Some(entry) = decode_or_skip::<CookieEntry>(self.codec(), TableType::Cookies, &key, &value) {
                 cookies.push(entry);
             }
         }
@@ -660,18 +2533,83 @@ impl<'a> CookiesTable<'a> {
     }
 }
 
+/// Bodies written by [`CacheTable::insert_streaming`] are split into
+/// chunks kept next to the entry, which itself stores an empty body. The
+/// chunk list is `chunk:cache:<url_hash BE>` holding `(chunks, bytes)`, and
+/// chunk `n` is that key followed by `n` as a big-endian `u32`.
+const CACHE_CHUNK_PREFIX: &[u8] = b"chunk:cache:";
+
+/// Size of one streamed body chunk. Above the LSM's blob threshold, so
+/// chunks go to the blob log and the memtable only holds pointers.
+const CACHE_CHUNK_SIZE: usize = 256 * 1024;
+
+fn cache_chunk_list_key(url_hash: u128) -> Vec<u8> {
+    let mut key = CACHE_CHUNK_PREFIX.to_vec();
+    key.extend_from_slice(&keys::ordered_u128(url_hash));
+    key
+}
+
+fn cache_chunk_key(url_hash: u128, chunk: u32) -> Vec<u8> {
+    let mut key = cache_chunk_list_key(url_hash);
+    key.extend_from_slice(&chunk.to_be_bytes());
+    key
+}
+
+fn is_cache_chunk(key: &[u8]) -> bool {
+    key.starts_with(CACHE_CHUNK_PREFIX)
+}
+
+/// Replaces the empty body of a streamed entry with its chunks, read
+/// through `get`.
+fn fill_chunked_body(codec: ValueCodec, entry: &mut CacheEntry, get: impl Fn(&[u8]) -> Option<Vec<u8>>) -> Result<(), Box<dyn std::error::Error>> {
+    if !entry.body.is_empty() {
+        return Ok(());
+    }
+    let list_key = cache_chunk_list_key(entry.url_hash);
+    let Some(list) = get(&list_key) else { return Ok(()) };
+    let (chunks, bytes): (u32, u64) = decode(codec, TableType::Cache, &list_key, &list)?;
+    let mut body = Vec::with_capacity(bytes as usize);
+    for chunk in 0..chunks {
+        let data = get(&cache_chunk_key(entry.url_hash, chunk))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "cache body chunk missing"))?;
+        body.extend_from_slice(&data);
+    }
+    entry.body = body;
+    Ok(())
+}
+
 pub struct CacheTable<'a> { container: &'a Container }
 impl<'a> CacheTable<'a> {
+    fn codec(&self) -> ValueCodec {
+        self.container.codec(TableType::Cache)
+    }
+
     pub fn count(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => Ok(pm.cache.all_entries().len()),
-            CurrentMode::Ultra(um) => Ok(um.cache.entry_count.load(std::sync::atomic::Ordering::SeqCst)),
+        match self.container.switcher.table(TableType::Cache) {
+            TableHandle::Persistent(t) => Ok(t.all_entries().iter().filter(|e| !is_cache_chunk(&e.key)).count()),
+            TableHandle::Ultra(t) => Ok(t.live_count(|k| !is_cache_chunk(k))),
         }
     }
 
     pub fn insert(&self, entry: &CacheEntry) -> Result<(), Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&entry.url_hash)?;
-        let value = bincode::serialize(entry)?;
+        let key = keys::cache_key(entry.url_hash);
+        let value = self.codec().encode(entry)?;
+        self.drop_chunks(entry.url_hash, 0)?;
+        match &*self.container.switcher.current_mode.read() {
+            CurrentMode::Persistent(pm) => pm.cache.put(key, value)?,
+            CurrentMode::Ultra(um) => um.cache.put(key, value, 0),
+        }
+        Ok(())
+    }
+
+    fn get_raw(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.container.switcher.table(TableType::Cache) {
+            TableHandle::Persistent(t) => t.get(key).map(|e| e.value),
+            TableHandle::Ultra(t) => t.get(key),
+        }
+    }
+
+    fn put_raw(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => pm.cache.put(key, value)?,
             CurrentMode::Ultra(um) => um.cache.put(key, value, 0),
@@ -679,41 +2617,332 @@ impl<'a> CacheTable<'a> {
         Ok(())
     }
 
+    fn delete_raw(&self, key: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        match &*self.container.switcher.current_mode.read() {
+            CurrentMode::Persistent(pm) => pm.cache.delete(key)?,
+            CurrentMode::Ultra(um) => um.cache.delete(&key),
+        }
+        Ok(())
+    }
+
+    /// Deletes the chunks of a streamed body from `keep` on, and the chunk
+    /// list with them when `keep` is 0.
+    fn drop_chunks(&self, url_hash: u128, keep: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let list_key = cache_chunk_list_key(url_hash);
+        let Some(list) = self.get_raw(&list_key) else { return Ok(()) };
+        let (chunks, _): (u32, u64) = decode(self.codec(), TableType::Cache, &list_key, &list)?;
+        for chunk in keep..chunks {
+            self.delete_raw(cache_chunk_key(url_hash, chunk))?;
+        }
+        if keep == 0 {
+            self.delete_raw(list_key)?;
+        }
+        Ok(())
+    }
+
+    /// Stores an entry whose body is read from `body` in fixed-size chunks,
+    /// so a large download never has to be held in memory whole. Returns
+    /// the body length. [`CacheTable::get`] still returns the full body;
+    /// [`CacheTable::get_streaming`] reads it back chunk by chunk.
+    pub fn insert_streaming(
+        &self,
+        url_hash: u128,
+        headers: &str,
+        etag: &str,
+        last_modified: u128,
+        mut body: impl Read,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut buf = vec![0u8; CACHE_CHUNK_SIZE];
+        let mut chunks = 0u32;
+        let mut bytes = 0u64;
+        loop {
+            let mut filled = 0;
+            while filled < buf.len() {
+                match body.read(&mut buf[filled..]) {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            if filled == 0 {
+                break;
+            }
+            self.put_raw(cache_chunk_key(url_hash, chunks), buf[..filled].to_vec())?;
+            chunks += 1;
+            bytes += filled as u64;
+            if filled < buf.len() {
+                break;
+            }
+        }
+
+        // Chunks past the new end belong to an older, longer body.
+        self.drop_chunks(url_hash, chunks)?;
+        self.put_raw(cache_chunk_list_key(url_hash), self.codec().encode(&(chunks, bytes))?)?;
+        let entry = CacheEntry {
+            url_hash,
+            headers: headers.to_string(),
+            body: Vec::new(),
+            etag: etag.to_string(),
+            last_modified,
+            expires_at: 0,
+        };
+        self.put_raw(keys::cache_key(url_hash), self.codec().encode(&entry)?)?;
+        Ok(bytes)
+    }
+
+    /// The entry for `url_hash` with an empty `body`, and a reader over the
+    /// body that loads one chunk at a time. Works for entries stored with
+    /// [`CacheTable::insert`] too, whose body is already in memory.
+    pub fn get_streaming(&self, url_hash: u128) -> Result<Option<(CacheEntry, CacheBodyReader<'a>)>, Box<dyn std::error::Error>> {
+        let Some(mut entry) = self.get_live(url_hash)? else { return Ok(None) };
+        let list_key = cache_chunk_list_key(url_hash);
+        let chunks = match self.get_raw(&list_key) {
+            Some(list) if entry.body.is_empty() => decode::<(u32, u64)>(self.codec(), TableType::Cache, &list_key, &list)?.0,
+            _ => 0,
+        };
+        let body = std::mem::take(&mut entry.body);
+        let reader = CacheBodyReader {
+            table: CacheTable { container: self.container },
+            url_hash,
+            next_chunk: 0,
+            chunks,
+            current: io::Cursor::new(body),
+        };
+        Ok(Some((entry, reader)))
+    }
+
+    /// The entry for `url_hash`, or `None` if there is none or it is stale.
+    /// A stale entry is deleted on the way, streamed body included.
     pub fn get(&self, url_hash: u128) -> Result<Option<CacheEntry>, Box<dyn std::error::Error>> {
-        let key = bincode::serialize(&url_hash)?;
-        let value_opt = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => pm.cache.get(&key).map(|e| e.value),
-            CurrentMode::Ultra(um) => um.cache.get(&key),
+        let Some(mut entry) = self.get_live(url_hash)? else { return Ok(None) };
+        fill_chunked_body(self.codec(), &mut entry, |k| self.get_raw(k))?;
+        Ok(Some(entry))
+    }
+
+    /// The stored entry for `url_hash` with its body not yet filled in,
+    /// deleting it instead if it is stale.
+    fn get_live(&self, url_hash: u128) -> Result<Option<CacheEntry>, Box<dyn std::error::Error>> {
+        let key = keys::cache_key_bytes(url_hash);
+        let Some(value) = self.get_raw(&key) else { return Ok(None) };
+        let entry = located(TableType::Cache, &key, CacheEntry::decode(self.codec(), &value))?;
+        if entry.is_expired(self.now()) {
+            self.remove_entry(url_hash)?;
+            return Ok(None);
+        }
+        Ok(Some(entry))
+    }
+
+    fn now(&self) -> u64 {
+        self.container.switcher.config.ext_config.clock.now_ms()
+    }
+
+    fn remove_entry(&self, url_hash: u128) -> Result<(), Box<dyn std::error::Error>> {
+        self.drop_chunks(url_hash, 0)?;
+        self.delete_raw(keys::cache_key(url_hash))
+    }
+
+    /// Deletes every entry that is stale at `now`, in milliseconds since
+    /// the epoch, and returns how many there were. Entries are otherwise
+    /// only dropped when read, so this keeps responses nobody asks for
+    /// again from taking up space.
+    pub fn purge_stale(&self, now: u64) -> Result<usize, Box<dyn std::error::Error>> {
+        let stale = self.stale_entries(now);
+        for (entry, _) in &stale {
+            self.remove_entry(entry.url_hash)?;
+        }
+        Ok(stale.len())
+    }
+
+    /// What [`CacheTable::purge_stale`] would remove at `now`, without
+    /// removing it.
+    pub fn count_stale(&self, now: u64) -> Result<RemovalCount, Box<dyn std::error::Error>> {
+        let stale = self.stale_entries(now);
+        Ok(RemovalCount { entries: stale.len(), bytes: stale.iter().map(|(_, size)| size).sum() })
+    }
+
+    /// Entries stale at `now`, with the size of their keys and values.
+    fn stale_entries(&self, now: u64) -> Vec<(CacheEntry, u64)> {
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = match self.container.switcher.table(TableType::Cache) {
+            TableHandle::Persistent(t) => t.all_entries().into_iter().filter(|e| !is_cache_chunk(&e.key)).map(|e| (e.key, e.value)).collect(),
+            TableHandle::Ultra(t) => t.all_entries().into_iter().filter(|(k, _)| !is_cache_chunk(k)).collect(),
+        };
+        let mut stale = Vec::new();
+        for (key, value) in entries {
+            let Some(entry) = skip_undecodable(located(TableType::Cache, &key, CacheEntry::decode(self.codec(), &value))) else { continue };
+            if entry.is_expired(now) {
+                stale.push((entry, (key.len() + value.len()) as u64));
+            }
+        }
+        stale
+    }
+
+    /// Size of the cache's keys and values, streamed chunks included, as
+    /// [`Container::count_wipe`] counts bytes.
+    pub fn size_bytes(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(count_prefix(self.container.switcher.table(TableType::Cache), &[]).bytes)
+    }
+
+    /// Evicts entries, coldest first, until the cache's [`size_bytes`]
+    /// is at most `max_bytes`, and returns the bytes freed. Entries are
+    /// ranked by read heat, then by when they were last read, then by
+    /// `last_modified`; Ultra mode tracks no heat, so only the last applies
+    /// there. The space on disk comes back as compaction drops the deleted
+    /// values.
+    ///
+    /// [`size_bytes`]: CacheTable::size_bytes
+    pub fn evict_to_budget(&self, max_bytes: u64) -> Result<u64, Box<dyn std::error::Error>> {
+        let table = self.container.switcher.table(TableType::Cache);
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = match &table {
+            TableHandle::Persistent(t) => t.all_entries().into_iter().map(|e| (e.key, e.value)).collect(),
+            TableHandle::Ultra(t) => t.all_entries(),
         };
 
-        if let Some(value) = value_opt {
-            let entry = bincode::deserialize(&value)?;
-            Ok(Some(entry))
-        } else {
-            Ok(None)
+        let mut total = 0;
+        let mut sizes: HashMap<u128, u64> = HashMap::new();
+        let mut ranked = Vec::new();
+        for (key, value) in entries {
+            let size = (key.len() + value.len()) as u64;
+            total += size;
+            if is_cache_chunk(&key) {
+                let hash = key.get(CACHE_CHUNK_PREFIX.len()..CACHE_CHUNK_PREFIX.len() + 16).and_then(keys::decode_ordered_u128);
+                if let Some(hash) = hash {
+                    *sizes.entry(hash).or_default() += size;
+                }
+                continue;
+            }
+            let Some(entry) = skip_undecodable(located(TableType::Cache, &key, CacheEntry::decode(self.codec(), &value))) else { continue };
+            *sizes.entry(entry.url_hash).or_default() += size;
+            let (heat, last_read) = match &table {
+                TableHandle::Persistent(t) => (t.inner.heat_tracker.get_heat(&key), t.inner.heat_tracker.last_access(&key)),
+                TableHandle::Ultra(_) => (0, None),
+            };
+            ranked.push(((heat, last_read, entry.last_modified), entry.url_hash));
+        }
+
+        let mut freed = 0;
+        if total <= max_bytes {
+            return Ok(freed);
+        }
+        ranked.sort_unstable_by_key(|(rank, _)| *rank);
+        for (_, url_hash) in ranked {
+            if total - freed <= max_bytes {
+                break;
+            }
+            self.remove_entry(url_hash)?;
+            freed += sizes[&url_hash];
+        }
+        Ok(freed)
+    }
+
+    /// Calls `f` with the cached body of `url_hash` without copying it out
+    /// of storage, where it can: see `LSMTree::with_value`. Bodies large
+    /// enough to be blob-separated, or written with `insert_streaming`, are
+    /// read into memory first, as are all bodies under the JSON codec, which
+    /// stores them as arrays of numbers.
+    pub fn with_body<R>(&self, url_hash: u128, f: impl FnOnce(&[u8]) -> R) -> Result<Option<R>, Box<dyn std::error::Error>> {
+        if self.codec() != ValueCodec::Bincode || self.get_raw(&cache_chunk_list_key(url_hash)).is_some() {
+            return Ok(self.get(url_hash)?.map(|entry| f(&entry.body)));
+        }
+        let key = keys::cache_key_bytes(url_hash);
+        let now = self.now();
+        let read = |value: &[u8]| {
+            located(TableType::Cache, &key, CacheEntryRef::decode(self.codec(), value)).map(|entry| (entry.expires_at == 0 || entry.expires_at > now).then(|| f(entry.body)))
+        };
+        let result = match self.container.switcher.table(TableType::Cache) {
+            TableHandle::Persistent(t) => t.with_value(&key, read),
+            TableHandle::Ultra(t) => t.with_value(&key, read),
+        };
+        match result.transpose()? {
+            Some(None) => {
+                self.remove_entry(url_hash)?;
+                Ok(None)
+            }
+            found => Ok(found.flatten()),
+        }
+    }
+
+    /// Looks up many entries through a single table handle. Results are
+    /// returned in the same order as `url_hashes`.
+    pub fn get_many(&self, url_hashes: &[u128]) -> Result<Vec<Option<CacheEntry>>, Box<dyn std::error::Error>> {
+        let keys: Vec<Vec<u8>> = url_hashes.iter().map(|&h| keys::cache_key(h)).collect();
+        let values = match self.container.switcher.table(TableType::Cache) {
+            TableHandle::Persistent(t) => probe_sorted(&keys, |k| t.get(k).map(|e| e.value)),
+            TableHandle::Ultra(t) => probe_sorted(&keys, |k| t.get(k)),
+        };
+        let now = self.now();
+        let mut entries = Vec::with_capacity(values.len());
+        for (key, value) in keys.iter().zip(values) {
+            let entry = match value {
+                Some(v) => Some(located(TableType::Cache, key, CacheEntry::decode(self.codec(), &v))?),
+                None => None,
+            };
+            entries.push(match entry {
+                Some(entry) if entry.is_expired(now) => {
+                    self.remove_entry(entry.url_hash)?;
+                    None
+                }
+                Some(mut entry) => {
+                    fill_chunked_body(self.codec(), &mut entry, |k| self.get_raw(k))?;
+                    Some(entry)
+                }
+                None => None,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Reader over a cached body, from [`CacheTable::get_streaming`].
+pub struct CacheBodyReader<'a> {
+    table: CacheTable<'a>,
+    url_hash: u128,
+    next_chunk: u32,
+    chunks: u32,
+    current: io::Cursor<Vec<u8>>,
+}
+
+impl Read for CacheBodyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 || buf.is_empty() || self.next_chunk == self.chunks {
+                return Ok(n);
+            }
+            let chunk = self
+                .table
+                .get_raw(&cache_chunk_key(self.url_hash, self.next_chunk))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "cache body chunk missing"))?;
+            self.next_chunk += 1;
+            self.current = io::Cursor::new(chunk);
         }
     }
 }
 
 pub struct LocalStoreTable<'a> { container: &'a Container }
 impl<'a> LocalStoreTable<'a> {
+    fn codec(&self) -> ValueCodec {
+        self.container.codec(TableType::LocalStore)
+    }
+
     pub fn count(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => Ok(pm.localstore.all_entries().len()),
-            CurrentMode::Ultra(um) => Ok(um.localstore.entry_count.load(std::sync::atomic::Ordering::SeqCst)),
+        match self.container.switcher.table(TableType::LocalStore) {
+            TableHandle::Persistent(t) => Ok(t.all_entries().len()),
+            // Ultra mode keeps the value index in the table itself.
+            TableHandle::Ultra(t) => Ok(t.live_count(|k| !k.starts_with(b"idx:localstore:"))),
         }
     }
 
     pub fn insert(&self, entry: &LocalStoreEntry) -> Result<(), Box<dyn std::error::Error>> {
-        let primary_key = bincode::serialize(&(entry.origin_hash, &entry.key))?;
-        let value = bincode::serialize(entry)?;
+        let primary_key = keys::localstore_key(entry.origin_hash, &entry.key);
+        let value = self.codec().encode(entry)?;
 
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => pm.localstore.put(primary_key, value)?,
             CurrentMode::Ultra(um) => {
                 um.localstore.put(primary_key.clone(), value.clone(), 0);
                 // Ultra mode still needs manual indexing for now
-                if let Some(idx_key) = Self::extract_value_index(&primary_key, &value) {
+                if let Some(idx_key) = Self::extract_value_index(self.codec(), &primary_key, &value) {
                     um.localstore.put(idx_key, primary_key, 0);
                 }
             }
@@ -722,7 +2951,7 @@ impl<'a> LocalStoreTable<'a> {
     }
 
     pub fn remove(&self, origin_hash: u128, key: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let primary_key = bincode::serialize(&(origin_hash, key))?;
+        let primary_key = keys::localstore_key(origin_hash, key);
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => pm.localstore.delete(primary_key)?,
             CurrentMode::Ultra(um) => um.localstore.delete(&primary_key),
@@ -730,41 +2959,54 @@ impl<'a> LocalStoreTable<'a> {
         Ok(())
     }
 
-    pub fn clear_origin(&self, origin_hash: u128) -> Result<(), Box<dyn std::error::Error>> {
-        let prefix = bincode::serialize(&origin_hash)?;
+    /// Removes every key of `origin_hash`. Returns how many were removed.
+    pub fn clear_origin(&self, origin_hash: u128) -> Result<usize, Box<dyn std::error::Error>> {
+        let prefix = keys::localstore_prefix(origin_hash);
         match &*self.container.switcher.current_mode.read() {
             CurrentMode::Persistent(pm) => {
                 let entries = pm.localstore.scan_prefix(&prefix);
+                let count = entries.len();
                 for entry in entries {
                     pm.localstore.delete(entry.key)?;
                 }
+                Ok(count)
             },
             CurrentMode::Ultra(um) => {
-                let mut current = um.localstore.data.write();
-                current.retain(|k, _| !k.starts_with(&prefix));
+                let keys: Vec<Vec<u8>> = um.localstore.all_entries().into_iter()
+                    .map(|(k, _)| k)
+                    .filter(|k| k.starts_with(&prefix))
+                    .collect();
+                for key in &keys {
+                    um.localstore.delete(key);
+                }
+                Ok(keys.len())
             }
         }
-        Ok(())
     }
 
-    pub(crate) fn extract_value_index(_k: &[u8], v: &[u8]) -> Option<Vec<u8>> {
-        if let Ok(entry) = bincode::deserialize::<LocalStoreEntry>(v) {
+    /// What [`LocalStoreTable::clear_origin`] would remove, without removing it.
+    pub fn count_origin(&self, origin_hash: u128) -> Result<RemovalCount, Box<dyn std::error::Error>> {
+        Ok(count_prefix(self.container.switcher.table(TableType::LocalStore), &keys::localstore_prefix(origin_hash)))
+    }
+
+    pub(crate) fn extract_value_index(codec: ValueCodec, _k: &[u8], v: &[u8]) -> Option<Vec<u8>> {
+        if let Ok(entry) = codec.decode::<LocalStoreEntry>(v) {
             Some(format!("idx:localstore:value:{}:{}", entry.value, entry.key).into_bytes())
         } else {
             None
         }
     }
 
-    pub(crate) fn extract_key_index(_k: &[u8], v: &[u8]) -> Option<Vec<u8>> {
-        if let Ok(entry) = bincode::deserialize::<LocalStoreEntry>(v) {
+    pub(crate) fn extract_key_index(codec: ValueCodec, _k: &[u8], v: &[u8]) -> Option<Vec<u8>> {
+        if let Ok(entry) = codec.decode::<LocalStoreEntry>(v) {
             Some(format!("idx:localstore:key:{}:{}", entry.key, entry.value).into_bytes())
         } else {
             None
         }
     }
 
-    pub(crate) fn extract_origin_index(_k: &[u8], v: &[u8]) -> Option<Vec<u8>> {
-        if let Ok(entry) = bincode::deserialize::<LocalStoreEntry>(v) {
+    pub(crate) fn extract_origin_index(codec: ValueCodec, _k: &[u8], v: &[u8]) -> Option<Vec<u8>> {
+        if let Ok(entry) = codec.decode::<LocalStoreEntry>(v) {
             Some(format!("idx:localstore:origin:{}:{}:{}", entry.origin_hash, entry.key, entry.value).into_bytes())
         } else {
             None
@@ -792,8 +3034,8 @@ impl<'a> LocalStoreTable<'a> {
             }
         }
 
-        let primary_key = bincode::serialize(&(entry.origin_hash, &entry.key))?;
-        let value = bincode::serialize(entry)?;
+        let primary_key = keys::localstore_key(entry.origin_hash, &entry.key);
+        let value = self.codec().encode(entry)?;
 
         let allowed: Option<Vec<&str>> = if index_fields.is_empty() {
             None
@@ -813,9 +3055,9 @@ impl<'a> LocalStoreTable<'a> {
                 um.localstore.put(primary_key.clone(), value.clone(), 0);
                 for field in allowed.as_deref().unwrap_or(&["value"]) {
                     let idx_key = match *field {
-                        "value" => Self::extract_value_index(&primary_key, &value),
-                        "key" => Self::extract_key_index(&primary_key, &value),
-                        "origin_hash" => Self::extract_origin_index(&primary_key, &value),
+                        "value" => Self::extract_value_index(self.codec(), &primary_key, &value),
+                        "key" => Self::extract_key_index(self.codec(), &primary_key, &value),
+                        "origin_hash" => Self::extract_origin_index(self.codec(), &primary_key, &value),
                         _ => None,
                     };
                     if let Some(idx_key) = idx_key {
@@ -828,13 +3070,13 @@ impl<'a> LocalStoreTable<'a> {
     }
 
     pub fn get(&self, origin_hash: u128, key: &str) -> Result<Option<LocalStoreEntry>, Box<dyn std::error::Error>> {
-        let primary_key = bincode::serialize(&(origin_hash, key))?;
-        let value_opt = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => pm.localstore.get(&primary_key).map(|e| e.value),
-            CurrentMode::Ultra(um) => um.localstore.get(&primary_key),
+        let primary_key = keys::localstore_key(origin_hash, key);
+        let value_opt = match self.container.switcher.table(TableType::LocalStore) {
+            TableHandle::Persistent(t) => t.get(&primary_key).map(|e| e.value),
+            TableHandle::Ultra(t) => t.get(&primary_key),
         };
         if let Some(value) = value_opt {
-            let entry = bincode::deserialize::<LocalStoreEntry>(&value)?;
+            let entry = decode(self.codec(), TableType::LocalStore, &primary_key, &value)?;
             Ok(Some(entry))
         } else {
             Ok(None)
@@ -842,24 +3084,24 @@ impl<'a> LocalStoreTable<'a> {
     }
 
     pub fn get_by_origin(&self, origin_hash: u128) -> Result<Vec<LocalStoreEntry>, Box<dyn std::error::Error>> {
-        let prefix = bincode::serialize(&origin_hash)?;
+        let prefix = keys::localstore_prefix(origin_hash);
 
-        let values: Vec<Vec<u8>> = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => {
-                pm.localstore.scan_prefix(&prefix).into_iter().map(|e| e.value).collect()
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = match self.container.switcher.table(TableType::LocalStore) {
+            TableHandle::Persistent(t) => {
+                t.scan_prefix(&prefix).into_iter().map(|e| (e.key, e.value)).collect()
             },
-            CurrentMode::Ultra(um) => {
-                um.localstore.all_entries().into_iter()
+            TableHandle::Ultra(t) => {
+                t.all_entries().into_iter()
                     .filter(|(k, _)| k.starts_with(&prefix))
-                    .map(|(_, v)| v)
                     .collect()
             }
         };
 
         let mut results = Vec::new();
-        for value in values {
-            let entry: LocalStoreEntry = bincode::deserialize(&value)?;
-            results.push(entry);
+        for (key, value) in entries {
+            if let Some(entry) = decode_or_skip(self.codec(), TableType::LocalStore, &key, &value) {
+                results.push(entry);
+            }
         }
         Ok(results)
     }
@@ -912,27 +3154,27 @@ impl<'q, 'a> QueryBuilder<'q, 'a> {
     }
 
     pub fn execute(self) -> Result<Vec<LocalStoreEntry>, Box<dyn std::error::Error>> {
-        let current_mode = self.table.container.switcher.current_mode.read();
+        let handle = self.table.container.switcher.table(TableType::LocalStore);
 
         let mut results = Vec::new();
 
-        match &*current_mode {
-            CurrentMode::Persistent(pm) => {
+        match handle {
+            TableHandle::Persistent(t) => {
                 // Optimized path if we have value_eq and indices
                 if let Some(val) = &self.value_eq {
                     let idx_prefix = format!("idx:localstore:value:{}:", val).into_bytes();
 
                     // Use the native secondary index
-                    let index_tree = pm.localstore.inner.indices.iter().find(|i| i.name == "value");
+                    let index_tree = t.inner.indices.iter().find(|i| i.name == "value");
                     let idx_entries = if let Some(idx) = index_tree {
                         idx.tree.scan_prefix(&idx_prefix)
                     } else {
-                        pm.localstore.scan_prefix(&idx_prefix)
+                        t.scan_prefix(&idx_prefix)
                     };
 
                     for idx_kv in idx_entries {
-                        if let Some(primary_kv) = pm.localstore.get(&idx_kv.value) {
-                            if let Ok(entry) = bincode::deserialize::<LocalStoreEntry>(&primary_kv.value) {
+                        if let Some(primary_kv) = t.get(&idx_kv.value) {
+                            if let Some(entry) = decode_or_skip::<LocalStoreEntry>(self.table.codec(), TableType::LocalStore, &primary_kv.key, &primary_kv.value) {
                                 if self.filters.iter().all(|f| f(&entry)) {
                                     results.push(entry);
                                 }
@@ -944,15 +3186,12 @@ impl<'q, 'a> QueryBuilder<'q, 'a> {
                     }
                 } else {
                     // Standard predicate-based scan
-                    let kvs = pm.localstore.scan_with_predicate(&self.prefix, |kv| {
-                        if let Ok(entry) = bincode::deserialize::<LocalStoreEntry>(&kv.value) {
-                            self.filters.iter().all(|f| f(&entry))
-                        } else {
-                            false
-                        }
+                    let kvs = t.scan_with_predicate(&self.prefix, |kv| {
+                        decode_or_skip::<LocalStoreEntry>(self.table.codec(), TableType::LocalStore, &kv.key, &kv.value)
+                            .is_some_and(|entry| self.filters.iter().all(|f| f(&entry)))
                     });
                     for kv in kvs {
-                        if let Ok(entry) = bincode::deserialize::<LocalStoreEntry>(&kv.value) {
+                        if let Some(entry) = decode_or_skip::<LocalStoreEntry>(self.table.codec(), TableType::LocalStore, &kv.key, &kv.value) {
                             results.push(entry);
                         }
                         if let Some(l) = self.limit {
@@ -961,11 +3200,12 @@ impl<'q, 'a> QueryBuilder<'q, 'a> {
                     }
                 }
             },
-            CurrentMode::Ultra(um) => {
-                let all = um.localstore.all_entries();
+            TableHandle::Ultra(t) => {
+                let all = t.all_entries();
                 for (k, v) in all {
-                    if !k.starts_with(&self.prefix) { continue; }
-                    if let Ok(entry) = bincode::deserialize::<LocalStoreEntry>(&v) {
+                    // Ultra mode keeps the value index in the table itself.
+                    if !k.starts_with(&self.prefix) || k.starts_with(b"idx:localstore:") { continue; }
+                    if let Some(entry) = decode_or_skip::<LocalStoreEntry>(self.table.codec(), TableType::LocalStore, &k, &v) {
                         if self.filters.iter().all(|f| f(&entry)) {
                             if let Some(val) = &self.value_eq {
                                 if entry.value != *val { continue; }
@@ -987,9 +3227,9 @@ impl<'q, 'a> QueryBuilder<'q, 'a> {
 pub struct BinaryStoreTable<'a> { container: &'a Container }
 impl<'a> BinaryStoreTable<'a> {
     pub fn count(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => Ok(pm.binarystore.all_entries().len()),
-            CurrentMode::Ultra(um) => Ok(um.binarystore.entry_count.load(std::sync::atomic::Ordering::SeqCst)),
+        match self.container.switcher.table(TableType::BinaryStore) {
+            TableHandle::Persistent(t) => Ok(t.all_entries().len()),
+            TableHandle::Ultra(t) => Ok(t.live_count(|_| true)),
         }
     }
     pub fn put(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
@@ -1000,9 +3240,9 @@ impl<'a> BinaryStoreTable<'a> {
         Ok(())
     }
     pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
-        let value_opt = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => pm.binarystore.get(key).map(|e| e.value),
-            CurrentMode::Ultra(um) => um.binarystore.get(key),
+        let value_opt = match self.container.switcher.table(TableType::BinaryStore) {
+            TableHandle::Persistent(t) => t.get(key).map(|e| e.value),
+            TableHandle::Ultra(t) => t.get(key),
         };
         Ok(value_opt)
     }
@@ -1014,12 +3254,12 @@ impl<'a> BinaryStoreTable<'a> {
         Ok(())
     }
     pub fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Box<dyn std::error::Error>> {
-        let entries = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => {
-                pm.binarystore.scan_prefix(prefix).into_iter().map(|e| (e.key, e.value)).collect()
+        let entries = match self.container.switcher.table(TableType::BinaryStore) {
+            TableHandle::Persistent(t) => {
+                t.scan_prefix(prefix).into_iter().map(|e| (e.key, e.value)).collect()
             },
-            CurrentMode::Ultra(um) => {
-                um.binarystore.all_entries().into_iter()
+            TableHandle::Ultra(t) => {
+                t.all_entries().into_iter()
                     .filter(|(k, _)| k.starts_with(prefix))
                     .collect()
             }
@@ -1027,22 +3267,66 @@ impl<'a> BinaryStoreTable<'a> {
         Ok(entries)
     }
     pub fn all_entries(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Box<dyn std::error::Error>> {
-        let entries = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => {
-                pm.binarystore.all_entries().into_iter().map(|e| (e.key, e.value)).collect()
+        let entries = match self.container.switcher.table(TableType::BinaryStore) {
+            TableHandle::Persistent(t) => {
+                t.all_entries().into_iter().map(|e| (e.key, e.value)).collect()
             },
-            CurrentMode::Ultra(um) => um.binarystore.all_entries(),
+            TableHandle::Ultra(t) => t.all_entries(),
         };
         Ok(entries)
     }
 }
 
+/// Prefix of every [`KvStore`] key in the binary store.
+const KV_KEY_PREFIX: &[u8] = b"kv:";
+
+/// `(key, value)` pairs returned by [`KvStore::scan_prefix`].
+pub type KvPairs = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Schema-less byte storage for embedders, split into namespaces. Entries
+/// live in the binary store under a reserved `kv:` prefix followed by the
+/// length-prefixed namespace, so no namespace can read or shadow another,
+/// nor the typed tables. `binarystore().all_entries()` lists them too.
+pub struct KvStore<'a> { container: &'a Container }
+impl<'a> KvStore<'a> {
+    fn namespaced(namespace: &str, key: &[u8]) -> Vec<u8> {
+        let mut full = Vec::with_capacity(KV_KEY_PREFIX.len() + 4 + namespace.len() + key.len());
+        full.extend_from_slice(KV_KEY_PREFIX);
+        full.extend_from_slice(&(namespace.len() as u32).to_be_bytes());
+        full.extend_from_slice(namespace.as_bytes());
+        full.extend_from_slice(key);
+        full
+    }
+
+    pub fn put(&self, namespace: &str, key: &[u8], value: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.container.binarystore().put(Self::namespaced(namespace, key), value.to_vec())
+    }
+    pub fn get(&self, namespace: &str, key: &[u8]) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        self.container.binarystore().get(&Self::namespaced(namespace, key))
+    }
+    pub fn delete(&self, namespace: &str, key: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.container.binarystore().delete(&Self::namespaced(namespace, key))
+    }
+    /// Entries of `namespace` whose key starts with `prefix`, in key order,
+    /// with the namespace stripped from the keys.
+    pub fn scan_prefix(&self, namespace: &str, prefix: &[u8]) -> Result<KvPairs, Box<dyn std::error::Error>> {
+        let strip = Self::namespaced(namespace, &[]).len();
+        let mut entries: KvPairs = self.container.binarystore()
+            .scan_prefix(&Self::namespaced(namespace, prefix))?
+            .into_iter()
+            .map(|(k, v)| (k[strip..].to_vec(), v))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+}
+
 pub struct SettingsTable<'a> { container: &'a Container }
 impl<'a> SettingsTable<'a> {
     pub fn count(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => Ok(pm.settings.all_entries().len()),
-            CurrentMode::Ultra(um) => Ok(um.settings.entry_count.load(std::sync::atomic::Ordering::SeqCst)),
+        match self.container.switcher.table(TableType::Settings) {
+            TableHandle::Persistent(t) => Ok(t.all_entries().len()),
+            TableHandle::Ultra(t) => Ok(t.live_count(|_| true)),
         }
     }
     pub fn set(&self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -1057,9 +3341,9 @@ impl<'a> SettingsTable<'a> {
     
     pub fn get(&self, key: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
         let k = key.as_bytes();
-        let value_opt = match &*self.container.switcher.current_mode.read() {
-            CurrentMode::Persistent(pm) => pm.settings.get(k).map(|e| e.value),
-            CurrentMode::Ultra(um) => um.settings.get(k),
+        let value_opt = match self.container.switcher.table(TableType::Settings) {
+            TableHandle::Persistent(t) => t.get(k).map(|e| e.value),
+            TableHandle::Ultra(t) => t.get(k),
         };
         
         if let Some(v) = value_opt {
@@ -1068,4 +3352,57 @@ impl<'a> SettingsTable<'a> {
             Ok(None)
         }
     }
+
+    /// The value of `key`, first setting it to `default` if it is not set.
+    /// Of several callers racing to set it, one wins and all of them get
+    /// its value.
+    pub fn get_or_insert(&self, key: &str, default: &str) -> Result<String, Box<dyn std::error::Error>> {
+        loop {
+            if let Some(value) = self.get(key)? {
+                return Ok(value);
+            }
+            let k = key.as_bytes().to_vec();
+            let v = default.as_bytes().to_vec();
+            let inserted = match &*self.container.switcher.current_mode.read() {
+                CurrentMode::Persistent(pm) => pm.settings.compare_and_swap(k, None, v)?,
+                CurrentMode::Ultra(um) => um.settings.compare_and_swap(k, None, v),
+            };
+            if inserted {
+                return Ok(default.to_string());
+            }
+        }
+    }
+
+    /// All settings in key order.
+    pub fn list(&self) -> Result<Vec<SettingEntry>, Box<dyn std::error::Error>> {
+        let entries = match self.container.switcher.table(TableType::Settings) {
+            TableHandle::Persistent(t) => {
+                t.all_entries().into_iter().map(|e| (e.key, e.value)).collect()
+            },
+            TableHandle::Ultra(t) => {
+                let mut entries = t.all_entries();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                entries
+            }
+        };
+
+        let mut settings = Vec::with_capacity(entries.len());
+        for (k, v) in entries {
+            settings.push(SettingEntry { key: String::from_utf8(k)?, value: String::from_utf8(v)? });
+        }
+        Ok(settings)
+    }
+
+    /// Removes a setting. Returns `false` if it was not set.
+    pub fn delete(&self, key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.get(key)?.is_none() {
+            return Ok(false);
+        }
+        let k = key.as_bytes();
+        match &*self.container.switcher.current_mode.read() {
+            CurrentMode::Persistent(pm) => pm.settings.delete(k.to_vec())?,
+            CurrentMode::Ultra(um) => um.settings.delete(k),
+        }
+        Ok(true)
+    }
 }
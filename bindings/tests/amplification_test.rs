@@ -0,0 +1,89 @@
+use browserdb::core::format::TableType;
+use browserdb::{Amplification, BrowserDB, CacheEntry, CookieEntry, DatabaseMode};
+use tempfile::tempdir;
+
+mod common;
+
+fn cookie(domain_hash: u128, round: u32) -> CookieEntry {
+    CookieEntry {
+        domain_hash,
+        value: format!("round-{}", round),
+        ..common::cookie(&format!("site{}.example", domain_hash), "id")
+    }
+}
+
+fn cookies_amplification(db: &BrowserDB) -> Amplification {
+    db.amplification().into_iter().find(|(t, _)| *t == TableType::Cookies).unwrap().1
+}
+
+#[test]
+fn test_compaction_raises_write_amplification() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_eq!(cookies_amplification(&db), Amplification::default());
+    assert_eq!(cookies_amplification(&db).write_amplification(), 0.0);
+
+    for round in 0..3 {
+        for domain_hash in 0..300 {
+            db.cookies().insert(&cookie(domain_hash, round)).unwrap();
+        }
+        db.flush_table(TableType::Cookies).unwrap();
+    }
+    let flushed = cookies_amplification(&db);
+    assert!(flushed.user_bytes_written > 0);
+    // Every byte went to the WAL and then to an SSTable.
+    assert!(flushed.write_amplification() > 1.0, "{:?}", flushed);
+
+    db.major_compact(TableType::Cookies).unwrap();
+    let compacted = cookies_amplification(&db);
+    assert_eq!(compacted.user_bytes_written, flushed.user_bytes_written);
+    assert!(compacted.disk_bytes_written > flushed.disk_bytes_written);
+    assert!(compacted.write_amplification() > flushed.write_amplification());
+
+    for domain_hash in 0..50 {
+        assert_eq!(db.cookies().get(domain_hash, "id").unwrap().unwrap().value, "round-2");
+    }
+    let read = cookies_amplification(&db);
+    assert!(read.bytes_returned > 0);
+    // Each lookup reads a whole block for one entry.
+    assert!(read.read_amplification() > 1.0, "{:?}", read);
+
+    let metrics = db.metrics_prometheus();
+    assert!(metrics.contains("browserdb_write_amplification{table=\"cookies\"}"), "{}", metrics);
+    assert!(metrics.contains("browserdb_read_amplification{table=\"cookies\"}"));
+}
+
+#[test]
+fn test_no_amplification_report_in_ultra_mode() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    db.cookies().insert(&cookie(1, 0)).unwrap();
+    assert!(db.amplification().is_empty());
+}
+
+#[test]
+fn test_zero_copy_reads_count_towards_read_amplification() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for url_hash in 0..100 {
+        db.cache()
+            .insert(&CacheEntry {
+                url_hash,
+                headers: String::new(),
+                body: vec![b'x'; 200],
+                etag: String::new(),
+                last_modified: 0,
+                expires_at: 0,
+            })
+            .unwrap();
+    }
+    db.flush_table(TableType::Cache).unwrap();
+    let cache_amplification = |db: &BrowserDB| db.amplification().into_iter().find(|(t, _)| *t == TableType::Cache).unwrap().1;
+    let before = cache_amplification(&db);
+
+    assert_eq!(db.cache().with_body(42, |b| b.len()).unwrap(), Some(200));
+    let after = cache_amplification(&db);
+    assert!(after.disk_bytes_read > before.disk_bytes_read, "{:?}", after);
+    assert!(after.bytes_returned > before.bytes_returned, "{:?}", after);
+}
@@ -0,0 +1,70 @@
+use browserdb::core::format::TableType;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::open_tree;
+
+fn files_with_suffix(dir: &Path, suffix: &str) -> Vec<String> {
+    fs::read_dir(dir)
+        .unwrap()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|n| n.ends_with(suffix))
+        .collect()
+}
+
+#[test]
+fn test_create_leaves_no_tmp_file() {
+    let dir = tempdir().unwrap();
+    let tree = open_tree(dir.path(), TableType::Cookies);
+    tree.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+    tree.flush().unwrap();
+
+    assert_eq!(files_with_suffix(dir.path(), ".sst").len(), 1);
+    assert!(files_with_suffix(dir.path(), ".tmp").is_empty());
+}
+
+#[test]
+fn test_leftover_tmp_is_skipped_on_reopen() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = open_tree(dir.path(), TableType::Cookies);
+        tree.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+        tree.flush().unwrap();
+    }
+
+    // A crash mid-write: one truncated copy of a real table, one pure garbage.
+    let sst = dir.path().join(&files_with_suffix(dir.path(), ".sst")[0]);
+    let bytes = fs::read(&sst).unwrap();
+    fs::write(dir.path().join("cookies_0_1_1.sst.tmp"), &bytes[..bytes.len() / 2]).unwrap();
+    fs::write(dir.path().join("cookies_1_2_2.sst.tmp"), b"not an sstable").unwrap();
+
+    let tree = open_tree(dir.path(), TableType::Cookies);
+    assert_eq!(tree.get(b"k").unwrap().value, b"v".to_vec());
+    let loaded: usize = tree.inner.levels.iter().map(|l| l.read().len()).sum();
+    assert_eq!(loaded, 1);
+    assert!(files_with_suffix(dir.path(), ".tmp").is_empty());
+}
+
+#[test]
+fn test_reserved_name_is_dropped_on_reopen() {
+    let dir = tempdir().unwrap();
+
+    // A crash during the first flush, after its name was reserved but
+    // before the finished table was renamed over it.
+    fs::write(dir.path().join("cookies_0_1_1.sst"), b"").unwrap();
+    fs::write(dir.path().join("cookies_0_1_1.sst.tmp"), b"not finished").unwrap();
+
+    let tree = open_tree(dir.path(), TableType::Cookies);
+    assert!(tree.open_warnings().is_empty());
+    assert!(files_with_suffix(dir.path(), ".sst").is_empty());
+    assert!(files_with_suffix(dir.path(), ".tmp").is_empty());
+
+    tree.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+    tree.flush().unwrap();
+    drop(tree);
+    let tree = open_tree(dir.path(), TableType::Cookies);
+    assert_eq!(tree.get(b"k").unwrap().value, b"v".to_vec());
+}
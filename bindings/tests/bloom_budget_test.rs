@@ -0,0 +1,61 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::heatmap::BloomFilter;
+use browserdb::core::lsm_tree::LSMTree;
+use tempfile::tempdir;
+
+/// Flushes 2 000 keys into one table and returns the tree.
+fn cache_tree(dir: &std::path::Path, budget: usize) -> LSMTree {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.bloom_budget_bytes.cache = budget;
+    config.lsm_tree.memtable_max_ops = 0;
+    let tree = LSMTree::new(dir, TableType::Cache, 1024 * 1024, config).unwrap();
+    for i in 0..2_000u32 {
+        tree.put(format!("{:05}/entry", i).into_bytes(), vec![1]).unwrap();
+    }
+    tree.flush().unwrap();
+    tree
+}
+
+#[test]
+fn test_filter_stays_within_budget_and_reports_its_rate() {
+    let dir = tempdir().unwrap();
+    let unbounded = cache_tree(dir.path(), 0).level_report().remove(0);
+    assert!(unbounded.bloom_bytes > 4_096, "{} bytes", unbounded.bloom_bytes);
+    assert!(unbounded.bloom_fpr.unwrap() < 0.015, "{:?}", unbounded.bloom_fpr);
+
+    let dir = tempdir().unwrap();
+    let tree = cache_tree(dir.path(), 4_096);
+    let level0 = tree.level_report().remove(0);
+    assert_eq!(level0.sstables, 1);
+    assert!(level0.bloom_bytes <= 4_096, "{} bytes", level0.bloom_bytes);
+    let fpr = level0.bloom_fpr.unwrap();
+    assert!(fpr > 0.01 && fpr < 0.1, "{}", fpr);
+    assert!(tree.level_report()[1].bloom_fpr.is_none());
+
+    // A smaller filter only costs extra probes, never a missed key.
+    for i in 0..2_000u32 {
+        assert_eq!(tree.get(format!("{:05}/entry", i).as_bytes()).unwrap().value, vec![1]);
+    }
+}
+
+#[test]
+fn test_reported_rate_matches_observed_false_positives() {
+    let mut bloom = BloomFilter::with_budget(4_000, 0.01, 1_024);
+    assert_eq!(bloom.size_bytes(), 1_024);
+    for i in 0..4_000u32 {
+        bloom.add(format!("present{}", i).as_bytes());
+    }
+    let probes = 100_000;
+    let hits = (0..probes).filter(|i| bloom.might_contain(format!("absent{}", i).as_bytes())).count();
+    let observed = hits as f64 / probes as f64;
+    let reported = bloom.estimated_fpr();
+    assert!(reported > 0.1, "{}", reported);
+    assert!((observed - reported).abs() < reported * 0.25, "observed {}, reported {}", observed, reported);
+
+    // Enough budget leaves the filter as `new` would size it.
+    assert_eq!(
+        BloomFilter::with_budget(4_000, 0.01, 1 << 20).size_bytes(),
+        BloomFilter::new(4_000, 0.01).size_bytes()
+    );
+}
@@ -0,0 +1,60 @@
+use browserdb::core::config::{BrowserDBConfig, BLOOM_FPR};
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn flushed_bloom_size(dir: &Path, table_type: TableType, config: &BrowserDBConfig) -> usize {
+    let mut config = config.clone();
+    // All 1000 keys in one table, so the filter is sized for them.
+    config.lsm_tree.memtable_max_ops = 0;
+    let tree = LSMTree::new(dir, table_type, 64 * 1024 * 1024, config).unwrap();
+    for i in 0..1000u32 {
+        tree.put(format!("key{:05}", i).into_bytes(), b"v".to_vec()).unwrap();
+    }
+    tree.flush().unwrap();
+    let level0 = tree.inner.levels[0].read();
+    level0[0].bloom_filter.as_ref().unwrap().size_bytes()
+}
+
+#[test]
+fn test_per_table_fpr_changes_filter_size() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.bloom_fpr.cookies = 0.001;
+    config.lsm_tree.bloom_fpr.cache = 0.05;
+
+    let tight = flushed_bloom_size(dir.path(), TableType::Cookies, &config);
+    let loose = flushed_bloom_size(dir.path(), TableType::Cache, &config);
+    let default = flushed_bloom_size(dir.path(), TableType::History, &config);
+
+    // Bits per key scale with -ln(fpr): 6.9 at 0.1%, 4.6 at 1%, 3.0 at 5%.
+    assert!(tight > default && default > loose, "{} / {} / {}", tight, default, loose);
+    assert!(tight * 10 > loose * 20, "{} vs {}", tight, loose);
+}
+
+#[test]
+fn test_filter_size_survives_reopen() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.bloom_fpr.cookies = 0.001;
+    let written = flushed_bloom_size(dir.path(), TableType::Cookies, &config);
+
+    // The stored filter is used as-is, whatever the config says now.
+    let tree = LSMTree::new(dir.path(), TableType::Cookies, 64 * 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    let level0 = tree.inner.levels[0].read();
+    assert_eq!(level0[0].bloom_filter.as_ref().unwrap().size_bytes(), written);
+}
+
+#[test]
+fn test_fpr_config_defaults_and_parsing() {
+    let config: BrowserDBConfig = toml::from_str(
+        "[lsm_tree]\nmax_level0_files = 4\nmax_memtable_size_mb = 20\nlevel_size_thresholds_mb = [10]\n\n[lsm_tree.bloom_fpr]\ncookies = 0.001\nsettings = 1.5\n",
+    )
+    .unwrap();
+    let fpr = &config.lsm_tree.bloom_fpr;
+    assert_eq!(fpr.for_table(TableType::Cookies), 0.001);
+    assert_eq!(fpr.for_table(TableType::Cache), BLOOM_FPR);
+    // Out-of-range rates fall back to the default.
+    assert_eq!(fpr.for_table(TableType::Settings), BLOOM_FPR);
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "browser-import")]
+
+use browserdb::hash::hash_str;
+use browserdb::import::ImportResult;
+use browserdb::BrowserDB;
+use rusqlite::{params, Connection};
+use tempfile::tempdir;
+
+// 2023-11-14T22:13:20Z
+const UNIX_MS: u128 = 1_700_000_000_000;
+const WEBKIT_MICROS: i64 = (1_700_000_000_000 + 11_644_473_600_000) * 1000;
+
+#[test]
+fn test_import_chrome_history() {
+    let dir = tempdir().unwrap();
+    let fixture = dir.path().join("History");
+    let conn = Connection::open(&fixture).unwrap();
+    conn.execute_batch("CREATE TABLE urls (id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR, visit_count INTEGER, last_visit_time INTEGER)").unwrap();
+    let rows: [(&str, Option<&str>, i64, i64); 4] = [
+        ("https://www.rust-lang.org/", Some("Rust"), 12, WEBKIT_MICROS),
+        ("https://example.com/a,b", None, 1, WEBKIT_MICROS + 5_000),
+        ("not a url", Some("Broken"), 3, WEBKIT_MICROS),
+        ("", Some("Empty"), 1, WEBKIT_MICROS),
+    ];
+    for (url, title, visits, time) in rows {
+        conn.execute("INSERT INTO urls (url, title, visit_count, last_visit_time) VALUES (?1, ?2, ?3, ?4)", params![url, title, visits, time]).unwrap();
+    }
+    drop(conn);
+
+    let db = BrowserDB::open(dir.path().join("db")).unwrap();
+    let result = db.history().import_chrome_history(&fixture).unwrap();
+    assert_eq!(result, ImportResult { imported: 2, skipped: 2, overwritten: 0 });
+    assert_eq!(db.history().count().unwrap(), 2);
+
+    let rust = db.history().get(hash_str("https://www.rust-lang.org/")).unwrap().unwrap();
+    assert_eq!(rust.timestamp, UNIX_MS);
+    assert_eq!(rust.title, "Rust");
+    assert_eq!(rust.visit_count, 12);
+    let untitled = db.history().get(hash_str("https://example.com/a,b")).unwrap().unwrap();
+    assert_eq!(untitled.timestamp, UNIX_MS + 5);
+    assert_eq!(untitled.title, "");
+}
+
+#[test]
+fn test_import_firefox_history() {
+    let dir = tempdir().unwrap();
+    let fixture = dir.path().join("places.sqlite");
+    let conn = Connection::open(&fixture).unwrap();
+    conn.execute_batch("CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR, visit_count INTEGER DEFAULT 0, last_visit_date INTEGER)").unwrap();
+    let rows: [(&str, Option<&str>, Option<i64>); 3] = [
+        ("https://developer.mozilla.org/", Some("MDN"), Some(UNIX_MS as i64 * 1000)),
+        ("place:sort=8&maxResults=10", Some("Recent"), None),
+        ("http://bad host/", None, Some(0)),
+    ];
+    for (url, title, time) in rows {
+        conn.execute("INSERT INTO moz_places (url, title, visit_count, last_visit_date) VALUES (?1, ?2, 4, ?3)", params![url, title, time]).unwrap();
+    }
+    drop(conn);
+
+    let db = BrowserDB::open(dir.path().join("db")).unwrap();
+    let result = db.history().import_firefox_history(&fixture).unwrap();
+    assert_eq!(result, ImportResult { imported: 2, skipped: 1, overwritten: 0 });
+
+    let mdn = db.history().get(hash_str("https://developer.mozilla.org/")).unwrap().unwrap();
+    assert_eq!(mdn.timestamp, UNIX_MS);
+    assert_eq!(mdn.visit_count, 4);
+    // Never-visited places have no last_visit_date.
+    assert_eq!(db.history().get(hash_str("place:sort=8&maxResults=10")).unwrap().unwrap().timestamp, 0);
+    // The import is searchable like any other history.
+    assert_eq!(db.history().search("mdn", 10).unwrap().len(), 1);
+}
@@ -0,0 +1,101 @@
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{EntryType, TableType};
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::modes::CurrentMode;
+use std::time::Instant;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+/// Flushes buffered history writes, so both paths are timed until their data is on disk.
+fn flush_history(db: &BrowserDB) {
+    let container = db.container("default").unwrap();
+    if let CurrentMode::Persistent(pm) = &*container.switcher.current_mode.read() {
+        pm.history.flush().unwrap();
+    };
+}
+
+#[test]
+fn test_bulk_vs_looped_insert_throughput() {
+    const N: u128 = 100_000;
+    let entries: Vec<HistoryEntry> = (0..N).map(page).collect();
+
+    let looped_dir = tempdir().unwrap();
+    let looped = BrowserDB::open(looped_dir.path()).unwrap();
+    let start = Instant::now();
+    for entry in &entries {
+        looped.history().insert(entry).unwrap();
+    }
+    flush_history(&looped);
+    let looped_time = start.elapsed();
+
+    let bulk_dir = tempdir().unwrap();
+    let bulk = BrowserDB::open(bulk_dir.path()).unwrap();
+    let start = Instant::now();
+    bulk.history().insert_bulk(&entries).unwrap();
+    flush_history(&bulk);
+    let bulk_time = start.elapsed();
+    assert_eq!(looped.history().count().unwrap(), N as usize);
+
+    println!(
+        "looped: {:.0} entries/s, bulk: {:.0} entries/s",
+        N as f64 / looped_time.as_secs_f64(),
+        N as f64 / bulk_time.as_secs_f64()
+    );
+    // Timings are reported, not asserted: they vary with the build profile and machine.
+
+    assert_eq!(bulk.history().count().unwrap(), N as usize);
+    assert_eq!(bulk.history().get(4242).unwrap().unwrap().url, "https://example.test/4242");
+    let hits = bulk.history().search("page 4242", 10).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].url_hash, 4242);
+}
+
+#[test]
+fn test_bulk_insert_in_ultra_mode() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+
+    db.history().insert(&page(1)).unwrap();
+    let mut renamed = page(1);
+    renamed.title = "Renamed".to_string();
+    db.history().insert_bulk(&[renamed, page(2), page(3)]).unwrap();
+
+    assert_eq!(db.history().count().unwrap(), 3);
+    assert_eq!(db.history().get(1).unwrap().unwrap().title, "Renamed");
+    assert!(db.history().search("bulk", 10).unwrap().iter().all(|e| e.url_hash != 1));
+    assert_eq!(db.history().search("renamed", 10).unwrap().len(), 1);
+}
+
+#[test]
+fn test_large_batch_bypasses_the_memtable() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.max_level0_files = 100;
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 64 * 1024, config).unwrap();
+    tree.put(b"key00010".to_vec(), b"old".to_vec()).unwrap();
+
+    let entries: Vec<(Vec<u8>, Vec<u8>)> = (0..5000u32)
+        .map(|i| (format!("key{:05}", i).into_bytes(), vec![b'v'; 32]))
+        .collect();
+    tree.put_bulk(entries).unwrap();
+
+    // The buffered write was flushed first, then the batch was cut into
+    // memtable-sized tables: 200 000 bytes in chunks of 64 KiB.
+    assert_eq!(tree.inner.levels[0].read().len(), 1 + 4);
+    assert!(tree.inner.memtable.iter().all(|m| m.read().is_empty()));
+
+    assert_eq!(tree.all_entries().len(), 5000);
+    let overwritten = tree.get(b"key00010").unwrap();
+    assert_eq!(overwritten.value, vec![b'v'; 32]);
+    assert_eq!(overwritten.entry_type, EntryType::Update);
+    assert_eq!(tree.get(b"key04999").unwrap().entry_type, EntryType::Insert);
+
+    // Small batches stay in the memtable.
+    tree.put_bulk(vec![(b"small".to_vec(), b"1".to_vec())]).unwrap();
+    assert_eq!(tree.inner.levels[0].read().len(), 1 + 4);
+    assert_eq!(tree.get(b"small").unwrap().value, b"1".to_vec());
+}
@@ -0,0 +1,63 @@
+use browserdb::{BrowserDB, CacheEntry, DatabaseMode};
+use std::io::Cursor;
+use tempfile::tempdir;
+
+fn response(url_hash: u128, last_modified: u128) -> CacheEntry {
+    CacheEntry {
+        url_hash,
+        headers: "Content-Type: image/png\r\n".to_string(),
+        body: vec![url_hash as u8; 1000],
+        etag: String::new(),
+        last_modified,
+        expires_at: 0,
+    }
+}
+
+#[test]
+fn test_evict_to_budget_keeps_the_hottest_entries() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let cache = db.cache();
+    for url_hash in 0..20 {
+        cache.insert(&response(url_hash, 0)).unwrap();
+    }
+    cache.insert_streaming(100, "", "", 0, Cursor::new(vec![1u8; 300_000])).unwrap();
+    for _ in 0..5 {
+        for url_hash in 15..20 {
+            cache.get(url_hash).unwrap().unwrap();
+        }
+    }
+
+    let before = cache.size_bytes().unwrap();
+    assert!(before > 320_000, "{}", before);
+    assert_eq!(cache.evict_to_budget(before).unwrap(), 0);
+
+    // Room for about eight small entries: the large cold body goes first.
+    let budget = 8 * 1100;
+    let freed = cache.evict_to_budget(budget).unwrap();
+    let after = cache.size_bytes().unwrap();
+    assert!(after <= budget, "{} > {}", after, budget);
+    assert_eq!(before - after, freed);
+    assert!(cache.get_streaming(100).unwrap().is_none());
+    for url_hash in 15..20 {
+        assert!(cache.get(url_hash).unwrap().is_some(), "hot entry {} evicted", url_hash);
+    }
+    assert!(cache.count().unwrap() < 20);
+}
+
+#[test]
+fn test_evict_to_budget_in_ultra_mode_drops_the_oldest() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    let cache = db.cache();
+    for url_hash in 0..10 {
+        cache.insert(&response(url_hash, 1_000 + url_hash)).unwrap();
+    }
+
+    let budget = cache.size_bytes().unwrap() / 2;
+    cache.evict_to_budget(budget).unwrap();
+    assert!(cache.size_bytes().unwrap() <= budget);
+    assert!(cache.get(0).unwrap().is_none());
+    assert!(cache.get(9).unwrap().is_some());
+}
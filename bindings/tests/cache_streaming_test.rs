@@ -0,0 +1,125 @@
+use browserdb::{BrowserDB, CacheEntry, DatabaseMode};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::{self, Read};
+use tempfile::tempdir;
+
+/// Tracks the current thread's live heap bytes and their peak, so
+/// background flush and compaction threads do not disturb the measurement.
+struct PeakAlloc;
+
+thread_local! {
+    static LIVE: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LIVE.try_with(|live| {
+            live.set(live.get() + layout.size() as isize);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(live.get())));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE.try_with(|live| live.set(live.get() - layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc;
+
+/// Peak heap growth of the current thread while `f` runs.
+fn peak_during<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let base = LIVE.with(|l| l.get());
+    PEAK.with(|p| p.set(base));
+    let result = f();
+    (result, (PEAK.with(|p| p.get()) - base).max(0) as usize)
+}
+
+/// A deterministic body of `len` bytes that is generated, never stored.
+struct Generated {
+    pos: u64,
+    len: u64,
+}
+
+fn byte_at(pos: u64) -> u8 {
+    (pos.wrapping_mul(2654435761) >> 7) as u8
+}
+
+impl Read for Generated {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min((self.len - self.pos) as usize);
+        for (i, b) in buf[..n].iter_mut().enumerate() {
+            *b = byte_at(self.pos + i as u64);
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+const BODY: u64 = 50 * 1024 * 1024;
+const MAX_PEAK: usize = 8 * 1024 * 1024;
+
+#[test]
+fn test_stream_large_body_in_and_out() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+
+    let (written, peak) = peak_during(|| {
+        db.cache()
+            .insert_streaming(42, "content-type: video/mp4", "\"big\"", 7, Generated { pos: 0, len: BODY })
+            .unwrap()
+    });
+    assert_eq!(written, BODY);
+    assert!(peak < MAX_PEAK, "insert peaked at {} bytes", peak);
+
+    let (read, peak) = peak_during(|| {
+        let (entry, mut body) = db.cache().get_streaming(42).unwrap().unwrap();
+        assert_eq!(entry.etag, "\"big\"");
+        assert!(entry.body.is_empty());
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut pos = 0u64;
+        loop {
+            let n = body.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            for (i, b) in buf[..n].iter().enumerate() {
+                assert_eq!(*b, byte_at(pos + i as u64), "byte {} differs", pos + i as u64);
+            }
+            pos += n as u64;
+        }
+        pos
+    });
+    assert_eq!(read, BODY);
+    assert!(peak < MAX_PEAK, "read peaked at {} bytes", peak);
+    assert_eq!(db.cache().count().unwrap(), 1);
+}
+
+#[test]
+fn test_streamed_body_is_replaced_by_plain_insert() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    let body: Vec<u8> = (0..600_000u32).map(|i| byte_at(i as u64)).collect();
+
+    db.cache().insert_streaming(1, "", "", 0, &body[..]).unwrap();
+    assert_eq!(db.cache().get(1).unwrap().unwrap().body, body);
+    assert_eq!(db.cache().with_body(1, |b| b.len()).unwrap(), Some(body.len()));
+    // A shorter streamed body leaves no stale chunks behind.
+    db.cache().insert_streaming(1, "", "", 0, &body[..10]).unwrap();
+    assert_eq!(db.cache().get(1).unwrap().unwrap().body, &body[..10]);
+
+    db.cache()
+        .insert(&CacheEntry { url_hash: 1, headers: String::new(), body: Vec::new(), etag: String::new(), last_modified: 0, expires_at: 0 })
+        .unwrap();
+    assert!(db.cache().get(1).unwrap().unwrap().body.is_empty());
+    let (_, mut reader) = db.cache().get_streaming(1).unwrap().unwrap();
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert!(out.is_empty());
+    assert_eq!(db.cache().count().unwrap(), 1);
+}
@@ -0,0 +1,95 @@
+use browserdb::core::clock::{Clock, SharedClock};
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::modes::CurrentMode;
+use browserdb::{keys, BrowserDB, CacheEntry, DatabaseMode};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+const NOW: u64 = 1_700_000_000_000;
+
+struct FixedClock;
+
+impl Clock for FixedClock {
+    fn now_ms(&self) -> u64 {
+        NOW
+    }
+}
+
+fn open(dir: &Path) -> BrowserDB {
+    let config = BrowserDBConfig { clock: SharedClock::new(Arc::new(FixedClock)), ..Default::default() };
+    BrowserDB::open_with_config(dir, config).unwrap()
+}
+
+fn response(url_hash: u128, expires_at: u64) -> CacheEntry {
+    CacheEntry {
+        url_hash,
+        headers: "cache-control: max-age=60".to_string(),
+        body: format!("body {}", url_hash).into_bytes(),
+        etag: String::new(),
+        last_modified: 0,
+        expires_at,
+    }
+}
+
+#[test]
+fn test_expired_entry_is_not_served() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        db.set_mode(mode).unwrap();
+        db.cache().insert(&response(1, NOW - 1)).unwrap();
+        db.cache().insert(&response(2, NOW + 60_000)).unwrap();
+        db.cache().insert(&response(3, 0)).unwrap();
+
+        assert!(db.cache().get(1).unwrap().is_none());
+        assert_eq!(db.cache().get(2).unwrap().unwrap().body, b"body 2");
+        assert_eq!(db.cache().get(3).unwrap().unwrap().expires_at, 0);
+        // The stale entry was deleted by the read.
+        assert_eq!(db.cache().count().unwrap(), 2);
+
+        db.cache().insert(&response(1, NOW)).unwrap();
+        assert_eq!(db.cache().with_body(1, |body| body.to_vec()).unwrap(), None);
+        assert!(db.cache().get_streaming(1).unwrap().is_none());
+        db.cache().insert(&response(1, NOW)).unwrap();
+        let found = db.cache().get_many(&[1, 2]).unwrap();
+        assert!(found[0].is_none() && found[1].is_some());
+        assert_eq!(db.cache().count().unwrap(), 2);
+        db.cache().purge_stale(u64::MAX).unwrap();
+    }
+}
+
+#[test]
+fn test_purge_stale_sweeps_unread_entries() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    for i in 0..10 {
+        db.cache().insert(&response(i, NOW + i as u64 * 1_000)).unwrap();
+    }
+    let body = vec![7u8; 600 * 1024];
+    db.cache().insert_streaming(100, "", "", 0, &body[..]).unwrap();
+
+    assert_eq!(db.cache().purge_stale(NOW + 4_500).unwrap(), 5);
+    assert_eq!(db.cache().count().unwrap(), 6);
+    assert!(db.cache().get(4).unwrap().is_none());
+    assert!(db.cache().get(5).unwrap().is_some());
+    // Streamed entries never expire.
+    assert_eq!(db.cache().get(100).unwrap().unwrap().body, body);
+}
+
+#[test]
+fn test_entries_written_before_expiry_still_decode() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    // The layout `CacheEntry` had without `expires_at`.
+    let old = bincode::serialize(&(9u128, "h".to_string(), b"old body".to_vec(), "e".to_string(), 5u128)).unwrap();
+    match &*db.container("default").unwrap().switcher.current_mode.read() {
+        CurrentMode::Persistent(pm) => pm.cache.put(keys::cache_key(9), old).unwrap(),
+        CurrentMode::Ultra(_) => unreachable!(),
+    }
+
+    let entry = db.cache().get(9).unwrap().unwrap();
+    assert_eq!((entry.body.as_slice(), entry.last_modified, entry.expires_at), (&b"old body"[..], 5, 0));
+    assert_eq!(db.cache().with_body(9, |body| body.len()).unwrap(), Some(8));
+    assert_eq!(db.cache().purge_stale(u64::MAX).unwrap(), 0);
+}
@@ -0,0 +1,64 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::modes::CurrentMode;
+use browserdb::BrowserDB;
+use std::sync::atomic::Ordering;
+use tempfile::tempdir;
+
+mod common;
+use common::{manual_compaction_config, page};
+
+const TRIGGER: usize = 4;
+
+/// Flushes every 100 writes and never compacts in the background, so
+/// level 0 piles up until something settles it.
+fn config() -> BrowserDBConfig {
+    let mut config = manual_compaction_config();
+    config.lsm_tree.memtable_max_ops = 100;
+    config.lsm_tree.max_level0_files = TRIGGER;
+    config
+}
+
+#[test]
+fn test_checkpoint_leaves_empty_memtables_and_a_settled_level0() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open_with_config(dir.path(), config()).unwrap();
+    for i in 0..2_050 {
+        db.history().insert(&page(i)).unwrap();
+    }
+    for i in 0..30 {
+        db.settings().set(&format!("key{}", i), "v").unwrap();
+    }
+
+    let container = db.container("default").unwrap();
+    let level0 = || match &*container.switcher.current_mode.read() {
+        CurrentMode::Persistent(pm) => pm.history.inner.levels[0].read().len(),
+        CurrentMode::Ultra(_) => unreachable!(),
+    };
+    // History postings land in the same tree, so it flushes often.
+    assert!(level0() > TRIGGER, "level 0 holds {} tables", level0());
+
+    db.checkpoint().unwrap();
+
+    match &*container.switcher.current_mode.read() {
+        CurrentMode::Persistent(pm) => {
+            for table in [&pm.history, &pm.bookmarks, &pm.cookies, &pm.cache, &pm.localstore, &pm.settings, &pm.binarystore] {
+                assert_eq!(table.memtable_bytes(), 0, "{:?}", table.inner.table_type);
+                assert!(table.inner.levels[0].read().len() < TRIGGER, "{:?}", table.inner.table_type);
+            }
+            assert!(!pm.history.inner.levels[1].read().is_empty());
+        }
+        CurrentMode::Ultra(_) => unreachable!(),
+    }
+    assert_eq!(db.history().count().unwrap(), 2_050);
+    assert_eq!(db.history().get(1_234).unwrap().unwrap().title, "Page 1234");
+    assert_eq!(db.settings().get("key29").unwrap(), Some("v".to_string()));
+
+    // Nothing is left for a second checkpoint to do.
+    let compactions = || match &*container.switcher.current_mode.read() {
+        CurrentMode::Persistent(pm) => pm.history.inner.compactions.load(Ordering::Relaxed),
+        CurrentMode::Ultra(_) => unreachable!(),
+    };
+    let before = compactions();
+    db.checkpoint().unwrap();
+    assert_eq!(compactions(), before);
+}
@@ -0,0 +1,65 @@
+use browserdb::core::clock::{Clock, SharedClock};
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+/// A wall clock the test sets by hand.
+struct SettableClock(AtomicU64);
+
+impl Clock for SettableClock {
+    fn now_ms(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+fn config(clock: &Arc<SettableClock>) -> BrowserDBConfig {
+    BrowserDBConfig { clock: SharedClock::new(clock.clone()), ..Default::default() }
+}
+
+#[test]
+fn test_newest_write_wins_after_clock_steps_back() {
+    let dir = tempdir().unwrap();
+    let clock = Arc::new(SettableClock(AtomicU64::new(1_000_000)));
+    let key = b"homepage".to_vec();
+    {
+        let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, config(&clock)).unwrap();
+        tree.put(key.clone(), b"first".to_vec()).unwrap();
+        tree.flush().unwrap();
+
+        // NTP correction: the clock goes back ten minutes.
+        clock.0.store(400_000, Ordering::SeqCst);
+        tree.put(key.clone(), b"second".to_vec()).unwrap();
+        tree.flush().unwrap();
+        tree.vacuum().unwrap();
+
+        let entry = tree.get(&key).unwrap();
+        assert_eq!(entry.value, b"second");
+        assert!(entry.timestamp > 1_000_000);
+        assert_eq!(tree.all_entries()[0].value, b"second");
+    }
+
+    // Reopened behind the clock that wrote the data on disk.
+    clock.0.store(1, Ordering::SeqCst);
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, config(&clock)).unwrap();
+    tree.put(key.clone(), b"third".to_vec()).unwrap();
+    tree.flush().unwrap();
+    tree.vacuum().unwrap();
+    assert_eq!(tree.get(&key).unwrap().value, b"third");
+}
+
+#[test]
+fn test_timestamps_strictly_increase_on_a_stopped_clock() {
+    let dir = tempdir().unwrap();
+    let clock = Arc::new(SettableClock(AtomicU64::new(5)));
+    let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, config(&clock)).unwrap();
+    let mut last = 0;
+    for i in 0..100u8 {
+        tree.put(vec![i], vec![i]).unwrap();
+        let timestamp = tree.get(&[i]).unwrap().timestamp;
+        assert!(timestamp > last);
+        last = timestamp;
+    }
+}
@@ -0,0 +1,99 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::{BookmarkEntry, BrowserDB, CacheEntry, Codec, DatabaseMode, HistoryEntry, JsonCodec, LocalStoreEntry, ValueCodec};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+fn json_config() -> BrowserDBConfig {
+    let mut config = BrowserDBConfig::default();
+    config.codecs.history = ValueCodec::Json;
+    config.codecs.cache = ValueCodec::Json;
+    config.codecs.localstore = ValueCodec::Json;
+    config
+}
+
+/// Every byte the default container's `prefix` table has on disk: its
+/// SSTables and WAL.
+fn table_bytes(base: &Path, prefix: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for entry in fs::read_dir(base.join("container_default")).unwrap().flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&format!("{}_", prefix)) && entry.path().is_file() {
+            bytes.extend(fs::read(entry.path()).unwrap());
+        }
+    }
+    bytes
+}
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle.as_bytes())
+}
+
+#[test]
+fn test_json_codec_round_trips_a_history_entry() {
+    let entry = page(7);
+    let bytes = JsonCodec.encode(&entry).unwrap();
+    let text = std::str::from_utf8(&bytes).unwrap();
+    assert!(text.contains(r#""url":"https://example.test/7""#), "{}", text);
+    let back: HistoryEntry = JsonCodec.decode(&bytes).unwrap();
+    assert_eq!((back.url, back.title, back.visit_count), (entry.url, entry.title, entry.visit_count));
+}
+
+#[test]
+fn test_json_tables_are_readable_on_disk_and_keep_their_codec() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open_with_config(dir.path(), json_config()).unwrap();
+        db.history().insert(&page(1)).unwrap();
+        db.history().insert(&page(2)).unwrap();
+        db.bookmarks().insert(&BookmarkEntry {
+            url_hash: 1,
+            url: "https://example.test/1".to_string(),
+            title: "Bincode bookmark".to_string(),
+            folder: "bar".to_string(),
+            created_at: 1,
+        }).unwrap();
+        db.flush_table(TableType::History).unwrap();
+        db.flush_table(TableType::Bookmarks).unwrap();
+
+        let history = table_bytes(dir.path(), "history");
+        assert!(contains(&history, r#""title":"Page 2""#));
+        // Bookmarks were left on bincode: no field names on disk.
+        assert!(!contains(&table_bytes(dir.path(), "bookmarks"), r#""title""#));
+        assert_eq!(db.history().get(2).unwrap().unwrap().title, "Page 2");
+        assert_eq!(db.history().search("page", 10).unwrap().len(), 2);
+    }
+
+    // Reopened with the default config: the recorded codecs still apply.
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_eq!(db.history().get(1).unwrap().unwrap().url, "https://example.test/1");
+    assert_eq!(db.bookmarks().get_all().unwrap()[0].title, "Bincode bookmark");
+    assert!(fs::read_to_string(dir.path().join("METADATA")).unwrap().contains(r#"history = "json""#));
+}
+
+#[test]
+fn test_json_codec_in_both_modes() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open_with_config(dir.path(), json_config()).unwrap();
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        db.set_mode(mode).unwrap();
+        db.cache().insert(&CacheEntry {
+            url_hash: 5,
+            headers: "Content-Type: text/html\r\n".to_string(),
+            body: b"<html>".to_vec(),
+            etag: "\"v1\"".to_string(),
+            last_modified: 0,
+            expires_at: 0,
+        }).unwrap();
+        assert_eq!(db.cache().get(5).unwrap().unwrap().body, b"<html>", "{:?}", mode);
+        assert_eq!(db.cache().with_body(5, |b| b.len()).unwrap(), Some(6), "{:?}", mode);
+
+        db.localstore().insert(&LocalStoreEntry { origin_hash: 9, key: "theme".to_string(), value: "dark".to_string() }).unwrap();
+        let found = db.localstore().query().value_eq("dark".to_string()).execute().unwrap();
+        assert_eq!(found.len(), 1, "{:?}", mode);
+        assert_eq!(db.localstore().get(9, "theme").unwrap().unwrap().value, "dark");
+    }
+}
@@ -0,0 +1,105 @@
+//! Fixtures shared by the integration tests. Each test file is a crate of
+//! its own and uses only some of them.
+#![allow(dead_code)]
+
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{EntryType, TableType};
+use browserdb::core::lsm_tree::{KVEntry, LSMTree};
+use browserdb::hash::domain_hash;
+use browserdb::{CookieEntry, HistoryEntry, LocalStoreEntry};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// History entry for `url_hash`, visited once.
+pub fn page(url_hash: u128) -> HistoryEntry {
+    HistoryEntry {
+        timestamp: 1_700_000_000_000 + url_hash,
+        url: format!("https://example.test/{}", url_hash),
+        url_hash,
+        title: format!("Page {}", url_hash),
+        visit_count: 1,
+    }
+}
+
+/// A visit to `url`, filed under `url_hash`.
+pub fn visit(url: &str, url_hash: u128) -> HistoryEntry {
+    HistoryEntry { timestamp: 1_700_000_000_000, url: url.to_string(), url_hash, title: "page".to_string(), visit_count: 1 }
+}
+
+/// Session cookie `name` for `domain`, at path `/`.
+pub fn cookie(domain: &str, name: &str) -> CookieEntry {
+    CookieEntry {
+        domain_hash: domain_hash(domain),
+        name: name.to_string(),
+        value: "v".to_string(),
+        path: "/".to_string(),
+        domain: domain.to_string(),
+        expiry: 0,
+        flags: 0,
+    }
+}
+
+/// localStorage item `key` of `domain`'s origin.
+pub fn storage(domain: &str, key: &str) -> LocalStoreEntry {
+    LocalStoreEntry { origin_hash: domain_hash(domain), key: key.to_string(), value: "x".to_string() }
+}
+
+/// Wall-clock time in milliseconds since the Unix epoch.
+pub fn now_ms() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
+
+/// A `table_type` tree in `dir` with the default config.
+pub fn open_tree(dir: &Path, table_type: TableType) -> LSMTree {
+    LSMTree::new(dir, table_type, 1024 * 1024, BrowserDBConfig::default()).unwrap()
+}
+
+/// A config under which nothing compacts on its own: level 0 never fills,
+/// never stalls writers and is never idle or overdue.
+pub fn manual_compaction_config() -> BrowserDBConfig {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.max_level0_files = 1_000;
+    config.lsm_tree.l0_slowdown_files = 1_000;
+    config.lsm_tree.l0_stop_files = 1_000;
+    config.lsm_tree.compaction_idle_threshold_ms = u64::MAX;
+    config.lsm_tree.compaction_deadline_sec = u64::MAX;
+    config
+}
+
+/// An `entry_type` entry for `key`, written at `timestamp`.
+pub fn entry(key: &[u8], value: &[u8], timestamp: u64, entry_type: EntryType) -> KVEntry {
+    KVEntry {
+        key: key.to_vec(),
+        value: value.to_vec(),
+        timestamp,
+        expires_at: 0,
+        entry_type,
+        deleted: entry_type == EntryType::Delete,
+    }
+}
+
+/// The SSTables in `dir`, by name.
+pub fn sst_files(dir: &Path) -> Vec<PathBuf> {
+    table_sst_files(dir, "")
+}
+
+/// The SSTables in `dir` whose name starts with `prefix`, by name.
+pub fn table_sst_files(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "sst"))
+        .filter(|p| p.file_name().unwrap().to_string_lossy().starts_with(prefix))
+        .collect();
+    files.sort();
+    files
+}
+
+/// [`sst_files`] as bare file names.
+pub fn sst_names(dir: &Path) -> Vec<String> {
+    sst_files(dir).iter().map(|p| p.file_name().unwrap().to_string_lossy().into_owned()).collect()
+}
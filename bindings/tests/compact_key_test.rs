@@ -0,0 +1,72 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use tempfile::tempdir;
+
+mod common;
+use common::manual_compaction_config;
+
+/// Flushes every 50 writes and leaves compaction to the caller.
+fn config() -> BrowserDBConfig {
+    let mut config = manual_compaction_config();
+    config.lsm_tree.memtable_max_ops = 50;
+    config
+}
+
+/// Versions of `key` in SSTables, across every level.
+fn versions_on_disk(tree: &LSMTree, key: &[u8]) -> usize {
+    tree.inner.levels.iter().map(|lvl| lvl.read().iter().filter(|t| t.get(key).is_some()).count()).sum()
+}
+
+#[test]
+fn test_overwritten_key_collapses_to_one_version() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 64 * 1024 * 1024, config()).unwrap();
+    for i in 0..1_000u32 {
+        tree.put(b"counter".to_vec(), i.to_le_bytes().to_vec()).unwrap();
+        // Neighbours in the same tables must survive the rewrite.
+        if i % 100 == 0 {
+            tree.put(format!("other{}", i).into_bytes(), b"x".to_vec()).unwrap();
+        }
+    }
+    tree.flush().unwrap();
+    assert!(versions_on_disk(&tree, b"counter") > 1);
+
+    let dropped = tree.compact_key(b"counter").unwrap();
+    assert_eq!(versions_on_disk(&tree, b"counter"), 1);
+    assert!(dropped > 0);
+    assert_eq!(tree.get(b"counter").unwrap().value, 999u32.to_le_bytes());
+    for i in (0..1_000).step_by(100) {
+        assert_eq!(tree.get(format!("other{}", i).as_bytes()).unwrap().value, b"x");
+    }
+    assert_eq!(tree.compact_key(b"counter").unwrap(), 0);
+
+    // The collapsed layout is what the manifest reopens.
+    drop(tree);
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 64 * 1024 * 1024, config()).unwrap();
+    assert_eq!(versions_on_disk(&tree, b"counter"), 1);
+    assert_eq!(tree.get(b"counter").unwrap().value, 999u32.to_le_bytes());
+}
+
+#[test]
+fn test_counters_and_deleted_keys_collapse() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 64 * 1024 * 1024, config()).unwrap();
+    for _ in 0..300 {
+        tree.increment(b"visits".to_vec(), 2).unwrap();
+        tree.put(b"gone".to_vec(), b"v".to_vec()).unwrap();
+    }
+    tree.delete(b"gone".to_vec()).unwrap();
+    tree.flush().unwrap();
+
+    assert_eq!(tree.get(b"visits").unwrap().value, 600i64.to_le_bytes());
+    tree.compact_key(b"visits").unwrap();
+    assert_eq!(versions_on_disk(&tree, b"visits"), 1);
+    assert_eq!(tree.get(b"visits").unwrap().value, 600i64.to_le_bytes());
+    tree.increment(b"visits".to_vec(), 1).unwrap();
+    assert_eq!(tree.get(b"visits").unwrap().value, 601i64.to_le_bytes());
+
+    tree.compact_key(b"gone").unwrap();
+    assert_eq!(versions_on_disk(&tree, b"gone"), 0);
+    assert!(tree.get(b"gone").is_none());
+}
@@ -31,7 +31,7 @@ fn test_compaction_cascade() {
     let mut found_high_level = false;
     for _ in 0..10 {
         thread::sleep(Duration::from_millis(500));
-        for level in 1..10 {
+        for level in 1..lsm_tree.inner.levels.len() {
             if !lsm_tree.inner.levels[level].read().is_empty() {
                 found_high_level = true;
                 println!("Found files at level {}", level);
@@ -54,7 +54,7 @@ fn test_compaction_cascade() {
     let mut found_l2 = false;
     for _ in 0..10 {
         thread::sleep(Duration::from_millis(500));
-        for level in 2..10 {
+        for level in 2..lsm_tree.inner.levels.len() {
             if !lsm_tree.inner.levels[level].read().is_empty() {
                 found_l2 = true;
                 println!("Found files at level {}", level);
@@ -0,0 +1,83 @@
+use browserdb::core::compaction_pool::CompactionPool;
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::events::DbEvent;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+#[test]
+fn test_tables_compact_in_parallel_up_to_the_worker_cap() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.compaction_workers = 2;
+    config.lsm_tree.max_level0_files = 2;
+    config.lsm_tree.compaction_idle_threshold_ms = 0;
+    config.lsm_tree.compaction_deadline_sec = 0;
+
+    // Compacted events are emitted from inside a job, so a slow hook keeps
+    // its slot taken and shows how many jobs overlap.
+    let running = Arc::new(AtomicUsize::new(0));
+    let most_running = Arc::new(AtomicUsize::new(0));
+    let compacted = Arc::new(AtomicUsize::new(0));
+    {
+        let (running, most_running, compacted) = (Arc::clone(&running), Arc::clone(&most_running), Arc::clone(&compacted));
+        config.events.subscribe(Box::new(move |event| {
+            if let DbEvent::Compacted { .. } = event {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most_running.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(200));
+                running.fetch_sub(1, Ordering::SeqCst);
+                compacted.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+    }
+
+    let tables = [TableType::History, TableType::Cookies, TableType::Cache, TableType::Settings];
+    let trees: Vec<LSMTree> = tables
+        .iter()
+        .map(|&table| LSMTree::new(dir.path(), table, 1024 * 1024, config.clone()).unwrap())
+        .collect();
+    for tree in &trees {
+        for round in 0..2u8 {
+            for i in 0..20u32 {
+                tree.put(format!("key{:02}", i).into_bytes(), vec![round]).unwrap();
+            }
+            tree.flush().unwrap();
+        }
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while compacted.load(Ordering::SeqCst) < tables.len() {
+        assert!(Instant::now() < deadline, "not every table was compacted");
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    assert_eq!(most_running.load(Ordering::SeqCst), 2);
+    assert!(config.compaction_pool.peak() <= 2);
+    assert!(trees.iter().all(|t| t.level_report()[1].sstables > 0));
+    for tree in &trees {
+        assert_eq!(tree.get(b"key07").unwrap().value, vec![1]);
+    }
+}
+
+#[test]
+fn test_slots_are_returned_when_dropped() {
+    let pool = CompactionPool::default();
+    let first = pool.acquire_timeout(1, Duration::ZERO).unwrap();
+    assert_eq!(pool.active(), 1);
+    assert!(pool.acquire_timeout(1, Duration::from_millis(20)).is_none());
+
+    let waiter = {
+        let pool = pool.clone();
+        thread::spawn(move || pool.acquire_timeout(1, Duration::from_secs(5)).is_some())
+    };
+    thread::sleep(Duration::from_millis(50));
+    drop(first);
+    assert!(waiter.join().unwrap());
+    assert_eq!(pool.active(), 0);
+    assert_eq!(pool.peak(), 1);
+}
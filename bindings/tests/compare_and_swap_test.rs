@@ -0,0 +1,80 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::{BrowserDB, DatabaseMode};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use tempfile::tempdir;
+
+#[test]
+fn test_racing_swaps_have_exactly_one_winner() {
+    let dir = tempdir().unwrap();
+    let tree = Arc::new(LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, BrowserDBConfig::default()).unwrap());
+    tree.put(b"counter".to_vec(), 0u64.to_le_bytes().to_vec()).unwrap();
+
+    for round in 0..200u64 {
+        let barrier = Arc::new(Barrier::new(2));
+        let racers: Vec<_> = (0..2u64)
+            .map(|racer| {
+                let (tree, barrier) = (Arc::clone(&tree), Arc::clone(&barrier));
+                thread::spawn(move || {
+                    barrier.wait();
+                    let expected = round.to_le_bytes().to_vec();
+                    let new = (round + 1 + racer * 1_000_000).to_le_bytes().to_vec();
+                    tree.compare_and_swap(b"counter".to_vec(), Some(expected), new).unwrap()
+                })
+            })
+            .collect();
+        let won: Vec<bool> = racers.into_iter().map(|r| r.join().unwrap()).collect();
+        assert_eq!(won.iter().filter(|w| **w).count(), 1, "round {}: {:?}", round, won);
+
+        let winner = won.iter().position(|w| *w).unwrap() as u64;
+        let stored = tree.get(b"counter").unwrap().value;
+        assert_eq!(stored, (round + 1 + winner * 1_000_000).to_le_bytes());
+        // Put the counter back on the shared sequence for the next round.
+        assert!(tree.compare_and_swap(b"counter".to_vec(), Some(stored), (round + 1).to_le_bytes().to_vec()).unwrap());
+    }
+}
+
+#[test]
+fn test_swap_checks_flushed_and_deleted_values() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    assert!(!tree.compare_and_swap(b"k".to_vec(), Some(b"a".to_vec()), b"b".to_vec()).unwrap());
+    assert!(tree.compare_and_swap(b"k".to_vec(), None, b"a".to_vec()).unwrap());
+    assert!(!tree.compare_and_swap(b"k".to_vec(), None, b"b".to_vec()).unwrap());
+    tree.flush().unwrap();
+
+    // The current value now only lives in an SSTable.
+    assert!(!tree.compare_and_swap(b"k".to_vec(), Some(b"x".to_vec()), b"b".to_vec()).unwrap());
+    assert!(tree.compare_and_swap(b"k".to_vec(), Some(b"a".to_vec()), b"b".to_vec()).unwrap());
+    assert_eq!(tree.get(b"k").unwrap().value, b"b");
+
+    tree.delete(b"k".to_vec()).unwrap();
+    assert!(tree.compare_and_swap(b"k".to_vec(), None, b"c".to_vec()).unwrap());
+    assert_eq!(tree.get(b"k").unwrap().value, b"c");
+}
+
+#[test]
+fn test_get_or_insert_keeps_the_first_value() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(BrowserDB::open(dir.path()).unwrap());
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        db.set_mode(mode).unwrap();
+        let key = format!("profile_id_{:?}", mode);
+        let barrier = Arc::new(Barrier::new(8));
+        let callers: Vec<_> = (0..8)
+            .map(|i| {
+                let (db, barrier, key) = (Arc::clone(&db), Arc::clone(&barrier), key.clone());
+                thread::spawn(move || {
+                    barrier.wait();
+                    db.settings().get_or_insert(&key, &format!("caller {}", i)).unwrap()
+                })
+            })
+            .collect();
+        let seen: Vec<String> = callers.into_iter().map(|c| c.join().unwrap()).collect();
+        let stored = db.settings().get(&key).unwrap().unwrap();
+        assert!(seen.iter().all(|v| *v == stored), "{:?}: {:?} vs {}", mode, seen, stored);
+        assert_eq!(db.settings().get_or_insert(&key, "later").unwrap(), stored);
+    }
+}
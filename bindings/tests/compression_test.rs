@@ -0,0 +1,143 @@
+use browserdb::core::compression;
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{BDBFileHeader, CompressionType, TableType, BDB_HEADER_SIZE};
+use browserdb::core::lsm_tree::{LSMTree, SSTable};
+use browserdb::core::repair::QUARANTINE_DIR;
+use browserdb::{BrowserDB, BrowserDbError, HistoryEntry};
+use std::fs;
+use std::io;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::sst_files;
+
+fn open(dir: &Path, codec: CompressionType) -> io::Result<LSMTree> {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.compression = codec;
+    LSMTree::new(dir, TableType::Cache, 1024 * 1024, config)
+}
+
+fn header(path: &Path) -> BDBFileHeader {
+    BDBFileHeader::read(&mut &fs::read(path).unwrap()[..BDB_HEADER_SIZE]).unwrap()
+}
+
+/// Rewrites the header of the SSTable at `path` to claim zstd values and
+/// returns the file's new contents.
+fn relabel_as_zstd(path: &Path) -> Vec<u8> {
+    let mut bytes = fs::read(path).unwrap();
+    let mut zstd = header(path);
+    zstd.compression = CompressionType::Zstd;
+    zstd.write(&mut &mut bytes[..BDB_HEADER_SIZE]).unwrap();
+    fs::write(path, &bytes).unwrap();
+    bytes
+}
+
+fn value(i: u32) -> Vec<u8> {
+    format!("<html><body>page {}</body></html>", i).repeat(20).into_bytes()
+}
+
+/// Writes 200 compressible rows as one SSTable and returns its size.
+fn write_table(dir: &Path, codec: CompressionType) -> u64 {
+    let tree = open(dir, codec).unwrap();
+    for i in 0..200u32 {
+        tree.put(format!("url/{:04}", i).into_bytes(), value(i)).unwrap();
+    }
+    tree.flush().unwrap();
+    fs::metadata(&sst_files(dir)[0]).unwrap().len()
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn test_lz4_tables_round_trip() {
+    let plain = tempdir().unwrap();
+    let plain_size = write_table(plain.path(), CompressionType::None);
+    let dir = tempdir().unwrap();
+    let lz4_size = write_table(dir.path(), CompressionType::Lz4);
+    assert!(lz4_size * 4 < plain_size, "lz4 {} bytes, plain {} bytes", lz4_size, plain_size);
+    assert_eq!(header(&sst_files(dir.path())[0]).compression, CompressionType::Lz4);
+
+    // Reopened without compression configured: the header decides.
+    let tree = open(dir.path(), CompressionType::None).unwrap();
+    assert_eq!(tree.get(b"url/0042").unwrap().value, value(42));
+    assert_eq!(tree.with_value(b"url/0007", |v| v.to_vec()), Some(value(7)));
+    let rows = tree.scan_prefix(b"url/");
+    assert_eq!(rows.len(), 200);
+    assert!(rows.iter().enumerate().all(|(i, kv)| kv.value == value(i as u32)));
+}
+
+#[test]
+fn test_missing_codec_falls_back_to_the_best_available() {
+    let best = if cfg!(feature = "lz4") { CompressionType::Lz4 } else { CompressionType::None };
+    assert!(!compression::is_available(CompressionType::Zstd));
+    assert_eq!(compression::resolve(CompressionType::Zstd), best);
+    assert_eq!(compression::resolve(CompressionType::None), CompressionType::None);
+
+    let dir = tempdir().unwrap();
+    write_table(dir.path(), CompressionType::Zstd);
+    assert_eq!(header(&sst_files(dir.path())[0]).compression, best);
+    let tree = open(dir.path(), CompressionType::Zstd).unwrap();
+    assert_eq!(tree.get(b"url/0199").unwrap().value, value(199));
+}
+
+#[test]
+fn test_table_in_a_missing_codec_fails_cleanly() {
+    let dir = tempdir().unwrap();
+    write_table(dir.path(), CompressionType::None);
+    let path = sst_files(dir.path()).remove(0);
+    let bytes = relabel_as_zstd(&path);
+
+    let err = open(dir.path(), CompressionType::None).err().expect("opened a zstd table without zstd");
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    assert!(err.to_string().contains("compressed with zstd"), "{}", err);
+
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+    assert!(!dir.path().join(QUARANTINE_DIR).exists());
+    assert_eq!(SSTable::open(path, 0, true, 0.01).err().unwrap().kind(), io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn test_database_reports_the_missing_codec() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        db.history()
+            .insert(&HistoryEntry {
+                timestamp: 1_700_000_000_000,
+                url: "https://codec.example/".to_string(),
+                url_hash: 1,
+                title: "codec".to_string(),
+                visit_count: 1,
+            })
+            .unwrap();
+        db.flush_table(TableType::History).unwrap();
+    }
+    let container = dir.path().join("container_default");
+    let path = sst_files(&container)
+        .into_iter()
+        .find(|p| p.file_name().unwrap().to_str().unwrap().starts_with("history_"))
+        .unwrap();
+    relabel_as_zstd(&path);
+
+    for err in [BrowserDB::open(dir.path()).err().unwrap(), BrowserDB::repair(dir.path()).err().unwrap()] {
+        match err.downcast_ref::<BrowserDbError>() {
+            Some(BrowserDbError::UnsupportedCodec { table, codec, file }) => {
+                assert_eq!(*table, TableType::History);
+                assert_eq!(*codec, CompressionType::Zstd);
+                assert_eq!(file, &path);
+            }
+            other => panic!("expected UnsupportedCodec, got {:?}", other),
+        }
+        assert!(err.to_string().contains("enable the `zstd` feature"), "{}", err);
+    }
+}
+
+#[test]
+fn test_unknown_codec_byte_is_rejected() {
+    let mut bytes = Vec::new();
+    BDBFileHeader::new(TableType::Cache).write(&mut bytes).unwrap();
+    // magic, version, created_at, modified_at, flags, reserved, table_type
+    bytes[9 + 1 + 8 + 8 + 4 + 4 + 1] = 9;
+    let err = BDBFileHeader::read(&mut &bytes[..]).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
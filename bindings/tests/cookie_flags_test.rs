@@ -0,0 +1,48 @@
+use browserdb::{cookie_flags, BrowserDB, CookieEntry};
+use tempfile::tempdir;
+
+#[test]
+fn test_all_cookie_flags_round_trip() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+
+    let mut cookie = CookieEntry::new(42, "sid".to_string(), "v".to_string(), 0);
+    cookie.set_secure();
+    cookie.set_httponly();
+    cookie.set_same_site();
+    cookie.set_persistent();
+    cookie.set_partitioned();
+    db.cookies().insert(&cookie).unwrap();
+
+    let stored = db.cookies().get(42, "sid").unwrap().unwrap();
+    assert_eq!(stored.flags, 0b1_1111);
+    assert!(stored.is_secure());
+    assert!(stored.is_httponly());
+    assert!(stored.is_same_site());
+    assert!(stored.is_persistent());
+    assert!(stored.is_partitioned());
+}
+
+#[test]
+fn test_cookie_flags_are_independent() {
+    let setters: [(u8, fn(&mut CookieEntry)); 5] = [
+        (cookie_flags::SECURE, CookieEntry::set_secure),
+        (cookie_flags::HTTPONLY, CookieEntry::set_httponly),
+        (cookie_flags::SAME_SITE, CookieEntry::set_same_site),
+        (cookie_flags::PERSISTENT, CookieEntry::set_persistent),
+        (cookie_flags::PARTITIONED, CookieEntry::set_partitioned),
+    ];
+    for (bit, set) in setters {
+        let mut cookie = CookieEntry::new(1, "c".to_string(), String::new(), 0);
+        set(&mut cookie);
+        assert_eq!(cookie.flags, bit);
+        let readers = [
+            cookie.is_secure(),
+            cookie.is_httponly(),
+            cookie.is_same_site(),
+            cookie.is_persistent(),
+            cookie.is_partitioned(),
+        ];
+        assert_eq!(readers.iter().filter(|r| **r).count(), 1);
+    }
+}
@@ -0,0 +1,57 @@
+use browserdb::{BrowserDB, CookieEntry, DatabaseMode, ImportResult};
+use tempfile::tempdir;
+
+mod common;
+
+fn cookie(domain_hash: u128, name: &str, value: &str, expiry: u64) -> CookieEntry {
+    CookieEntry { domain_hash, value: value.to_string(), expiry, ..common::cookie("example.com", name) }
+}
+
+#[test]
+fn test_dedupe_keeps_the_longer_lived_cookie() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let cookies = db.cookies();
+
+    let jar = [cookie(1, "sid", "long", 2_000_000_000), cookie(1, "sid", "short", 1_800_000_000), cookie(2, "sid", "other", 0)];
+    let result = cookies.import_cookies(&jar, true).unwrap();
+    assert_eq!(result, ImportResult { imported: 2, skipped: 1, overwritten: 0 });
+    assert_eq!(cookies.get(1, "sid").unwrap().unwrap().value, "long");
+
+    // A second jar: an identical cookie, one that outlives the stored one
+    // and a session cookie that loses to it.
+    let jar = [cookie(1, "sid", "long", 2_000_000_000), cookie(2, "sid", "persistent", 1_900_000_000), cookie(3, "pref", "dark", 0)];
+    let result = cookies.import_cookies(&jar, true).unwrap();
+    assert_eq!(result, ImportResult { imported: 2, skipped: 1, overwritten: 1 });
+    assert_eq!(cookies.get(2, "sid").unwrap().unwrap().value, "persistent");
+
+    let result = cookies.import_cookies(&[cookie(2, "sid", "session", 0)], true).unwrap();
+    assert_eq!(result, ImportResult { imported: 0, skipped: 1, overwritten: 0 });
+    assert_eq!(cookies.get(2, "sid").unwrap().unwrap().value, "persistent");
+    assert_eq!(cookies.count().unwrap(), 3);
+}
+
+#[test]
+fn test_dedupe_tie_goes_to_the_newer_write() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    let cookies = db.cookies();
+    cookies.insert(&cookie(1, "sid", "stored", 100)).unwrap();
+
+    let jar = [cookie(1, "sid", "first", 100), cookie(1, "sid", "second", 100)];
+    let result = cookies.import_cookies(&jar, true).unwrap();
+    assert_eq!(result, ImportResult { imported: 1, skipped: 1, overwritten: 1 });
+    assert_eq!(cookies.get(1, "sid").unwrap().unwrap().value, "second");
+}
+
+#[test]
+fn test_import_without_dedupe_writes_every_cookie() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let cookies = db.cookies();
+    let jar = [cookie(1, "sid", "long", 2_000_000_000), cookie(1, "sid", "short", 1_800_000_000)];
+    let result = cookies.import_cookies(&jar, false).unwrap();
+    assert_eq!(result, ImportResult { imported: 2, skipped: 0, overwritten: 0 });
+    assert_eq!(cookies.get(1, "sid").unwrap().unwrap().value, "short");
+}
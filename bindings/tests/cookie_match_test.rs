@@ -0,0 +1,30 @@
+use browserdb::{BrowserDB, CookieEntry};
+use tempfile::tempdir;
+
+mod common;
+use common::cookie;
+
+fn names(mut cookies: Vec<CookieEntry>) -> Vec<String> {
+    cookies.sort_by(|a, b| a.name.cmp(&b.name));
+    cookies.into_iter().map(|c| c.name).collect()
+}
+
+#[test]
+fn test_match_for_host_follows_domain_and_path_rules() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let cookies = db.cookies();
+    cookies.insert(&cookie(".example.com", "parent")).unwrap();
+    cookies.insert(&cookie("example.com", "host_only")).unwrap();
+    cookies.insert(&cookie("sub.example.com", "own")).unwrap();
+    cookies.insert(&CookieEntry { path: "/docs".to_string(), ..cookie(".example.com", "docs") }).unwrap();
+    cookies.insert(&CookieEntry { expiry: 1, ..cookie(".example.com", "expired") }).unwrap();
+    cookies.insert(&cookie(".example.org", "other")).unwrap();
+
+    assert_eq!(names(cookies.match_for_host("sub.example.com", "/path").unwrap()), ["own", "parent"]);
+    assert_eq!(names(cookies.match_for_host("Sub.Example.com", "/docs/a").unwrap()), ["docs", "own", "parent"]);
+    assert_eq!(names(cookies.match_for_host("example.com", "/docsx").unwrap()), ["host_only", "parent"]);
+    assert_eq!(names(cookies.match_for_host("example.org", "/").unwrap()), ["other"]);
+    assert_eq!(names(cookies.match_for_host("notexample.com", "/").unwrap()), Vec::<String>::new());
+    assert_eq!(names(cookies.match_for_host("a.example.org", "/").unwrap()), ["other"]);
+}
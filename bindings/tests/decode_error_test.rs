@@ -0,0 +1,42 @@
+use browserdb::core::format::TableType;
+use browserdb::core::modes::CurrentMode;
+use browserdb::{keys, BrowserDB, BrowserDbError, DatabaseMode};
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+#[test]
+fn test_malformed_record_is_reported_and_skipped_by_scans() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        db.set_mode(mode).unwrap();
+        for i in [1, 3] {
+            db.history().insert(&page(i)).unwrap();
+        }
+        // Too short for a `HistoryEntry`, as if written by another layout.
+        let bad = keys::history_key(2);
+        match &*db.container("default").unwrap().switcher.current_mode.read() {
+            CurrentMode::Persistent(pm) => pm.history.put(bad.clone(), vec![0xff; 5]).unwrap(),
+            CurrentMode::Ultra(um) => um.history.put(bad.clone(), vec![0xff; 5], 0),
+        }
+
+        let err = db.history().get(2).unwrap_err();
+        match err.downcast_ref::<BrowserDbError>() {
+            Some(BrowserDbError::Serialization { table, key, .. }) => {
+                assert_eq!((*table, key), (TableType::History, &bad), "{:?}", mode);
+            }
+            _ => panic!("{:?}: untyped error {}", mode, err),
+        }
+        assert!(db.history().get_many(&[1, 2]).is_err());
+
+        let (found, next) = db.history().scan_page(None, 10).unwrap();
+        let hashes: Vec<u128> = found.iter().map(|e| e.url_hash).collect();
+        assert_eq!((hashes, next), (vec![1, 3], None), "{:?}", mode);
+        assert_eq!(db.history().search("page", 10).unwrap().len(), 2);
+        assert_eq!(db.history().hot_search("example.test", 10).unwrap().len(), 2);
+        assert_eq!(db.history().wipe_domain("example.test").unwrap(), 2);
+        assert!(db.history().get(1).unwrap().is_none());
+    }
+}
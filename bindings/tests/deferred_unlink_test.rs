@@ -0,0 +1,83 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+mod common;
+use common::sst_files;
+
+fn write_tables(tree: &LSMTree, tables: u32) {
+    for round in 0..tables {
+        for i in 0..20u32 {
+            tree.put(format!("key{:02}", i).into_bytes(), format!("v{}", round).into_bytes()).unwrap();
+        }
+        tree.flush().unwrap();
+    }
+}
+
+#[test]
+fn test_vacuum_keeps_files_until_snapshot_is_dropped() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    write_tables(&tree, 3);
+    let before = sst_files(dir.path());
+    assert_eq!(before.len(), 3);
+
+    let snapshot = tree.snapshot();
+    tree.vacuum().unwrap();
+    assert_eq!(tree.level_report().iter().map(|l| l.sstables).sum::<usize>(), 1);
+
+    // Replaced, but still mapped by the snapshot.
+    assert!(before.iter().all(|p| p.exists()));
+    for i in 0..20u32 {
+        let key = format!("key{:02}", i).into_bytes();
+        assert_eq!(snapshot.get(&key).unwrap().value, b"v2".to_vec());
+        assert_eq!(tree.get(&key).unwrap().value, b"v2".to_vec());
+    }
+
+    drop(snapshot);
+    assert!(before.iter().all(|p| !p.exists()));
+    assert_eq!(sst_files(dir.path()).len(), 1);
+}
+
+#[test]
+fn test_background_compaction_defers_unlink() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.max_level0_files = 2;
+    config.lsm_tree.compaction_idle_threshold_ms = 0;
+    config.lsm_tree.compaction_deadline_sec = 0;
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, config).unwrap();
+
+    write_tables(&tree, 1);
+    let snapshot = tree.snapshot();
+    let held = sst_files(dir.path());
+    write_tables(&tree, 2);
+
+    let mut compacted = false;
+    for _ in 0..50 {
+        thread::sleep(Duration::from_millis(100));
+        if tree.level_report()[1].sstables > 0 {
+            compacted = true;
+            break;
+        }
+    }
+    assert!(compacted, "level 0 was never compacted");
+    assert!(held[0].exists());
+    assert_eq!(snapshot.get(b"key07").unwrap().value, b"v0".to_vec());
+    assert_eq!(tree.get(b"key07").unwrap().value, b"v1".to_vec());
+
+    drop(snapshot);
+    assert!(!held[0].exists());
+}
+
+#[test]
+fn test_clear_removes_files_without_readers() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    write_tables(&tree, 2);
+    tree.clear().unwrap();
+    assert!(sst_files(dir.path()).is_empty());
+}
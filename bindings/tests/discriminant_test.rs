@@ -0,0 +1,42 @@
+use browserdb::core::format::{BDBFileHeader, BDBLogEntry, EntryType, TableType, BDB_VERSION};
+use std::io;
+
+/// Offset of the table type byte: magic, version, created_at, modified_at,
+/// flags, reserved.
+const TABLE_TYPE_OFFSET: usize = 9 + 1 + 8 + 8 + 4 + 4;
+
+#[test]
+fn test_unknown_table_type_in_header_is_an_error() {
+    let mut bytes = Vec::new();
+    BDBFileHeader::new(TableType::Cookies).write(&mut bytes).unwrap();
+    assert_eq!(BDBFileHeader::read(&mut &bytes[..]).unwrap().table_type, TableType::Cookies);
+
+    bytes[TABLE_TYPE_OFFSET] = 99;
+    let err = BDBFileHeader::read(&mut &bytes[..]).expect_err("read table type 99");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("unknown table type 99"), "{}", err);
+}
+
+#[test]
+fn test_unknown_entry_type_in_log_is_an_error() {
+    let mut bytes = Vec::new();
+    BDBLogEntry::new(EntryType::Delete, b"key".to_vec(), Vec::new()).write(&mut bytes).unwrap();
+    assert_eq!(BDBLogEntry::read(&mut &bytes[..], BDB_VERSION).unwrap().entry_type, EntryType::Delete);
+
+    // Used to come back as an Insert, bringing a deleted key back to life.
+    bytes[0] = 99;
+    let err = BDBLogEntry::read(&mut &bytes[..], BDB_VERSION).expect_err("read entry type 99");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_known_discriminants_round_trip() {
+    for v in 1..=7u8 {
+        assert_eq!(TableType::try_from(v).unwrap() as u8, v);
+        assert_eq!(EntryType::try_from(v).unwrap() as u8, v);
+    }
+    for v in [0u8, 8, 255] {
+        assert!(TableType::try_from(v).is_err());
+        assert!(EntryType::try_from(v).is_err());
+    }
+}
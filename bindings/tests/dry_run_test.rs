@@ -0,0 +1,79 @@
+use browserdb::hash::domain_hash;
+use browserdb::{BrowserDB, CacheEntry, DatabaseMode, PrivacyWipeResult, RemovalCount};
+use tempfile::tempdir;
+
+mod common;
+use common::{cookie, storage, visit};
+
+fn response(url_hash: u128, expires_at: u64) -> CacheEntry {
+    CacheEntry {
+        url_hash,
+        headers: String::new(),
+        body: vec![7; 100],
+        etag: String::new(),
+        last_modified: 0,
+        expires_at,
+    }
+}
+
+fn populate(db: &BrowserDB) {
+    db.history().insert(&visit("https://tracker.example/a", 1)).unwrap();
+    db.history().insert(&visit("https://cdn.tracker.example/b", 2)).unwrap();
+    db.history().insert(&visit("https://keep.example/", 3)).unwrap();
+    db.cookies().insert(&cookie("tracker.example", "id")).unwrap();
+    db.cookies().insert(&cookie("keep.example", "id")).unwrap();
+    db.localstore().insert(&storage("tracker.example", "uid")).unwrap();
+    db.localstore().insert(&storage("tracker.example", "seen")).unwrap();
+    db.localstore().insert(&storage("keep.example", "theme")).unwrap();
+    db.cache().insert(&response(10, 1_000)).unwrap();
+    db.cache().insert(&response(11, 1_500)).unwrap();
+    db.cache().insert(&response(12, 0)).unwrap();
+}
+
+fn assert_counts_then_removes(db: &BrowserDB) {
+    populate(db);
+    let before = db.stats().unwrap().total_entries;
+
+    let history = db.history().count_domain("tracker.example").unwrap();
+    let cookies = db.cookies().count_domain(domain_hash("tracker.example")).unwrap();
+    let origin = db.localstore().count_origin(domain_hash("tracker.example")).unwrap();
+    let stale = db.cache().count_stale(2_000).unwrap();
+    let privacy = db.count_privacy_wipe_domain("tracker.example").unwrap();
+    assert_eq!((history.entries, cookies.entries, origin.entries, stale.entries), (2, 1, 2, 2));
+    assert!(history.bytes > 0 && cookies.bytes > 0 && origin.bytes > 0);
+    assert!(stale.bytes > 200, "{:?}", stale);
+    assert_eq!(privacy, PrivacyWipeResult { history: 2, cookies: 1, localstore: 2, total_wiped: 5 });
+
+    // Counting twice changes nothing.
+    assert_eq!(db.stats().unwrap().total_entries, before);
+    assert_eq!(db.history().count_domain("tracker.example").unwrap(), history);
+    assert_eq!(db.cache().count_stale(2_000).unwrap(), stale);
+
+    assert_eq!(db.cache().purge_stale(2_000).unwrap(), stale.entries);
+    assert_eq!(db.privacy_wipe_domain("tracker.example").unwrap(), privacy);
+    assert_eq!(db.stats().unwrap().total_entries, before - 7);
+    assert_eq!(db.count_privacy_wipe_domain("tracker.example").unwrap().total_wiped, 0);
+    assert_eq!(db.cache().count_stale(2_000).unwrap(), RemovalCount::default());
+
+    let wipe = db.count_wipe().unwrap();
+    assert_eq!(wipe.entries as u64, before - 7);
+    assert!(wipe.bytes > 0);
+    assert!(db.history().get(3).unwrap().is_some());
+    db.wipe().unwrap();
+    assert_eq!(db.count_wipe().unwrap(), RemovalCount::default());
+}
+
+#[test]
+fn test_dry_run_counts_persistent() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_counts_then_removes(&db);
+}
+
+#[test]
+fn test_dry_run_counts_ultra() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    assert_counts_then_removes(&db);
+}
@@ -0,0 +1,83 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::{BrowserDB, DatabaseMode, LocalStoreEntry};
+use tempfile::tempdir;
+
+fn blank(origin_hash: u128, key: &str) -> LocalStoreEntry {
+    LocalStoreEntry { origin_hash, key: key.to_string(), value: String::new() }
+}
+
+fn assert_empty_values(db: &BrowserDB, stage: &str) {
+    let entry = db.localstore().get(1, "draft").unwrap();
+    assert_eq!(entry.map(|e| e.value), Some(String::new()), "localstore at {}", stage);
+    assert!(db.localstore().get(1, "gone").unwrap().is_none(), "deleted localstore key at {}", stage);
+    assert_eq!(db.settings().get("homepage").unwrap(), Some(String::new()), "setting at {}", stage);
+    assert_eq!(db.binarystore().get(b"empty").unwrap(), Some(Vec::new()), "binary value at {}", stage);
+    assert_eq!(db.binarystore().get(b"").unwrap(), Some(b"empty key".to_vec()), "empty key at {}", stage);
+    assert!(db.binarystore().get(b"deleted").unwrap().is_none(), "deleted binary key at {}", stage);
+    assert!(db.settings().get("cleared").unwrap().is_none(), "deleted setting at {}", stage);
+}
+
+fn write_empty_values(db: &BrowserDB) {
+    db.localstore().insert(&blank(1, "draft")).unwrap();
+    db.localstore().insert(&blank(1, "gone")).unwrap();
+    db.localstore().remove(1, "gone").unwrap();
+    db.settings().set("homepage", "").unwrap();
+    db.settings().set("cleared", "").unwrap();
+    db.settings().delete("cleared").unwrap();
+    db.binarystore().put(b"empty".to_vec(), Vec::new()).unwrap();
+    db.binarystore().put(Vec::new(), b"empty key".to_vec()).unwrap();
+    db.binarystore().put(b"deleted".to_vec(), Vec::new()).unwrap();
+    db.binarystore().delete(b"deleted").unwrap();
+}
+
+#[test]
+fn test_empty_values_survive_flush_compaction_and_reopen() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        write_empty_values(&db);
+        assert_empty_values(&db, "memtable");
+        assert_eq!(db.settings().count().unwrap(), 1);
+        assert_eq!(db.binarystore().count().unwrap(), 2);
+    }
+    // Reopening replays the WAL.
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_empty_values(&db, "WAL replay");
+    db.vacuum().unwrap();
+    assert_empty_values(&db, "compaction");
+    drop(db);
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_empty_values(&db, "SSTable");
+    assert_eq!(db.settings().list().unwrap().len(), 1);
+}
+
+#[test]
+fn test_empty_values_in_ultra_mode() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    write_empty_values(&db);
+    assert_empty_values(&db, "ultra");
+    db.set_mode(DatabaseMode::Persistent).unwrap();
+    assert_empty_values(&db, "switch back");
+}
+
+#[test]
+fn test_empty_key_in_several_sstables_streams_once() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::BinaryStore, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    tree.put(Vec::new(), b"old".to_vec()).unwrap();
+    tree.put(b"a".to_vec(), Vec::new()).unwrap();
+    tree.flush().unwrap();
+    tree.put(Vec::new(), Vec::new()).unwrap();
+    tree.flush().unwrap();
+
+    let entries: Vec<_> = tree.streaming_iter(&[]).map(|e| e.unwrap()).collect();
+    let keys: Vec<&[u8]> = entries.iter().map(|e| e.key.as_slice()).collect();
+    assert_eq!(keys, vec![&b""[..], &b"a"[..]]);
+    assert!(entries.iter().all(|e| e.value.is_empty()));
+    assert_eq!(tree.get(b"").map(|e| e.value), Some(Vec::new()));
+}
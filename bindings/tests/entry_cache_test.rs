@@ -0,0 +1,101 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::modes::CurrentMode;
+use browserdb::BrowserDB;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use tempfile::tempdir;
+
+fn open_tree(path: &Path, cache_entries: usize) -> LSMTree {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.entry_cache_entries = cache_entries;
+    let tree = LSMTree::new(path, TableType::Cookies, 1024 * 1024, config).unwrap();
+    for i in 0..200u32 {
+        tree.put(format!("cookie{:04}", i).into_bytes(), vec![b'v'; 32]).unwrap();
+    }
+    tree.flush().unwrap();
+    tree
+}
+
+fn decodes(tree: &LSMTree) -> u64 {
+    tree.inner.levels.iter()
+        .flat_map(|level| level.read().iter().map(|t| t.decoded.load(Ordering::Relaxed)).collect::<Vec<_>>())
+        .sum()
+}
+
+#[test]
+fn test_hot_key_is_decoded_once() {
+    let dir = tempdir().unwrap();
+    let tree = open_tree(dir.path(), 64);
+
+    assert!(tree.get(b"cookie0150").is_some());
+    let first = decodes(&tree);
+    assert!(first > 0);
+    for _ in 0..100 {
+        assert_eq!(tree.get(b"cookie0150").unwrap().value, vec![b'v'; 32]);
+    }
+    assert_eq!(decodes(&tree), first);
+    assert_eq!(tree.inner.entry_cache.hits_and_misses(), (100, 1));
+}
+
+#[test]
+fn test_disabled_cache_decodes_every_read() {
+    let dir = tempdir().unwrap();
+    let tree = open_tree(dir.path(), 0);
+
+    tree.get(b"cookie0150").unwrap();
+    let first = decodes(&tree);
+    for _ in 0..10 {
+        tree.get(b"cookie0150").unwrap();
+    }
+    assert_eq!(decodes(&tree), first * 11);
+}
+
+#[test]
+fn test_compaction_invalidates_cached_entries() {
+    let dir = tempdir().unwrap();
+    let tree = open_tree(dir.path(), 64);
+    tree.get(b"cookie0001").unwrap();
+    assert_eq!(tree.inner.entry_cache.len(), 1);
+
+    tree.put(b"cookie0001".to_vec(), b"new".to_vec()).unwrap();
+    tree.vacuum().unwrap();
+    assert!(tree.inner.entry_cache.is_empty());
+    assert_eq!(tree.get(b"cookie0001").unwrap().value, b"new".to_vec());
+}
+
+#[test]
+fn test_lru_evicts_least_recently_used() {
+    let dir = tempdir().unwrap();
+    let tree = open_tree(dir.path(), 2);
+    tree.get(b"cookie0001").unwrap();
+    tree.get(b"cookie0002").unwrap();
+    tree.get(b"cookie0001").unwrap();
+    tree.get(b"cookie0003").unwrap();
+    assert_eq!(tree.inner.entry_cache.len(), 2);
+
+    // cookie0002 was evicted; cookie0001 is still cached.
+    let before = tree.inner.entry_cache.hits_and_misses();
+    tree.get(b"cookie0001").unwrap();
+    tree.get(b"cookie0002").unwrap();
+    let after = tree.inner.entry_cache.hits_and_misses();
+    assert_eq!((after.0 - before.0, after.1 - before.1), (1, 1));
+}
+
+#[test]
+fn test_stats_report_cache_hit_rate() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.settings().set("theme", "dark").unwrap();
+    let container = db.container("default").unwrap();
+    if let CurrentMode::Persistent(pm) = &*container.switcher.current_mode.read() {
+        pm.settings.flush().unwrap();
+    };
+    assert_eq!(db.stats().unwrap().cache_hit_rate, 0.0);
+
+    for _ in 0..4 {
+        db.settings().get("theme").unwrap();
+    }
+    assert_eq!(db.stats().unwrap().cache_hit_rate, 0.75);
+}
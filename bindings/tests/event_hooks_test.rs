@@ -0,0 +1,92 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{BDBLogEntry, EntryType, TableType};
+use browserdb::core::modes::CurrentMode;
+use browserdb::core::wal::WALManager;
+use browserdb::{BrowserDB, DatabaseMode, DbEvent};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+fn recorder(db: &BrowserDB) -> Arc<Mutex<Vec<DbEvent>>> {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    db.on_event(Box::new(move |event| sink.lock().push(event.clone())));
+    events
+}
+
+#[test]
+fn test_hook_fires_on_forced_flush() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let flushes = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&flushes);
+    db.on_event(Box::new(move |event| {
+        if matches!(event, DbEvent::Flushed { .. }) {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    }));
+    let events = recorder(&db);
+
+    for i in 0..10 {
+        db.settings().set(&format!("k{}", i), "v").unwrap();
+    }
+    let container = db.container("default").unwrap();
+    if let CurrentMode::Persistent(pm) = &*container.switcher.current_mode.read() {
+        pm.settings.flush().unwrap();
+    };
+
+    assert_eq!(flushes.load(Ordering::SeqCst), 1);
+    let events = events.lock();
+    match &events[..] {
+        [DbEvent::Flushed { table, entries, bytes }] => {
+            assert_eq!(*table, TableType::Settings);
+            assert_eq!(*entries, 10);
+            assert!(*bytes > 0);
+        }
+        other => panic!("unexpected events {:?}", other),
+    }
+}
+
+#[test]
+fn test_vacuum_and_mode_switch_events() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.settings().set("a", "1").unwrap();
+    db.settings().set("a", "2").unwrap();
+    db.vacuum().unwrap();
+    let events = recorder(&db);
+
+    db.settings().set("a", "3").unwrap();
+    db.vacuum().unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+
+    let events = events.lock();
+    assert!(events.iter().any(|e| matches!(e, DbEvent::Flushed { table: TableType::Settings, entries: 1, .. })));
+    assert!(events.iter().any(|e| matches!(e, DbEvent::Compacted { table: TableType::Settings, reclaimed, .. } if *reclaimed > 0)));
+    assert_eq!(events.last(), Some(&DbEvent::ModeSwitched { from: DatabaseMode::Persistent, to: DatabaseMode::Ultra }));
+}
+
+#[test]
+fn test_wal_replay_event_on_open() {
+    let dir = tempdir().unwrap();
+    // A WAL left behind by a crash before the memtable was flushed.
+    {
+        let container_dir = dir.path().join("container_default");
+        std::fs::create_dir_all(&container_dir).unwrap();
+        let wal = WALManager::new(&container_dir.join("settings.wal"), TableType::Settings).unwrap();
+        wal.log(&mut BDBLogEntry::new(EntryType::Insert, b"unflushed".to_vec(), b"v".to_vec())).unwrap();
+    }
+
+    let config = BrowserDBConfig::default();
+    let replayed = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&replayed);
+    config.events.subscribe(Box::new(move |event| {
+        if let DbEvent::WalReplayed { table: TableType::Settings, entries } = event {
+            counter.fetch_add(*entries, Ordering::SeqCst);
+        }
+    }));
+    let db = BrowserDB::open_with_config(dir.path(), config).unwrap();
+    assert_eq!(replayed.load(Ordering::SeqCst), 1);
+    assert_eq!(db.settings().get("unflushed").unwrap().as_deref(), Some("v"));
+}
@@ -0,0 +1,135 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::modes::CurrentMode;
+use browserdb::{BrowserDB, BrowserDbError, DatabaseMode, DbEvent};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::fs;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+/// Leaves flushing to the test.
+fn config() -> BrowserDBConfig {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.memtable_max_ops = 0;
+    config
+}
+
+#[test]
+fn test_failed_flush_keeps_entries_for_a_retry() {
+    let dir = tempdir().unwrap();
+    let container_dir = dir.path().join("container_default");
+    let db = BrowserDB::open_with_config(dir.path(), config()).unwrap();
+    for i in 0..100 {
+        db.settings().set(&format!("key{}", i), &format!("value{}", i)).unwrap();
+    }
+
+    // Permission bits do not stop root, so the directory is taken away
+    // instead: no SSTable can be created in it either way.
+    fs::remove_dir_all(&container_dir).unwrap();
+    let err = db.flush_table(TableType::Settings).unwrap_err();
+    match err.downcast_ref::<BrowserDbError>() {
+        Some(BrowserDbError::Io { table, .. }) => assert_eq!(*table, TableType::Settings),
+        _ => panic!("untyped error {}", err),
+    }
+    for i in 0..100 {
+        assert_eq!(db.settings().get(&format!("key{}", i)).unwrap(), Some(format!("value{}", i)));
+    }
+    // Writes after the failure land on top of the retained entries.
+    db.settings().set("key0", "rewritten").unwrap();
+
+    fs::create_dir_all(&container_dir).unwrap();
+    db.flush_table(TableType::Settings).unwrap();
+    let container = db.container("default").unwrap();
+    match &*container.switcher.current_mode.read() {
+        CurrentMode::Persistent(pm) => {
+            assert_eq!(pm.settings.memtable_bytes(), 0);
+            assert_eq!(pm.settings.inner.levels[0].read().len(), 1);
+        }
+        CurrentMode::Ultra(_) => unreachable!(),
+    }
+    drop(container);
+    drop(db);
+
+    let db = BrowserDB::open_with_config(dir.path(), config()).unwrap();
+    assert_eq!(db.settings().get("key0").unwrap(), Some("rewritten".to_string()));
+    assert_eq!(db.settings().get("key99").unwrap(), Some("value99".to_string()));
+}
+
+#[test]
+fn test_failed_mode_switch_keeps_flushing() {
+    let dir = tempdir().unwrap();
+    let container_dir = dir.path().join("container_default");
+    let mut config = config();
+    config.lsm_tree.memtable_max_ops = 50;
+    let db = BrowserDB::open_with_config(dir.path(), config.clone()).unwrap();
+    for i in 0..10 {
+        db.settings().set(&format!("key{}", i), &format!("value{}", i)).unwrap();
+    }
+
+    // History has nothing to flush and closes; settings cannot.
+    fs::remove_dir_all(&container_dir).unwrap();
+    assert!(db.set_mode(DatabaseMode::Ultra).is_err());
+    fs::create_dir_all(&container_dir).unwrap();
+
+    // Every table is open again, background flushing included.
+    for h in 0..100 {
+        db.history().insert(&page(h)).unwrap();
+    }
+    let container = db.container("default").unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match &*container.switcher.current_mode.read() {
+            CurrentMode::Persistent(pm) => {
+                if !pm.history.inner.levels[0].read().is_empty() {
+                    break;
+                }
+            }
+            CurrentMode::Ultra(_) => panic!("switched despite the failed close"),
+        }
+        assert!(Instant::now() < deadline, "history was never flushed");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    drop(container);
+    drop(db);
+
+    let db = BrowserDB::open_with_config(dir.path(), config).unwrap();
+    assert_eq!(db.settings().get("key9").unwrap(), Some("value9".to_string()));
+    assert_eq!(db.history().count().unwrap(), 100);
+}
+
+#[test]
+fn test_failed_background_flush_is_reported() {
+    let dir = tempdir().unwrap();
+    let container_dir = dir.path().join("container_default");
+    let mut config = config();
+    config.lsm_tree.memtable_max_ops = 50;
+    let db = BrowserDB::open_with_config(dir.path(), config).unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    db.on_event(Box::new(move |event| sink.lock().push(event.clone())));
+    let wait_for = |wanted: &dyn Fn(&DbEvent) -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !events.lock().iter().any(wanted) {
+            assert!(Instant::now() < deadline, "no matching event in {:?}", events.lock());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    fs::remove_dir_all(&container_dir).unwrap();
+    for i in 0..50 {
+        db.settings().set(&format!("key{}", i), "value").unwrap();
+    }
+    wait_for(&|e| matches!(e, DbEvent::FlushFailed { table: TableType::Settings, entries: 50, .. }));
+    assert!(!events.lock().iter().any(|e| matches!(e, DbEvent::Flushed { .. })));
+
+    // The retained entries go out with the next flush.
+    fs::create_dir_all(&container_dir).unwrap();
+    for i in 50..100 {
+        db.settings().set(&format!("key{}", i), "value").unwrap();
+    }
+    wait_for(&|e| matches!(e, DbEvent::Flushed { table: TableType::Settings, entries: 100, .. }));
+}
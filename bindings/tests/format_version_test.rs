@@ -0,0 +1,174 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{BDBFileHeader, BDBLogEntry, EntryType, TableType, BDB_HEADER_SIZE, BDB_VERSION};
+use browserdb::core::lsm_tree::{KVEntry, LSMTree, SSTable};
+use browserdb::core::repair::QUARANTINE_DIR;
+use browserdb::{keys, BrowserDB};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{entry, page, sst_files};
+
+fn open(dir: &Path) -> io::Result<LSMTree> {
+    LSMTree::new(dir, TableType::Cookies, 1024 * 1024, BrowserDBConfig::default())
+}
+
+fn entries() -> BTreeMap<Vec<u8>, KVEntry> {
+    (0..100u32)
+        .map(|i| {
+            let key = format!("site{:03}/cookie", i).into_bytes();
+            let entry = KVEntry {
+                key: key.clone(),
+                value: i.to_le_bytes().to_vec(),
+                timestamp: 1_000 + i as u64,
+                expires_at: 0,
+                entry_type: EntryType::Insert,
+                deleted: false,
+            };
+            (key, entry)
+        })
+        .collect()
+}
+
+#[test]
+fn test_v1_table_is_readable_and_upgraded_on_open() {
+    let dir = tempdir().unwrap();
+    let config = BrowserDBConfig::default().lsm_tree;
+    let v1 = SSTable::create_in_format(1, 0, &entries(), dir.path(), TableType::Cookies, None, &config).unwrap();
+    assert_eq!(v1.format_version, 1);
+    assert_eq!(v1.get(b"site042/cookie").unwrap().value, 42u32.to_le_bytes().to_vec());
+    assert_eq!(v1.iter().count(), 100);
+    let old_path = v1.file_path.clone();
+    drop(v1);
+
+    {
+        let tree = open(dir.path()).unwrap();
+        assert!(tree.open_warnings().is_empty());
+        for i in 0..100u32 {
+            let key = format!("site{:03}/cookie", i).into_bytes();
+            assert_eq!(tree.get(&key).unwrap().value, i.to_le_bytes().to_vec());
+        }
+        let tables = tree.inner.levels[0].read();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].format_version, BDB_VERSION);
+    }
+
+    assert!(!old_path.exists());
+    let files = sst_files(dir.path());
+    assert_eq!(files.len(), 1);
+    let bytes = fs::read(&files[0]).unwrap();
+    assert_eq!(BDBFileHeader::read(&mut &bytes[..BDB_HEADER_SIZE]).unwrap().version, BDB_VERSION);
+
+    // The upgrade is recorded in the manifest, so the next open is a no-op.
+    let tree = open(dir.path()).unwrap();
+    assert_eq!(sst_files(dir.path()), files);
+    assert_eq!(tree.get(b"site099/cookie").unwrap().value, 99u32.to_le_bytes().to_vec());
+}
+
+#[test]
+fn test_newer_format_is_refused_and_left_alone() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = open(dir.path()).unwrap();
+        tree.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+        tree.flush().unwrap();
+    }
+    let path = sst_files(dir.path()).remove(0);
+    let mut bytes = fs::read(&path).unwrap();
+    let mut header = BDBFileHeader::read(&mut &bytes[..BDB_HEADER_SIZE]).unwrap();
+    header.version = BDB_VERSION + 1;
+    header.write(&mut &mut bytes[..BDB_HEADER_SIZE]).unwrap();
+    fs::write(&path, &bytes).unwrap();
+
+    let err = open(dir.path()).err().expect("opened a table from a newer format");
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    assert!(err.to_string().contains(&format!("version {}", BDB_VERSION + 1)), "{}", err);
+
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+    assert!(!dir.path().join(QUARANTINE_DIR).exists());
+    assert_eq!(SSTable::open(path, 0, true, 0.01).err().unwrap().kind(), io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn test_cannot_write_unknown_format() {
+    let dir = tempdir().unwrap();
+    let config = BrowserDBConfig::default().lsm_tree;
+    for version in [0, BDB_VERSION + 1] {
+        let err = SSTable::create_in_format(version, 0, &entries(), dir.path(), TableType::Cookies, None, &config).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+    assert!(sst_files(dir.path()).is_empty());
+}
+
+fn insert(key: Vec<u8>, value: Vec<u8>) -> (Vec<u8>, KVEntry) {
+    (key.clone(), entry(&key, &value, 1_000, EntryType::Insert))
+}
+
+#[test]
+fn test_v2_url_hash_keys_are_upgraded_on_open() {
+    let dir = tempdir().unwrap();
+    let container = dir.path().join("container_default");
+    fs::create_dir_all(&container).unwrap();
+    let config = BrowserDBConfig::default().lsm_tree;
+
+    // Version 2 keyed history by little-endian url_hash, rows and the title
+    // postings pointing at them alike, in SSTables...
+    let title_posting = |h: u128| [b"idx:history:title:page:".as_slice(), &h.to_le_bytes()].concat();
+    let stored: BTreeMap<_, _> = [1u128, 2, 256]
+        .into_iter()
+        .flat_map(|h| {
+            [
+                insert(h.to_le_bytes().to_vec(), bincode::serialize(&page(h)).unwrap()),
+                insert(title_posting(h), Vec::new()),
+            ]
+        })
+        .collect();
+    SSTable::create_in_format(2, 0, &stored, &container, TableType::History, None, &config).unwrap();
+    // ...and in the WAL.
+    let mut segment = Vec::new();
+    let mut header = BDBFileHeader::new(TableType::History);
+    header.version = 2;
+    header.write(&mut segment).unwrap();
+    let logged = 1u128 << 64;
+    let mut record = BDBLogEntry::new(EntryType::Insert, logged.to_le_bytes().to_vec(), bincode::serialize(&page(logged)).unwrap());
+    record.write(&mut segment).unwrap();
+    BDBLogEntry::new(EntryType::Insert, title_posting(logged), Vec::new()).write(&mut segment).unwrap();
+    fs::write(container.join("history.wal-000001"), segment).unwrap();
+
+    let found = |db: &BrowserDB| {
+        let mut hashes: Vec<_> = db.history().search("page", 10).unwrap().iter().map(|e| e.url_hash).collect();
+        hashes.sort();
+        hashes
+    };
+    for _ in 0..2 {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        for h in [1, 2, 256, logged] {
+            assert_eq!(db.history().get(h).unwrap().unwrap().url, page(h).url);
+        }
+        let (first, _) = db.history().scan_page(None, 10).unwrap();
+        assert_eq!(first.iter().map(|e| e.url_hash).collect::<Vec<_>>(), vec![1, 2, 256, logged]);
+        assert_eq!(found(&db), vec![1, 2, 256, logged]);
+        db.flush_table(TableType::History).unwrap();
+    }
+
+    // A retitled page drops its upgraded posting.
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.history().insert(&browserdb::HistoryEntry { title: "Renamed".to_string(), ..page(256) }).unwrap();
+    assert_eq!(found(&db), vec![1, 2, logged]);
+    assert_eq!(db.history().search("renamed", 10).unwrap().len(), 1);
+    drop(db);
+
+    // Cache keys move the same way; other tables keep theirs.
+    let cache_dir = tempdir().unwrap();
+    let stored: BTreeMap<_, _> = [insert(256u128.to_le_bytes().to_vec(), b"body".to_vec()), insert(b"site/cookie".to_vec(), b"1".to_vec())]
+        .into_iter()
+        .collect();
+    SSTable::create_in_format(2, 0, &stored, cache_dir.path(), TableType::Cache, None, &config).unwrap();
+    let tree = LSMTree::new(cache_dir.path(), TableType::Cache, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    assert_eq!(tree.get(&keys::cache_key(256)).unwrap().value, b"body");
+    assert!(tree.get(&256u128.to_le_bytes()).is_none());
+    assert_eq!(tree.get(b"site/cookie").unwrap().value, b"1");
+}
@@ -0,0 +1,96 @@
+use browserdb::core::clock::{Clock, SharedClock};
+use browserdb::core::config::{BrowserDBConfig, FrecencyConfig};
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+const NOW: u64 = 1_700_000_000_000;
+const DAY: u128 = 86_400_000;
+
+struct FixedClock;
+
+impl Clock for FixedClock {
+    fn now_ms(&self) -> u64 {
+        NOW
+    }
+}
+
+fn open(dir: &Path, visit_weight: f64, recency_weight: f64, heat_weight: f64) -> BrowserDB {
+    let config = BrowserDBConfig {
+        clock: SharedClock::new(Arc::new(FixedClock)),
+        frecency: FrecencyConfig { visit_weight, recency_weight, heat_weight, recency_half_life_days: 7.0 },
+        ..Default::default()
+    };
+    BrowserDB::open_with_config(dir, config).unwrap()
+}
+
+fn page(url_hash: u128, days_ago: u128, visit_count: u32) -> HistoryEntry {
+    HistoryEntry {
+        timestamp: NOW as u128 - days_ago * DAY,
+        url: format!("https://news.example/{}", url_hash),
+        url_hash,
+        title: format!("News {}", url_hash),
+        visit_count,
+    }
+}
+
+/// Page 1 was visited a minute ago once; page 2 two months ago 500 times.
+fn insert_recent_and_stale(db: &BrowserDB) {
+    db.history().insert(&page(1, 0, 1)).unwrap();
+    db.history().insert(&page(2, 60, 500)).unwrap();
+    db.history().insert(&HistoryEntry { url: "https://other.example/".to_string(), title: "Other".to_string(), ..page(3, 0, 900) }).unwrap();
+}
+
+fn ranking(db: &BrowserDB, query: &str) -> Vec<u128> {
+    db.history().search_frecency(query, 10).unwrap().iter().map(|e| e.url_hash).collect()
+}
+
+#[test]
+fn test_recency_heavy_weights_favour_the_recent_page() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path(), 0.1, 10.0, 0.0);
+    insert_recent_and_stale(&db);
+    assert_eq!(ranking(&db, "news"), vec![1, 2]);
+}
+
+#[test]
+fn test_visit_heavy_weights_favour_the_frequent_page() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path(), 10.0, 0.1, 0.0);
+    insert_recent_and_stale(&db);
+    assert_eq!(ranking(&db, "NEWS.example"), vec![2, 1]);
+    assert_eq!(ranking(&db, ""), vec![3, 2, 1]);
+    assert_eq!(db.history().search_frecency("", 1).unwrap().len(), 1);
+}
+
+#[test]
+fn test_heat_weight_ranks_pages_read_often() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path(), 0.0, 0.0, 1.0);
+    db.history().insert(&page(1, 3, 5)).unwrap();
+    db.history().insert(&page(2, 3, 5)).unwrap();
+    for _ in 0..20 {
+        db.history().get(2).unwrap();
+    }
+    assert_eq!(ranking(&db, "news"), vec![2, 1]);
+
+    // No heat in Ultra mode: equal scores fall back to recency.
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    db.history().insert(&page(1, 1, 5)).unwrap();
+    assert_eq!(ranking(&db, "news"), vec![1, 2]);
+}
+
+#[test]
+fn test_frecency_config_defaults_and_score() {
+    let config: BrowserDBConfig = toml::from_str("[frecency]\nheat_weight = 0.5\n").unwrap();
+    assert_eq!(config.frecency.visit_weight, 1.0);
+    assert_eq!(config.frecency.recency_weight, 1.0);
+    assert_eq!(config.frecency.heat_weight, 0.5);
+    assert_eq!(config.frecency.recency_half_life_days, 7.0);
+
+    let recency_only = FrecencyConfig { visit_weight: 0.0, recency_weight: 1.0, heat_weight: 0.0, recency_half_life_days: 7.0 };
+    assert_eq!(recency_only.score(1, 0.0, 0), 1.0);
+    assert!((recency_only.score(1, 7.0 * DAY as f64, 0) - 0.5).abs() < 1e-9);
+    assert_eq!(recency_only.score(1, -1000.0, 0), 1.0);
+}
@@ -0,0 +1,86 @@
+use browserdb::BrowserDB;
+use browserdb::core::config::{BrowserDBConfig, FsyncPolicy};
+use browserdb::core::modes::CurrentMode;
+use std::sync::Arc;
+use std::thread;
+use tempfile::tempdir;
+
+fn wal_syncs(db: &BrowserDB) -> u64 {
+    let container = db.container("default").unwrap();
+    let current_mode = container.switcher.current_mode.read();
+    match &*current_mode {
+        CurrentMode::Persistent(pm) => pm.settings.inner.wal.read().sync_count(),
+        CurrentMode::Ultra(_) => panic!("expected persistent mode"),
+    }
+}
+
+fn roundtrip(policy: FsyncPolicy) -> u64 {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.fsync_policy = policy;
+
+    let syncs = {
+        let db = BrowserDB::open_with_config(dir.path(), config.clone()).unwrap();
+        for i in 0..20 {
+            db.settings().set(&format!("k{}", i), "v").unwrap();
+        }
+        wal_syncs(&db)
+    };
+
+    let db = BrowserDB::open_with_config(dir.path(), config).unwrap();
+    assert_eq!(db.settings().count().unwrap(), 20);
+    assert_eq!(db.settings().get("k7").unwrap().as_deref(), Some("v"));
+    syncs
+}
+
+#[test]
+fn test_always_syncs_every_write() {
+    assert!(roundtrip(FsyncPolicy::Always) >= 20);
+}
+
+#[test]
+fn test_always_group_commits_concurrent_writers() {
+    const THREADS: usize = 8;
+    const PER_THREAD: usize = 100;
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.fsync_policy = FsyncPolicy::Always;
+
+    let syncs = {
+        let db = Arc::new(BrowserDB::open_with_config(dir.path(), config.clone()).unwrap());
+        let writers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let db = Arc::clone(&db);
+                thread::spawn(move || {
+                    for i in 0..PER_THREAD {
+                        db.settings().set(&format!("t{}_k{}", t, i), "v").unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        wal_syncs(&db)
+    };
+
+    // Every write waited for its sync, but they shared them.
+    let writes = (THREADS * PER_THREAD) as u64;
+    assert!(syncs > 0 && syncs < writes / 2, "{} syncs for {} writes", syncs, writes);
+
+    let db = BrowserDB::open_with_config(dir.path(), config).unwrap();
+    assert_eq!(db.settings().count().unwrap(), THREADS * PER_THREAD);
+    assert_eq!(db.settings().get("t7_k99").unwrap().as_deref(), Some("v"));
+}
+
+#[test]
+fn test_never_skips_sync_but_survives_reopen() {
+    assert_eq!(roundtrip(FsyncPolicy::Never), 0);
+}
+
+#[test]
+fn test_policy_parses_from_toml() {
+    let config: BrowserDBConfig = toml::from_str("[lsm_tree]\nmax_level0_files = 4\nmax_memtable_size_mb = 20\nlevel_size_thresholds_mb = [10]\nfsync_policy = \"never\"\n").unwrap();
+    assert_eq!(config.lsm_tree.fsync_policy, FsyncPolicy::Never);
+    assert_eq!(BrowserDBConfig::default().lsm_tree.fsync_policy, FsyncPolicy::OnFlush);
+}
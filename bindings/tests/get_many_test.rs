@@ -0,0 +1,84 @@
+use browserdb::{BrowserDB, CacheEntry, CookieEntry, DatabaseMode};
+use browserdb::core::modes::CurrentMode;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+fn populate(db: &BrowserDB) {
+    for i in (0..200u128).step_by(2) {
+        db.history().insert(&page(i)).unwrap();
+        db.cookies().insert(&CookieEntry::new(i % 7, format!("c{}", i), format!("v{}", i), 0)).unwrap();
+        db.cache().insert(&CacheEntry {
+            url_hash: i,
+            headers: String::new(),
+            body: vec![i as u8; 16],
+            etag: format!("etag{}", i),
+            last_modified: i,
+            expires_at: 0,
+        }).unwrap();
+    }
+}
+
+fn assert_matches_get(db: &BrowserDB) {
+    // Unsorted, with misses and duplicates.
+    let hashes: Vec<u128> = vec![198, 3, 0, 42, 42, 500, 17, 100, 2];
+
+    let many = db.history().get_many(&hashes).unwrap();
+    assert_eq!(many.len(), hashes.len());
+    for (hash, got) in hashes.iter().zip(&many) {
+        let single = db.history().get(*hash).unwrap();
+        assert_eq!(got.as_ref().map(|e| e.url.clone()), single.map(|e| e.url));
+    }
+    assert!(many[0].is_some() && many[1].is_none() && many[5].is_none());
+
+    let many = db.cache().get_many(&hashes).unwrap();
+    for (hash, got) in hashes.iter().zip(&many) {
+        let single = db.cache().get(*hash).unwrap();
+        assert_eq!(got.as_ref().map(|e| e.etag.clone()), single.map(|e| e.etag));
+    }
+
+    let names: Vec<String> = hashes.iter().map(|h| format!("c{}", h)).collect();
+    let keys: Vec<(u128, &str)> = hashes.iter().zip(&names).map(|(h, n)| (h % 7, n.as_str())).collect();
+    let many = db.cookies().get_many(&keys).unwrap();
+    for ((domain_hash, name), got) in keys.iter().zip(&many) {
+        let single = db.cookies().get(*domain_hash, name).unwrap();
+        assert_eq!(got.as_ref().map(|e| e.value.clone()), single.map(|e| e.value));
+    }
+    assert_eq!(many[3].as_ref().unwrap().value, "v42");
+}
+
+#[test]
+fn test_get_many_matches_get_persistent() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    populate(&db);
+
+    // Split the data between SSTables and the memtable.
+    let container = db.container("default").unwrap();
+    if let CurrentMode::Persistent(pm) = &*container.switcher.current_mode.read() {
+        pm.history.flush().unwrap();
+        pm.cookies.flush().unwrap();
+        pm.cache.flush().unwrap();
+    }
+    db.history().insert(&page(17)).unwrap();
+
+    assert_matches_get(&db);
+}
+
+#[test]
+fn test_get_many_matches_get_ultra() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    populate(&db);
+
+    assert_matches_get(&db);
+}
+
+#[test]
+fn test_get_many_empty() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert!(db.history().get_many(&[]).unwrap().is_empty());
+}
@@ -0,0 +1,64 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn key(i: u32) -> Vec<u8> {
+    format!("page{:04}", i).into_bytes()
+}
+
+/// The shallowest level holding `key`, as a lookup would find it.
+fn level_of(tree: &LSMTree, key: &[u8]) -> Option<usize> {
+    tree.inner.levels.iter().position(|level| level.read().iter().any(|t| t.get(key).is_some()))
+}
+
+#[test]
+fn test_hot_keys_stay_in_a_lower_level() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.max_level0_files = 2;
+    config.lsm_tree.compaction_idle_threshold_ms = 0;
+    config.lsm_tree.compaction_deadline_sec = 0;
+    config.lsm_tree.memtable_max_ops = 0;
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, config).unwrap();
+
+    for i in 0..100 {
+        tree.put(key(i), vec![1; 32]).unwrap();
+    }
+    tree.flush().unwrap();
+    // Pages 0..10 are read far more often than the hot threshold.
+    for _ in 0..20 {
+        for i in 0..10 {
+            assert!(tree.get(&key(i)).is_some());
+        }
+    }
+    for i in 100..200 {
+        tree.put(key(i), vec![2; 32]).unwrap();
+    }
+    tree.flush().unwrap();
+
+    let mut compacted = false;
+    for _ in 0..40 {
+        thread::sleep(Duration::from_millis(100));
+        if tree.level_report()[1].sstables > 0 {
+            compacted = true;
+            break;
+        }
+    }
+    assert!(compacted, "level 0 was never compacted");
+
+    for i in 0..10 {
+        assert_eq!(level_of(&tree, &key(i)), Some(0), "hot key {} left level 0", i);
+    }
+    for i in 10..200 {
+        assert_eq!(level_of(&tree, &key(i)), Some(1), "cold key {} not in level 1", i);
+    }
+    let report = tree.level_report();
+    assert_eq!(report[0].sstables, 1);
+    assert_eq!(report[1].sstables, 1);
+    for i in 0..200 {
+        assert!(tree.get(&key(i)).is_some());
+    }
+}
@@ -0,0 +1,53 @@
+use browserdb::{heat_thresholds, BrowserDB, DatabaseMode};
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+#[test]
+fn test_categorize_heat_thresholds() {
+    assert_eq!(heat_thresholds::categorize_heat(0), "cold");
+    assert_eq!(heat_thresholds::categorize_heat(heat_thresholds::WARM), "warm");
+    assert_eq!(heat_thresholds::categorize_heat(heat_thresholds::HOT - 1), "warm");
+    assert_eq!(heat_thresholds::categorize_heat(heat_thresholds::HOT), "hot");
+}
+
+#[test]
+fn test_heatmap_stats_and_category() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for i in 1..=5 {
+        db.history().insert(&page(i)).unwrap();
+    }
+
+    for _ in 0..heat_thresholds::HOT {
+        db.history().get(1).unwrap();
+    }
+    for _ in 0..heat_thresholds::WARM {
+        db.history().get(2).unwrap();
+    }
+
+    assert_eq!(db.history().heat_category(1), "hot");
+    assert_eq!(db.history().heat_category(2), "warm");
+    assert_eq!(db.history().heat_category(3), "cold");
+    assert_eq!(db.history().heat_category(999), "cold");
+
+    let stats = db.heatmap_stats().unwrap();
+    assert_eq!(stats.hot_entries, 1);
+    assert_eq!(stats.warm_entries, 1);
+    assert_eq!(stats.total_entries, stats.hot_entries + stats.warm_entries + stats.cold_entries);
+    assert!(stats.total_entries >= 5);
+    assert!(stats.average_heat > 0.0);
+}
+
+#[test]
+fn test_heatmap_stats_empty_in_ultra() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    db.history().insert(&page(1)).unwrap();
+    db.history().get(1).unwrap();
+
+    assert_eq!(db.heatmap_stats().unwrap().total_entries, 0);
+    assert_eq!(db.history().heat_category(1), "cold");
+}
@@ -0,0 +1,68 @@
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use tempfile::tempdir;
+
+fn entries() -> Vec<HistoryEntry> {
+    let titles = [
+        "Plain title",
+        "Commas, in, the title",
+        "She said \"hello\"",
+        "\"Quoted\", and, \"\"doubled\"\"",
+        "Line one\nline two",
+        "",
+    ];
+    titles
+        .iter()
+        .enumerate()
+        .map(|(i, title)| HistoryEntry {
+            timestamp: 1_700_000_000_000 + i as u128,
+            url: format!("https://csv.example/?a={},b", i),
+            url_hash: u128::MAX - i as u128,
+            title: title.to_string(),
+            visit_count: i as u32 * 3,
+        })
+        .collect()
+}
+
+#[test]
+fn test_csv_round_trip_preserves_fields() {
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        let src_dir = tempdir().unwrap();
+        let src = BrowserDB::open(src_dir.path()).unwrap();
+        src.set_mode(mode).unwrap();
+        let entries = entries();
+        src.history().insert_bulk(&entries).unwrap();
+
+        let mut csv = Vec::new();
+        assert_eq!(src.history().export_csv(&mut csv).unwrap(), entries.len());
+        let text = String::from_utf8(csv.clone()).unwrap();
+        assert!(text.starts_with("timestamp,url_hash,url,title,visit_count\n"));
+        assert!(text.contains("\"She said \"\"hello\"\"\""));
+
+        let dst_dir = tempdir().unwrap();
+        let dst = BrowserDB::open(dst_dir.path()).unwrap();
+        assert_eq!(dst.history().import_csv(csv.as_slice()).unwrap(), entries.len());
+        assert_eq!(dst.history().count().unwrap(), entries.len());
+        for entry in &entries {
+            let got = dst.history().get(entry.url_hash).unwrap().unwrap();
+            assert_eq!(got.timestamp, entry.timestamp, "{:?}", mode);
+            assert_eq!(got.url, entry.url);
+            assert_eq!(got.title, entry.title);
+            assert_eq!(got.visit_count, entry.visit_count);
+        }
+        // Imported titles are searchable like inserted ones.
+        assert_eq!(dst.history().search("commas title", 10).unwrap().len(), 1);
+    }
+}
+
+#[test]
+fn test_import_rejects_malformed_csv() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+
+    assert!(db.history().import_csv("url,title\n".as_bytes()).is_err());
+    let bad_count = "timestamp,url_hash,url,title,visit_count\n1,2,https://x/,Title,many\n";
+    assert!(db.history().import_csv(bad_count.as_bytes()).is_err());
+    let short = "timestamp,url_hash,url,title,visit_count\n1,2,https://x/\n";
+    assert!(db.history().import_csv(short.as_bytes()).is_err());
+    assert_eq!(db.history().count().unwrap(), 0);
+}
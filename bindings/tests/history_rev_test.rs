@@ -0,0 +1,71 @@
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use tempfile::tempdir;
+
+fn visit(url_hash: u128, timestamp: u128) -> HistoryEntry {
+    HistoryEntry {
+        timestamp,
+        url_hash,
+        url: format!("https://rev.example/{}", url_hash),
+        title: format!("Page {}", url_hash),
+        visit_count: 1,
+    }
+}
+
+fn assert_newest_first(entries: &[HistoryEntry]) {
+    for pair in entries.windows(2) {
+        assert!(
+            (pair[0].timestamp, std::cmp::Reverse(pair[0].url_hash)) > (pair[1].timestamp, std::cmp::Reverse(pair[1].url_hash)),
+            "{:?} listed before {:?}",
+            (pair[0].timestamp, pair[0].url_hash),
+            (pair[1].timestamp, pair[1].url_hash)
+        );
+    }
+}
+
+#[test]
+fn test_iter_rev_is_newest_first_across_flushes() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    // Hashes run against time, so key order alone would come out wrong.
+    for i in 0..300u128 {
+        db.history().insert(&visit(1000 - i, 1_700_000_000_000 + i * 10)).unwrap();
+    }
+    db.vacuum().unwrap();
+    for i in 300..400u128 {
+        db.history().insert(&visit(1000 - i, 1_700_000_000_000 + i * 10)).unwrap();
+    }
+    // A revisit moves the entry to the front; a delete drops it.
+    db.history().insert(&visit(1000, 1_800_000_000_000)).unwrap();
+    db.history().delete(900).unwrap();
+
+    let entries: Vec<HistoryEntry> = db.history().iter_rev().map(|e| e.unwrap()).collect();
+    assert_eq!(entries.len(), 399);
+    assert_eq!(entries[0].url_hash, 1000);
+    assert!(entries.iter().all(|e| e.url_hash != 900));
+    assert_eq!(entries.iter().filter(|e| e.url_hash == 1000).count(), 1);
+    assert_newest_first(&entries);
+}
+
+#[test]
+fn test_scan_page_rev_pages_and_ties() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    let entries: Vec<HistoryEntry> = (0..25u128).map(|i| visit(i, 1_700_000_000_000 + i / 3)).collect();
+    db.history().insert_bulk(&entries).unwrap();
+
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (page, next) = db.history().scan_page_rev(cursor, 4).unwrap();
+        assert!(page.len() <= 4);
+        seen.extend(page);
+        match next {
+            Some(c) => cursor = Some(c),
+            None => break,
+        }
+    }
+    assert_eq!(seen.len(), 25);
+    assert_newest_first(&seen);
+    assert_eq!(seen[0].url_hash, 24);
+}
@@ -0,0 +1,42 @@
+use browserdb::core::modes::{CurrentMode, ModeSwitchError};
+use browserdb::{BrowserDB, DatabaseMode, LocalStoreEntry};
+use std::path::Path;
+
+mod common;
+use common::page;
+
+#[test]
+fn test_in_memory_database_reads_its_writes() {
+    let db = BrowserDB::open_in_memory().unwrap();
+    assert!(db.is_in_memory());
+    for i in 0..100 {
+        db.history().insert(&page(i)).unwrap();
+    }
+    db.settings().set("theme", "dark").unwrap();
+    db.localstore()
+        .insert(&LocalStoreEntry { origin_hash: 7, key: "token".to_string(), value: "abc".to_string() })
+        .unwrap();
+
+    assert_eq!(db.history().get(42).unwrap().unwrap().title, "Page 42");
+    assert_eq!(db.settings().get("theme").unwrap(), Some("dark".to_string()));
+    assert_eq!(db.localstore().get(7, "token").unwrap().unwrap().value, "abc");
+
+    let work = db.container("work").unwrap();
+    work.history().insert(&page(1)).unwrap();
+    assert!(work.history().get(1).unwrap().is_some());
+    assert!(matches!(&*work.switcher.current_mode.read(), CurrentMode::Ultra(_)));
+    assert!(!Path::new("container_work").exists());
+    db.close().unwrap();
+}
+
+#[test]
+fn test_in_memory_database_cannot_become_persistent() {
+    let db = BrowserDB::open_in_memory().unwrap();
+    db.settings().set("kept", "yes").unwrap();
+
+    let err = db.set_mode(DatabaseMode::Persistent).unwrap_err();
+    assert!(matches!(err.downcast_ref::<ModeSwitchError>(), Some(ModeSwitchError::NoDirectory)), "{}", err);
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    assert_eq!(db.settings().get("kept").unwrap(), Some("yes".to_string()));
+    assert!(db.lsm_report().unwrap().is_empty());
+}
@@ -0,0 +1,85 @@
+use browserdb::{BrowserDB, SnapshotMeta};
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+#[test]
+fn test_incremental_snapshot_restores_with_its_base() {
+    let dir = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+    let (full_dir, incr_dir) = (backups.path().join("full"), backups.path().join("incr"));
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for i in 0..500 {
+        db.history().insert(&page(i)).unwrap();
+    }
+    db.settings().set("theme", "light").unwrap();
+    let full = db.snapshot(&full_dir).unwrap();
+    assert!(full.is_full());
+    assert_eq!(full.stored_bytes(), full.files.iter().map(|f| f.len).sum::<u64>());
+
+    for i in 500..600 {
+        db.history().insert(&page(i)).unwrap();
+    }
+    db.history().delete(7).unwrap();
+    db.settings().set("theme", "dark").unwrap();
+    let incr = db.snapshot_incremental(&full, &incr_dir).unwrap();
+    assert_eq!(incr.chain, vec![full.id.clone()]);
+    assert_eq!(SnapshotMeta::load(&incr_dir).unwrap(), incr);
+    // The base's SSTables are referenced, not copied again.
+    let reused: Vec<_> = incr.files.iter().filter(|f| f.stored_in == full.id).collect();
+    assert!(reused.iter().any(|f| f.path.ends_with(".sst")), "{:?}", incr.files);
+    assert!(incr.stored_bytes() < full.stored_bytes());
+    db.close().unwrap();
+
+    let restored = backups.path().join("restored");
+    BrowserDB::restore(&[&full_dir, &incr_dir], &restored).unwrap();
+    let db = BrowserDB::open(&restored).unwrap();
+    assert_eq!(db.history().get(599).unwrap().unwrap().title, "Page 599");
+    assert!(db.history().get(7).unwrap().is_none());
+    assert_eq!(db.history().get(8).unwrap().unwrap().title, "Page 8");
+    assert_eq!(db.settings().get("theme").unwrap(), Some("dark".to_string()));
+    drop(db);
+
+    // The base alone still restores the earlier state.
+    let earlier = backups.path().join("earlier");
+    BrowserDB::restore(&[&full_dir], &earlier).unwrap();
+    let db = BrowserDB::open(&earlier).unwrap();
+    assert!(db.history().get(7).unwrap().is_some());
+    assert!(db.history().get(599).unwrap().is_none());
+    assert_eq!(db.settings().get("theme").unwrap(), Some("light".to_string()));
+}
+
+#[test]
+fn test_restore_rejects_damaged_or_incomplete_chains() {
+    let dir = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+    let (full_dir, incr_dir) = (backups.path().join("full"), backups.path().join("incr"));
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for i in 0..100 {
+        db.history().insert(&page(i)).unwrap();
+    }
+    let full = db.snapshot(&full_dir).unwrap();
+    db.history().insert(&page(100)).unwrap();
+    let incr = db.snapshot_incremental(&full, &incr_dir).unwrap();
+
+    // An increment cannot be restored without its base.
+    let err = BrowserDB::restore(&[&incr_dir], backups.path().join("a")).unwrap_err();
+    assert_eq!(err.downcast_ref::<io::Error>().unwrap().kind(), io::ErrorKind::InvalidInput);
+    // Nor can a snapshot be written over another.
+    assert!(db.snapshot(&full_dir).is_err());
+
+    let sst = incr.files.iter().find(|f| f.stored_in == incr.id && f.path.ends_with(".sst")).unwrap();
+    let path = incr_dir.join(&sst.path);
+    let mut bytes = fs::read(&path).unwrap();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0xff;
+    fs::write(&path, bytes).unwrap();
+    let err = BrowserDB::restore(&[&full_dir, &incr_dir], backups.path().join("b")).unwrap_err();
+    let err = err.downcast_ref::<io::Error>().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains(&sst.path), "{}", err);
+}
@@ -0,0 +1,66 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::manifest::Manifest;
+use std::io;
+use tempfile::tempdir;
+
+fn sorted_entries(n: u32) -> impl Iterator<Item = (Vec<u8>, Vec<u8>)> {
+    (0..n).map(|i| (format!("visit:{:08}", i).into_bytes(), format!("row {}", i).into_bytes()))
+}
+
+#[test]
+fn test_ingest_sorted_bypasses_memtable() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    tree.ingest_sorted(sorted_entries(50_000)).unwrap();
+
+    assert!(tree.inner.memtable.iter().all(|m| m.read().is_empty()));
+    let l0 = tree.inner.levels[0].read().len();
+    assert!(l0 >= 2, "1 MiB memtable should split the import, got {} tables", l0);
+
+    for i in (0..50_000).step_by(997) {
+        let key = format!("visit:{:08}", i).into_bytes();
+        assert_eq!(tree.get(&key).unwrap().value, format!("row {}", i).into_bytes());
+    }
+    assert_eq!(tree.all_entries().len(), 50_000);
+
+    // The tables are in the manifest, so they survive a reopen.
+    let (_, listed) = Manifest::new(dir.path(), "history").load().unwrap().unwrap();
+    assert_eq!(listed.len(), l0);
+    drop(tree);
+    let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    assert_eq!(tree.all_entries().len(), 50_000);
+}
+
+#[test]
+fn test_ingest_sorted_shadows_buffered_writes() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    tree.put(b"visit:00000010".to_vec(), b"stale".to_vec()).unwrap();
+
+    tree.ingest_sorted(sorted_entries(100)).unwrap();
+    assert_eq!(tree.get(b"visit:00000010").unwrap().value, b"row 10".to_vec());
+}
+
+#[test]
+fn test_ingest_sorted_rejects_unsorted_input() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::History, 64 * 1024, BrowserDBConfig::default()).unwrap();
+
+    // Fails well after the first table has been written.
+    let entries = sorted_entries(5000).chain(std::iter::once((b"visit:00000001".to_vec(), b"late".to_vec())));
+    let err = tree.ingest_sorted(entries).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    assert!(tree.inner.levels.iter().all(|l| l.read().is_empty()));
+    assert!(tree.get(b"visit:00000001").is_none());
+    let leftover = std::fs::read_dir(dir.path()).unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".sst"))
+        .count();
+    assert_eq!(leftover, 0);
+
+    let dup = vec![(b"a".to_vec(), b"1".to_vec()), (b"a".to_vec(), b"2".to_vec())];
+    assert_eq!(tree.ingest_sorted(dup.into_iter()).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
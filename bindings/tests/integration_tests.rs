@@ -129,6 +129,7 @@ fn test_cache_operations() {
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_millis(),
+        expires_at: 0,
     };
     
     // 插入缓存条目
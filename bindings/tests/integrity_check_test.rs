@@ -0,0 +1,105 @@
+use browserdb::core::config::BLOOM_FPR;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::SSTable;
+use browserdb::BrowserDB;
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+fn history_sstables(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir.join("container_default"))
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.file_name().unwrap().to_str().unwrap().starts_with("history_") && p.extension().is_some_and(|e| e == "sst"))
+        .collect()
+}
+
+/// Offset of the entry in the middle of the SSTable at `path`.
+fn middle_entry_offset(path: &Path) -> u64 {
+    let sst = SSTable::open(path.to_path_buf(), 0, true, BLOOM_FPR).unwrap();
+    let mut offsets = Vec::new();
+    for block in &sst.index {
+        let mut iter = sst.block_iter(block);
+        loop {
+            let offset = iter.position();
+            if iter.next().is_none() {
+                break;
+            }
+            offsets.push(offset);
+        }
+    }
+    offsets[offsets.len() / 2]
+}
+
+fn open_with_history(dir: &Path) -> BrowserDB {
+    let db = BrowserDB::open(dir).unwrap();
+    for i in 0..500 {
+        db.history().insert(&page(i)).unwrap();
+    }
+    db.flush_table(TableType::History).unwrap();
+    db
+}
+
+#[test]
+fn test_healthy_database_passes() {
+    let dir = tempdir().unwrap();
+    let db = open_with_history(dir.path());
+    let report = db.check_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert!(report.sstables_checked >= 1);
+    assert!(report.entries_checked >= 500);
+}
+
+#[test]
+fn test_damaged_entry_is_pinpointed() {
+    let dir = tempdir().unwrap();
+    let db = open_with_history(dir.path());
+    let sstables = history_sstables(dir.path());
+    assert_eq!(sstables.len(), 1);
+    let path = &sstables[0];
+    let offset = middle_entry_offset(path);
+
+    // Flip a byte inside the entry, in place, as a bad sector would.
+    let mut file = OpenOptions::new().read(true).write(true).open(path).unwrap();
+    let mut bytes = fs::read(path).unwrap();
+    bytes[offset as usize + 12] ^= 0xff;
+    file.seek(SeekFrom::Start(offset + 12)).unwrap();
+    file.write_all(&bytes[offset as usize + 12..offset as usize + 13]).unwrap();
+    drop(file);
+
+    let report = db.check_integrity().unwrap();
+    assert!(!report.is_ok());
+    assert!(report.problems.iter().all(|p| p.table == TableType::History && &p.file == path), "{:?}", report.problems);
+    let damaged = report.problems.iter().find(|p| p.reason.contains("damaged entry")).expect("no damaged entry reported");
+    assert_eq!(damaged.offset, Some(offset));
+    // The block holding it fails its checksum too, and so does the file.
+    assert!(report.problems.iter().any(|p| p.reason.contains("checksum") && p.offset.is_some_and(|o| o <= offset)));
+    // The check changed nothing.
+    assert_eq!(fs::read(path).unwrap(), bytes);
+}
+
+#[test]
+fn test_manifest_mismatches_are_reported() {
+    let dir = tempdir().unwrap();
+    let db = open_with_history(dir.path());
+    let listed = history_sstables(dir.path()).pop().unwrap();
+    let stray = listed.with_file_name("history_0_1_999999.sst");
+    fs::copy(&listed, &stray).unwrap();
+    fs::remove_file(&listed).unwrap();
+
+    let report = db.check_integrity().unwrap();
+    let reasons: Vec<(&Path, &str)> = report.problems.iter().map(|p| (p.file.as_path(), p.reason.as_str())).collect();
+    assert_eq!(
+        reasons,
+        vec![
+            (stray.as_path(), "SSTable is not listed in the manifest"),
+            (listed.as_path(), "SSTable listed in the manifest is missing"),
+        ]
+    );
+    assert!(stray.exists());
+}
@@ -0,0 +1,95 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::ffi::*;
+use browserdb::hash::hash_str;
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use std::ffi::{CStr, CString};
+use std::path::Path;
+use std::thread;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+/// Writes `profile`'s pages 0..200 under the same url hashes the other
+/// profile uses, flushing along the way.
+fn fill(db: &BrowserDB, profile: &str) {
+    for url_hash in 0..200 {
+        let entry = HistoryEntry {
+            url: format!("https://{}.example/{}", profile, url_hash),
+            title: format!("{} page", profile),
+            ..page(url_hash)
+        };
+        db.history().insert(&entry).unwrap();
+        if url_hash % 50 == 49 {
+            db.flush_table(TableType::History).unwrap();
+        }
+    }
+    db.settings().set("profile", profile).unwrap();
+}
+
+fn assert_only(db: &BrowserDB, profile: &str) {
+    assert_eq!(db.history().count().unwrap(), 200);
+    for url_hash in [0, 77, 199] {
+        assert_eq!(db.history().get(url_hash).unwrap().unwrap().title, format!("{} page", profile));
+    }
+    assert_eq!(db.history().search(profile, 500).unwrap().len(), 200);
+    assert_eq!(db.settings().get("profile").unwrap().as_deref(), Some(profile));
+}
+
+fn open(path: &Path, config: &BrowserDBConfig) -> BrowserDB {
+    BrowserDB::open_with_config(path, config.clone()).unwrap()
+}
+
+#[test]
+fn test_databases_at_different_paths_are_independent() {
+    let (work_dir, home_dir) = (tempdir().unwrap(), tempdir().unwrap());
+    // One config for both: they share its compaction pool and nothing else.
+    let config = BrowserDBConfig::default();
+    let work = open(work_dir.path(), &config);
+    let home = open(home_dir.path(), &config);
+
+    thread::scope(|s| {
+        s.spawn(|| fill(&work, "work"));
+        s.spawn(|| fill(&home, "home"));
+    });
+    assert_only(&work, "work");
+    assert_only(&home, "home");
+
+    home.set_mode(DatabaseMode::Ultra).unwrap();
+    home.wipe().unwrap();
+    work.vacuum().unwrap();
+    assert_eq!(home.history().count().unwrap(), 0);
+    assert_only(&work, "work");
+
+    drop((work, home));
+    assert_only(&open(work_dir.path(), &config), "work");
+}
+
+#[test]
+fn test_ffi_handles_are_independent() {
+    let (a, b) = (tempdir().unwrap(), tempdir().unwrap());
+    let (path_a, path_b) = (CString::new(a.path().to_str().unwrap()).unwrap(), CString::new(b.path().to_str().unwrap()).unwrap());
+    let db_a = browserdb_open(path_a.as_ptr());
+    let db_b = browserdb_open(path_b.as_ptr());
+    assert!(!db_a.is_null() && !db_b.is_null());
+
+    let url = CString::new("https://shared.example/").unwrap();
+    let (title_a, title_b) = (CString::new("Seen in A").unwrap(), CString::new("Seen in B").unwrap());
+    assert_eq!(browserdb_history_insert(db_a, url.as_ptr(), title_a.as_ptr(), 1), 0);
+    assert_eq!(browserdb_history_insert(db_b, url.as_ptr(), title_b.as_ptr(), 1), 0);
+
+    let url_hash = hash_str("https://shared.example/");
+    for (db, expected) in [(db_a, "Seen in A"), (db_b, "Seen in B")] {
+        let title = browserdb_history_get_title(db, url_hash as u64, (url_hash >> 64) as u64);
+        assert!(!title.is_null());
+        assert_eq!(unsafe { CStr::from_ptr(title) }.to_str().unwrap(), expected);
+        browserdb_free_string(title);
+    }
+
+    browserdb_close(db_a);
+    let title = browserdb_history_get_title(db_b, url_hash as u64, (url_hash >> 64) as u64);
+    assert_eq!(unsafe { CStr::from_ptr(title) }.to_str().unwrap(), "Seen in B");
+    browserdb_free_string(title);
+    browserdb_close(db_b);
+}
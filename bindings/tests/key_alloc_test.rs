@@ -0,0 +1,78 @@
+use browserdb::{BrowserDB, DatabaseMode};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations_during<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATIONS.with(|n| n.get());
+    let result = f();
+    (result, ALLOCATIONS.with(|n| n.get()) - before)
+}
+
+/// Hashes of 1000 entries that are never stored.
+fn missing() -> impl Iterator<Item = u128> {
+    (0..1_000u128).map(|i| (i << 8) | 1)
+}
+
+/// Allocations of `HistoryTable::get` for 1000 hashes that are not stored,
+/// after each was looked up once so the heat tracker knows it. A miss has
+/// no value to copy, so anything counted here is spent on the key. Stored
+/// hashes are multiples of 256, whose keys all start with a zero byte; the
+/// missing ones do not, so SSTables rule them out by key range and no
+/// bloom filter false positive decodes a block.
+fn allocations_per_miss_round(db: &BrowserDB) -> usize {
+    let history = db.history();
+    for h in missing() {
+        assert!(history.get(h).unwrap().is_none());
+    }
+    let (_, n) = allocations_during(|| {
+        for h in missing() {
+            assert!(history.get(h).unwrap().is_none());
+        }
+    });
+    n
+}
+
+#[test]
+fn test_get_does_not_allocate_for_the_key() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for i in 0..500 {
+        db.history().insert(&page(i << 8)).unwrap();
+    }
+    assert_eq!(allocations_per_miss_round(&db), 0, "memtable");
+
+    db.vacuum().unwrap();
+    assert_eq!(allocations_per_miss_round(&db), 0, "SSTable");
+
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    assert_eq!(allocations_per_miss_round(&db), 0, "Ultra");
+    // A hit allocates the value copy and the entry's url and title, and
+    // nothing for the key.
+    let (entry, n) = allocations_during(|| db.history().get(7 << 8).unwrap().unwrap());
+    assert_eq!((entry.url_hash, entry.title), (7 << 8, page(7 << 8).title));
+    assert_eq!(n, 3);
+}
@@ -0,0 +1,90 @@
+use browserdb::keys;
+use browserdb::{BrowserDB, CookieEntry, DatabaseMode, LocalStoreEntry};
+use tempfile::tempdir;
+
+const HASH: u128 = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210;
+
+#[test]
+fn test_keys_round_trip() {
+    assert_eq!(keys::decode_history_key(&keys::history_key(HASH)), Some(HASH));
+    assert_eq!(keys::decode_cache_key(&keys::cache_key(u128::MAX)), Some(u128::MAX));
+    assert_eq!(keys::decode_cookie_key(&keys::cookie_key(HASH, "session")), Some((HASH, "session".to_string())));
+    assert_eq!(keys::decode_localstore_key(&keys::localstore_key(0, "")), Some((0, String::new())));
+    assert_eq!(keys::decode_localstore_key(&keys::localstore_key(HASH, "ключ")), Some((HASH, "ключ".to_string())));
+
+    assert_eq!(keys::decode_history_key(&keys::cookie_key(HASH, "a")), None);
+    assert_eq!(keys::decode_cookie_key(&keys::history_key(HASH)), None);
+    let mut truncated = keys::cookie_key(HASH, "session");
+    truncated.pop();
+    assert_eq!(keys::decode_cookie_key(&truncated), None);
+}
+
+#[test]
+fn test_keys_match_stored_layout() {
+    // Existing databases were written with bincode keys.
+    assert_eq!(keys::bookmark_key(HASH), bincode::serialize(&HASH).unwrap());
+    assert_eq!(keys::cookie_key(HASH, "id"), bincode::serialize(&(HASH, "id")).unwrap());
+    assert_eq!(keys::localstore_key(HASH, "theme"), bincode::serialize(&(HASH, "theme")).unwrap());
+}
+
+#[test]
+fn test_prefix_is_a_byte_prefix_of_composite_keys() {
+    for name in ["", "a", "session", "a much longer cookie name"] {
+        assert!(keys::cookie_key(HASH, name).starts_with(&keys::cookie_prefix(HASH)));
+        assert!(keys::localstore_key(HASH, name).starts_with(&keys::localstore_prefix(HASH)));
+        assert!(!keys::cookie_key(HASH + 1, name).starts_with(&keys::cookie_prefix(HASH)));
+    }
+}
+
+#[test]
+fn test_tables_agree_on_keys() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        db.set_mode(mode).unwrap();
+        let cookie = CookieEntry {
+            domain_hash: HASH,
+            name: "id".to_string(),
+            value: "1".to_string(),
+            path: "/".to_string(),
+            domain: "example.com".to_string(),
+            expiry: 0,
+            flags: 0,
+        };
+        db.cookies().insert(&cookie).unwrap();
+        db.cookies().insert(&CookieEntry { domain_hash: HASH + 1, ..cookie.clone() }).unwrap();
+        assert_eq!(db.cookies().get(HASH, "id").unwrap().unwrap().value, "1");
+        assert_eq!(db.cookies().get_by_domain(HASH).unwrap().len(), 1);
+        db.cookies().delete(HASH, "id").unwrap();
+        assert!(db.cookies().get(HASH, "id").unwrap().is_none());
+
+        let item = LocalStoreEntry { origin_hash: HASH, key: "theme".to_string(), value: "dark".to_string() };
+        db.localstore().insert(&item).unwrap();
+        assert_eq!(db.localstore().get(HASH, "theme").unwrap().unwrap().value, "dark");
+        assert_eq!(db.localstore().get_by_origin(HASH).unwrap().len(), 1);
+        db.localstore().remove(HASH, "theme").unwrap();
+        assert!(db.localstore().get(HASH, "theme").unwrap().is_none());
+    }
+}
+
+#[test]
+fn test_ordered_keys_sort_numerically() {
+    let mut encoded: Vec<[u8; 16]> = (1..=256u128).rev().map(keys::ordered_u128).collect();
+    encoded.sort();
+    let decoded: Vec<u128> = encoded.iter().map(|k| keys::decode_ordered_u128(k).unwrap()).collect();
+    assert_eq!(decoded, (1..=256).collect::<Vec<_>>());
+
+    let mut encoded: Vec<[u8; 8]> = [u64::MAX, 0, 1 << 40, 255, 256].into_iter().map(keys::ordered_u64).collect();
+    encoded.sort();
+    let decoded: Vec<u64> = encoded.iter().map(|k| keys::decode_ordered_u64(k).unwrap()).collect();
+    assert_eq!(decoded, vec![0, 255, 256, 1 << 40, u64::MAX]);
+    assert_eq!(keys::decode_ordered_u64(&[1, 2, 3]), None);
+
+    let mut history: Vec<Vec<u8>> = (1..=256u128).rev().map(keys::history_key).collect();
+    history.sort();
+    assert_eq!(history, (1..=256u128).map(keys::history_key).collect::<Vec<_>>());
+    assert!(keys::cache_key(1) < keys::cache_key(256));
+
+    // Bookmark keys keep bincode's little-endian layout, which does not.
+    assert!(keys::bookmark_key(256) < keys::bookmark_key(1));
+}
@@ -0,0 +1,52 @@
+use browserdb::{BrowserDB, DatabaseMode};
+use tempfile::tempdir;
+
+fn fill(db: &BrowserDB) {
+    let kv = db.kv();
+    kv.put("sync", &[0x00, 0xFF, b':', 1], b"first").unwrap();
+    kv.put("sync", &[0x00, 0xFF, b':', 2], &[0u8, 1, 2, 255]).unwrap();
+    kv.put("sync", &[0x01], b"other prefix").unwrap();
+    // A namespace that is a prefix of another stays separate.
+    kv.put("syn", &[b'c', 0x00, 0xFF], b"shadow").unwrap();
+}
+
+fn assert_scans(db: &BrowserDB) {
+    let kv = db.kv();
+    let scanned = kv.scan_prefix("sync", &[0x00, 0xFF]).unwrap();
+    assert_eq!(
+        scanned,
+        vec![
+            (vec![0x00, 0xFF, b':', 1], b"first".to_vec()),
+            (vec![0x00, 0xFF, b':', 2], vec![0u8, 1, 2, 255]),
+        ]
+    );
+    assert_eq!(kv.scan_prefix("sync", &[]).unwrap().len(), 3);
+    assert_eq!(kv.scan_prefix("syn", &[]).unwrap(), vec![(vec![b'c', 0x00, 0xFF], b"shadow".to_vec())]);
+    assert_eq!(kv.get("sync", &[0x01]).unwrap(), Some(b"other prefix".to_vec()));
+    assert_eq!(kv.get("other", &[0x01]).unwrap(), None);
+}
+
+#[test]
+fn test_kv_binary_keys_and_namespaces() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    fill(&db);
+    assert_scans(&db);
+
+    db.vacuum().unwrap();
+    assert_scans(&db);
+
+    db.kv().delete("sync", &[0x01]).unwrap();
+    assert_eq!(db.kv().get("sync", &[0x01]).unwrap(), None);
+    assert_eq!(db.kv().scan_prefix("sync", &[]).unwrap().len(), 2);
+    assert!(db.settings().list().unwrap().is_empty());
+}
+
+#[test]
+fn test_kv_in_ultra_mode() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    fill(&db);
+    assert_scans(&db);
+}
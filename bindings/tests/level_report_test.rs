@@ -0,0 +1,58 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::modes::CurrentMode;
+use browserdb::{BrowserDB, DatabaseMode};
+use tempfile::tempdir;
+
+#[test]
+fn test_level_report_counts_level0_tables() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    // Keep every flush in level 0.
+    config.lsm_tree.max_level0_files = 100;
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, config).unwrap();
+
+    for batch in 0..3u8 {
+        for i in 0..50u8 {
+            tree.put(vec![b'a' + batch, i], vec![0; 100]).unwrap();
+        }
+        tree.flush().unwrap();
+    }
+
+    let report = tree.level_report();
+    assert_eq!(report.len(), 7);
+    let l0 = &report[0];
+    assert_eq!(l0.level, 0);
+    assert_eq!(l0.sstables, 3);
+    assert!(l0.bytes > 3 * 50 * 100);
+    assert_eq!(l0.key_range, Some((vec![b'a', 0], vec![b'c', 49])));
+    assert!(report[1..].iter().all(|l| l.sstables == 0 && l.bytes == 0 && l.key_range.is_none()));
+
+    // A vacuum folds them into one table.
+    tree.vacuum().unwrap();
+    let tables: usize = tree.level_report().iter().map(|l| l.sstables).sum();
+    assert_eq!(tables, 1);
+}
+
+#[test]
+fn test_lsm_report_covers_every_table() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.settings().set("theme", "dark").unwrap();
+    let container = db.container("default").unwrap();
+    if let CurrentMode::Persistent(pm) = &*container.switcher.current_mode.read() {
+        pm.settings.flush().unwrap();
+    };
+
+    let report = db.lsm_report().unwrap();
+    assert_eq!(report.len(), 7);
+    let (_, settings) = report.iter().find(|(t, _)| *t == TableType::Settings).unwrap();
+    assert_eq!(settings[0].sstables, 1);
+    assert_eq!(settings[0].key_range, Some((b"theme".to_vec(), b"theme".to_vec())));
+    let (_, history) = report.iter().find(|(t, _)| *t == TableType::History).unwrap();
+    assert!(history.iter().all(|l| l.sstables == 0));
+
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    assert!(db.lsm_report().unwrap().is_empty());
+}
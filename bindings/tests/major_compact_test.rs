@@ -0,0 +1,80 @@
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::SSTable;
+use browserdb::{BookmarkEntry, BrowserDB, CompactionReport, DatabaseMode, HistoryEntry};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+fn bookmark(url_hash: u128) -> BookmarkEntry {
+    BookmarkEntry {
+        url_hash,
+        url: format!("https://bulk.example/{}", url_hash),
+        title: format!("Bookmark {}", url_hash),
+        folder: "imported".to_string(),
+        created_at: url_hash as u64,
+    }
+}
+
+fn bookmark_files(base: &Path) -> Vec<PathBuf> {
+    fs::read_dir(base.join("container_default"))
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "sst"))
+        .filter(|p| p.file_name().unwrap().to_string_lossy().starts_with("bookmarks_"))
+        .collect()
+}
+
+#[test]
+fn test_major_compact_leaves_one_file_of_live_keys() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for batch in 0..4u128 {
+        for url_hash in batch * 100..(batch + 1) * 100 {
+            db.bookmarks().insert(&bookmark(url_hash)).unwrap();
+        }
+        db.flush_table(TableType::Bookmarks).unwrap();
+    }
+    for url_hash in (0..400).step_by(2) {
+        db.bookmarks().delete(url_hash).unwrap();
+    }
+    db.history().insert(&HistoryEntry {
+        timestamp: 1,
+        url: "https://other.example/".to_string(),
+        url_hash: 1,
+        title: "Untouched".to_string(),
+        visit_count: 1,
+    }).unwrap();
+
+    let report = db.major_compact(TableType::Bookmarks).unwrap();
+    assert!(report.bytes_after < report.bytes_before, "{:?}", report);
+
+    let levels = &db.lsm_report().unwrap().into_iter().find(|(t, _)| *t == TableType::Bookmarks).unwrap().1;
+    let populated: Vec<_> = levels.iter().filter(|l| l.sstables > 0).collect();
+    assert_eq!(populated.len(), 1);
+    assert_eq!(populated[0].sstables, 1);
+    assert_eq!(populated[0].bytes, report.bytes_after);
+
+    let files = bookmark_files(dir.path());
+    assert_eq!(files.len(), 1, "{:?}", files);
+    let sstable = SSTable::open(files[0].clone(), populated[0].level as u8, true, 0.01).unwrap();
+    let entries: Vec<_> = sstable.iter().map(|e| e.unwrap()).collect();
+    assert_eq!(entries.len(), 200);
+    assert!(entries.iter().all(|e| !e.deleted));
+    assert_eq!(db.bookmarks().count().unwrap(), 200);
+    assert!(db.history().get(1).unwrap().is_some());
+
+    // Nothing left to drop the second time.
+    let again = db.major_compact(TableType::Bookmarks).unwrap();
+    assert_eq!(again.bytes_before, again.bytes_after);
+}
+
+#[test]
+fn test_major_compact_is_a_no_op_in_ultra_mode() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    db.bookmarks().insert(&bookmark(1)).unwrap();
+    assert_eq!(db.major_compact(TableType::Bookmarks).unwrap(), CompactionReport::default());
+    assert_eq!(db.bookmarks().count().unwrap(), 1);
+}
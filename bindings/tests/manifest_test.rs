@@ -0,0 +1,84 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{EntryType, TableType};
+use browserdb::core::lsm_tree::SSTable;
+use browserdb::core::manifest::Manifest;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{entry, open_tree, sst_names};
+
+fn manifest_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = Manifest::new(dir, "history")
+        .load()
+        .unwrap()
+        .expect("manifest missing")
+        .1
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_manifest_tracks_flushes() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = open_tree(dir.path(), TableType::History);
+        for round in 0..3u8 {
+            tree.put(vec![b'k', round], vec![round]).unwrap();
+            tree.flush().unwrap();
+        }
+        assert_eq!(manifest_names(dir.path()), sst_names(dir.path()));
+    }
+
+    let tree = open_tree(dir.path(), TableType::History);
+    assert_eq!(tree.inner.levels[0].read().len(), 3);
+    assert_eq!(tree.get(&[b'k', 2]).unwrap().value, vec![2]);
+}
+
+#[test]
+fn test_recovery_ignores_orphan_from_interrupted_compaction() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = open_tree(dir.path(), TableType::History);
+        tree.put(b"a".to_vec(), b"old".to_vec()).unwrap();
+        tree.flush().unwrap();
+        tree.put(b"a".to_vec(), b"new".to_vec()).unwrap();
+        tree.flush().unwrap();
+    }
+    let live = sst_names(dir.path());
+    assert_eq!(live.len(), 2);
+
+    // A compaction wrote its output but crashed before committing it.
+    let mut merged = BTreeMap::new();
+    merged.insert(b"a".to_vec(), entry(b"a", b"old", 0, EntryType::Insert));
+    merged.insert(b"ghost".to_vec(), entry(b"ghost", b"boo", 0, EntryType::Insert));
+    SSTable::create(1, &merged, dir.path(), TableType::History, None, &BrowserDBConfig::default().lsm_tree).unwrap();
+    assert_eq!(sst_names(dir.path()).len(), 3);
+
+    let tree = open_tree(dir.path(), TableType::History);
+    assert_eq!(tree.inner.levels[0].read().len(), 2);
+    assert!(tree.inner.levels[1].read().is_empty());
+    assert_eq!(tree.get(b"a").unwrap().value, b"new".to_vec());
+    assert!(tree.get(b"ghost").is_none());
+    assert_eq!(sst_names(dir.path()), live);
+}
+
+#[test]
+fn test_legacy_directory_gets_manifest() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = open_tree(dir.path(), TableType::History);
+        tree.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+        tree.flush().unwrap();
+    }
+    fs::remove_file(Manifest::new(dir.path(), "history").path()).unwrap();
+
+    let tree = open_tree(dir.path(), TableType::History);
+    assert_eq!(tree.get(b"a").unwrap().value, b"1".to_vec());
+    assert_eq!(manifest_names(dir.path()), sst_names(dir.path()));
+}
@@ -0,0 +1,64 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::manifest::Manifest;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn small_tree_config(max_levels: usize) -> BrowserDBConfig {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.max_levels = max_levels;
+    // Every flush compacts, and every non-empty level overflows.
+    config.lsm_tree.max_level0_files = 1;
+    config.lsm_tree.level_size_thresholds_mb = vec![0; 8];
+    config.lsm_tree.compaction_idle_threshold_ms = 0;
+    config.lsm_tree.compaction_deadline_sec = 0;
+    config
+}
+
+#[test]
+fn test_compaction_stays_within_configured_levels() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, small_tree_config(4)).unwrap();
+    assert_eq!(tree.level_report().len(), 4);
+
+    for i in 0..40u32 {
+        tree.put(format!("key{:03}", i).into_bytes(), vec![0; 512]).unwrap();
+        tree.flush().unwrap();
+    }
+
+    let mut reached_last = false;
+    for _ in 0..20 {
+        thread::sleep(Duration::from_millis(250));
+        if tree.level_report()[3].sstables > 0 {
+            reached_last = true;
+            break;
+        }
+    }
+    assert!(reached_last, "compaction never reached the last level");
+
+    let report = tree.level_report();
+    assert_eq!(report.len(), 4);
+    assert!(report.iter().all(|l| l.level < 4));
+    let (levels, tables) = Manifest::new(dir.path(), "settings").load().unwrap().unwrap();
+    assert_eq!(levels, 4);
+    assert!(tables.iter().all(|(level, _)| *level < 4));
+    for i in 0..40u32 {
+        assert!(tree.get(format!("key{:03}", i).as_bytes()).is_some());
+    }
+}
+
+#[test]
+fn test_reopen_keeps_recorded_level_count() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, small_tree_config(3)).unwrap();
+        tree.put(b"homepage".to_vec(), b"about:blank".to_vec()).unwrap();
+        tree.flush().unwrap();
+    }
+
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    assert_eq!(tree.level_report().len(), 3);
+    assert_eq!(tree.get(b"homepage").unwrap().value, b"about:blank".to_vec());
+}
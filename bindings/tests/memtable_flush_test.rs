@@ -0,0 +1,48 @@
+use browserdb::core::format::TableType;
+use browserdb::core::modes::CurrentMode;
+use browserdb::{BrowserDB, DatabaseMode};
+use tempfile::tempdir;
+
+fn fill_ratio(db: &BrowserDB, table: TableType) -> f32 {
+    let container = db.container("default").unwrap();
+    let ratio = match &*container.switcher.current_mode.read() {
+        CurrentMode::Persistent(pm) => pm.table(table).memtable_fill_ratio(),
+        CurrentMode::Ultra(_) => unreachable!(),
+    };
+    ratio
+}
+
+#[test]
+fn test_flush_largest_picks_fullest_memtable() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_eq!(db.flush_largest().unwrap(), None);
+
+    db.settings().set("theme", "dark").unwrap();
+    for i in 0..200 {
+        db.binarystore().put(format!("blob{}", i).into_bytes(), vec![7u8; 512]).unwrap();
+    }
+    let settings = fill_ratio(&db, TableType::Settings);
+    let binary = fill_ratio(&db, TableType::BinaryStore);
+    assert!(binary > settings && settings > 0.0);
+
+    assert_eq!(db.flush_largest().unwrap(), Some(TableType::BinaryStore));
+    assert_eq!(fill_ratio(&db, TableType::BinaryStore), 0.0);
+    assert!(fill_ratio(&db, TableType::Settings) > 0.0);
+    assert_eq!(db.binarystore().get(b"blob42").unwrap(), Some(vec![7u8; 512]));
+
+    db.flush_table(TableType::Settings).unwrap();
+    assert_eq!(fill_ratio(&db, TableType::Settings), 0.0);
+    assert_eq!(db.settings().get("theme").unwrap().as_deref(), Some("dark"));
+    assert_eq!(db.flush_largest().unwrap(), None);
+}
+
+#[test]
+fn test_flush_controls_are_noops_in_ultra() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    db.settings().set("theme", "dark").unwrap();
+    db.flush_table(TableType::Settings).unwrap();
+    assert_eq!(db.flush_largest().unwrap(), None);
+}
@@ -0,0 +1,64 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+fn open(dir: &Path, max_ops: usize) -> LSMTree {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.memtable_max_ops = max_ops;
+    LSMTree::new(dir, TableType::Settings, 20 * 1024 * 1024, config).unwrap()
+}
+
+fn write_tiny(tree: &LSMTree, count: u32) {
+    for i in 0..count {
+        tree.put(i.to_le_bytes().to_vec(), b"x".to_vec()).unwrap();
+    }
+}
+
+fn wait_for_flush(tree: &LSMTree, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if tree.inner.flushes.load(Ordering::Relaxed) > 0 {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn test_op_count_flushes_tiny_entries_under_byte_limit() {
+    let dir = tempdir().unwrap();
+    let tree = open(dir.path(), 1000);
+    write_tiny(&tree, 1001);
+    assert!(tree.memtable_bytes() < 1024 * 1024);
+
+    assert!(wait_for_flush(&tree, Duration::from_secs(5)), "1001 writes did not flush");
+    assert!(tree.level_report()[0].sstables > 0);
+    for i in 0..1001u32 {
+        assert_eq!(tree.get(&i.to_le_bytes()).map(|e| e.value), Some(b"x".to_vec()));
+    }
+    assert_eq!(tree.all_entries().len(), 1001);
+}
+
+#[test]
+fn test_zero_max_ops_flushes_on_size_alone() {
+    let dir = tempdir().unwrap();
+    let tree = open(dir.path(), 0);
+    write_tiny(&tree, 1001);
+
+    // The flush thread polls every 100 ms; give it a few rounds.
+    assert!(!wait_for_flush(&tree, Duration::from_millis(400)));
+    assert_eq!(tree.level_report()[0].sstables, 0);
+    assert_eq!(tree.all_entries().len(), 1001);
+}
+
+#[test]
+fn test_max_ops_defaults_when_missing_from_toml() {
+    let config: BrowserDBConfig =
+        toml::from_str("[lsm_tree]\nmax_level0_files = 4\nmax_memtable_size_mb = 20\nlevel_size_thresholds_mb = [10]\n").unwrap();
+    assert_eq!(config.lsm_tree.memtable_max_ops, 1000);
+}
@@ -0,0 +1,64 @@
+use browserdb::{BrowserDB, DatabaseMode};
+use browserdb::core::config::{BrowserDBConfig, FsyncPolicy};
+use browserdb::core::metadata::DatabaseMetadata;
+use browserdb::core::modes::CurrentMode;
+use std::fs;
+use tempfile::tempdir;
+
+fn current_mode(db: &BrowserDB) -> DatabaseMode {
+    match &*db.container("default").unwrap().switcher.current_mode.read() {
+        CurrentMode::Persistent(_) => DatabaseMode::Persistent,
+        CurrentMode::Ultra(_) => DatabaseMode::Ultra,
+    }
+}
+
+#[test]
+fn test_fresh_database_opens_persistent() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_eq!(current_mode(&db), DatabaseMode::Persistent);
+    assert_eq!(DatabaseMetadata::load(dir.path()).unwrap().mode, DatabaseMode::Persistent);
+}
+
+#[test]
+fn test_reopen_restores_ultra_mode() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        db.settings().set("theme", "dark").unwrap();
+        db.set_mode(DatabaseMode::Ultra).unwrap();
+    }
+    assert_eq!(DatabaseMetadata::load(dir.path()).unwrap().mode, DatabaseMode::Ultra);
+
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        assert_eq!(current_mode(&db), DatabaseMode::Ultra);
+        // Data on disk is loaded into the in-memory tables.
+        assert_eq!(db.settings().get("theme").unwrap().as_deref(), Some("dark"));
+        db.set_mode(DatabaseMode::Persistent).unwrap();
+    }
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_eq!(current_mode(&db), DatabaseMode::Persistent);
+}
+
+#[test]
+fn test_reopen_restores_last_config() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.fsync_policy = FsyncPolicy::Never;
+    drop(BrowserDB::open_with_config(dir.path(), config).unwrap());
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let container = db.container("default").unwrap();
+    assert_eq!(container.switcher.config.ext_config.lsm_tree.fsync_policy, FsyncPolicy::Never);
+}
+
+#[test]
+fn test_unreadable_metadata_falls_back_to_persistent() {
+    let dir = tempdir().unwrap();
+    fs::write(DatabaseMetadata::path(dir.path()), "mode = 42").unwrap();
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_eq!(current_mode(&db), DatabaseMode::Persistent);
+}
@@ -0,0 +1,77 @@
+use browserdb::core::clock::{MockClock, SharedClock};
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+
+const START: u64 = 1_700_000_000_000;
+
+fn config(clock: &Arc<MockClock>) -> BrowserDBConfig {
+    BrowserDBConfig { clock: SharedClock::new(clock.clone()), ..Default::default() }
+}
+
+#[test]
+fn test_heat_decays_by_the_factor_per_minute() {
+    let dir = tempdir().unwrap();
+    let clock = Arc::new(MockClock::new(START));
+    let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, config(&clock)).unwrap();
+    tree.put(b"page".to_vec(), b"v".to_vec()).unwrap();
+    for _ in 0..100 {
+        tree.get(b"page").unwrap();
+    }
+    let heat = &tree.inner.heat_tracker;
+    assert_eq!(heat.get_heat(b"page"), 100);
+
+    // Under a minute nothing decays.
+    clock.advance(Duration::from_secs(59));
+    assert_eq!(heat.get_heat(b"page"), 100);
+    // 0.95 per whole minute since the last access.
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(heat.get_heat(b"page"), 95);
+    clock.advance(Duration::from_secs(4 * 60));
+    assert_eq!(heat.get_heat(b"page"), (100.0 * 0.95f64.powi(5)) as u32);
+}
+
+#[test]
+fn test_ttl_expires_when_the_clock_passes_it() {
+    let dir = tempdir().unwrap();
+    let clock = Arc::new(MockClock::new(START));
+    let tree = LSMTree::new(dir.path(), TableType::Cache, 1024 * 1024, config(&clock)).unwrap();
+    tree.put_with_ttl(b"memory".to_vec(), b"v".to_vec(), 60_000).unwrap();
+    tree.put_with_ttl(b"flushed".to_vec(), b"v".to_vec(), 60_000).unwrap();
+    tree.flush().unwrap();
+    tree.put_with_ttl(b"memory".to_vec(), b"v".to_vec(), 60_000).unwrap();
+
+    clock.advance(Duration::from_secs(60));
+    assert!(tree.get(b"memory").is_some());
+    assert_eq!(tree.scan_prefix(b"").len(), 2);
+
+    clock.advance(Duration::from_millis(1));
+    assert!(tree.get(b"memory").is_none());
+    assert!(tree.get(b"flushed").is_none());
+    assert_eq!(tree.scan_prefix(b"").len(), 0);
+}
+
+#[test]
+fn test_ultra_ttl_follows_the_database_clock() {
+    let dir = tempdir().unwrap();
+    let clock = Arc::new(MockClock::new(START));
+    let db = BrowserDB::open_with_config(dir.path(), config(&clock)).unwrap();
+    let page = |url_hash| HistoryEntry {
+        timestamp: START as u128,
+        url: "https://clock.example/".to_string(),
+        url_hash,
+        title: "Clock".to_string(),
+        visit_count: 1,
+    };
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        db.set_mode(mode).unwrap();
+        db.history().insert_with_ttl(&page(1), 1_000).unwrap();
+        assert!(db.history().get(1).unwrap().is_some(), "{:?}", mode);
+        clock.advance(Duration::from_secs(2));
+        assert!(db.history().get(1).unwrap().is_none(), "{:?}", mode);
+    }
+}
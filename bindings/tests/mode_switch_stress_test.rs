@@ -0,0 +1,92 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+const WRITERS: u128 = 4;
+const WRITES: u128 = 1000;
+
+fn visit(writer: u128, i: u128) -> HistoryEntry {
+    let url_hash = writer << 32 | i;
+    HistoryEntry {
+        timestamp: 1_700_000_000_000 + i,
+        url_hash,
+        url: format!("https://switch.example/{}/{}", writer, i),
+        title: format!("page {}", i),
+        visit_count: 1,
+    }
+}
+
+fn assert_all_written(db: &BrowserDB, stage: &str) {
+    for writer in 0..WRITERS {
+        for i in 0..WRITES {
+            assert!(db.history().get(visit(writer, i).url_hash).unwrap().is_some(), "lost {}/{} {}", writer, i, stage);
+        }
+        let last = db.settings().get(&format!("writer{}", writer)).unwrap();
+        assert_eq!(last, Some((WRITES - 1).to_string()));
+    }
+}
+
+#[test]
+fn test_mode_switches_under_concurrent_reads_and_writes() {
+    let dir = tempdir().unwrap();
+    {
+        // A small memtable keeps flushes running while the modes switch.
+        let mut config = BrowserDBConfig::default();
+        config.lsm_tree.max_memtable_size_mb = 1;
+        let db = Arc::new(BrowserDB::open_with_config(dir.path(), config).unwrap());
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|writer| {
+                let db = Arc::clone(&db);
+                thread::spawn(move || {
+                    for i in 0..WRITES {
+                        db.history().insert(&visit(writer, i)).unwrap();
+                        db.settings().set(&format!("writer{}", writer), &i.to_string()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..WRITERS)
+            .map(|writer| {
+                let (db, stop) = (Arc::clone(&db), Arc::clone(&stop));
+                thread::spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        if let Some(entry) = db.history().get(visit(writer, 0).url_hash).unwrap() {
+                            assert_eq!(entry.url, visit(writer, 0).url);
+                        }
+                        db.settings().list().unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        // Keep switching until the writers are done, ending in Persistent.
+        let mut switches = 0;
+        while switches < 6 || !writers.iter().all(|w| w.is_finished()) || switches % 2 == 1 {
+            let mode = if switches % 2 == 0 { DatabaseMode::Ultra } else { DatabaseMode::Persistent };
+            db.set_mode(mode).unwrap();
+            switches += 1;
+            thread::sleep(Duration::from_millis(20));
+        }
+        stop.store(true, Ordering::Relaxed);
+        for thread in readers.into_iter().chain(writers) {
+            thread.join().unwrap();
+        }
+
+        // Let the last flushes land; a read racing the flush thread is not
+        // what this test is about.
+        db.flush_table(TableType::History).unwrap();
+        db.flush_table(TableType::Settings).unwrap();
+        assert_all_written(&db, "before reopen");
+    }
+
+    // The last switch left the database in Persistent mode; reopening
+    // must find every write on disk.
+    assert_all_written(&BrowserDB::open(dir.path()).unwrap(), "after reopen");
+}
@@ -0,0 +1,105 @@
+use browserdb::core::format::TableType;
+use browserdb::BrowserDB;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{open_tree, table_sst_files};
+
+/// Three flushed tables, keys `round/0..10` in each.
+fn write_three_tables(dir: &Path) {
+    let tree = open_tree(dir, TableType::Cookies);
+    for round in 0..3 {
+        for i in 0..10 {
+            tree.put(format!("{}/{}", round, i).into_bytes(), vec![round as u8]).unwrap();
+        }
+        tree.flush().unwrap();
+    }
+}
+
+fn assert_one_bad_table_skipped(dir: &Path, bad: &Path) {
+    let tree = open_tree(dir, TableType::Cookies);
+    let loaded: usize = tree.inner.levels.iter().map(|l| l.read().len()).sum();
+    assert_eq!(loaded, 2);
+
+    let warnings = tree.open_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].file, bad);
+    assert!(!warnings[0].reason.is_empty());
+    let quarantined = warnings[0].quarantined.as_ref().unwrap();
+    assert!(quarantined.exists());
+    assert!(!bad.exists());
+
+    let survivors: Vec<u8> = (0..3u8).filter(|r| tree.get(format!("{}/0", r).as_bytes()).is_some()).collect();
+    assert_eq!(survivors.len(), 2);
+    for round in survivors {
+        for i in 0..10 {
+            assert_eq!(tree.get(format!("{}/{}", round, i).as_bytes()).unwrap().value, vec![round]);
+        }
+    }
+}
+
+#[test]
+fn test_corrupt_table_listed_in_manifest() {
+    let dir = tempdir().unwrap();
+    write_three_tables(dir.path());
+    let bad = table_sst_files(dir.path(), "cookies_")[1].clone();
+    fs::write(&bad, b"not an sstable").unwrap();
+
+    assert_one_bad_table_skipped(dir.path(), &bad);
+    // Quarantined, so the next open is clean.
+    assert!(open_tree(dir.path(), TableType::Cookies).open_warnings().is_empty());
+}
+
+#[test]
+fn test_corrupt_table_without_manifest() {
+    let dir = tempdir().unwrap();
+    write_three_tables(dir.path());
+    fs::remove_file(dir.path().join("cookies.manifest")).unwrap();
+    let bad = table_sst_files(dir.path(), "cookies_")[0].clone();
+    let bytes = fs::read(&bad).unwrap();
+    fs::write(&bad, &bytes[..bytes.len() / 2]).unwrap();
+
+    assert_one_bad_table_skipped(dir.path(), &bad);
+}
+
+#[test]
+fn test_missing_table_is_reported_but_not_quarantined() {
+    let dir = tempdir().unwrap();
+    write_three_tables(dir.path());
+    let gone = table_sst_files(dir.path(), "cookies_")[2].clone();
+    fs::remove_file(&gone).unwrap();
+
+    let tree = open_tree(dir.path(), TableType::Cookies);
+    let warnings = tree.open_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].file, gone);
+    assert_eq!(warnings[0].quarantined, None);
+    assert!(tree.get(b"0/0").is_some());
+}
+
+#[test]
+fn test_database_reports_warnings_per_table() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        db.settings().set("theme", "dark").unwrap();
+        db.flush_table(TableType::Settings).unwrap();
+        db.settings().set("homepage", "about:blank").unwrap();
+        db.flush_table(TableType::Settings).unwrap();
+        assert!(db.open_warnings().is_empty());
+    }
+
+    let container = dir.path().join("container_default");
+    let bad = table_sst_files(&container, "settings_")[0].clone();
+    fs::write(&bad, b"not an sstable").unwrap();
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let warnings = db.open_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].0, TableType::Settings);
+    assert_eq!(warnings[0].1.file, bad);
+    assert_eq!(db.settings().get("homepage").unwrap(), Some("about:blank".to_string()));
+    assert_eq!(db.settings().get("theme").unwrap(), None);
+}
@@ -0,0 +1,70 @@
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::format::TableType;
+use browserdb::core::config::BrowserDBConfig;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::sst_files;
+
+const KEY_COUNT: usize = 2000;
+
+fn key(i: usize) -> Vec<u8> {
+    format!("sql:data:bench_table:{:08}", i).into_bytes()
+}
+
+fn sst_size(dir: &Path) -> u64 {
+    sst_files(dir).iter().map(|p| fs::metadata(p).unwrap().len()).sum()
+}
+
+/// Writes the prefixed keys into a fresh tree, flushes, reopens it and
+/// returns the on-disk SSTable size plus every value read back.
+fn write_and_reopen(dir: &Path, prefix_compression: bool) -> (u64, Vec<Option<Vec<u8>>>) {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.prefix_compression = prefix_compression;
+
+    {
+        let tree = LSMTree::new(dir, TableType::LocalStore, 64 * 1024 * 1024, config.clone()).unwrap();
+        for i in 0..KEY_COUNT {
+            tree.put(key(i), format!("v{}", i).into_bytes()).unwrap();
+        }
+        tree.flush().unwrap();
+    }
+
+    let size = sst_size(dir);
+    let tree = LSMTree::new(dir, TableType::LocalStore, 64 * 1024 * 1024, config).unwrap();
+    let values = (0..KEY_COUNT + 1).map(|i| tree.get(&key(i)).map(|e| e.value)).collect();
+    (size, values)
+}
+
+#[test]
+fn test_prefix_compression_shrinks_sstable() {
+    let plain_dir = tempdir().unwrap();
+    let compressed_dir = tempdir().unwrap();
+
+    let (plain_size, plain_values) = write_and_reopen(plain_dir.path(), false);
+    let (compressed_size, compressed_values) = write_and_reopen(compressed_dir.path(), true);
+
+    assert!(
+        compressed_size * 10 < plain_size * 7,
+        "expected at least 30% reduction: {} -> {}",
+        plain_size,
+        compressed_size
+    );
+    assert_eq!(plain_values, compressed_values);
+    assert_eq!(compressed_values[1234], Some(b"v1234".to_vec()));
+    assert_eq!(compressed_values[KEY_COUNT], None);
+}
+
+#[test]
+fn test_prefix_compressed_scan_after_reopen() {
+    let dir = tempdir().unwrap();
+    let (_, _) = write_and_reopen(dir.path(), true);
+
+    let tree = LSMTree::new(dir.path(), TableType::LocalStore, 64 * 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    let entries = tree.scan_prefix(b"sql:data:bench_table:0000012");
+    let keys: Vec<Vec<u8>> = entries.into_iter().map(|e| e.key).collect();
+    let expected: Vec<Vec<u8>> = (120..130).map(key).collect();
+    assert_eq!(keys, expected);
+}
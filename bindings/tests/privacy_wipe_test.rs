@@ -0,0 +1,57 @@
+use browserdb::hash::domain_hash;
+use browserdb::{BrowserDB, DatabaseMode, PrivacyWipeResult};
+use tempfile::tempdir;
+
+mod common;
+use common::{cookie, storage, visit};
+
+/// Three history entries, two cookies and two storage keys for
+/// tracker.example, and one of each for keep.example.
+fn populate(db: &BrowserDB) {
+    db.history().insert(&visit("https://tracker.example/a", 1)).unwrap();
+    db.history().insert(&visit("https://tracker.example/b", 2)).unwrap();
+    db.history().insert(&visit("https://cdn.tracker.example/c", 3)).unwrap();
+    db.history().insert(&visit("https://keep.example/", 4)).unwrap();
+    db.cookies().insert(&cookie("tracker.example", "id")).unwrap();
+    db.cookies().insert(&cookie(".tracker.example", "session")).unwrap();
+    db.cookies().insert(&cookie("keep.example", "id")).unwrap();
+    db.localstore().insert(&storage("tracker.example", "uid")).unwrap();
+    db.localstore().insert(&storage("tracker.example", "seen")).unwrap();
+    db.localstore().insert(&storage("keep.example", "theme")).unwrap();
+}
+
+fn assert_wiped(db: &BrowserDB) {
+    let result = db.privacy_wipe_domain("tracker.example").unwrap();
+    assert_eq!(result, PrivacyWipeResult { history: 3, cookies: 2, localstore: 2, total_wiped: 7 });
+
+    for url_hash in 1..=3 {
+        assert!(db.history().get(url_hash).unwrap().is_none());
+    }
+    assert!(db.cookies().get_by_domain(domain_hash("tracker.example")).unwrap().is_empty());
+    assert!(db.localstore().get(domain_hash("tracker.example"), "uid").unwrap().is_none());
+    assert!(db.localstore().get(domain_hash("tracker.example"), "seen").unwrap().is_none());
+
+    assert!(db.history().get(4).unwrap().is_some());
+    assert!(db.cookies().get(domain_hash("keep.example"), "id").unwrap().is_some());
+    assert!(db.localstore().get(domain_hash("keep.example"), "theme").unwrap().is_some());
+    assert_eq!(db.cookies().count().unwrap(), 1);
+
+    assert_eq!(db.privacy_wipe_domain("tracker.example").unwrap().total_wiped, 0);
+}
+
+#[test]
+fn test_privacy_wipe_domain_persistent() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    populate(&db);
+    assert_wiped(&db);
+}
+
+#[test]
+fn test_privacy_wipe_domain_ultra() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    populate(&db);
+    assert_wiped(&db);
+}
@@ -0,0 +1,89 @@
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use std::collections::HashMap;
+use tempfile::tempdir;
+
+/// Parses the text exposition format strictly enough to catch malformed
+/// output: every sample belongs to a family declared by a `# TYPE` line and
+/// has a numeric value. Returns `name{labels}` -> value.
+fn parse(text: &str) -> HashMap<String, f64> {
+    let mut types = HashMap::new();
+    let mut samples = HashMap::new();
+    for line in text.lines() {
+        if let Some(comment) = line.strip_prefix("# ") {
+            let parts: Vec<&str> = comment.splitn(3, ' ').collect();
+            assert!(parts.len() == 3 && (parts[0] == "HELP" || parts[0] == "TYPE"), "bad comment: {}", line);
+            if parts[0] == "TYPE" {
+                assert!(["gauge", "counter"].contains(&parts[2]), "bad type: {}", line);
+                assert!(types.insert(parts[1].to_string(), parts[2].to_string()).is_none(), "duplicate family: {}", line);
+            }
+            continue;
+        }
+        let (series, value) = line.rsplit_once(' ').unwrap_or_else(|| panic!("bad sample: {}", line));
+        let name = series.split('{').next().unwrap();
+        assert!(types.contains_key(name), "sample before its # TYPE: {}", line);
+        if let Some(labels) = series.strip_prefix(name) {
+            assert!(labels.is_empty() || (labels.starts_with('{') && labels.ends_with('}')), "bad labels: {}", line);
+            for pair in labels.trim_matches(|c| c == '{' || c == '}').split(',').filter(|p| !p.is_empty()) {
+                let (key, quoted) = pair.split_once('=').unwrap();
+                assert!(key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'), "bad label: {}", line);
+                assert!(quoted.len() >= 2 && quoted.starts_with('"') && quoted.ends_with('"'), "bad label: {}", line);
+            }
+        }
+        let value: f64 = value.parse().unwrap_or_else(|_| panic!("bad value: {}", line));
+        assert!(samples.insert(series.to_string(), value).is_none(), "duplicate series: {}", line);
+    }
+    samples
+}
+
+#[test]
+fn test_metrics_prometheus_exports_table_and_lsm_series() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for i in 0..40u128 {
+        db.history()
+            .insert(&HistoryEntry {
+                timestamp: 1_700_000_000_000 + i,
+                url_hash: i,
+                url: format!("https://metrics.example/{}", i),
+                title: format!("page {}", i),
+                visit_count: 1,
+            })
+            .unwrap();
+    }
+    db.vacuum().unwrap();
+    db.settings().set("homepage", "about:blank").unwrap();
+    for i in 0..40u128 {
+        db.history().get(i).unwrap();
+    }
+
+    let metrics = parse(&db.metrics_prometheus());
+    assert_eq!(metrics["browserdb_entries{table=\"history\"}"], 40.0);
+    assert_eq!(metrics["browserdb_entries{table=\"settings\"}"], 1.0);
+    assert!(metrics.contains_key("browserdb_entries{table=\"binarystore\"}"));
+    let history_tables: f64 = metrics
+        .iter()
+        .filter(|(series, _)| series.starts_with("browserdb_sstables{table=\"history\",level="))
+        .map(|(_, v)| v)
+        .sum();
+    assert!(history_tables >= 1.0);
+    assert!(metrics["browserdb_memtable_bytes{table=\"settings\"}"] > 0.0);
+    assert!(metrics["browserdb_flushes_total{table=\"history\"}"] >= 1.0);
+    assert!(metrics.contains_key("browserdb_compactions_total{table=\"history\"}"));
+    assert!(metrics.contains_key("browserdb_write_stalls_total{table=\"history\"}"));
+    let hit_ratio = metrics["browserdb_entry_cache_hit_ratio{table=\"history\"}"];
+    assert!((0.0..=1.0).contains(&hit_ratio));
+    let fpr = metrics["browserdb_bloom_fpr_estimate{table=\"history\"}"];
+    assert!(fpr > 0.0 && fpr < 0.5, "fpr {}", fpr);
+}
+
+#[test]
+fn test_metrics_prometheus_in_ultra_mode_has_only_entries() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    db.settings().set("theme", "dark").unwrap();
+
+    let metrics = parse(&db.metrics_prometheus());
+    assert_eq!(metrics["browserdb_entries{table=\"settings\"}"], 1.0);
+    assert!(metrics.keys().all(|series| series.starts_with("browserdb_entries{")));
+}
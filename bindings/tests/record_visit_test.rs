@@ -0,0 +1,79 @@
+use browserdb::hash::hash_str;
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use std::sync::Arc;
+use std::thread;
+use tempfile::tempdir;
+
+#[test]
+fn test_record_visit_counts_repeat_visits() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let url = "https://visits.example/article";
+
+    let first = db.history().record_visit(url, "Draft title").unwrap();
+    assert_eq!(first.visit_count, 1);
+    db.history().record_visit(url, "Final title").unwrap();
+    let third = db.history().record_visit(url, "").unwrap();
+
+    let stored = db.history().get(hash_str(url)).unwrap().unwrap();
+    assert_eq!(stored.visit_count, 3);
+    assert_eq!(stored.url, url);
+    assert_eq!(stored.title, "Final title");
+    assert_eq!(stored.timestamp, third.timestamp);
+    assert!(stored.timestamp >= first.timestamp);
+    assert_eq!(db.history().count().unwrap(), 1);
+    assert_eq!(db.history().search("final", 10).unwrap().len(), 1);
+    assert!(db.history().search("draft", 10).unwrap().is_empty());
+}
+
+#[test]
+fn test_concurrent_visits_are_all_counted() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(BrowserDB::open(dir.path()).unwrap());
+    let url = "https://visits.example/busy";
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let db = Arc::clone(&db);
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    db.history().record_visit(url, "Busy").unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(db.history().get(hash_str(url)).unwrap().unwrap().visit_count, 100);
+}
+
+#[test]
+fn test_concurrent_increment_visit_counts_every_visit() {
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        let dir = tempdir().unwrap();
+        let db = Arc::new(BrowserDB::open(dir.path()).unwrap());
+        db.set_mode(mode).unwrap();
+        let url = "https://visits.example/tabs";
+        let url_hash = hash_str(url);
+        db.history()
+            .insert(&HistoryEntry { timestamp: 1, url: url.to_string(), url_hash, title: "Tabs".to_string(), visit_count: 0 })
+            .unwrap();
+
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let db = Arc::clone(&db);
+                thread::spawn(move || db.history().increment_visit(url_hash, "").unwrap().unwrap())
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stored = db.history().get(url_hash).unwrap().unwrap();
+        assert_eq!(stored.visit_count, 10);
+        assert_eq!(stored.title, "Tabs");
+        assert!(stored.timestamp > 1);
+        assert!(db.history().increment_visit(hash_str("https://visits.example/never"), "").unwrap().is_none());
+    }
+}
@@ -0,0 +1,42 @@
+use browserdb::core::format::TableType;
+use browserdb::core::manifest::Manifest;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::tempdir;
+
+mod common;
+use common::{open_tree, sst_files};
+
+#[test]
+fn test_tables_without_a_manifest_reopen_in_write_order() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = open_tree(dir.path(), TableType::History);
+        tree.put(b"page".to_vec(), b"old".to_vec()).unwrap();
+        tree.flush().unwrap();
+        tree.put(b"page".to_vec(), b"new".to_vec()).unwrap();
+        tree.put(b"other".to_vec(), b"x".to_vec()).unwrap();
+        tree.flush().unwrap();
+        assert_eq!(tree.inner.levels[0].read().len(), 2);
+    }
+
+    // Swap the two files' names so name order says the old table is the
+    // newer one, and drop the manifest so recovery has nothing else to go by.
+    let paths = sst_files(dir.path());
+    assert_eq!(paths.len(), 2);
+    let tmp = dir.path().join("swap");
+    fs::rename(&paths[0], &tmp).unwrap();
+    fs::rename(&paths[1], &paths[0]).unwrap();
+    fs::rename(&tmp, &paths[1]).unwrap();
+    fs::remove_file(Manifest::new(dir.path(), "history").path()).unwrap();
+
+    let tree = open_tree(dir.path(), TableType::History);
+    let l0: Vec<(PathBuf, u64)> = tree.inner.levels[0].read().iter().map(|t| (t.file_path.clone(), t.max_timestamp())).collect();
+    assert_eq!(l0.len(), 2);
+    assert!(l0[0].1 < l0[1].1, "{:?}", l0);
+    assert_eq!(l0[1].0, paths[0]);
+
+    assert_eq!(tree.get(b"page").unwrap().value, b"new");
+    let scanned: Vec<Vec<u8>> = tree.scan_prefix(b"page").into_iter().map(|e| e.value).collect();
+    assert_eq!(scanned, vec![b"new".to_vec()]);
+}
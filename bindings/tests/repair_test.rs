@@ -0,0 +1,104 @@
+use browserdb::{BrowserDB, LocalStoreEntry};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+fn files_with_extension(dir: &Path, ext: &str) -> Vec<std::path::PathBuf> {
+    fs::read_dir(dir)
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == ext))
+        .collect()
+}
+
+#[test]
+fn test_repair_without_manifests_recovers_every_key() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        for i in 0..300 {
+            db.history().insert(&page(i)).unwrap();
+        }
+        db.localstore()
+            .insert(&LocalStoreEntry { origin_hash: 5, key: "theme".to_string(), value: "dark".to_string() })
+            .unwrap();
+        db.vacuum().unwrap();
+        // A second generation of SSTables on top of the compacted one.
+        for i in 300..400 {
+            db.history().insert(&page(i)).unwrap();
+        }
+        db.history().delete(7).unwrap();
+        db.vacuum().unwrap();
+    }
+
+    let container = dir.path().join("container_default");
+    for manifest in files_with_extension(&container, "manifest") {
+        fs::remove_file(manifest).unwrap();
+    }
+    fs::remove_dir_all(container.join("localstore_idx_value")).unwrap();
+
+    let report = BrowserDB::repair(dir.path()).unwrap();
+    assert!(report.sstables_rebuilt > 0);
+    assert!(report.entries_recovered >= 400);
+    assert_eq!(report.blocks_discarded, 0);
+    assert!(report.quarantined.is_empty());
+    assert!(container.join("history.manifest").exists());
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for i in (0..400).filter(|i| *i != 7) {
+        assert_eq!(db.history().get(i).unwrap().unwrap().url, page(i).url);
+    }
+    assert!(db.history().get(7).unwrap().is_none());
+    let by_value = db.localstore().query().value_eq("dark".to_string()).execute().unwrap();
+    assert_eq!(by_value.len(), 1);
+    assert_eq!(by_value[0].key, "theme");
+    assert!(db.lsm_report().unwrap().iter().all(|(_, levels)| levels[1..].iter().all(|l| l.sstables == 0)));
+}
+
+#[test]
+fn test_repair_quarantines_damaged_sstables() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        for i in 0..50 {
+            db.history().insert(&page(i)).unwrap();
+        }
+        db.settings().set("homepage", "about:blank").unwrap();
+        db.vacuum().unwrap();
+    }
+
+    let container = dir.path().join("container_default");
+    let settings_sst = files_with_extension(&container, "sst")
+        .into_iter()
+        .find(|p| p.file_name().unwrap().to_str().unwrap().starts_with("settings_"))
+        .unwrap();
+    let mut file = OpenOptions::new().write(true).open(&settings_sst).unwrap();
+    file.seek(SeekFrom::Start(47)).unwrap();
+    file.write_all(b"corrupted").unwrap();
+    drop(file);
+    fs::write(container.join("cookies_0_1_1.sst"), b"not an sstable").unwrap();
+
+    let report = BrowserDB::repair(dir.path()).unwrap();
+    assert!(report.blocks_discarded >= 1);
+    assert_eq!(report.quarantined.len(), 2);
+    assert!(report.quarantined.iter().all(|p| p.parent() == Some(container.join("corrupt").as_path())));
+    assert!(container.join("corrupt").join("cookies_0_1_1.sst").exists());
+    assert!(!settings_sst.exists());
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for i in 0..50 {
+        assert!(db.history().get(i).unwrap().is_some());
+    }
+}
+
+#[test]
+fn test_repair_refuses_open_database() {
+    let dir = tempdir().unwrap();
+    let _db = BrowserDB::open(dir.path()).unwrap();
+    assert!(BrowserDB::repair(dir.path()).is_err());
+}
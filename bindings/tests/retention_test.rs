@@ -0,0 +1,76 @@
+use browserdb::{BrowserDB, HistoryEntry};
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::modes::CurrentMode;
+use tempfile::tempdir;
+
+mod common;
+use common::now_ms;
+
+const DAY_MS: u128 = 24 * 60 * 60 * 1000;
+
+fn entry(url_hash: u128, timestamp: u128) -> HistoryEntry {
+    HistoryEntry {
+        timestamp,
+        url: format!("https://retention.example/{}", url_hash),
+        url_hash,
+        title: format!("Page {}", url_hash),
+        visit_count: 1,
+    }
+}
+
+#[test]
+fn test_prune_older_than_removes_only_old_entries() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let now = now_ms();
+
+    // Old entries flushed to an SSTable, so the prune has to look past the memtable.
+    db.history().insert(&entry(1, now - 40 * DAY_MS)).unwrap();
+    db.history().insert(&entry(2, now - 31 * DAY_MS)).unwrap();
+    let container = db.container("default").unwrap();
+    if let CurrentMode::Persistent(pm) = &*container.switcher.current_mode.read() {
+        pm.history.flush().unwrap();
+    }
+
+    db.history().insert(&entry(3, now - 45 * DAY_MS)).unwrap();
+    db.history().insert(&entry(4, now - DAY_MS)).unwrap();
+    db.history().insert(&entry(5, now)).unwrap();
+
+    let cutoff = (now - 30 * DAY_MS) as u64;
+    let pruned = db.history().prune_older_than(cutoff).unwrap();
+    assert_eq!(pruned, 3);
+
+    assert!(db.history().get(1).unwrap().is_none());
+    assert!(db.history().get(2).unwrap().is_none());
+    assert!(db.history().get(3).unwrap().is_none());
+    assert!(db.history().get(4).unwrap().is_some());
+    assert!(db.history().get(5).unwrap().is_some());
+    assert_eq!(db.history().count().unwrap(), 2);
+
+    // A second pass finds nothing left to prune.
+    assert_eq!(db.history().prune_older_than(cutoff).unwrap(), 0);
+}
+
+#[test]
+fn test_background_retention_sweeper() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.retention.history_max_age_days = 7;
+    config.retention.sweep_interval_sec = 1;
+    let db = BrowserDB::open_with_config(dir.path(), config).unwrap();
+
+    let now = now_ms();
+    db.history().insert(&entry(10, now - 8 * DAY_MS)).unwrap();
+    db.history().insert(&entry(11, now)).unwrap();
+
+    let mut pruned = false;
+    for _ in 0..30 {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        if db.history().get(10).unwrap().is_none() {
+            pruned = true;
+            break;
+        }
+    }
+    assert!(pruned, "sweeper should prune the stale entry");
+    assert!(db.history().get(11).unwrap().is_some());
+}
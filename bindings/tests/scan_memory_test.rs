@@ -0,0 +1,73 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::tempdir;
+
+/// Tracks live heap bytes and their high-water mark. Only one test lives in
+/// this binary, so nothing else allocates while it measures.
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(live, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Peak heap growth while `f` runs.
+fn peak_during<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = LIVE.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    let result = f();
+    (result, PEAK.load(Ordering::Relaxed) - before)
+}
+
+#[test]
+fn test_scan_memory_does_not_grow_with_the_table() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    // Loaded as a few tables; keep the background workers quiet.
+    config.lsm_tree.max_level0_files = 100;
+    config.lsm_tree.compaction_idle_threshold_ms = u64::MAX;
+    config.lsm_tree.compaction_deadline_sec = u64::MAX;
+    let tree = LSMTree::new(dir.path(), TableType::History, 64 * 1024 * 1024, config).unwrap();
+
+    for table in 0..5u32 {
+        let rows = (0..20_000u32).map(|i| (format!("page/{:06}", table * 20_000 + i).into_bytes(), vec![b'v'; 200]));
+        tree.ingest_sorted(rows).unwrap();
+    }
+    assert_eq!(tree.level_report()[0].sstables, 5);
+    // About 20 MB of entries on disk, plus a few in the memtable.
+    for i in 0..10u32 {
+        tree.put(format!("page/{:06}", i * 10_000).into_bytes(), b"new".to_vec()).unwrap();
+    }
+
+    let (matches, peak) = peak_during(|| tree.scan_with_predicate(b"page/", |kv| kv.value == b"new"));
+    assert_eq!(matches.len(), 10);
+    assert!(matches.windows(2).all(|w| w[0].key < w[1].key));
+    assert!(peak < 1024 * 1024, "scan peaked at {} bytes", peak);
+
+    let (count, peak) = peak_during(|| tree.streaming_iter(b"page/").count());
+    assert_eq!(count, 100_000);
+    assert!(peak < 1024 * 1024, "streaming scan peaked at {} bytes", peak);
+
+    // `page/` is shorter than the prefixes the tables' blooms hold.
+    assert_eq!(tree.scan_prefix(b"page/").len(), 100_000);
+}
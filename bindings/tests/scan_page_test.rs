@@ -0,0 +1,72 @@
+use browserdb::{BrowserDB, DatabaseMode};
+use browserdb::core::modes::CurrentMode;
+use std::collections::HashSet;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+fn collect_pages(db: &BrowserDB, limit: usize) -> Vec<Vec<u128>> {
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (entries, next) = db.history().scan_page(cursor, limit).unwrap();
+        pages.push(entries.iter().map(|e| e.url_hash).collect());
+        match next {
+            Some(c) => cursor = Some(c),
+            None => break,
+        }
+    }
+    pages
+}
+
+#[test]
+fn test_pages_have_no_duplicates_or_gaps() {
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        let dir = tempdir().unwrap();
+        let db = BrowserDB::open(dir.path()).unwrap();
+        db.set_mode(mode).unwrap();
+
+        for i in 1..=25u128 {
+            db.history().insert(&page(i * 7919)).unwrap();
+            // Spread the entries over the memtable and an SSTable.
+            if i == 12 {
+                let container = db.container("default").unwrap();
+                if let CurrentMode::Persistent(pm) = &*container.switcher.current_mode.read() {
+                    pm.history.flush().unwrap();
+                };
+            }
+        }
+
+        let pages = collect_pages(&db, 10);
+        let sizes: Vec<usize> = pages.iter().map(|p| p.len()).collect();
+        assert_eq!(sizes, vec![10, 10, 5], "{:?}", mode);
+
+        let all: Vec<u128> = pages.into_iter().flatten().collect();
+        let unique: HashSet<u128> = all.iter().copied().collect();
+        assert_eq!(unique.len(), 25);
+
+        // Pages come back in numeric url_hash order.
+        assert_eq!(all, (1..=25u128).map(|i| i * 7919).collect::<Vec<_>>(), "{:?}", mode);
+    }
+}
+
+#[test]
+fn test_exact_multiple_ends_without_empty_page() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    for i in 1..=20u128 {
+        db.history().insert(&page(i)).unwrap();
+    }
+
+    let sizes: Vec<usize> = collect_pages(&db, 10).iter().map(|p| p.len()).collect();
+    assert_eq!(sizes, vec![10, 10]);
+
+    // Deleted entries are skipped, and a cursor past the end yields nothing.
+    db.history().delete(5).unwrap();
+    let (entries, next) = db.history().scan_page(None, 100).unwrap();
+    assert_eq!(entries.len(), 19);
+    assert_eq!(next, None);
+    let last = entries.last().unwrap().url_hash;
+    assert!(db.history().scan_page(Some(last), 10).unwrap().0.is_empty());
+}
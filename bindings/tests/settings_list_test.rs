@@ -0,0 +1,29 @@
+use browserdb::{BrowserDB, DatabaseMode};
+use tempfile::tempdir;
+
+#[test]
+fn test_list_and_delete_settings() {
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        let dir = tempdir().unwrap();
+        let db = BrowserDB::open(dir.path()).unwrap();
+        db.set_mode(mode).unwrap();
+
+        db.settings().set("theme", "dark").unwrap();
+        db.settings().set("homepage", "https://example.com").unwrap();
+        db.settings().set("zoom", "110").unwrap();
+
+        let keys: Vec<String> = db.settings().list().unwrap().into_iter().map(|s| s.key).collect();
+        assert_eq!(keys, vec!["homepage", "theme", "zoom"], "{:?}", mode);
+
+        assert!(db.settings().delete("theme").unwrap());
+        assert!(!db.settings().delete("theme").unwrap());
+        assert_eq!(db.settings().get("theme").unwrap(), None);
+
+        let settings = db.settings().list().unwrap();
+        assert_eq!(settings.len(), 2);
+        assert_eq!(settings[0].key, "homepage");
+        assert_eq!(settings[0].value, "https://example.com");
+        assert_eq!(settings[1].key, "zoom");
+        assert_eq!(settings[1].value, "110");
+    }
+}
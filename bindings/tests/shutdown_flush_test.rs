@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use browserdb::core::config::{BrowserDBConfig, FsyncPolicy};
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn config() -> BrowserDBConfig {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.memtable_max_ops = 0;
+    config.lsm_tree.fsync_policy = FsyncPolicy::Always;
+    config
+}
+
+fn open(dir: &Path) -> LSMTree {
+    LSMTree::new(dir, TableType::Settings, 1024 * 1024, config()).unwrap()
+}
+
+/// Caps the size of files this process writes, so a flush's SSTable cannot
+/// be written while a short record still can. Permission bits do not stop
+/// root, and this is the only test in its binary, so the limit affects
+/// nothing else.
+fn limit_file_size(bytes: libc::rlim_t) {
+    unsafe {
+        libc::signal(libc::SIGXFSZ, libc::SIG_IGN);
+        let mut limit: libc::rlimit = std::mem::zeroed();
+        libc::getrlimit(libc::RLIMIT_FSIZE, &mut limit);
+        limit.rlim_cur = bytes.min(limit.rlim_max);
+        assert_eq!(libc::setrlimit(libc::RLIMIT_FSIZE, &limit), 0);
+    }
+}
+
+#[test]
+fn test_failed_flush_on_drop_is_reported_by_the_next_open() {
+    let dir = tempdir().unwrap();
+    let tree = open(dir.path());
+    for i in 0..200 {
+        tree.put(format!("key-{:04}", i).into_bytes(), vec![b'v'; 100]).unwrap();
+    }
+
+    limit_file_size(4096);
+    drop(tree);
+    limit_file_size(libc::RLIM_INFINITY);
+
+    let record = dir.path().join("settings.flush-error");
+    assert!(record.exists());
+
+    let tree = open(dir.path());
+    let warnings = tree.open_warnings();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert_eq!(warnings[0].file, record);
+    assert!(warnings[0].reason.contains("failed to persist 200 entries"), "{}", warnings[0].reason);
+    assert!(warnings[0].reason.contains("200 were replayed"), "{}", warnings[0].reason);
+    assert!(!record.exists());
+    // Nothing was lost: the WAL still held every entry.
+    assert_eq!(tree.get(b"key-0199").unwrap().value, vec![b'v'; 100]);
+
+    tree.close().unwrap();
+    drop(tree);
+    assert!(open(dir.path()).open_warnings().is_empty());
+}
@@ -0,0 +1,84 @@
+use browserdb::core::config::{BrowserDBConfig, CompactionStrategy};
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::manifest::Manifest;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn size_tiered_config() -> BrowserDBConfig {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.compaction_strategy.history = CompactionStrategy::SizeTiered;
+    config.lsm_tree.max_level0_files = 4;
+    config.lsm_tree.compaction_idle_threshold_ms = 0;
+    config.lsm_tree.compaction_deadline_sec = 0;
+    config
+}
+
+fn key(i: u32) -> Vec<u8> {
+    format!("visit{:05}", i).into_bytes()
+}
+
+#[test]
+fn test_size_tiered_bounds_table_count() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, size_tiered_config()).unwrap();
+
+    // 150 flushes of 20 new visits each, revisiting and deleting older ones.
+    for batch in 0..150u32 {
+        for i in 0..20 {
+            tree.put(key(batch * 20 + i), vec![batch as u8; 200]).unwrap();
+        }
+        if batch > 0 {
+            tree.put(key((batch - 1) * 20), vec![0xAA; 200]).unwrap();
+            tree.delete(key((batch - 1) * 20 + 1)).unwrap();
+        }
+        tree.flush().unwrap();
+    }
+
+    let mut l0 = usize::MAX;
+    for _ in 0..40 {
+        thread::sleep(Duration::from_millis(100));
+        l0 = tree.level_report()[0].sstables;
+        if l0 <= 12 {
+            break;
+        }
+    }
+    // Each tier holds fewer than four tables of one size, and sizes grow
+    // fourfold per tier, so 150 flushes need only a handful of tiers.
+    assert!(l0 <= 12, "{} level-0 tables left", l0);
+    assert!(tree.level_report()[1..].iter().all(|l| l.sstables == 0));
+    let (_, listed) = Manifest::new(dir.path(), "history").load().unwrap().unwrap();
+    assert!(listed.iter().all(|(level, _)| *level == 0));
+
+    for batch in 0..150u32 {
+        for i in 0..20 {
+            let value = tree.get(&key(batch * 20 + i)).map(|e| e.value);
+            match i {
+                0 if batch < 149 => assert_eq!(value, Some(vec![0xAA; 200])),
+                1 if batch < 149 => assert_eq!(value, None),
+                _ => assert_eq!(value, Some(vec![batch as u8; 200])),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_size_tiered_survives_reopen() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, size_tiered_config()).unwrap();
+        for batch in 0..12u32 {
+            for i in 0..10 {
+                tree.put(key(batch * 10 + i), b"v".to_vec()).unwrap();
+            }
+            tree.flush().unwrap();
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    for i in 0..120 {
+        assert!(tree.get(&key(i)).is_some());
+    }
+}
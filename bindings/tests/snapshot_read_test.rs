@@ -0,0 +1,59 @@
+use browserdb::{BrowserDB, CookieEntry, DatabaseMode, LocalStoreEntry};
+use tempfile::tempdir;
+
+mod common;
+
+fn cookie(value: &str) -> CookieEntry {
+    CookieEntry { domain_hash: 7, value: value.to_string(), ..common::cookie("snapshot.example", "session") }
+}
+
+fn local(value: &str) -> LocalStoreEntry {
+    LocalStoreEntry {
+        origin_hash: 7,
+        key: "theme".to_string(),
+        value: value.to_string(),
+    }
+}
+
+#[test]
+fn test_snapshot_survives_writes_flush_and_vacuum() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.cookies().insert(&cookie("old")).unwrap();
+    db.settings().set("flushed", "old").unwrap();
+    db.vacuum().unwrap();
+    db.localstore().insert(&local("old")).unwrap();
+
+    let snapshot = db.snapshot_read().unwrap();
+
+    db.cookies().insert(&cookie("new")).unwrap();
+    db.localstore().insert(&local("new")).unwrap();
+    db.settings().set("flushed", "new").unwrap();
+    db.settings().set("added", "x").unwrap();
+    // Flushes the memtables and compacts away the SSTables the snapshot holds.
+    db.vacuum().unwrap();
+
+    assert_eq!(snapshot.get_cookie(7, "session").unwrap().unwrap().value, "old");
+    assert_eq!(snapshot.get_localstore(7, "theme").unwrap().unwrap().value, "old");
+    assert_eq!(snapshot.get_setting("flushed").unwrap().as_deref(), Some("old"));
+    assert_eq!(snapshot.get_setting("added").unwrap(), None);
+
+    assert_eq!(db.cookies().get(7, "session").unwrap().unwrap().value, "new");
+    assert_eq!(db.settings().get("flushed").unwrap().as_deref(), Some("new"));
+    drop(snapshot);
+    assert_eq!(db.snapshot_read().unwrap().get_setting("added").unwrap().as_deref(), Some("x"));
+}
+
+#[test]
+fn test_snapshot_hides_later_delete_in_ultra() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    db.binarystore().put(b"blob".to_vec(), b"v1".to_vec()).unwrap();
+
+    let snapshot = db.snapshot_read().unwrap();
+    db.binarystore().delete(b"blob").unwrap();
+
+    assert_eq!(snapshot.get_binary(b"blob").unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(db.binarystore().get(b"blob").unwrap(), None);
+}
@@ -0,0 +1,68 @@
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::format::{TableType, BDB_BLOCK_SIZE};
+use browserdb::core::config::BrowserDBConfig;
+use tempfile::tempdir;
+
+const KEY_COUNT: usize = 20_000;
+
+fn key(i: usize) -> Vec<u8> {
+    format!("cookie:{:06}", i).into_bytes()
+}
+
+fn value(i: usize) -> Vec<u8> {
+    format!("value-{}-{}", i, "p".repeat(64)).into_bytes()
+}
+
+fn assert_lookups(tree: &LSMTree) {
+    for i in (0..KEY_COUNT).step_by(97) {
+        assert_eq!(tree.get(&key(i)).map(|e| e.value), Some(value(i)), "key {}", i);
+    }
+    assert_eq!(tree.get(&key(KEY_COUNT - 1)).map(|e| e.value), Some(value(KEY_COUNT - 1)));
+    assert!(tree.get(&key(KEY_COUNT)).is_none());
+    assert!(tree.get(b"cookie:").is_none());
+    assert!(tree.get(b"aaa").is_none());
+    assert!(tree.get(b"zzz").is_none());
+
+    let scanned: Vec<Vec<u8>> = tree.scan_prefix(b"cookie:00123").into_iter().map(|e| e.key).collect();
+    let expected: Vec<Vec<u8>> = (1230..1240).map(key).collect();
+    assert_eq!(scanned, expected);
+}
+
+fn assert_sparse_index(tree: &LSMTree) {
+    let levels = tree.inner.levels[0].read();
+    assert_eq!(levels.len(), 1);
+    let sstable = &levels[0];
+
+    let expected_blocks = sstable.data_end / BDB_BLOCK_SIZE;
+    assert!(
+        sstable.index.len() >= expected_blocks / 2 && sstable.index.len() <= expected_blocks * 2 + 1,
+        "index has {} entries for {} bytes of data",
+        sstable.index.len(),
+        sstable.data_end
+    );
+    assert!(sstable.index.len() * 20 < KEY_COUNT);
+    assert!(sstable.bloom_filter.is_some());
+}
+
+#[test]
+fn test_sparse_index_lookups() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    // One flush, one table: only the size threshold may trigger it.
+    config.lsm_tree.memtable_max_ops = 0;
+    {
+        let tree = LSMTree::new(dir.path(), TableType::Cookies, 64 * 1024 * 1024, config.clone()).unwrap();
+        for i in 0..KEY_COUNT {
+            tree.put(key(i), value(i)).unwrap();
+        }
+        tree.flush().unwrap();
+
+        assert_sparse_index(&tree);
+        assert_lookups(&tree);
+    }
+
+    // The index and bloom filter are read back from disk on reopen.
+    let tree = LSMTree::new(dir.path(), TableType::Cookies, 64 * 1024 * 1024, config).unwrap();
+    assert_sparse_index(&tree);
+    assert_lookups(&tree);
+}
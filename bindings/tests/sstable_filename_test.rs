@@ -0,0 +1,97 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{EntryType, TableType};
+use browserdb::core::lsm_tree::{KVEntry, LSMTree, SSTable};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+use tempfile::tempdir;
+
+mod common;
+use common::sst_names;
+
+fn entries(round: u32) -> BTreeMap<Vec<u8>, KVEntry> {
+    (0..4u32)
+        .map(|i| {
+            let key = format!("key{}", i).into_bytes();
+            let entry = KVEntry {
+                key: key.clone(),
+                value: round.to_le_bytes().to_vec(),
+                timestamp: round as u64,
+                expires_at: 0,
+                entry_type: EntryType::Insert,
+                deleted: false,
+            };
+            (key, entry)
+        })
+        .collect()
+}
+
+#[test]
+fn test_same_millisecond_tables_get_distinct_files() {
+    let dir = tempdir().unwrap();
+    let config = BrowserDBConfig::default().lsm_tree;
+    // Same level, same entry count, back to back: many land in one millisecond.
+    let tables: Vec<SSTable> = (0..50)
+        .map(|round| SSTable::create(0, &entries(round), dir.path(), TableType::Cookies, None, &config).unwrap())
+        .collect();
+
+    let paths: HashSet<_> = tables.iter().map(|t| t.file_path.clone()).collect();
+    assert_eq!(paths.len(), 50);
+    assert_eq!(sst_names(dir.path()).len(), 50);
+    for (round, table) in tables.iter().enumerate() {
+        assert_eq!(table.get(b"key3").unwrap().value, (round as u32).to_le_bytes().to_vec());
+    }
+}
+
+#[test]
+fn test_concurrent_creates_do_not_clobber_each_other() {
+    let dir = Arc::new(tempdir().unwrap());
+    let threads: Vec<_> = (0..8u32)
+        .map(|t| {
+            let dir = Arc::clone(&dir);
+            thread::spawn(move || {
+                let config = BrowserDBConfig::default().lsm_tree;
+                (0..10)
+                    .map(|i| {
+                        let round = t * 10 + i;
+                        let table = SSTable::create(0, &entries(round), dir.path(), TableType::Cookies, None, &config).unwrap();
+                        (round, table.file_path.clone())
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    let created: Vec<_> = threads.into_iter().flat_map(|t| t.join().unwrap()).collect();
+    assert_eq!(sst_names(dir.path()).len(), 80);
+    assert!(fs::read_dir(dir.path()).unwrap().flatten().all(|e| !e.file_name().to_string_lossy().ends_with(".tmp")));
+    for (round, path) in created {
+        let table = SSTable::open(path, 0, true, 0.01).unwrap();
+        assert_eq!(table.get(b"key0").unwrap().value, round.to_le_bytes().to_vec());
+    }
+}
+
+#[test]
+fn test_rapid_flushes_with_equal_counts_keep_all_data() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = LSMTree::new(dir.path(), TableType::Cookies, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+        tree.put(b"a1".to_vec(), b"first".to_vec()).unwrap();
+        tree.put(b"a2".to_vec(), b"first".to_vec()).unwrap();
+        tree.flush().unwrap();
+        tree.put(b"b1".to_vec(), b"second".to_vec()).unwrap();
+        tree.put(b"b2".to_vec(), b"second".to_vec()).unwrap();
+        tree.flush().unwrap();
+        assert_eq!(sst_names(dir.path()).len(), 2);
+    }
+
+    let tree = LSMTree::new(dir.path(), TableType::Cookies, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    assert_eq!(tree.inner.levels[0].read().len(), 2);
+    for key in [&b"a1"[..], b"a2"] {
+        assert_eq!(tree.get(key).unwrap().value, b"first".to_vec());
+    }
+    for key in [&b"b1"[..], b"b2"] {
+        assert_eq!(tree.get(key).unwrap().value, b"second".to_vec());
+    }
+}
@@ -0,0 +1,70 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::{LSMTree, SSTable};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+fn write_sstable(dir: &Path) -> PathBuf {
+    let tree = LSMTree::new(dir, TableType::History, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    for i in 0..200u32 {
+        tree.put(format!("key{:04}", i).into_bytes(), vec![7; 64]).unwrap();
+    }
+    tree.flush().unwrap();
+    drop(tree);
+    fs::read_dir(dir)
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .find(|p| p.extension().is_some_and(|e| e == "sst"))
+        .unwrap()
+}
+
+fn assert_corrupt(result: io::Result<SSTable>) {
+    let err = result.err().expect("corrupt SSTable opened");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("corrupt"), "{}", err);
+}
+
+#[test]
+fn test_intact_sstable_opens() {
+    let dir = tempdir().unwrap();
+    let path = write_sstable(dir.path());
+    let sst = SSTable::open(path, 0, false, 0.01).unwrap();
+    assert!(sst.get(b"key0100").is_some());
+}
+
+#[test]
+fn test_truncated_sstable_reports_corruption() {
+    let dir = tempdir().unwrap();
+    let path = write_sstable(dir.path());
+    let len = fs::metadata(&path).unwrap().len();
+    OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 100).unwrap();
+    assert_corrupt(SSTable::open(path, 0, false, 0.01));
+}
+
+#[test]
+fn test_damaged_data_region_reports_corruption() {
+    let dir = tempdir().unwrap();
+    let path = write_sstable(dir.path());
+    let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.seek(SeekFrom::Start(300)).unwrap();
+    file.write_all(b"bitrot").unwrap();
+    drop(file);
+    // The file CRC is checked whether or not per-block checks are enabled.
+    assert_corrupt(SSTable::open(path, 0, false, 0.01));
+}
+
+#[test]
+fn test_corrupt_sstable_is_quarantined_on_recovery() {
+    let dir = tempdir().unwrap();
+    let path = write_sstable(dir.path());
+    let len = fs::metadata(&path).unwrap().len();
+    OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 1).unwrap();
+
+    let tree = LSMTree::new(dir.path(), TableType::History, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    assert!(tree.get(b"key0000").is_none());
+    assert!(!path.exists());
+    assert!(dir.path().join("corrupt").join(path.file_name().unwrap()).exists());
+}
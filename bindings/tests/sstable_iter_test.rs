@@ -0,0 +1,63 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{CompressionType, EntryType, TableType, BDB_HEADER_SIZE};
+use browserdb::core::lsm_tree::{KVEntry, SSTable};
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::tempdir;
+
+fn written() -> BTreeMap<Vec<u8>, KVEntry> {
+    (0..500u32)
+        .map(|i| {
+            let key = format!("page:{:05}", i).into_bytes();
+            let deleted = i % 7 == 0;
+            let entry = KVEntry {
+                key: key.clone(),
+                value: if deleted { Vec::new() } else { format!("title {}", i).repeat(3).into_bytes() },
+                timestamp: 1_000 + i as u64,
+                expires_at: if i % 5 == 0 { 9_000 + i as u64 } else { 0 },
+                entry_type: if deleted { EntryType::Delete } else { EntryType::Insert },
+                deleted,
+            };
+            (key, entry)
+        })
+        .collect()
+}
+
+type Row = (Vec<u8>, Vec<u8>, u64, u64, EntryType, bool);
+
+fn row(e: &KVEntry) -> Row {
+    (e.key.clone(), e.value.clone(), e.timestamp, e.expires_at, e.entry_type, e.deleted)
+}
+
+#[test]
+fn test_iter_yields_exactly_the_written_entries() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default().lsm_tree;
+    for compression in [CompressionType::None, CompressionType::Lz4] {
+        config.compression = compression;
+        let entries = written();
+        let path = SSTable::create(0, &entries, dir.path(), TableType::History, None, &config).unwrap().file_path.clone();
+
+        let sstable = SSTable::open(path, 0, true, 0.01).unwrap();
+        let read: Vec<Row> = sstable.iter().map(|e| row(&e.unwrap())).collect();
+        let expected: Vec<Row> = entries.values().map(row).collect();
+        assert_eq!(read, expected);
+        assert_eq!(read.iter().filter(|r| r.5).count(), 72);
+    }
+}
+
+#[test]
+fn test_iter_stops_after_a_damaged_entry() {
+    let dir = tempdir().unwrap();
+    let config = BrowserDBConfig::default().lsm_tree;
+    let path = SSTable::create(0, &written(), dir.path(), TableType::History, None, &config).unwrap().file_path.clone();
+
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[BDB_HEADER_SIZE + 100] ^= 0xff;
+    fs::write(&path, &bytes).unwrap();
+
+    let sstable = SSTable::open_for_repair(path, 0, 0.01).unwrap();
+    let results: Vec<_> = sstable.iter().take(1_000).collect();
+    assert!(results.len() < 500);
+    assert!(results.last().unwrap().is_err());
+}
@@ -0,0 +1,113 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{EntryType, TableType};
+use browserdb::core::lsm_tree::{KVEntry, LSMTree, SSTable};
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+mod common;
+use common::{entry, manual_compaction_config};
+
+/// Leaves the level layout to the test.
+fn config() -> BrowserDBConfig {
+    let mut config = manual_compaction_config();
+    config.lsm_tree.entry_cache_entries = 0;
+    config
+}
+
+/// Writes `entries` as a table and puts it on top of `level`.
+fn push_table(tree: &LSMTree, dir: &std::path::Path, level: usize, entries: Vec<KVEntry>) -> Arc<SSTable> {
+    let entries: BTreeMap<Vec<u8>, KVEntry> = entries.into_iter().map(|e| (e.key.clone(), e)).collect();
+    let table = Arc::new(SSTable::create(level as u8, &entries, dir, TableType::Settings, None, &config().lsm_tree).unwrap());
+    tree.inner.levels[level].write().push(Arc::clone(&table));
+    table
+}
+
+/// Moves everything in level 0 down to level 1, as a compaction would.
+fn sink_level0(tree: &LSMTree) {
+    let tables: Vec<_> = tree.inner.levels[0].write().drain(..).collect();
+    tree.inner.levels[1].write().extend(tables);
+}
+
+#[test]
+fn test_compacted_copy_wins_over_stale_level0_copies() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 64 * 1024 * 1024, config()).unwrap();
+    tree.put(b"page".to_vec(), b"authoritative".to_vec()).unwrap();
+    tree.delete(b"gone".to_vec()).unwrap();
+    tree.flush().unwrap();
+    sink_level0(&tree);
+
+    // Older copies that ended up above it, as a repair can leave them.
+    for ts in 1..=3 {
+        push_table(&tree, dir.path(), 0, vec![
+            entry(b"page", format!("stale {}", ts).as_bytes(), ts, EntryType::Insert),
+            entry(b"gone", b"resurrected", ts, EntryType::Insert),
+        ]);
+    }
+    assert_eq!(tree.get(b"page").unwrap().value, b"authoritative");
+    assert!(tree.get(b"gone").is_none());
+    assert_eq!(tree.with_value(b"page", |v| v.to_vec()).unwrap(), b"authoritative");
+    assert!(tree.with_value(b"gone", |v| v.to_vec()).is_none());
+
+    // A newer level-0 version still wins.
+    push_table(&tree, dir.path(), 0, vec![entry(b"page", b"newest", u64::MAX / 2, EntryType::Insert)]);
+    assert_eq!(tree.get(b"page").unwrap().value, b"newest");
+    assert_eq!(tree.with_value(b"page", |v| v.to_vec()).unwrap(), b"newest");
+}
+
+#[test]
+fn test_expired_stale_copy_does_not_hide_the_live_value() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 64 * 1024 * 1024, config()).unwrap();
+    tree.put(b"page".to_vec(), b"live".to_vec()).unwrap();
+    tree.flush().unwrap();
+    sink_level0(&tree);
+
+    let mut expired = entry(b"page", b"expired", 1, EntryType::Insert);
+    expired.expires_at = 2;
+    push_table(&tree, dir.path(), 0, vec![expired]);
+    assert_eq!(tree.get(b"page").unwrap().value, b"live");
+    assert_eq!(tree.with_value(b"page", |v| v.to_vec()).unwrap(), b"live");
+}
+
+#[test]
+fn test_counter_folds_by_timestamp_and_counts_each_version_once() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 64 * 1024 * 1024, config()).unwrap();
+    tree.put(b"visits".to_vec(), 10i64.to_le_bytes().to_vec()).unwrap();
+    tree.flush().unwrap();
+    sink_level0(&tree);
+
+    let after = u64::MAX / 2;
+    // Older than the base: already part of it.
+    push_table(&tree, dir.path(), 0, vec![entry(b"visits", &5i64.to_le_bytes(), 1, EntryType::Increment)]);
+    // Newer, and present twice, as while a flush is being published.
+    for _ in 0..2 {
+        push_table(&tree, dir.path(), 0, vec![entry(b"visits", &2i64.to_le_bytes(), after, EntryType::Increment)]);
+    }
+    assert_eq!(tree.get(b"visits").unwrap().value, 12i64.to_le_bytes());
+    assert_eq!(tree.snapshot().get(b"visits").unwrap().value, 12i64.to_le_bytes());
+}
+
+#[test]
+fn test_tables_that_cannot_hold_a_newer_version_are_not_probed() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 64 * 1024 * 1024, config()).unwrap();
+    let older = push_table(&tree, dir.path(), 1, vec![entry(b"page", b"old", 1, EntryType::Insert)]);
+    let elsewhere = push_table(&tree, dir.path(), 1, vec![
+        entry(b"a", b"x", u64::MAX / 2, EntryType::Insert),
+        entry(b"b", b"x", u64::MAX / 2, EntryType::Insert),
+    ]);
+    tree.put(b"page".to_vec(), b"new".to_vec()).unwrap();
+    tree.flush().unwrap();
+    // `put` itself looked the key up while it was only in `older`.
+    let probed = older.decoded.load(Ordering::Relaxed);
+
+    assert_eq!(tree.get(b"page").unwrap().value, b"new");
+    // Settled by level 0: the older table is skipped by timestamp and the
+    // other by key range, so neither is read.
+    assert_eq!(older.decoded.load(Ordering::Relaxed), probed);
+    assert_eq!(elsewhere.decoded.load(Ordering::Relaxed), 0);
+}
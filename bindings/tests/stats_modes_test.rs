@@ -0,0 +1,115 @@
+use browserdb::{
+    BookmarkEntry, BrowserDB, CacheEntry, CookieEntry, DatabaseMode, DatabaseStats, HistoryEntry, LocalStoreEntry,
+};
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+/// The same records in every table, plus the extra keys each mode keeps
+/// beside them: history postings, cache body chunks, an expired entry and,
+/// in Ultra mode, LocalStore's value index.
+fn populate(db: &BrowserDB) {
+    for i in 0..20u128 {
+        db.history().insert(&HistoryEntry {
+            timestamp: 1_700_000_000_000 + i,
+            url: format!("https://stats.example/{}", i),
+            url_hash: i,
+            title: format!("Stats page {}", i),
+            visit_count: 1,
+        }).unwrap();
+    }
+    db.history().insert_with_ttl(&HistoryEntry {
+        timestamp: 1_700_000_000_000,
+        url: "https://stats.example/gone".to_string(),
+        url_hash: 99,
+        title: "Gone".to_string(),
+        visit_count: 1,
+    }, 1).unwrap();
+    for i in 0..3u128 {
+        db.bookmarks().insert(&BookmarkEntry {
+            url_hash: i,
+            url: format!("https://stats.example/{}", i),
+            title: "Bookmark".to_string(),
+            folder: "bar".to_string(),
+            created_at: 0,
+        }).unwrap();
+    }
+    for i in 0..4 {
+        db.cookies().insert(&CookieEntry {
+            domain_hash: 1,
+            name: format!("c{}", i),
+            value: "v".to_string(),
+            path: "/".to_string(),
+            domain: "stats.example".to_string(),
+            expiry: 0,
+            flags: 0,
+        }).unwrap();
+    }
+    db.cache().insert(&CacheEntry {
+        url_hash: 1,
+        headers: String::new(),
+        body: b"small".to_vec(),
+        etag: String::new(),
+        last_modified: 0,
+        expires_at: 0,
+    }).unwrap();
+    db.cache().insert_streaming(2, "", "", 0, &vec![7u8; 600 * 1024][..]).unwrap();
+    for i in 0..5 {
+        db.localstore().insert(&LocalStoreEntry { origin_hash: 1, key: format!("k{}", i), value: "same".to_string() }).unwrap();
+    }
+    db.settings().set("theme", "dark").unwrap();
+    db.binarystore().put(b"blob".to_vec(), vec![1; 16]).unwrap();
+    db.kv().put("ns", b"key", b"value").unwrap();
+    thread::sleep(Duration::from_millis(5));
+}
+
+fn counts(stats: &DatabaseStats) -> [u64; 8] {
+    [
+        stats.total_entries,
+        stats.history_entries,
+        stats.bookmark_entries,
+        stats.cookie_entries,
+        stats.cache_entries,
+        stats.localstore_entries,
+        stats.settings_entries,
+        stats.binarystore_entries,
+    ]
+}
+
+#[test]
+fn test_both_modes_report_the_same_counts() {
+    let persistent_dir = tempdir().unwrap();
+    let persistent = BrowserDB::open(persistent_dir.path()).unwrap();
+    populate(&persistent);
+
+    let ultra_dir = tempdir().unwrap();
+    let ultra = BrowserDB::open(ultra_dir.path()).unwrap();
+    ultra.set_mode(DatabaseMode::Ultra).unwrap();
+    populate(&ultra);
+
+    let (p, u) = (persistent.stats().unwrap(), ultra.stats().unwrap());
+    assert_eq!(counts(&p), [37, 20, 3, 4, 2, 5, 1, 2]);
+    assert_eq!(counts(&u), counts(&p));
+
+    // Ultra mode holds the streamed body itself; Persistent mode only
+    // what is still in its memtables.
+    assert!(u.memory_usage_bytes > 600 * 1024, "{:?}", u);
+    assert!(p.memory_usage_bytes > 0 && p.memory_usage_bytes < u.memory_usage_bytes, "{:?}", p);
+    for stats in [&p, &u] {
+        assert_eq!(stats.memory_usage_mb, stats.memory_usage_bytes / 1024 / 1024);
+        assert_eq!(stats.disk_usage_mb, stats.disk_usage_bytes / 1024 / 1024);
+    }
+    assert!(p.disk_usage_bytes > 0);
+}
+
+#[test]
+fn test_stats_follow_a_mode_switch() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    populate(&db);
+    let before = db.stats().unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    assert_eq!(counts(&db.stats().unwrap()), counts(&before));
+    db.set_mode(DatabaseMode::Persistent).unwrap();
+    assert_eq!(counts(&db.stats().unwrap()), counts(&before));
+}
@@ -0,0 +1,106 @@
+use browserdb::{keys, BrowserDB, CookieEntry, DatabaseMode};
+use browserdb::core::format::TableType;
+use browserdb::core::modes::TableHandle;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+fn populate(db: &BrowserDB) {
+    for i in 0..200u128 {
+        db.history().insert(&page(i)).unwrap();
+        db.cookies().insert(&CookieEntry::new(i, "sid".to_string(), format!("v{}", i), 0)).unwrap();
+    }
+}
+
+/// Runs readers on history and cookies alongside a settings writer and
+/// returns once every thread has finished.
+fn run_concurrent_readers(db: &Arc<BrowserDB>, during: impl FnOnce()) {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    thread::scope(|scope| {
+        let mut handles = Vec::new();
+        for t in 0..4u128 {
+            let history_db = Arc::clone(db);
+            handles.push(scope.spawn(move || {
+                for round in 0..50u128 {
+                    let i = (t * 50 + round) % 200;
+                    let entry = history_db.history().get(i).unwrap().unwrap();
+                    assert_eq!(entry.url, page(i).url);
+                }
+            }));
+            let cookie_db = Arc::clone(db);
+            handles.push(scope.spawn(move || {
+                for round in 0..50u128 {
+                    let i = (t * 50 + round) % 200;
+                    let cookie = cookie_db.cookies().get(i, "sid").unwrap().unwrap();
+                    assert_eq!(cookie.value, format!("v{}", i));
+                }
+            }));
+        }
+        let writer_db = Arc::clone(db);
+        handles.push(scope.spawn(move || {
+            for i in 0..100 {
+                writer_db.settings().set(&format!("key{}", i), "value").unwrap();
+            }
+        }));
+
+        during();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    });
+    assert!(Instant::now() < deadline, "concurrent readers took too long");
+}
+
+#[test]
+fn test_concurrent_readers_on_different_tables() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(BrowserDB::open(dir.path()).unwrap());
+    populate(&db);
+
+    run_concurrent_readers(&db, || {});
+
+    assert_eq!(db.settings().count().unwrap(), 100);
+}
+
+#[test]
+fn test_readers_survive_mode_switch() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(BrowserDB::open(dir.path()).unwrap());
+    populate(&db);
+
+    let switch_db = Arc::clone(&db);
+    run_concurrent_readers(&db, move || {
+        switch_db.set_mode(DatabaseMode::Ultra).unwrap();
+    });
+
+    assert_eq!(db.history().count().unwrap(), 200);
+    assert_eq!(db.settings().count().unwrap(), 100);
+}
+
+#[test]
+fn test_table_handle_outlives_mode_lock() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    populate(&db);
+
+    let container = db.container("default").unwrap();
+    let handle = container.switcher.table(TableType::History);
+
+    // Holding a handle must not block a mode switch.
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+
+    match handle {
+        TableHandle::Persistent(t) => assert!(t.get(&keys::history_key(7)).is_some()),
+        TableHandle::Ultra(_) => panic!("handle was taken in persistent mode"),
+    }
+    match container.switcher.table(TableType::History) {
+        TableHandle::Ultra(t) => assert!(t.get(&keys::history_key(7)).is_some()),
+        TableHandle::Persistent(_) => panic!("mode switch did not take effect"),
+    }
+}
+
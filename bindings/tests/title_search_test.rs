@@ -0,0 +1,70 @@
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use tempfile::tempdir;
+
+mod common;
+
+fn page(url_hash: u128, title: &str, visit_count: u32) -> HistoryEntry {
+    HistoryEntry { title: title.to_string(), visit_count, ..common::page(url_hash) }
+}
+
+fn populate(db: &BrowserDB) {
+    db.history().insert(&page(1, "Rust async book", 3)).unwrap();
+    db.history().insert(&page(2, "The Rust Programming Language", 10)).unwrap();
+    db.history().insert(&page(3, "Async programming in JavaScript", 7)).unwrap();
+    db.history().insert(&page(4, "Book review: Programming Pearls", 1)).unwrap();
+    db.history().insert(&page(5, "rust-lang / async-book", 20)).unwrap();
+}
+
+fn hashes(results: &[HistoryEntry]) -> Vec<u128> {
+    results.iter().map(|e| e.url_hash).collect()
+}
+
+fn assert_search(db: &BrowserDB) {
+    // Every term must match; word order and case do not matter.
+    assert_eq!(hashes(&db.history().search("async rust", 10).unwrap()), vec![5, 1]);
+    assert_eq!(hashes(&db.history().search("PROGRAMMING", 10).unwrap()), vec![2, 3, 4]);
+    assert_eq!(hashes(&db.history().search("programming rust", 10).unwrap()), vec![2]);
+    assert_eq!(hashes(&db.history().search("book", 2).unwrap()), vec![5, 1]);
+    assert!(db.history().search("rust python", 10).unwrap().is_empty());
+    assert!(db.history().search("  ", 10).unwrap().is_empty());
+}
+
+#[test]
+fn test_search_requires_all_terms() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    populate(&db);
+
+    assert_search(&db);
+    // Postings live in their own namespace and are not counted as pages.
+    assert_eq!(db.history().count().unwrap(), 5);
+}
+
+#[test]
+fn test_search_ultra_mode() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    populate(&db);
+
+    assert_search(&db);
+    assert_eq!(db.history().count().unwrap(), 5);
+}
+
+#[test]
+fn test_search_follows_updates_and_deletes() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    populate(&db);
+
+    db.history().insert(&page(1, "Go concurrency patterns", 3)).unwrap();
+    assert_eq!(hashes(&db.history().search("async rust", 10).unwrap()), vec![5]);
+    assert_eq!(hashes(&db.history().search("concurrency", 10).unwrap()), vec![1]);
+
+    db.history().delete(5).unwrap();
+    assert!(db.history().search("async rust", 10).unwrap().is_empty());
+
+    assert_eq!(db.history().wipe_domain("example.test/2").unwrap(), 1);
+    assert_eq!(hashes(&db.history().search("programming", 10).unwrap()), vec![3, 4]);
+    assert_eq!(db.history().count().unwrap(), 3);
+}
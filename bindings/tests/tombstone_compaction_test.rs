@@ -0,0 +1,71 @@
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+fn key(i: usize) -> Vec<u8> {
+    format!("visit:{:05}", i).into_bytes()
+}
+
+fn open(dir: &Path, ratio: f64) -> LSMTree {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.memtable_max_ops = 0;
+    config.lsm_tree.compaction_idle_threshold_ms = 0;
+    config.lsm_tree.tombstone_compaction_ratio = ratio;
+    LSMTree::new(dir, TableType::History, 64 * 1024 * 1024, config).unwrap()
+}
+
+/// Writes 3000 keys, then deletes all but every tenth one: two level 0
+/// tables, well under `max_level0_files` and every size threshold.
+fn insert_then_prune(tree: &LSMTree) {
+    for i in 0..3000 {
+        tree.put(key(i), vec![b'v'; 200]).unwrap();
+    }
+    tree.flush().unwrap();
+    for i in (0..3000).filter(|i| i % 10 != 0) {
+        tree.delete(key(i)).unwrap();
+    }
+    tree.flush().unwrap();
+}
+
+fn bytes(tree: &LSMTree) -> u64 {
+    tree.level_report().iter().map(|l| l.bytes).sum()
+}
+
+#[test]
+fn test_tombstone_density_is_reported_and_can_be_disabled() {
+    let dir = tempdir().unwrap();
+    let tree = open(dir.path(), 0.0);
+    insert_then_prune(&tree);
+    thread::sleep(Duration::from_millis(300));
+
+    let level0 = &tree.level_report()[0];
+    assert_eq!(level0.sstables, 2);
+    assert_eq!(level0.tombstones, 2700);
+    assert!((level0.tombstone_density - 2700.0 / 5700.0).abs() < 1e-9, "{}", level0.tombstone_density);
+    assert!(tree.level_report()[1..].iter().all(|l| l.sstables == 0));
+}
+
+#[test]
+fn test_tombstone_density_triggers_compaction_and_reclaims_space() {
+    let dir = tempdir().unwrap();
+    let tree = open(dir.path(), 0.3);
+    insert_then_prune(&tree);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while tree.level_report()[0].sstables > 0 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(20));
+    }
+    let report = tree.level_report();
+    assert_eq!(report[0].sstables, 0, "{:?}", report);
+    // Nothing sits below the merged level, so the tombstones were dropped
+    // along with the values they shadowed.
+    assert!(report.iter().all(|l| l.tombstones == 0), "{:?}", report);
+    assert!(bytes(&tree) < 3000 * 200 / 5, "{:?}", report);
+
+    assert_eq!(tree.get(&key(10)).unwrap().value, vec![b'v'; 200]);
+    assert!(tree.get(&key(11)).is_none());
+}
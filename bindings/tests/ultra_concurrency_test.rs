@@ -0,0 +1,91 @@
+use browserdb::core::modes::UltraTable;
+use browserdb::{BrowserDB, DatabaseMode};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use tempfile::tempdir;
+
+const THREADS: u32 = 8;
+const PER_THREAD: u32 = 5_000;
+
+fn key(thread: u32, i: u32) -> Vec<u8> {
+    format!("t{}/k{}", thread, i).into_bytes()
+}
+
+#[test]
+fn test_parallel_puts_gets_and_deletes() {
+    let table = Arc::new(UltraTable::new());
+    let workers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let table = Arc::clone(&table);
+            thread::spawn(move || {
+                for i in 0..PER_THREAD {
+                    table.put(key(t, i), i.to_le_bytes().to_vec(), 0);
+                    assert_eq!(table.get(&key(t, i)), Some(i.to_le_bytes().to_vec()));
+                    table.increment(b"shared-counter", 1);
+                    if i % 10 == 0 {
+                        table.delete(&key(t, i));
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    let deleted = PER_THREAD / 10;
+    assert_eq!(table.entry_count.load(Ordering::SeqCst), (THREADS * (PER_THREAD - deleted) + 1) as usize);
+    assert_eq!(table.all_entries().len(), table.entry_count.load(Ordering::SeqCst));
+    for t in 0..THREADS {
+        for i in 0..PER_THREAD {
+            let expected = (i % 10 != 0).then(|| i.to_le_bytes().to_vec());
+            assert_eq!(table.get(&key(t, i)), expected);
+        }
+    }
+    let counter = table.get(b"shared-counter").unwrap();
+    assert_eq!(i64::from_le_bytes(counter.try_into().unwrap()), (THREADS * PER_THREAD) as i64);
+}
+
+#[test]
+fn test_whole_table_operations_see_every_stripe() {
+    let table = UltraTable::new();
+    table.put_many((0..1_000u32).map(|i| (i.to_be_bytes().to_vec(), vec![1])).collect());
+    assert_eq!(table.entry_count.load(Ordering::SeqCst), 1_000);
+    assert_eq!(table.count_keys(|k| k[3] % 2 == 0), 500);
+
+    let copy = table.raw_entries();
+    assert_eq!(copy.len(), 1_000);
+    table.clear();
+    assert!(table.all_entries().is_empty());
+    assert_eq!(table.entry_count.load(Ordering::SeqCst), 0);
+
+    table.replace_all(copy);
+    assert_eq!(table.entry_count.load(Ordering::SeqCst), 1_000);
+    assert_eq!(table.get(&999u32.to_be_bytes()), Some(vec![1]));
+}
+
+#[test]
+fn test_ultra_settings_from_many_threads() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(BrowserDB::open(dir.path()).unwrap());
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    let workers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let db = Arc::clone(&db);
+            thread::spawn(move || {
+                for i in 0..500u32 {
+                    db.settings().set(&format!("t{}/{}", t, i), &i.to_string()).unwrap();
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    for t in 0..THREADS {
+        for i in 0..500u32 {
+            assert_eq!(db.settings().get(&format!("t{}/{}", t, i)).unwrap(), Some(i.to_string()));
+        }
+    }
+}
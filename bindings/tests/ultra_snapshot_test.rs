@@ -0,0 +1,100 @@
+use browserdb::{BrowserDB, DatabaseMode};
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{BDB_VERSION, MAGIC_BYTES};
+use browserdb::core::modes::{UltraEntry, ULTRA_SNAPSHOT_FILE};
+use std::time::Duration;
+use tempfile::tempdir;
+
+mod common;
+use common::page;
+
+fn snapshot_config() -> BrowserDBConfig {
+    BrowserDBConfig { ultra_snapshot_on_close: true, ..Default::default() }
+}
+
+#[test]
+fn test_ultra_data_survives_clean_close() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open_with_config(dir.path(), snapshot_config()).unwrap();
+        db.settings().set("persisted", "before").unwrap();
+        db.set_mode(DatabaseMode::Ultra).unwrap();
+
+        db.history().insert(&page(1)).unwrap();
+        db.history().insert(&page(2)).unwrap();
+        db.settings().set("theme", "dark").unwrap();
+        db.settings().delete("persisted").unwrap();
+        db.history().insert_with_ttl(&page(9), 50).unwrap();
+        db.close().unwrap();
+    }
+    assert!(dir.path().join(ULTRA_SNAPSHOT_FILE).exists());
+    std::thread::sleep(Duration::from_millis(100));
+
+    // The flag is remembered in METADATA along with the mode.
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_eq!(db.history().get(2).unwrap().unwrap().url, "https://example.test/2");
+    assert_eq!(db.history().count().unwrap(), 2);
+    assert_eq!(db.history().search("page", 10).unwrap().len(), 2);
+    assert_eq!(db.settings().get("theme").unwrap().as_deref(), Some("dark"));
+    assert_eq!(db.settings().get("persisted").unwrap(), None);
+    // Expiry is kept across the snapshot.
+    assert!(db.history().get(9).unwrap().is_none());
+
+    // The snapshot is consumed by the open.
+    assert!(!dir.path().join(ULTRA_SNAPSHOT_FILE).exists());
+}
+
+#[test]
+fn test_drop_saves_snapshot_too() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open_with_config(dir.path(), snapshot_config()).unwrap();
+        db.set_mode(DatabaseMode::Ultra).unwrap();
+        db.history().insert(&page(7)).unwrap();
+    }
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert!(db.history().get(7).unwrap().is_some());
+}
+
+#[test]
+fn test_without_flag_ultra_data_is_lost() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        db.set_mode(DatabaseMode::Ultra).unwrap();
+        db.history().insert(&page(1)).unwrap();
+    }
+    assert!(!dir.path().join(ULTRA_SNAPSHOT_FILE).exists());
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert!(db.history().get(1).unwrap().is_none());
+}
+
+#[test]
+fn test_snapshot_from_before_ordered_keys_is_upgraded() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open_with_config(dir.path(), snapshot_config()).unwrap();
+        db.set_mode(DatabaseMode::Ultra).unwrap();
+        for h in [1, 2, 256] {
+            db.history().insert(&page(h)).unwrap();
+        }
+    }
+
+    // Rewrite it the way format version 2 did: no header, and history
+    // keyed by little-endian url_hash.
+    let path = dir.path().join(ULTRA_SNAPSHOT_FILE);
+    let bytes = std::fs::read(&path).unwrap();
+    assert!(bytes.starts_with(MAGIC_BYTES));
+    assert_eq!(bytes[MAGIC_BYTES.len()], BDB_VERSION);
+    let mut tables: Vec<Vec<(Vec<u8>, UltraEntry)>> = bincode::deserialize(&bytes[MAGIC_BYTES.len() + 1..]).unwrap();
+    for (key, _) in tables[0].iter_mut().filter(|(key, _)| key.len() == 16) {
+        key.reverse();
+    }
+    std::fs::write(&path, bincode::serialize(&tables).unwrap()).unwrap();
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let (entries, _) = db.history().scan_page(None, 10).unwrap();
+    assert_eq!(entries.iter().map(|e| e.url_hash).collect::<Vec<_>>(), vec![1, 2, 256]);
+    assert_eq!(db.history().get(256).unwrap().unwrap().url, "https://example.test/256");
+}
@@ -2,12 +2,8 @@ use browserdb::{BrowserDB, HistoryEntry};
 use browserdb::DatabaseMode;
 use tempfile::tempdir;
 
-fn now_ms() -> u128 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_millis()
-}
+mod common;
+use common::now_ms;
 
 #[test]
 fn test_ultra_ttl_read_side_enforcement() {
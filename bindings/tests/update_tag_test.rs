@@ -0,0 +1,107 @@
+use browserdb::{BrowserDB, DatabaseMode, HistoryEntry};
+use browserdb::core::config::BrowserDBConfig;
+use browserdb::core::format::{EntryType, TableType};
+use browserdb::core::lsm_tree::LSMTree;
+use tempfile::tempdir;
+
+fn entry_type(tree: &LSMTree, key: &[u8]) -> EntryType {
+    tree.get(key).unwrap().entry_type
+}
+
+#[test]
+fn test_put_tags_insert_and_update() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+
+    tree.put(b"k".to_vec(), b"1".to_vec()).unwrap();
+    assert_eq!(entry_type(&tree, b"k"), EntryType::Insert);
+    tree.put(b"k".to_vec(), b"2".to_vec()).unwrap();
+    assert_eq!(entry_type(&tree, b"k"), EntryType::Update);
+
+    // A deleted key is created anew.
+    tree.delete(b"k".to_vec()).unwrap();
+    tree.put(b"k".to_vec(), b"3".to_vec()).unwrap();
+    assert_eq!(entry_type(&tree, b"k"), EntryType::Insert);
+
+    // The existing value may only be on disk.
+    tree.flush().unwrap();
+    tree.put(b"k".to_vec(), b"4".to_vec()).unwrap();
+    assert_eq!(entry_type(&tree, b"k"), EntryType::Update);
+    tree.put_with_ttl(b"other".to_vec(), b"x".to_vec(), 60_000).unwrap();
+    assert_eq!(entry_type(&tree, b"other"), EntryType::Insert);
+
+    // The tag survives a flush and reopen.
+    tree.flush().unwrap();
+    drop(tree);
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    assert_eq!(entry_type(&tree, b"k"), EntryType::Update);
+    assert_eq!(tree.get(b"k").unwrap().value, b"4".to_vec());
+}
+
+#[test]
+fn test_overwrite_does_not_increase_total_entries() {
+    for mode in [DatabaseMode::Persistent, DatabaseMode::Ultra] {
+        let dir = tempdir().unwrap();
+        let db = BrowserDB::open(dir.path()).unwrap();
+        db.set_mode(mode).unwrap();
+
+        let mut entry = HistoryEntry {
+            timestamp: 1,
+            url: "https://update.example/".to_string(),
+            url_hash: 42,
+            title: "First".to_string(),
+            visit_count: 1,
+        };
+        db.history().insert(&entry).unwrap();
+        entry.visit_count = 2;
+        db.history().insert(&entry).unwrap();
+
+        let stats = db.stats().unwrap();
+        assert_eq!(stats.total_entries, 1, "{:?}", mode);
+        assert_eq!(stats.history_entries, 1, "{:?}", mode);
+        assert_eq!(db.history().get(42).unwrap().unwrap().visit_count, 2);
+    }
+}
+
+#[test]
+fn test_tagging_an_overwrite_leaves_the_entry_cache_alone() {
+    let dir = tempdir().unwrap();
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    assert!(tree.inner.entry_cache.is_enabled());
+    for i in 0..100u32 {
+        tree.put(i.to_be_bytes().to_vec(), b"old".to_vec()).unwrap();
+    }
+    tree.delete(7u32.to_be_bytes().to_vec()).unwrap();
+    tree.flush().unwrap();
+
+    for i in 0..100u32 {
+        tree.put(i.to_be_bytes().to_vec(), b"new".to_vec()).unwrap();
+    }
+    assert!(tree.inner.entry_cache.is_empty());
+    assert_eq!(tree.inner.entry_cache.hits_and_misses(), (0, 0));
+    // Nor do the probes count as reads.
+    assert_eq!(tree.amplification().disk_bytes_read, 0);
+
+    assert_eq!(entry_type(&tree, &0u32.to_be_bytes()), EntryType::Update);
+    assert_eq!(entry_type(&tree, &7u32.to_be_bytes()), EntryType::Insert);
+    assert_eq!(entry_type(&tree, &99u32.to_be_bytes()), EntryType::Update);
+}
+
+#[test]
+fn test_update_tags_can_be_turned_off_per_table() {
+    let dir = tempdir().unwrap();
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.update_tags.settings = false;
+    let tree = LSMTree::new(dir.path(), TableType::Settings, 1024 * 1024, config.clone()).unwrap();
+    tree.put(b"k".to_vec(), b"1".to_vec()).unwrap();
+    tree.flush().unwrap();
+    tree.put(b"k".to_vec(), b"2".to_vec()).unwrap();
+    assert_eq!(entry_type(&tree, b"k"), EntryType::Insert);
+    assert_eq!(tree.get(b"k").unwrap().value, b"2".to_vec());
+
+    let other = tempdir().unwrap();
+    let tree = LSMTree::new(other.path(), TableType::Cookies, 1024 * 1024, config).unwrap();
+    tree.put(b"k".to_vec(), b"1".to_vec()).unwrap();
+    tree.put(b"k".to_vec(), b"2".to_vec()).unwrap();
+    assert_eq!(entry_type(&tree, b"k"), EntryType::Update);
+}
@@ -0,0 +1,85 @@
+use browserdb::{BrowserDB, HistoryEntry};
+use browserdb::core::modes::CurrentMode;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn dir_size(path: &Path) -> u64 {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path).unwrap().flatten() {
+        let metadata = entry.metadata().unwrap();
+        if metadata.is_dir() {
+            total += dir_size(&entry.path());
+        } else {
+            total += metadata.len();
+        }
+    }
+    total
+}
+
+#[test]
+fn test_vacuum_reclaims_overwritten_and_deleted_entries() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let container = db.container("default").unwrap();
+
+    // Overwrite the same 50 keys several times, flushing each round so every
+    // version lands in its own SSTable. Kept below the L0 write-stall limit.
+    for round in 0..7u32 {
+        for i in 0..50u128 {
+            db.history().insert(&HistoryEntry {
+                timestamp: if i < 25 { round as u128 } else { 1000 + round as u128 },
+                url_hash: i,
+                url: format!("https://vacuum.example/{}", i),
+                title: format!("round {} {}", round, "x".repeat(1024)),
+                visit_count: round,
+            }).unwrap();
+        }
+        if let CurrentMode::Persistent(pm) = &*container.switcher.current_mode.read() {
+            pm.history.flush().unwrap();
+        }
+    }
+    assert_eq!(db.history().prune_older_than(1000).unwrap(), 25);
+
+    let before = dir_size(dir.path());
+    let reclaimed = db.vacuum().unwrap();
+    let after = dir_size(dir.path());
+
+    assert!(reclaimed > 0);
+    assert!(after * 4 < before, "disk usage should drop substantially: {} -> {}", before, after);
+
+    for i in 0..25u128 {
+        assert!(db.history().get(i).unwrap().is_none());
+    }
+    for i in 25..50u128 {
+        let entry = db.history().get(i).unwrap().unwrap();
+        assert_eq!(entry.visit_count, 6);
+    }
+    assert_eq!(db.history().count().unwrap(), 25);
+
+    // Nothing left to reclaim on a second pass.
+    assert_eq!(db.vacuum().unwrap(), 0);
+}
+
+#[test]
+fn test_vacuum_survives_reopen() {
+    let dir = tempdir().unwrap();
+    {
+        let db = BrowserDB::open(dir.path()).unwrap();
+        for i in 0..10u128 {
+            db.history().insert(&HistoryEntry {
+                timestamp: if i == 3 { 1 } else { 1000 },
+                url_hash: i,
+                url: format!("https://vacuum.example/{}", i),
+                title: "title".to_string(),
+                visit_count: 1,
+            }).unwrap();
+        }
+        db.history().prune_older_than(1000).unwrap();
+        db.vacuum().unwrap();
+    }
+
+    let db = BrowserDB::open(dir.path()).unwrap();
+    assert_eq!(db.history().count().unwrap(), 9);
+    assert!(db.history().get(3).unwrap().is_none());
+    assert!(db.history().get(4).unwrap().is_some());
+}
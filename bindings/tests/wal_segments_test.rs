@@ -0,0 +1,125 @@
+use browserdb::core::config::{BrowserDBConfig, FsyncPolicy};
+use browserdb::core::format::{BDBLogEntry, EntryType, TableType};
+use browserdb::core::lsm_tree::LSMTree;
+use browserdb::core::wal::WALManager;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key-{:04}", i).into_bytes()
+}
+
+fn value(i: usize) -> Vec<u8> {
+    format!("value-{:04}-{}", i, "x".repeat(80)).into_bytes()
+}
+
+fn small_segments() -> BrowserDBConfig {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.wal_segment_bytes = 4096;
+    config.lsm_tree.memtable_max_ops = 0;
+    config.lsm_tree.fsync_policy = FsyncPolicy::Always;
+    config
+}
+
+fn open_wal(dir: &Path) -> WALManager {
+    WALManager::open(&dir.join("history.wal"), TableType::History, FsyncPolicy::Always, 1024).unwrap()
+}
+
+#[test]
+fn test_wal_rotates_and_drops_flushed_segments() {
+    let dir = tempdir().unwrap();
+    {
+        let tree = LSMTree::new(dir.path(), TableType::History, 64 * 1024 * 1024, small_segments()).unwrap();
+        for i in 0..200 {
+            tree.put(key(i), value(i)).unwrap();
+        }
+        let segments = tree.inner.wal.read().segments().unwrap();
+        assert!(segments.len() >= 4, "{:?}", segments);
+        assert!(segments.iter().all(|s| fs::metadata(s).unwrap().len() <= 4096));
+
+        tree.flush().unwrap();
+        let remaining = tree.inner.wal.read().segments().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert!(!segments.contains(&remaining[0]), "segment ids are not reused");
+        assert!(tree.inner.wal.read().read_all().unwrap().is_empty());
+        assert_eq!(tree.get(&key(0)).unwrap().value, value(0));
+    }
+
+    let tree = LSMTree::new(dir.path(), TableType::History, 64 * 1024 * 1024, small_segments()).unwrap();
+    assert_eq!(tree.get(&key(199)).unwrap().value, value(199));
+}
+
+#[test]
+fn test_replay_stops_at_torn_tail_and_keeps_the_valid_prefix() {
+    let dir = tempdir().unwrap();
+    let tail = {
+        let wal = open_wal(dir.path());
+        for i in 0..40 {
+            wal.log(&mut BDBLogEntry::new(EntryType::Insert, key(i), value(i))).unwrap();
+        }
+        let segments = wal.segments().unwrap();
+        assert!(segments.len() >= 3, "{:?}", segments);
+        segments.last().unwrap().clone()
+    };
+
+    // Corrupt the last record the tail segment starts, as a torn write
+    // would: its bytes are there but its CRC no longer matches.
+    let mut bytes = fs::read(&tail).unwrap();
+    let first_in_tail = (0..40).find(|&i| bytes.windows(10).any(|w| w == &value(i)[..10])).unwrap();
+    let torn = first_in_tail + 2;
+    let at = bytes.windows(10).position(|w| w == &value(torn)[..10]).unwrap();
+    bytes[at + 20] ^= 0xff;
+    fs::write(&tail, &bytes).unwrap();
+
+    let tree = LSMTree::new(dir.path(), TableType::History, 64 * 1024 * 1024, BrowserDBConfig::default()).unwrap();
+    for i in 0..torn {
+        assert_eq!(tree.get(&key(i)).unwrap().value, value(i), "key {} lost", i);
+    }
+    for i in torn..40 {
+        assert!(tree.get(&key(i)).is_none(), "key {} replayed past the torn record", i);
+    }
+}
+
+#[test]
+fn test_writes_after_recovery_follow_the_valid_prefix() {
+    let dir = tempdir().unwrap();
+    let tail = {
+        let wal = open_wal(dir.path());
+        for i in 0..10 {
+            wal.log(&mut BDBLogEntry::new(EntryType::Insert, key(i), value(i))).unwrap();
+        }
+        wal.segments().unwrap().pop().unwrap()
+    };
+    let len = fs::metadata(&tail).unwrap().len();
+    fs::OpenOptions::new().write(true).open(&tail).unwrap().set_len(len - 3).unwrap();
+
+    {
+        let wal = open_wal(dir.path());
+        let recovered = wal.read_all().unwrap();
+        assert_eq!(recovered.len(), 9);
+        wal.log(&mut BDBLogEntry::new(EntryType::Insert, b"after".to_vec(), b"crash".to_vec())).unwrap();
+    }
+
+    let recovered = open_wal(dir.path()).read_all().unwrap();
+    let keys: Vec<Vec<u8>> = recovered.into_iter().map(|e| e.key).collect();
+    assert_eq!(keys.len(), 10);
+    assert_eq!(keys[8], key(8));
+    assert_eq!(keys[9], b"after");
+}
+
+#[test]
+fn test_single_file_wal_is_migrated_into_segments() {
+    let dir = tempdir().unwrap();
+    let legacy = dir.path().join("history.wal");
+    let mut bytes = Vec::new();
+    for i in 0..3 {
+        BDBLogEntry::new(EntryType::Insert, key(i), value(i)).write(&mut bytes).unwrap();
+    }
+    fs::write(&legacy, &bytes).unwrap();
+
+    let wal = open_wal(dir.path());
+    assert!(!legacy.exists());
+    assert_eq!(wal.read_all().unwrap().len(), 3);
+    assert_eq!(wal.segments().unwrap().len(), 1);
+}
@@ -0,0 +1,129 @@
+use browserdb::core::config::{BrowserDBConfig, WriteStall};
+use browserdb::core::format::TableType;
+use browserdb::core::lsm_tree::LSMTree;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+const STOP: usize = 4;
+
+/// A tree that flushes every 50 writes and compacts level 0 only when
+/// backpressure forces it.
+fn config(stall: WriteStall) -> BrowserDBConfig {
+    let mut config = BrowserDBConfig::default();
+    config.lsm_tree.memtable_max_ops = 50;
+    config.lsm_tree.max_level0_files = 2;
+    config.lsm_tree.l0_slowdown_files = 3;
+    config.lsm_tree.l0_stop_files = STOP;
+    config.lsm_tree.max_pending_flushes = 2;
+    config.lsm_tree.write_stall = stall;
+    config.lsm_tree.compaction_workers = 1;
+    config.lsm_tree.compaction_idle_threshold_ms = u64::MAX;
+    config.lsm_tree.compaction_deadline_sec = u64::MAX;
+    config
+}
+
+fn open(dir: &Path, config: BrowserDBConfig) -> Arc<LSMTree> {
+    Arc::new(LSMTree::new(dir, TableType::History, 64 * 1024 * 1024, config).unwrap())
+}
+
+fn level0(tree: &LSMTree) -> usize {
+    tree.inner.levels[0].read().len()
+}
+
+#[test]
+fn test_flood_of_writes_keeps_level0_under_the_ceiling() {
+    let dir = tempdir().unwrap();
+    let config = config(WriteStall::Block);
+    // Compaction falls behind for as long as the only slot is held.
+    let slot = config.compaction_pool.acquire_timeout(1, Duration::ZERO).unwrap();
+    let tree = open(dir.path(), config);
+
+    let done = Arc::new(AtomicBool::new(false));
+    let most = Arc::new(AtomicUsize::new(0));
+    let monitor = {
+        let (tree, done, most) = (Arc::clone(&tree), Arc::clone(&done), Arc::clone(&most));
+        thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                most.fetch_max(level0(&tree), Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(1));
+            }
+        })
+    };
+    let writer = {
+        let (tree, done) = (Arc::clone(&tree), Arc::clone(&done));
+        thread::spawn(move || {
+            let mut written = 0u32;
+            while !done.load(Ordering::SeqCst) {
+                tree.put(format!("page/{:08}", written).into_bytes(), vec![b'v'; 100]).unwrap();
+                written += 1;
+            }
+            written
+        })
+    };
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while level0(&tree) < STOP {
+        assert!(Instant::now() < deadline, "level 0 never filled up");
+        thread::sleep(Duration::from_millis(10));
+    }
+    // The writer is held at the ceiling rather than adding tables.
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(level0(&tree), STOP);
+    let stalls = tree.inner.write_stalls.load(Ordering::Relaxed);
+    drop(slot);
+    // Writes resume once compaction drains level 0.
+    while tree.inner.write_stalls.load(Ordering::Relaxed) == stalls || tree.inner.compactions.load(Ordering::Relaxed) == 0 {
+        assert!(Instant::now() < deadline, "writer never resumed");
+        thread::sleep(Duration::from_millis(10));
+    }
+    done.store(true, Ordering::SeqCst);
+    let written = writer.join().unwrap();
+    monitor.join().unwrap();
+
+    // One flush may already be under way when writers stop.
+    let most = most.load(Ordering::SeqCst);
+    assert!((STOP..=STOP + 1).contains(&most), "level 0 reached {} tables", most);
+    assert_eq!(tree.get(format!("page/{:08}", written / 2).as_bytes()).unwrap().value, vec![b'v'; 100]);
+}
+
+#[test]
+fn test_stopped_writes_fail_fast_or_wait_for_compaction() {
+    for stall in [WriteStall::Fail, WriteStall::Block] {
+        let dir = tempdir().unwrap();
+        let config = config(stall);
+        // Holding the only compaction slot keeps level 0 from draining.
+        let slot = config.compaction_pool.acquire_timeout(1, Duration::ZERO).unwrap();
+        let tree = open(dir.path(), config);
+        for round in 0..STOP as u8 {
+            tree.put(vec![b'k', round], vec![round]).unwrap();
+            tree.flush().unwrap();
+        }
+        assert_eq!(level0(&tree), STOP);
+
+        let writer = {
+            let tree = Arc::clone(&tree);
+            thread::spawn(move || tree.put(b"late".to_vec(), b"1".to_vec()))
+        };
+        thread::sleep(Duration::from_millis(300));
+        match stall {
+            WriteStall::Fail => {
+                let err = writer.join().unwrap().unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+                assert!(tree.get(b"late").is_none());
+                drop(slot);
+            }
+            WriteStall::Block => {
+                assert!(!writer.is_finished(), "write went through with level 0 full");
+                drop(slot);
+                writer.join().unwrap().unwrap();
+                assert!(level0(&tree) < STOP);
+                assert_eq!(tree.get(b"late").unwrap().value, b"1");
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use browserdb::{BrowserDB, CacheEntry, DatabaseMode};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use tempfile::tempdir;
+
+/// Counts the bytes the current thread allocates, so background flush and
+/// compaction threads do not disturb the measurement.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.try_with(|n| n.set(n.get() + layout.size()));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocated_during<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATED.with(|n| n.get());
+    let result = f();
+    (result, ALLOCATED.with(|n| n.get()) - before)
+}
+
+fn page(url_hash: u128, body: Vec<u8>) -> CacheEntry {
+    CacheEntry {
+        url_hash,
+        headers: "content-type: text/html".to_string(),
+        body,
+        etag: "\"v1\"".to_string(),
+        last_modified: 0,
+        expires_at: 0,
+    }
+}
+
+#[test]
+fn test_with_body_borrows_from_the_sstable() {
+    const BODY: usize = 32 * 1024;
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    let body: Vec<u8> = (0..BODY).map(|i| (i % 251) as u8).collect();
+    db.cache().insert(&page(9, body.clone())).unwrap();
+    db.vacuum().unwrap();
+
+    let (sum, bytes) = allocated_during(|| {
+        db.cache()
+            .with_body(9, |b| {
+                assert_eq!(b.len(), BODY);
+                b.iter().map(|x| *x as u64).sum::<u64>()
+            })
+            .unwrap()
+    });
+    assert_eq!(sum, Some(body.iter().map(|x| *x as u64).sum()));
+    assert!(bytes < BODY / 4, "with_body allocated {} bytes", bytes);
+
+    let (entry, bytes) = allocated_during(|| db.cache().get(9).unwrap().unwrap());
+    assert_eq!(entry.body, body);
+    assert!(bytes >= BODY);
+
+    assert_eq!(db.cache().with_body(10, |b| b.len()).unwrap(), None);
+}
+
+#[test]
+fn test_with_body_falls_back_for_memtable_blob_and_ultra() {
+    let dir = tempdir().unwrap();
+    let db = BrowserDB::open(dir.path()).unwrap();
+    db.cache().insert(&page(1, b"in memtable".to_vec())).unwrap();
+    // Over the blob separation threshold, so stored in the blob log.
+    let big = vec![3u8; 100 * 1024];
+    db.cache().insert(&page(2, big.clone())).unwrap();
+    db.cache().insert(&page(3, b"migrated".to_vec())).unwrap();
+    db.vacuum().unwrap();
+    db.cache().insert(&page(1, b"rewritten".to_vec())).unwrap();
+
+    assert_eq!(db.cache().with_body(1, |b| b.to_vec()).unwrap().unwrap(), b"rewritten");
+    assert_eq!(db.cache().with_body(2, |b| b == big.as_slice()).unwrap(), Some(true));
+
+    db.set_mode(DatabaseMode::Ultra).unwrap();
+    assert_eq!(db.cache().with_body(3, |b| b.to_vec()).unwrap().unwrap(), b"migrated");
+}
@@ -11,28 +11,31 @@ echo "========================"
 # 检查依赖
 echo "🔍 检查依赖..."
 
-if ! command -v zig &> /dev/null; then
-    echo "❌ Zig 未安装，请安装 Zig 0.13.0 或更高版本"
-    exit 1
-fi
-
 if ! command -v cargo &> /dev/null; then
     echo "❌ Rust/Cargo 未安装，请安装 Rust 1.75 或更高版本"
     exit 1
 fi
 
-ZIG_VERSION=$(zig version)
 RUST_VERSION=$(rustc --version | cut -d' ' -f2)
-
-echo "✅ Zig 版本: $ZIG_VERSION"
 echo "✅ Rust 版本: $RUST_VERSION"
 
+# Zig 核心是可选的：Rust 绑定自带纯 Rust 的 LSM 实现，不需要 Zig
+BUILD_ZIG=0
+if [ -d core ] && command -v zig &> /dev/null; then
+    BUILD_ZIG=1
+    echo "✅ Zig 版本: $(zig version)"
+else
+    echo "⚠️  未找到 Zig 或 core/ 目录，跳过 Zig 核心构建"
+fi
+
 # 清理之前的构建
 echo "🧹 清理之前的构建..."
-cd core && rm -rf zig-out build *.o *.a 2>/dev/null || true
-cd ../bindings && rm -rf target 2>/dev/null || true
-cd ..
+if [ "$BUILD_ZIG" = 1 ]; then
+    (cd core && rm -rf zig-out build *.o *.a 2>/dev/null) || true
+fi
+(cd bindings && rm -rf target 2>/dev/null) || true
 
+if [ "$BUILD_ZIG" = 1 ]; then
 # 构建 Zig 核心
 echo "🔨 构建 Zig 核心引擎..."
 cd core
@@ -65,14 +68,12 @@ else
 fi
 
 cd ..
+fi
 
 # 构建 Rust 绑定
 echo "🔨 构建 Rust 绑定..."
 cd bindings
 
-# 设置环境变量
-export BROWSERDB_ZIG_PATH=$(which zig)
-
 # 清理 Rust 构建缓存
 cargo clean
 
@@ -93,7 +94,6 @@ if [ $? -eq 0 ]; then
     echo "  ✅ Rust 测试通过"
 else
     echo "  ❌ Rust 测试失败"
-    echo "  ⚠️  一些测试可能需要实际的 Zig 库"
 fi
 
 cd ..
@@ -138,7 +138,9 @@ fi
 
 echo ""
 echo "🚀 快速开始:"
-echo "  cd core && ./zig-out/bin/browserdb"
+if [ "$BUILD_ZIG" = 1 ]; then
+    echo "  cd core && ./zig-out/bin/browserdb"
+fi
 echo "  cd bindings && cargo run --example basic_usage"
 
 # 生成构建报告